@@ -0,0 +1,51 @@
+/*
+ * The C interface to csc411_rpegio, built with the `ffi` feature. See the documentation of the
+ * crate's `ffi` module for how to build and link against it.
+ */
+
+#ifndef RPEGIO_H
+#define RPEGIO_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The function succeeded */
+#define RPEG_OK 0
+/* A pointer argument was null, a path wasn't UTF-8, or the length wasn't a whole number of words */
+#define RPEG_INVALID_ARGUMENT 1
+/* A file, stdin, or stdout couldn't be read or written */
+#define RPEG_IO_ERROR 2
+/* The data read wasn't valid rpeg data */
+#define RPEG_INVALID_DATA 3
+
+/* The words and dimensions of an image. Data from rpeg_read must be freed with rpeg_free */
+struct rpeg_data {
+    /* The bytes of the words, four to a word, most significant byte first */
+    uint8_t *bytes;
+    /* The number of bytes, which is four times the number of words */
+    size_t len;
+    uint32_t width;
+    uint32_t height;
+};
+
+/* Reads rpeg data from the file at path, or from stdin if path is NULL, into *out */
+int rpeg_read(const char *path, struct rpeg_data *out);
+
+/* Writes rpeg data to the file at path (created or replaced), or to stdout if path is NULL */
+int rpeg_write(const char *path, const struct rpeg_data *data);
+
+/* Frees the bytes of data read by rpeg_read, leaving it empty */
+void rpeg_free(struct rpeg_data *data);
+
+/* A description of the last error on this thread, valid until the next call on the thread */
+const char *rpeg_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
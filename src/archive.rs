@@ -0,0 +1,509 @@
+use crate::checksum::Crc32;
+use crate::{compression, deflate, RpegError, RpegImage};
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+const ZIP_LOCAL_HEADER: &[u8] = b"PK\x03\x04";
+const ZIP_CENTRAL_HEADER: &[u8] = b"PK\x01\x02";
+const ZIP_END_OF_DIRECTORY: &[u8] = b"PK\x05\x06";
+
+/// The length of a zip file's end of central directory record, without its comment.
+const ZIP_END_LEN: usize = 22;
+
+const TAR_BLOCK_LEN: usize = 512;
+
+/// A zip or tar archive of rpeg files, such as a zip of student submissions, which can be read
+/// without extracting it. Enabled by the `archive` feature.
+///
+/// The whole archive is read into memory, but each file in it is only decompressed (and parsed)
+/// when asked for. Zip files may be stored or deflated, and tar files may be gzip-compressed
+/// (`.tar.gz` or `.tgz`). ZIP64 archives aren't supported.
+///
+/// # Examples
+/// ```no_run
+/// use csc411_rpegio::RpegArchive;
+///
+/// let archive = RpegArchive::open("submissions.zip").unwrap();
+///
+/// for (path, image) in archive.images("*/output/*.rpeg") {
+///     match image {
+///         Ok(image) => println!("{path}: {}x{}", image.width(), image.height()),
+///         Err(err) => println!("{path}: {err}"),
+///     }
+/// }
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct RpegArchive {
+    bytes: Vec<u8>,
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Entry {
+    path: String,
+    data: Range<usize>,
+    // For deflated zip entries, the size and CRC32 of the data once decompressed
+    deflated: Option<(u64, u32)>,
+}
+
+impl RpegArchive {
+    /// Reads a zip or tar archive from a file. The kind of the archive is detected from its
+    /// contents, not its name.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the file can't be read
+    /// * If the file isn't a zip or tar archive, or its list of files is corrupted
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the archive
+    pub fn open(path: &str) -> Result<Self, RpegError> {
+        RpegArchive::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Like [`RpegArchive::open`], but with an archive which has already been read.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, RpegError> {
+        // A gzip-compressed tar file is decompressed first
+        let bytes = compression::decompress(&bytes)?.unwrap_or(bytes);
+
+        let entries =
+            if bytes.starts_with(ZIP_LOCAL_HEADER) || bytes.starts_with(ZIP_END_OF_DIRECTORY) {
+                read_zip_entries(&bytes)?
+            } else {
+                read_tar_entries(&bytes)?
+            };
+
+        Ok(RpegArchive { bytes, entries })
+    }
+
+    /// The paths of every file in the archive (not including directories), in the order they
+    /// appear in it.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.path.as_str())
+    }
+
+    /// Parses each file in the archive whose path matches the glob `pattern`, yielding its path
+    /// along with the image (or the reason it couldn't be read), so that one bad submission
+    /// doesn't stop the rest from being read.
+    ///
+    /// In the pattern, `*` matches anything except a `/`, `**` matches anything at all (so
+    /// `**/` matches any number of directories, including none), and `?` matches any one byte
+    /// except a `/`. Any leading `./` is removed from paths in the archive before matching.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - Which paths to read, such as `"**/*.rpeg"`
+    pub fn images<'a>(
+        &'a self,
+        pattern: &'a str,
+    ) -> impl Iterator<Item = (&'a str, Result<RpegImage, RpegError>)> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| glob_matches(pattern.as_bytes(), entry.path.as_bytes()))
+            .map(|entry| (entry.path.as_str(), self.read_image(entry)))
+    }
+
+    /// Like [`RpegArchive::images`], but yields the bytes of each file rather than parsing them.
+    pub(crate) fn files<'a>(
+        &'a self,
+        pattern: &'a str,
+    ) -> impl Iterator<Item = (&'a str, Result<Cow<'a, [u8]>, RpegError>)> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| glob_matches(pattern.as_bytes(), entry.path.as_bytes()))
+            .map(|entry| (entry.path.as_str(), self.read_file(entry)))
+    }
+
+    fn read_image(&self, entry: &Entry) -> Result<RpegImage, RpegError> {
+        RpegImage::from_bytes(&self.read_file(entry)?)
+    }
+
+    fn read_file(&self, entry: &Entry) -> Result<Cow<'_, [u8]>, RpegError> {
+        let data = &self.bytes[entry.data.clone()];
+
+        match entry.deflated {
+            None => Ok(Cow::Borrowed(data)),
+            Some((size, crc)) => {
+                let error = |message: &str| RpegError::Format {
+                    message: format!("{message} in {}", entry.path),
+                    offset: entry.data.start as u64,
+                };
+
+                let (data, _) = deflate::decompress(data).map_err(|message| error(&message))?;
+                let mut found = Crc32::new();
+                found.update(&data);
+                if data.len() as u64 != size || found.finish() != crc {
+                    return Err(error("The decompressed data doesn't match its checksum"));
+                }
+
+                Ok(Cow::Owned(data))
+            }
+        }
+    }
+}
+
+/// Shows the size of the archive and the paths of its entries, rather than every byte of it.
+impl fmt::Debug for RpegArchive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let paths: Vec<&str> = self.entries.iter().map(|entry| &*entry.path).collect();
+
+        f.debug_struct("RpegArchive")
+            .field("len", &self.bytes.len())
+            .field("entries", &paths)
+            .finish()
+    }
+}
+
+/// Lists the files in a zip archive, from its central directory.
+fn read_zip_entries(bytes: &[u8]) -> Result<Vec<Entry>, RpegError> {
+    let error = |message: &str, offset: usize| RpegError::Format {
+        message: message.to_string(),
+        offset: offset as u64,
+    };
+    let u16_at = |offset: usize| {
+        bytes
+            .get(offset..offset + 2)
+            .map(|field| u16::from_le_bytes([field[0], field[1]]) as usize)
+            .ok_or_else(|| error("The zip file ended unexpectedly", bytes.len()))
+    };
+    let u32_at = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|field| u32::from_le_bytes(field.try_into().unwrap()))
+            .ok_or_else(|| error("The zip file ended unexpectedly", bytes.len()))
+    };
+
+    // The end of central directory record is at the end, before a comment of up to 64 KiB
+    let earliest = bytes.len().saturating_sub(ZIP_END_LEN + u16::MAX as usize);
+    let end = (earliest..=bytes.len().saturating_sub(ZIP_END_LEN))
+        .rev()
+        .find(|&offset| bytes[offset..].starts_with(ZIP_END_OF_DIRECTORY))
+        .ok_or_else(|| {
+            error(
+                "Expected the end of the zip file's central directory",
+                bytes.len(),
+            )
+        })?;
+
+    let count = u16_at(end + 10)?;
+    let mut offset = u32_at(end + 16)? as usize;
+    if count == u16::MAX as usize || offset == u32::MAX as usize {
+        return Err(error("ZIP64 archives aren't supported", end));
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if !bytes[offset.min(bytes.len())..].starts_with(ZIP_CENTRAL_HEADER) {
+            return Err(error(
+                "Expected an entry of the zip file's central directory",
+                offset,
+            ));
+        }
+
+        let method = u16_at(offset + 10)?;
+        let crc = u32_at(offset + 16)?;
+        let compressed_len = u32_at(offset + 20)? as usize;
+        let size = u32_at(offset + 24)? as u64;
+        let name_len = u16_at(offset + 28)?;
+        let extra_len = u16_at(offset + 30)?;
+        let comment_len = u16_at(offset + 32)?;
+        let local = u32_at(offset + 42)? as usize;
+        let name = bytes
+            .get(offset + 46..offset + 46 + name_len)
+            .ok_or_else(|| error("The zip file ended unexpectedly", bytes.len()))?;
+        let path = normalize(&String::from_utf8_lossy(name));
+
+        // The data follows the file's local header, whose extra field may differ in length
+        if !bytes[local.min(bytes.len())..].starts_with(ZIP_LOCAL_HEADER) {
+            return Err(error("Expected a file's header in the zip file", local));
+        }
+        let start = local + 30 + u16_at(local + 26)? + u16_at(local + 28)?;
+        if start + compressed_len > bytes.len() {
+            return Err(error("The zip file ended unexpectedly", bytes.len()));
+        }
+
+        let deflated = match method {
+            0 => None,
+            8 => Some((size, crc)),
+            _ => {
+                return Err(error(
+                    "Only stored or deflated zip files are supported",
+                    offset,
+                ))
+            }
+        };
+        if !path.ends_with('/') {
+            entries.push(Entry {
+                path,
+                data: start..start + compressed_len,
+                deflated,
+            });
+        }
+
+        offset += 46 + name_len + extra_len + comment_len;
+    }
+
+    Ok(entries)
+}
+
+/// Lists the regular files in a tar archive, including those with long names in GNU or pax
+/// headers.
+fn read_tar_entries(bytes: &[u8]) -> Result<Vec<Entry>, RpegError> {
+    let error = |message: &str, offset: usize| RpegError::Format {
+        message: message.to_string(),
+        offset: offset as u64,
+    };
+    // Text fields end at the first NUL, if there is one
+    let field = |block: &[u8], range: Range<usize>| {
+        let field = &block[range];
+        let len = field
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(field.len());
+        String::from_utf8_lossy(&field[..len]).into_owned()
+    };
+
+    let mut entries = Vec::new();
+    let mut long_path = None;
+    let mut offset = 0;
+
+    while let Some(block) = bytes.get(offset..offset + TAR_BLOCK_LEN) {
+        // The archive ends with blocks of zeroes
+        if block.iter().all(|&byte| byte == 0) {
+            return Ok(entries);
+        }
+
+        let checksum: u32 = block
+            .iter()
+            .enumerate()
+            .map(|(index, &byte)| if (148..156).contains(&index) { b' ' } else { byte } as u32)
+            .sum();
+        if u32::from_str_radix(field(block, 148..156).trim(), 8) != Ok(checksum) {
+            return Err(error("Expected a tar header with a valid checksum", offset));
+        }
+        let size = u64::from_str_radix(field(block, 124..136).trim(), 8)
+            .map_err(|_| error("Expected the size of a file in the tar file", offset + 124))?;
+
+        let start = offset + TAR_BLOCK_LEN;
+        let end = start
+            .checked_add(size as usize)
+            .filter(|&end| end <= bytes.len())
+            .ok_or_else(|| error("The tar file ended unexpectedly", bytes.len()))?;
+        let data = &bytes[start..end];
+
+        match block[156] {
+            b'0' | 0 => {
+                let path = long_path.take().unwrap_or_else(|| {
+                    let prefix = field(block, 345..500);
+                    let name = field(block, 0..100);
+                    match block[257..].starts_with(b"ustar") && !prefix.is_empty() {
+                        true => format!("{prefix}/{name}"),
+                        false => name,
+                    }
+                });
+
+                entries.push(Entry {
+                    path: normalize(&path),
+                    data: start..end,
+                    deflated: None,
+                });
+            }
+            // A GNU long name, for the next file
+            b'L' => {
+                let len = data
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(data.len());
+                long_path = Some(String::from_utf8_lossy(&data[..len]).into_owned());
+            }
+            // Pax extended attributes for the next file, made of "{len} {key}={value}\n" records
+            b'x' => {
+                let records = String::from_utf8_lossy(data);
+                for record in records.lines() {
+                    if let Some((_, path)) = record.split_once(" path=") {
+                        long_path = Some(path.to_string());
+                    }
+                }
+            }
+            _ => long_path = None,
+        }
+
+        offset = start + size.next_multiple_of(TAR_BLOCK_LEN as u64) as usize;
+    }
+
+    match offset == bytes.len() {
+        true => Ok(entries),
+        false => Err(error("The tar file ended unexpectedly", bytes.len())),
+    }
+}
+
+fn normalize(path: &str) -> String {
+    let mut path = path;
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest;
+    }
+
+    path.to_string()
+}
+
+/// Whether `path` matches the glob `pattern` (see [`RpegArchive::images`]).
+fn glob_matches(pattern: &[u8], path: &[u8]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => {
+            glob_matches(rest, path)
+                || (0..path.len())
+                    .any(|index| path[index] == b'/' && glob_matches(rest, &path[index + 1..]))
+        }
+        [b'*', b'*', rest @ ..] => (0..=path.len()).any(|index| glob_matches(rest, &path[index..])),
+        [b'*', rest @ ..] => (0..=path.len())
+            .take_while(|&index| index == 0 || path[index - 1] != b'/')
+            .any(|index| glob_matches(rest, &path[index..])),
+        [b'?', rest @ ..] => {
+            matches!(path.first(), Some(&byte) if byte != b'/') && glob_matches(rest, &path[1..])
+        }
+        [byte, rest @ ..] => path.first() == Some(byte) && glob_matches(rest, &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_header(path: &str, kind: u8, size: usize) -> Vec<u8> {
+        let mut block = vec![0; TAR_BLOCK_LEN];
+        block[..path.len()].copy_from_slice(path.as_bytes());
+        block[124..135].copy_from_slice(format!("{size:011o}").as_bytes());
+        block[156] = kind;
+        block[257..263].copy_from_slice(b"ustar\0");
+        block[148..156].fill(b' ');
+        let checksum: u32 = block.iter().map(|&byte| byte as u32).sum();
+        block[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+
+        block
+    }
+
+    fn tar_file(tar: &mut Vec<u8>, path: &str, kind: u8, data: &[u8]) {
+        tar.extend(tar_header(path, kind, data.len()));
+        tar.extend(data);
+        tar.resize(tar.len().next_multiple_of(TAR_BLOCK_LEN), 0);
+    }
+
+    #[test]
+    fn test_tar() {
+        let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+        let long_path = format!("{}/output.rpeg", "student".repeat(20));
+
+        let mut tar = Vec::new();
+        tar_file(&mut tar, "./alice/", b'5', b"");
+        tar_file(&mut tar, "./alice/output.rpeg", b'0', &image.to_bytes());
+        tar_file(&mut tar, "./alice/notes.txt", b'0', b"Not an image");
+        tar_file(&mut tar, "././@LongLink", b'L', long_path.as_bytes());
+        tar_file(
+            &mut tar,
+            "truncated",
+            b'0',
+            b"Compressed image format 2\n2 2\n",
+        );
+        tar_file(&mut tar, "bob/output.rpeg", b'0', b"Not an image either");
+        tar.extend([0; 2 * TAR_BLOCK_LEN]);
+
+        let archive = RpegArchive::from_bytes(tar.clone()).unwrap();
+        assert_eq!(
+            archive.paths().collect::<Vec<_>>(),
+            [
+                "alice/output.rpeg",
+                "alice/notes.txt",
+                &long_path,
+                "bob/output.rpeg"
+            ]
+        );
+
+        let images = archive.images("**/*.rpeg").collect::<Vec<_>>();
+        assert_eq!(images.len(), 3);
+        assert_eq!(images[0].0, "alice/output.rpeg");
+        assert_eq!(images[0].1.as_ref().unwrap(), &image);
+        assert!(images[1].1.as_ref().unwrap().words.is_empty());
+        assert!(images[2].1.is_err());
+        assert_eq!(archive.images("*/*.txt").count(), 1);
+        assert_eq!(archive.images("*.rpeg").count(), 0);
+
+        tar[148] ^= 1;
+        assert!(RpegArchive::from_bytes(tar).is_err());
+    }
+
+    #[test]
+    fn test_zip() {
+        let image = RpegImage::new(vec![[0x44, 0x55, 0x66, 0x77]; 64], 16, 16);
+        let bytes = image.to_bytes();
+        let mut output = deflate::BitWriter::default();
+        deflate::compress_block(&bytes, true, &mut output);
+        let deflated = output.finish();
+        let mut crc = Crc32::new();
+        crc.update(&bytes);
+
+        // A local header and data for each file, then the central directory
+        let files = [
+            ("stored/output.rpeg", 0u16, &bytes, &bytes),
+            ("deflated/output.rpeg", 8, &deflated, &bytes),
+        ];
+        let (mut zip, mut directory) = (Vec::new(), Vec::new());
+        for (path, method, data, original) in files {
+            let mut fields = method.to_le_bytes().to_vec();
+            fields.extend([0; 4]);
+            fields.extend(crc.finish().to_le_bytes());
+            fields.extend((data.len() as u32).to_le_bytes());
+            fields.extend((original.len() as u32).to_le_bytes());
+            fields.extend((path.len() as u16).to_le_bytes());
+            fields.extend([0; 2]);
+
+            directory.extend(ZIP_CENTRAL_HEADER);
+            directory.extend([20, 0, 20, 0, 0, 0]);
+            directory.extend(&fields);
+            directory.extend([0; 10]);
+            directory.extend((zip.len() as u32).to_le_bytes());
+            directory.extend(path.as_bytes());
+
+            zip.extend(ZIP_LOCAL_HEADER);
+            zip.extend([20, 0, 0, 0]);
+            zip.extend(&fields);
+            zip.extend(path.as_bytes());
+            zip.extend(data.iter());
+        }
+        let directory_start = zip.len() as u32;
+        zip.extend(&directory);
+        zip.extend(ZIP_END_OF_DIRECTORY);
+        zip.extend([0, 0, 0, 0, 2, 0, 2, 0]);
+        zip.extend((directory.len() as u32).to_le_bytes());
+        zip.extend(directory_start.to_le_bytes());
+        zip.extend([0, 0]);
+
+        let archive = RpegArchive::from_bytes(zip.clone()).unwrap();
+        let images = archive.images("*/output.rpeg").collect::<Vec<_>>();
+        assert_eq!(images.len(), 2);
+        for (_, read) in images {
+            assert_eq!(read.unwrap(), image);
+        }
+
+        // Corrupting the deflated data is caught by its checksum, if not by decompressing it
+        let data_start = 30 + 18 + bytes.len() + 30 + 20;
+        zip[data_start + deflated.len() / 2] ^= 0x10;
+        let archive = RpegArchive::from_bytes(zip).unwrap();
+        assert!(archive.images("deflated/*").all(|(_, read)| read.is_err()));
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        let matches = |pattern: &str, path: &str| glob_matches(pattern.as_bytes(), path.as_bytes());
+
+        assert!(matches("*.rpeg", "a.rpeg"));
+        assert!(!matches("*.rpeg", "dir/a.rpeg"));
+        assert!(matches("**/*.rpeg", "a.rpeg"));
+        assert!(matches("**/*.rpeg", "a/b/c.rpeg"));
+        assert!(matches("a/**", "a/b/c"));
+        assert!(matches("?/out?.rpeg", "a/out1.rpeg"));
+        assert!(!matches("?/out?.rpeg", "ab/out1.rpeg"));
+    }
+}
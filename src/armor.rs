@@ -0,0 +1,120 @@
+use crate::header::{self, ByteReader};
+use crate::{base64, stdio, ReadOptions, RpegError, RpegImage};
+
+/// The suffix on the first line of the header which marks ASCII-armored data.
+const ARMOR_SUFFIX: &str = " [BASE64]";
+
+/// The length of each line of the base64 payload, as in MIME.
+const LINE_LEN: usize = 76;
+
+impl RpegImage {
+    /// Encodes the image as ASCII-armored rpeg data: the usual header (with ` [BASE64]` at the end
+    /// of its first line), followed by the payload (and any trailers) in base64, in lines of 76
+    /// characters. Unlike the binary format, this survives being pasted into forum posts, emails,
+    /// and web forms.
+    ///
+    /// Armored data is detected and decoded automatically by [`RpegImage::read`],
+    /// [`RpegImage::from_bytes`], and [`read_in_rpeg_data`](crate::read_in_rpeg_data). The
+    /// streaming readers don't support it.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+    /// let armored = image.to_armored();
+    ///
+    /// assert_eq!(armored, "Compressed image format 2 [BASE64]\n2 2\nABEiMw==\n");
+    /// assert_eq!(RpegImage::from_bytes(armored.as_bytes()).unwrap(), image);
+    /// ```
+    pub fn to_armored(&self) -> String {
+        let mut armored = Vec::new();
+        header::write_header_with_suffix(&mut armored, &self.header, ARMOR_SUFFIX)
+            .expect("Writing to a Vec can't fail");
+
+        // The binary payload is everything after the (identical but unsuffixed) binary header
+        let mut header_bytes = Vec::new();
+        header::write_header(&mut header_bytes, &self.header).expect("Writing to a Vec can't fail");
+        let bytes = self.to_bytes();
+        let payload = base64::encode(&bytes[header_bytes.len()..], LINE_LEN);
+
+        let mut armored = String::from_utf8(armored).expect("Headers are always valid UTF-8");
+        armored.push_str(&payload);
+        armored
+    }
+
+    /// Outputs the image to stdout as ASCII-armored rpeg data. See [`RpegImage::to_armored`].
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::BrokenPipe`] if stdout is closed before all of the data is written
+    /// * [`RpegError::Io`] if anything else goes wrong writing to stdout
+    pub fn write_armored(&self) -> Result<(), RpegError> {
+        use std::io::Write;
+
+        let mut stdout = stdio::output()?;
+        stdout.write_all(self.to_armored().as_bytes())?;
+        stdout.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Whether the first line of `bytes` marks it as ASCII-armored.
+pub(crate) fn is_armored(bytes: &[u8]) -> bool {
+    let first_line = bytes.split(|&byte| byte == b'\n').next().unwrap_or(bytes);
+    let first_line = first_line.strip_suffix(b"\r").unwrap_or(first_line);
+
+    first_line
+        .trim_ascii_end()
+        .ends_with(ARMOR_SUFFIX.as_bytes())
+}
+
+/// Decodes ASCII-armored rpeg data into the equivalent binary rpeg data.
+pub(crate) fn dearmor(bytes: &[u8], options: &ReadOptions) -> Result<Vec<u8>, RpegError> {
+    let mut byte_reader = ByteReader::new(bytes);
+    let header = header::read_header_with_suffix(&mut byte_reader, options, ARMOR_SUFFIX)?;
+    let header_len = byte_reader.position() as usize;
+
+    let payload = base64::decode(&bytes[header_len..]).map_err(|offset| RpegError::Format {
+        message: "The payload of armored data isn't valid base64".to_string(),
+        offset: (header_len + offset) as u64,
+    })?;
+
+    let mut binary = Vec::with_capacity(header_len + payload.len());
+    header::write_header(&mut binary, &header)?;
+    binary.extend_from_slice(&payload);
+
+    Ok(binary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_armor_round_trip() {
+        let words = (0..100u32).map(u32::to_be_bytes).collect();
+        let mut image = RpegImage::new(words, 20, 20);
+        image.header.checksum = true;
+        image.header.comments.push("pasted".to_string());
+
+        let armored = image.to_armored();
+        assert!(armored.starts_with("Compressed image format 2c [BASE64]\n# pasted\n20 20\n"));
+        assert!(armored.lines().all(|line| line.len() <= LINE_LEN));
+        assert!(armored.is_ascii());
+
+        // Pasting often mangles line endings
+        let pasted = armored.replace('\n', "\r\n");
+        assert_eq!(RpegImage::from_bytes(pasted.as_bytes()).unwrap(), image);
+    }
+
+    #[test]
+    fn test_bad_base64() {
+        let input = b"Compressed image format 2 [BASE64]\n2 2\nABE!Mw==\n";
+        assert!(matches!(
+            RpegImage::from_bytes(input),
+            Err(RpegError::Format { offset: 42, .. })
+        ));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{compare_rpeg, RpegImage};
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+
+/// The number of words shown on either side of the first differing word, when an
+/// [`assert_rpeg_eq!`] fails.
+const EXCERPT_CONTEXT: usize = 2;
+
+/// Asserts that two images are equal, like `assert_eq!`, but with a failure message that can
+/// actually be read: the size of each image, how many words differ and where the first one is,
+/// and the words around it from both images in hex (with the differing ones marked), in place of
+/// the `Debug` output of two long `Vec<[u8; 4]>`s.
+///
+/// Either side may be an [`RpegImage`] or the bytes of rpeg data (such as a `Vec<u8>`, or a
+/// byte string literal), which are parsed first. As with `assert_eq!`, a custom message (with
+/// format arguments) may follow the two sides.
+///
+/// The images are compared with `==`, so their headers must match too. If only the headers
+/// differ, both are shown instead of the words.
+///
+/// # Panics
+///
+/// * If the images differ
+/// * If either side is bytes which can't be parsed as rpeg data
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{assert_rpeg_eq, RpegImage};
+///
+/// let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+/// assert_rpeg_eq!(image, b"Compressed image format 2\n2 2\n\x00\x11\x22\x33");
+/// assert_rpeg_eq!(image, image.clone(), "writing {} changed it", "tiny.rpeg");
+/// ```
+///
+/// ```should_panic
+/// use csc411_rpegio::{assert_rpeg_eq, RpegImage};
+///
+/// let expected = RpegImage::new(vec![[0x00; 4]; 6], 4, 6);
+/// let mut actual = expected.clone();
+/// actual.words[3][1] = 0xFF;
+///
+/// // assertion `left == right` failed: rpeg images differ
+/// // 1 of 6 words differ, first at word 3 (row 1, column 1): 00000000 != 00FF0000
+/// //   left: 4x6, format 2, 6 words (24 bytes)
+/// //  right: 4x6, format 2, 6 words (24 bytes)
+/// // words 1 to 5:
+/// //   left: 00000000 00000000 00000000 00000000 00000000
+/// //  right: 00000000 00000000 00FF0000 00000000 00000000
+/// //                           ^^^^^^^^
+/// assert_rpeg_eq!(expected, actual);
+/// ```
+#[macro_export]
+macro_rules! assert_rpeg_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::__assert_rpeg_eq(&$left, &$right, None)
+    };
+    ($left:expr, $right:expr, $($message:tt)+) => {
+        $crate::__assert_rpeg_eq(&$left, &$right, Some(format_args!($($message)+)))
+    };
+}
+
+/// Something [`assert_rpeg_eq!`] can compare: an image, or the bytes of rpeg data.
+#[doc(hidden)]
+pub trait RpegOperand {
+    /// The image, or an error message if it's bytes which can't be parsed.
+    fn to_image(&self) -> Result<Cow<'_, RpegImage>, String>;
+}
+
+impl RpegOperand for RpegImage {
+    fn to_image(&self) -> Result<Cow<'_, RpegImage>, String> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl RpegOperand for [u8] {
+    fn to_image(&self) -> Result<Cow<'_, RpegImage>, String> {
+        RpegImage::from_bytes(self)
+            .map(Cow::Owned)
+            .map_err(|error| error.to_string())
+    }
+}
+
+impl<const LEN: usize> RpegOperand for [u8; LEN] {
+    fn to_image(&self) -> Result<Cow<'_, RpegImage>, String> {
+        self[..].to_image()
+    }
+}
+
+impl RpegOperand for Vec<u8> {
+    fn to_image(&self) -> Result<Cow<'_, RpegImage>, String> {
+        self[..].to_image()
+    }
+}
+
+impl<T: RpegOperand + ?Sized> RpegOperand for &T {
+    fn to_image(&self) -> Result<Cow<'_, RpegImage>, String> {
+        (**self).to_image()
+    }
+}
+
+/// The implementation of [`assert_rpeg_eq!`].
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_rpeg_eq(
+    left: &impl RpegOperand,
+    right: &impl RpegOperand,
+    message: Option<fmt::Arguments>,
+) {
+    let left = left.to_image();
+    let left = left.unwrap_or_else(|error| panic!("left isn't valid rpeg data: {error}"));
+    let right = right.to_image();
+    let right = right.unwrap_or_else(|error| panic!("right isn't valid rpeg data: {error}"));
+
+    if left == right {
+        return;
+    }
+
+    let explanation = explain(&left, &right);
+    match message {
+        Some(message) => panic!("assertion `left == right` failed: {message}\n{explanation}"),
+        None => panic!("assertion `left == right` failed: rpeg images differ\n{explanation}"),
+    }
+}
+
+/// Describes how two (different) images differ, for the message of a failed
+/// [`assert_rpeg_eq!`].
+fn explain(left: &RpegImage, right: &RpegImage) -> String {
+    let diff = compare_rpeg(left, right);
+    let mut explanation = String::new();
+
+    writeln!(explanation, "{diff}").unwrap();
+    writeln!(explanation, "  left: {left}").unwrap();
+    writeln!(explanation, " right: {right}").unwrap();
+
+    let Some(first) = diff.first_mismatch() else {
+        writeln!(explanation, "the words match, but the headers differ:").unwrap();
+        writeln!(explanation, "  left: {:?}", left.header).unwrap();
+        write!(explanation, " right: {:?}", right.header).unwrap();
+        return explanation;
+    };
+
+    let start = first.index.saturating_sub(EXCERPT_CONTEXT);
+    let end = (first.index + EXCERPT_CONTEXT + 1).min(diff.word_count);
+    writeln!(explanation, "words {start} to {}:", end - 1).unwrap();
+
+    let excerpt = |image: &RpegImage| {
+        let words = (start..end).map(|index| match image.words.get(index) {
+            Some(&word) => format!("{:08X}", u32::from_be_bytes(word)),
+            None => "--------".to_string(),
+        });
+        words.collect::<Vec<_>>().join(" ")
+    };
+    let differs = |index| left.words.get(index) != right.words.get(index);
+    let markers: Vec<&str> = (start..end)
+        .map(|index| {
+            if differs(index) {
+                "^^^^^^^^"
+            } else {
+                "        "
+            }
+        })
+        .collect();
+    writeln!(explanation, "  left: {}", excerpt(left)).unwrap();
+    writeln!(explanation, " right: {}", excerpt(right)).unwrap();
+    write!(explanation, "        {}", markers.join(" ").trim_end()).unwrap();
+
+    explanation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain() {
+        let left = RpegImage::new((0..8u8).map(|byte| [byte; 4]).collect(), 8, 4);
+        let mut right = left.clone();
+        right.words[6][0] = 0xAA;
+        right.words.pop();
+
+        assert_eq!(
+            explain(&left, &right),
+            "2 of 8 words differ, first at word 6 (row 1, column 2): 06060606 != AA060606\n  \
+             left: 8x4, format 2, 8 words (32 bytes)\n \
+             right: 8x4, format 2, 7 words (28 bytes)\n\
+             words 4 to 7:\n  \
+             left: 04040404 05050505 06060606 07070707\n \
+             right: 04040404 05050505 AA060606 --------\n        \
+             \x20                 ^^^^^^^^ ^^^^^^^^"
+        );
+
+        right = left.clone();
+        right.header.comments.push("different".to_string());
+        let explanation = explain(&left, &right);
+        assert!(explanation.starts_with("all 8 words match\n"));
+        assert!(explanation.contains("the headers differ"));
+    }
+
+    #[test]
+    fn test_assert_rpeg_eq() {
+        let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+        let bytes = image.to_bytes();
+        assert_rpeg_eq!(image, bytes);
+        assert_rpeg_eq!(&bytes[..], image, "with a {}", "message");
+
+        let mut other = image.clone();
+        other.words[0][3] = 0x34;
+        let failure = std::panic::catch_unwind(|| assert_rpeg_eq!(image, other, "word {}", 0));
+        let message = *failure.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.starts_with("assertion `left == right` failed: word 0\n1 of 1 words"));
+
+        let failure = std::panic::catch_unwind(|| assert_rpeg_eq!(image, b"not rpeg data"));
+        let message = *failure.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.starts_with("right isn't valid rpeg data: "));
+    }
+}
@@ -0,0 +1,103 @@
+//! Standard (RFC 4648) base64, with `=` padding.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `bytes` as base64, breaking it into lines of (at most) `line_len` characters, each
+/// ending in `\n`.
+pub(crate) fn encode(bytes: &[u8], line_len: usize) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (index, &byte)| {
+                group | (byte as u32) << (16 - 8 * index)
+            });
+
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * index) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    let mut lines = String::with_capacity(encoded.len() + encoded.len() / line_len + 1);
+    for line in encoded.as_bytes().chunks(line_len) {
+        // Base64 is always ASCII
+        lines.push_str(std::str::from_utf8(line).unwrap());
+        lines.push('\n');
+    }
+
+    lines
+}
+
+/// Decodes base64, ignoring any whitespace. On failure, returns the offset of the first
+/// character which couldn't be decoded.
+pub(crate) fn decode(text: &[u8]) -> Result<Vec<u8>, usize> {
+    let mut decoded = Vec::with_capacity(text.len() / 4 * 3);
+    let mut group = 0u32;
+    let mut group_len = 0;
+    let mut padding = 0;
+
+    for (offset, &byte) in text.iter().enumerate() {
+        let value = match byte {
+            _ if byte.is_ascii_whitespace() => continue,
+            b'=' if group_len >= 2 => {
+                padding += 1;
+                0
+            }
+            _ if padding > 0 => return Err(offset),
+            _ => match ALPHABET.iter().position(|&letter| letter == byte) {
+                Some(value) => value as u32,
+                None => return Err(offset),
+            },
+        };
+
+        group = group << 6 | value;
+        group_len += 1;
+
+        if group_len == 4 {
+            let bytes = group.to_be_bytes();
+            decoded.extend_from_slice(&bytes[1..4 - padding]);
+            (group, group_len) = (0, 0);
+        }
+    }
+
+    if group_len != 0 {
+        return Err(text.len());
+    }
+
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"", ""),
+            (b"f", "Zg==\n"),
+            (b"fo", "Zm8=\n"),
+            (b"foo", "Zm9v\n"),
+            (b"foob", "Zm9vYg==\n"),
+            (b"fooba", "Zm9vYmE=\n"),
+            (b"foobar", "Zm9vYmFy\n"),
+        ];
+
+        for &(bytes, text) in cases {
+            assert_eq!(encode(bytes, 76), text);
+            assert_eq!(decode(text.as_bytes()).unwrap(), bytes);
+        }
+
+        assert_eq!(encode(b"foobar", 4), "Zm9v\nYmFy\n");
+        assert_eq!(decode(b" Zm9v\r\nYmFy ").unwrap(), b"foobar");
+        assert_eq!(decode(b"Zm9v!"), Err(4));
+        assert_eq!(decode(b"Zm9"), Err(3));
+        assert_eq!(decode(b"Zg==Zg=="), Err(4));
+    }
+}
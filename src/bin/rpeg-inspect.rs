@@ -0,0 +1,62 @@
+//! Inspects the words of rpeg data interactively, optionally diffing them against a second file.
+//!
+//! Usage: `rpeg-inspect [--layout LAYOUT] [--page-len N] FILE [OTHER]`
+//!
+//! The layout defaults to the fields of the usual arith codeword,
+//! `a:9, b:5s, c:5s, d:5s, pb:4, pr:4`.
+
+use csc411_rpegio::{BitLayout, RpegImage, RpegInspector};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: rpeg-inspect [--layout LAYOUT] [--page-len N] FILE [OTHER]";
+
+const DEFAULT_LAYOUT: &str = "a:9, b:5s, c:5s, d:5s, pb:4, pr:4";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut layout = DEFAULT_LAYOUT.to_string();
+    let mut page_len = None;
+    let mut paths = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--layout" => layout = args.next().ok_or(USAGE)?,
+            "--page-len" => {
+                let len = args.next().ok_or(USAGE)?;
+                page_len = Some(len.parse().ok().filter(|&len| len > 0).ok_or(USAGE)?);
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() || paths.len() > 2 {
+        return Err(USAGE.to_string());
+    }
+
+    let read = |path: &str| RpegImage::read(Some(path)).map_err(|err| format!("{path}: {err}"));
+    let layout: BitLayout = layout.parse()?;
+    let mut inspector = RpegInspector::new(read(&paths[0])?).layout(layout);
+    if let Some(other) = paths.get(1) {
+        inspector = inspector.compare_with(read(other)?);
+    }
+    if let Some(page_len) = page_len {
+        inspector = inspector.page_len(page_len);
+    }
+
+    inspector
+        .run(std::io::stdin().lock(), std::io::stdout().lock())
+        .map_err(|err| err.to_string())
+}
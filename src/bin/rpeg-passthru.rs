@@ -0,0 +1,70 @@
+//! Copies rpeg data from stdin to stdout byte for byte, but only if it's valid, for putting
+//! between the stages of a pipeline to find which one corrupts the data:
+//!
+//! ```text
+//! compress image.ppm | rpeg-passthru --label compress | decompress
+//! ```
+//!
+//! Usage: `rpeg-passthru [--label LABEL]`
+//!
+//! The whole input is read, and checked as by `RpegImage::from_bytes` (checksums included),
+//! before any of it is written. Invalid data isn't written at all: what's wrong with it is
+//! printed on stderr, after the label if one is given, and the exit code is 1, so that the
+//! pipeline fails (with `set -o pipefail`) at the stage that went wrong.
+
+use csc411_rpegio::{RpegError, RpegImage};
+use std::io::{Read, Write};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: rpeg-passthru [--label LABEL]";
+
+fn main() -> ExitCode {
+    let mut label = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--label" if label.is_none() => match args.next() {
+                Some(next) => label = Some(next),
+                None => return usage(),
+            },
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ => return usage(),
+        }
+    }
+    let prefix = label.map_or("rpeg-passthru".to_string(), |label| {
+        format!("rpeg-passthru ({label})")
+    });
+
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        // The next stage stopped reading, which is its business
+        Err(RpegError::BrokenPipe) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{prefix}: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("{USAGE}");
+
+    ExitCode::FAILURE
+}
+
+fn run() -> Result<(), RpegError> {
+    let mut bytes = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut bytes)?;
+
+    RpegImage::from_bytes(&bytes)?;
+
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&bytes)?;
+    stdout.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+//! Converts rpeg data between the binary format and the readable text and debug formats, for
+//! inspecting and hand-editing compressed data.
+//!
+//! Usage: `rpegcat (--to-text [--hex] | --to-debug | --to-binary) [FILE]`
+//!
+//! Reads from stdin if no file (or `-`) is given, and always writes to stdout. `--to-text` and
+//! `--to-debug` read binary rpeg data. `--to-binary` reads data in the text format (as written
+//! by `--to-text`), the debug format (as written by `--to-debug`), or the binary format, which
+//! is rewritten in canonical form.
+
+use csc411_rpegio::{
+    debug_output_rpeg_data, read_in_debug_rpeg_data_from, RpegImage, RpegSource, TextStyle,
+};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: rpegcat (--to-text [--hex] | --to-debug | --to-binary) [FILE]";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    Text,
+    Debug,
+    Binary,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut output = None;
+    let mut style = TextStyle::Decimal;
+    let mut path = None;
+
+    for arg in std::env::args().skip(1) {
+        let mut set = |to| match output.replace(to) {
+            None => Ok(()),
+            Some(_) => Err(USAGE),
+        };
+        match arg.as_str() {
+            "--to-text" => set(Output::Text)?,
+            "--to-debug" => set(Output::Debug)?,
+            "--to-binary" => set(Output::Binary)?,
+            "--hex" => style = TextStyle::Hex,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            "-" if path.is_none() => path = Some(None),
+            _ if path.is_none() => path = Some(Some(arg)),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let output = output.ok_or(USAGE)?;
+    if style == TextStyle::Hex && output != Output::Text {
+        return Err(USAGE.to_string());
+    }
+
+    let path = path.flatten();
+    let name = path.as_deref().unwrap_or("(stdin)");
+    let bytes = path
+        .as_deref()
+        .read_bytes()
+        .map_err(|err| format!("{name}: {err}"))?;
+
+    match output {
+        Output::Text => {
+            let image = RpegImage::from_bytes(&bytes).map_err(|err| format!("{name}: {err}"))?;
+            image.write_text(style)
+        }
+        Output::Debug => {
+            let image = RpegImage::from_bytes(&bytes).map_err(|err| format!("{name}: {err}"))?;
+            debug_output_rpeg_data(&image.words, image.width(), image.height());
+            Ok(())
+        }
+        Output::Binary => parse_any(&bytes)
+            .map_err(|err| format!("{name}: {err}"))?
+            .write(),
+    }
+    .map_err(|err| err.to_string())
+}
+
+/// Parses data in the text, debug, or binary format, whichever it's in.
+fn parse_any(bytes: &[u8]) -> Result<RpegImage, String> {
+    let text_error = match std::str::from_utf8(bytes).map(RpegImage::from_text) {
+        Ok(Ok(image)) => return Ok(image),
+        Ok(Err(err)) => err.to_string(),
+        Err(_) => "the input isn't UTF-8".to_string(),
+    };
+    if let Ok((words, width, height)) = read_in_debug_rpeg_data_from(bytes) {
+        return Ok(RpegImage::new(words, width, height));
+    }
+    if let Ok(image) = RpegImage::from_bytes(bytes) {
+        return Ok(image);
+    }
+
+    Err(format!(
+        "Expected rpeg data in the text, debug, or binary format (as text: {text_error})"
+    ))
+}
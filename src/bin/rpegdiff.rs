@@ -0,0 +1,116 @@
+//! Compares two rpeg files word by word, and prints what differs: their sizes, how many words
+//! differ, and the first few differing words in hex (with the fields that changed, given a
+//! layout). Like `diff`, exits with 0 if the files match, 1 if they differ, and 2 on an error.
+//!
+//! Usage: `rpegdiff [--layout LAYOUT] [--show N] [--emit-patch PATCH] EXPECTED ACTUAL`
+//!
+//! `--show` sets how many differing words are printed (10 by default). `--emit-patch` also
+//! writes a patch to the file `PATCH` which turns `EXPECTED` into `ACTUAL`, for `rpegpatch
+//! apply`, so that a large image can be shared as a small patch against one that's already
+//! been shared.
+
+use csc411_rpegio::{compare_rpeg, diff, BitLayout, RpegImage};
+use std::process::ExitCode;
+
+const USAGE: &str =
+    "Usage: rpegdiff [--layout LAYOUT] [--show N] [--emit-patch PATCH] EXPECTED ACTUAL";
+
+const DEFAULT_SHOWN: usize = 10;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Prints the differences, returning whether the files match.
+fn run() -> Result<bool, String> {
+    let mut layout = None;
+    let mut shown = DEFAULT_SHOWN;
+    let mut patch_path = None;
+    let mut paths = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--layout" => layout = Some(args.next().ok_or(USAGE)?.parse::<BitLayout>()?),
+            "--show" => shown = args.next().and_then(|n| n.parse().ok()).ok_or(USAGE)?,
+            "--emit-patch" => patch_path = Some(args.next().ok_or(USAGE)?),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(true);
+            }
+            _ => paths.push(arg),
+        }
+    }
+    let [expected_path, actual_path] = paths.as_slice() else {
+        return Err(USAGE.to_string());
+    };
+
+    let read = |path: &str| RpegImage::read(Some(path)).map_err(|err| format!("{path}: {err}"));
+    let expected = read(expected_path)?;
+    let actual = read(actual_path)?;
+    if let Some(patch_path) = &patch_path {
+        std::fs::write(patch_path, diff(&expected, &actual).to_bytes())
+            .map_err(|err| format!("{patch_path}: {err}"))?;
+    }
+    let diff = compare_rpeg(&expected, &actual);
+
+    if diff.is_identical() {
+        println!(
+            "{expected_path} and {actual_path} match ({} words)",
+            diff.word_count
+        );
+        return Ok(true);
+    }
+
+    if !diff.dimensions_match() {
+        let (left, right) = (diff.left_size, diff.right_size);
+        println!(
+            "dimensions differ: {}x{} in {expected_path}, {}x{} in {actual_path}",
+            left.0, left.1, right.0, right.1
+        );
+    }
+    println!(
+        "{} of {} words differ",
+        diff.differing_words(),
+        diff.word_count
+    );
+
+    let word = |word: Option<[u8; 4]>| match word {
+        Some(word) => format!("{:08X}", u32::from_be_bytes(word)),
+        None => "(none)".to_string(),
+    };
+    for mismatch in diff.mismatches.iter().take(shown) {
+        print!(
+            "word {} (row {}, column {}): {} != {}",
+            mismatch.index,
+            mismatch.row,
+            mismatch.column,
+            word(mismatch.left),
+            word(mismatch.right)
+        );
+
+        if let (Some(layout), Some(left), Some(right)) = (&layout, mismatch.left, mismatch.right) {
+            let changed: Vec<String> = layout
+                .fields()
+                .filter(|(_, field)| field.value(left) != field.value(right))
+                .map(|(name, field)| {
+                    format!("{name}: {} -> {}", field.value(left), field.value(right))
+                })
+                .collect();
+            print!("  {}", changed.join(", "));
+        }
+        println!();
+    }
+    if diff.differing_words() > shown {
+        println!("... and {} more", diff.differing_words() - shown);
+    }
+
+    Ok(false)
+}
@@ -0,0 +1,139 @@
+//! Rewrites rpeg data in the canonical form every writer in this crate produces (see
+//! `canonicalize`), so that it can be compared byte for byte against a reference
+//! implementation's output. Describes what it changed on stderr.
+//!
+//! Usage: `rpegfmt [--in-place] [--fix-checksum] [FILE]`
+//!
+//! Reads from stdin if no file (or `-`) is given, and writes to stdout unless `--in-place` is
+//! given, which overwrites the file instead. Data whose checksum is wrong is an error, unless
+//! `--fix-checksum` is given, which recomputes it from the payload.
+
+use csc411_rpegio::{RpegError, RpegImage, RpegSource};
+use std::io::Write;
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: rpegfmt [--in-place] [--fix-checksum] [FILE]";
+
+/// The most bytes of a change shown in the description of it.
+const MAX_SHOWN: usize = 80;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut in_place = false;
+    let mut fix_checksum = false;
+    let mut path = None;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--in-place" => in_place = true,
+            "--fix-checksum" => fix_checksum = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let path = path.filter(|path| path != "-");
+    if in_place && path.is_none() {
+        return Err("--in-place needs a file".to_string());
+    }
+
+    let name = path.as_deref().unwrap_or("(stdin)");
+    let input = path
+        .as_deref()
+        .read_bytes()
+        .map_err(|err| format!("{name}: {err}"))?;
+
+    let output = match RpegImage::from_bytes(&input) {
+        Ok(image) => image.to_bytes(),
+        Err(RpegError::ChecksumMismatch { expected, found }) if fix_checksum => {
+            let mut image = RpegImage::from_bytes(&without_checksum(&input))
+                .map_err(|err| format!("{name}: {err}"))?;
+            image.header.checksum = true;
+            eprintln!("recomputed the checksum: 0x{expected:08X} -> 0x{found:08X}");
+            image.to_bytes()
+        }
+        Err(err) => return Err(format!("{name}: {err}")),
+    };
+    describe_changes(&input, &output);
+
+    let written = match &path {
+        Some(path) if in_place => std::fs::write(path, &output),
+        _ => {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(&output).and_then(|()| stdout.flush())
+        }
+    };
+    written.map_err(|err| err.to_string())
+}
+
+/// The data with its checksum removed: the `c` after the version on its first line, and the
+/// four bytes at the end.
+fn without_checksum(bytes: &[u8]) -> Vec<u8> {
+    let line_len = bytes
+        .iter()
+        .position(|&byte| byte == b'\n' || byte == b'\r')
+        .unwrap_or(bytes.len());
+    // The flags follow the version's digits, such as the "ct" of "Compressed image format 2ct"
+    let digits_end = bytes[..line_len]
+        .iter()
+        .rposition(u8::is_ascii_digit)
+        .map_or(0, |index| index + 1);
+    let flag = bytes[digits_end..line_len]
+        .iter()
+        .position(|&byte| byte == b'c')
+        .map(|index| digits_end + index);
+
+    let mut stripped = bytes[..bytes.len().saturating_sub(4)].to_vec();
+    if let Some(flag) = flag {
+        stripped.remove(flag);
+    }
+
+    stripped
+}
+
+/// Describes on stderr the bytes which differ between the input and output, as one span from
+/// the first difference to the last.
+fn describe_changes(input: &[u8], output: &[u8]) {
+    if input == output {
+        eprintln!("already canonical");
+        return;
+    }
+
+    let prefix = input.iter().zip(output).take_while(|(a, b)| a == b).count();
+    let max_suffix = input.len().min(output.len()) - prefix;
+    let suffix = input
+        .iter()
+        .rev()
+        .zip(output.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old = &input[prefix..input.len() - suffix];
+    let new = &output[prefix..output.len() - suffix];
+    if old.len().max(new.len()) > MAX_SHOWN {
+        eprintln!(
+            "changed {} bytes at offset {prefix} into {} bytes",
+            old.len(),
+            new.len()
+        );
+    } else {
+        eprintln!(
+            "changed \"{}\" at offset {prefix} to \"{}\"",
+            old.escape_ascii(),
+            new.escape_ascii()
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Writes a valid rpeg image of the given size to stdout, with one word for each 2x2 block, for
+//! testing a decompressor before there's a compressor to make its input.
+//!
+//! Usage: `rpeggen [--constant WORD | --gradient | --random SEED] WIDTH HEIGHT`
+//!
+//! The words follow one of these patterns:
+//!
+//! * `--constant WORD` - Every word is `WORD`, in hex (such as `0x00112233`)
+//! * `--gradient` - The 9-bit `a` field at the top of each word (the average brightness of the
+//!   block, in the usual arith codeword) rises from 0 at the top left to 511 at the bottom
+//!   right, and every other bit is 0. Decompressed, it's a smooth gray gradient
+//! * `--random SEED` - Pseudo-random words, as from `generate_rpeg`, which are always the
+//!   same for the same size and seed. This is the default, with a seed of 0
+
+use csc411_rpegio::{generate_rpeg, RpegImage};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: rpeggen [--constant WORD | --gradient | --random SEED] WIDTH HEIGHT";
+
+/// The largest value of the `a` field of the usual arith codeword.
+const MAX_A: u64 = (1 << 9) - 1;
+
+enum Pattern {
+    Constant(u32),
+    Gradient,
+    Random(u64),
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut pattern = Pattern::Random(0);
+    let mut dimensions = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--constant" => {
+                let word = args.next().ok_or(USAGE)?;
+                let digits = word.strip_prefix("0x").unwrap_or(&word);
+                let word = u32::from_str_radix(digits, 16)
+                    .map_err(|_| format!("Expected a word in hex, found {word:?}"))?;
+                pattern = Pattern::Constant(word);
+            }
+            "--gradient" => pattern = Pattern::Gradient,
+            "--random" => {
+                let seed = args.next().ok_or(USAGE)?;
+                pattern = Pattern::Random(seed.parse().map_err(|_| USAGE)?);
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => dimensions.push(arg.parse::<u32>().map_err(|_| USAGE)?),
+        }
+    }
+    let [width, height] = dimensions[..] else {
+        return Err(USAGE.to_string());
+    };
+
+    let image = match pattern {
+        Pattern::Random(seed) => generate_rpeg(width, height, seed),
+        Pattern::Constant(word) => {
+            let len = (width / 2) as usize * (height / 2) as usize;
+            RpegImage::new(vec![word.to_be_bytes(); len], width, height)
+        }
+        Pattern::Gradient => {
+            let (cols, rows) = ((width / 2) as u64, (height / 2) as u64);
+            // The distance along the diagonal, from 0 at the top left corner
+            let steps = (cols + rows).saturating_sub(2).max(1);
+            let words = (0..rows)
+                .flat_map(|row| (0..cols).map(move |col| (row + col) * MAX_A / steps))
+                .map(|a| ((a as u32) << 23).to_be_bytes())
+                .collect();
+            RpegImage::new(words, width, height)
+        }
+    };
+
+    image.write().map_err(|err| err.to_string())
+}
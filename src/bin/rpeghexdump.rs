@@ -0,0 +1,140 @@
+//! Dumps rpeg data in hex, knowing its layout: the header as text, then the payload as words,
+//! each line labelled with its offset in the file, the index of its first word, and the row and
+//! column of that word's 2x2 block, then any trailers.
+//!
+//! Usage: `rpeghexdump [--word N] [--row R] [--count K] [FILE]`
+//!
+//! Reads from stdin if no file (or `-`) is given. `--word N` starts the payload at word `N`,
+//! and `--row R` shows only row `R` of blocks; either way, `--count K` sets the most words shown
+//! (by default, 16 from `--word`, or the whole row). Data whose header can't be parsed is
+//! dumped as plain bytes.
+
+use csc411_rpegio::{hexdump, RpegImage, RpegReader, RpegSource};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: rpeghexdump [--word N] [--row R] [--count K] [FILE]";
+
+/// The most words on each line of the payload.
+const WORDS_PER_LINE: usize = 4;
+
+/// The number of words shown from `--word N`, unless `--count` says otherwise.
+const DEFAULT_COUNT: usize = 16;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut word = None;
+    let mut row = None;
+    let mut count = None;
+    let mut path = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut number = || {
+            args.next()
+                .and_then(|n| n.parse::<usize>().ok())
+                .ok_or(USAGE)
+        };
+        match arg.as_str() {
+            "--word" => word = Some(number()?),
+            "--row" => row = Some(number()?),
+            "--count" => count = Some(number()?),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    if word.is_some() && row.is_some() {
+        return Err(USAGE.to_string());
+    }
+    let path = path.filter(|path| path != "-");
+    let name = path.as_deref().unwrap_or("(stdin)");
+    let bytes = path
+        .as_deref()
+        .read_bytes()
+        .map_err(|err| format!("{name}: {err}"))?;
+
+    let reader = match RpegReader::new(&bytes[..]) {
+        Ok(reader) => reader,
+        Err(err) => {
+            println!("invalid header: {err}");
+            print!("{}", hexdump(&bytes));
+            return Ok(());
+        }
+    };
+    let header_len = reader.stats().header_bytes as usize;
+    let row_len = (reader.header().width as usize / 2).max(1);
+
+    println!("header ({header_len} bytes):");
+    for line in bytes[..header_len].split_inclusive(|&byte| byte == b'\n') {
+        println!("  {}", line.escape_ascii());
+    }
+
+    // Everything after the payload's words is trailers, if the data can be parsed
+    let rest = &bytes[header_len..];
+    let payload_len = match RpegImage::from_bytes(&bytes) {
+        Ok(image) => image.words.len() * 4,
+        Err(err) => {
+            println!("invalid payload: {err}");
+            rest.len()
+        }
+    };
+    let (payload, trailers) = rest.split_at(payload_len);
+    let words: Vec<&[u8]> = payload.chunks(4).collect();
+
+    let (start, end) = match (word, row) {
+        (Some(word), _) => (word, word.saturating_add(count.unwrap_or(DEFAULT_COUNT))),
+        (_, Some(row)) => {
+            let start = row.saturating_mul(row_len);
+            (start, start.saturating_add(count.unwrap_or(row_len)))
+        }
+        _ => (0, count.unwrap_or(usize::MAX)),
+    };
+    let (start, end) = (start.min(words.len()), end.min(words.len()));
+
+    println!("payload ({} words):", words.len());
+    let index_digits = words.len().saturating_sub(1).to_string().len();
+    let mut index = start;
+    while index < end {
+        // Lines break at the end of each row of blocks, as well as every few words
+        let line_end = end
+            .min(index + WORDS_PER_LINE)
+            .min((index / row_len + 1) * row_len);
+        let hex: Vec<String> = words[index..line_end]
+            .iter()
+            .map(|word| word.iter().map(|byte| format!("{byte:02x}")).collect())
+            .collect();
+        println!(
+            "  {:08x}  {index:>index_digits$}  r{} c{}:  {}",
+            header_len + index * 4,
+            index / row_len,
+            index % row_len,
+            hex.join(" ")
+        );
+        index = line_end;
+    }
+
+    if !trailers.is_empty() {
+        println!("trailers ({} bytes):", trailers.len());
+        let offset = header_len + payload_len;
+        for line in hexdump(trailers).lines() {
+            // Shift the offsets of the dump to where the trailers are in the file
+            let (line_offset, rest) = line.split_once(':').unwrap_or(("0", line));
+            let line_offset = usize::from_str_radix(line_offset, 16).unwrap_or(0);
+            println!("  {:08x}:{rest}", offset + line_offset);
+        }
+    }
+
+    Ok(())
+}
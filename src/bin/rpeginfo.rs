@@ -0,0 +1,107 @@
+//! Prints a summary of rpeg data: its format version, dimensions, number of codewords, payload
+//! size, and whether it's valid. Exits unsuccessfully if it isn't.
+//!
+//! Usage: `rpeginfo [FILE]`
+//!
+//! Reads from stdin if no file (or `-`) is given.
+
+use csc411_rpegio::{RpegHeader, RpegImage, RpegReader, RpegSource};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: rpeginfo [FILE]";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints the summary, returning whether the data is valid.
+fn run() -> Result<bool, String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let path = match args.as_slice() {
+        [] => None,
+        [arg] if arg == "-h" || arg == "--help" => {
+            println!("{USAGE}");
+            return Ok(true);
+        }
+        [arg] if arg == "-" => None,
+        [arg] => Some(arg.as_str()),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let name = path.unwrap_or("(stdin)");
+    let bytes = path.read_bytes().map_err(|err| format!("{name}: {err}"))?;
+
+    println!("file:           {name}");
+    println!("size:           {} bytes", bytes.len());
+
+    let image = RpegImage::from_bytes(&bytes);
+    let header = match &image {
+        Ok(image) => Some(image.header.clone()),
+        // The header may be fine even though the rest isn't
+        Err(_) => RpegReader::new(&bytes[..])
+            .ok()
+            .map(|reader| reader.header().clone()),
+    };
+    if let Some(header) = &header {
+        print_header(header);
+    }
+
+    let status = image.map_err(|err| err.to_string()).and_then(|image| {
+        let words = image.words.len();
+        let blocks = (image.width() / 2) as usize * (image.height() / 2) as usize;
+        println!("codewords:      {words}");
+        println!("payload size:   {} bytes", words * 4);
+
+        match words == blocks {
+            true => Ok(()),
+            false => Err(format!(
+                "has {words} codewords, but a {}x{} image needs {blocks}",
+                image.width(),
+                image.height()
+            )),
+        }
+    });
+
+    match status {
+        Ok(()) => {
+            println!("status:         valid");
+            Ok(true)
+        }
+        Err(problem) => {
+            println!("status:         invalid: {problem}");
+            Ok(false)
+        }
+    }
+}
+
+fn print_header(header: &RpegHeader) {
+    let mut trailers = Vec::new();
+    if header.word_count {
+        trailers.push("word count");
+    }
+    if header.checksum {
+        trailers.push("checksum");
+    }
+    if header.signed {
+        trailers.push("signature");
+    }
+
+    println!("format version: {}", header.version);
+    println!("dimensions:     {}x{}", header.width, header.height);
+    if header.binary {
+        println!("header:         binary");
+    }
+    if !trailers.is_empty() {
+        println!("trailers:       {}", trailers.join(", "));
+    }
+    if !header.comments.is_empty() {
+        println!("comments:       {}", header.comments.len());
+    }
+}
@@ -0,0 +1,64 @@
+//! Applies a patch made by `rpegdiff --emit-patch` to the rpeg file it was made from, giving
+//! back the file it was made against, so that large reference images can be shared as small
+//! patches against a base image.
+//!
+//! Usage: `rpegpatch apply [--output OUTPUT] BASE PATCH`
+//!
+//! Writes the patched image to stdout, or to the file `OUTPUT`. A patch records a checksum of
+//! its base image, so applying it to any other image is an error.
+
+use csc411_rpegio::{apply, RpegImage, RpegPatch};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: rpegpatch apply [--output OUTPUT] BASE PATCH";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("apply") => {}
+        Some("-h" | "--help") => {
+            println!("{USAGE}");
+            return Ok(());
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+
+    let mut output = None;
+    let mut paths = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" | "-o" if output.is_none() => output = Some(args.next().ok_or(USAGE)?),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ => paths.push(arg),
+        }
+    }
+    let [base_path, patch_path] = paths.as_slice() else {
+        return Err(USAGE.to_string());
+    };
+
+    let base = RpegImage::read(Some(base_path)).map_err(|err| format!("{base_path}: {err}"))?;
+    let patch = std::fs::read(patch_path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| RpegPatch::from_bytes(&bytes).map_err(|err| err.to_string()))
+        .map_err(|err| format!("{patch_path}: {err}"))?;
+    let patched = apply(&base, &patch).map_err(|err| format!("{base_path}: {err}"))?;
+
+    match &output {
+        Some(output) => patched.write_file(output),
+        None => patched.write(),
+    }
+    .map_err(|err| err.to_string())
+}
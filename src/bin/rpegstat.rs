@@ -0,0 +1,175 @@
+//! Prints statistics of the payloads of rpeg files: their entropy, their most common bytes, their
+//! longest runs of identical bytes, and their compression ratios, for lab write-ups.
+//!
+//! Usage: `rpegstat [--json] FILE...`
+//!
+//! The compression ratio of each file is against a PPM of the same dimensions (with a `P6`
+//! header and a maxval of 255), as the original image isn't known. `--json` prints an array of
+//! one object for each file instead, with the whole histogram. Files which can't be read are
+//! reported on stderr, and make the exit code 1, but the rest are still summarized.
+
+use csc411_rpegio::{CompressionReport, PayloadStats, RpegImage, RpegSource};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: rpegstat [--json] FILE...";
+
+/// The number of most common bytes in the summary of each file.
+const TOP_BYTES: usize = 4;
+
+fn main() -> ExitCode {
+    let mut json = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    }
+
+    let mut failed = false;
+    let mut summaries = Vec::new();
+    for path in &paths {
+        let image = path
+            .as_str()
+            .read_bytes()
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| RpegImage::from_bytes(&bytes).map_err(|err| err.to_string()));
+        match image {
+            Ok(image) => {
+                let ppm_bytes = ppm_size(image.width(), image.height());
+                let (stats, report) = (image.stats(), image.compression_report(ppm_bytes));
+                summaries.push(match json {
+                    true => to_json(path, &image, &stats, &report),
+                    false => summarize(path, &image, &stats, &report),
+                });
+            }
+            Err(err) => {
+                eprintln!("{path}: {err}");
+                failed = true;
+            }
+        }
+    }
+
+    match json {
+        true => println!("[{}]", summaries.join(",")),
+        false => print!("{}", summaries.join("\n")),
+    }
+
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
+
+/// The size of a binary PPM of the given dimensions, in bytes.
+fn ppm_size(width: u32, height: u32) -> u64 {
+    let header = format!("P6\n{width} {height}\n255\n");
+
+    header.len() as u64 + width as u64 * height as u64 * 3
+}
+
+/// The byte values of the payload from most to least common, with how often each appears.
+fn most_common(stats: &PayloadStats) -> Vec<(u8, u64)> {
+    let mut counts: Vec<(u8, u64)> = (0..=255).zip(stats.histogram).collect();
+    // Stable, so that ties stay in order of byte value
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    counts
+}
+
+fn summarize(
+    path: &str,
+    image: &RpegImage,
+    stats: &PayloadStats,
+    report: &CompressionReport,
+) -> String {
+    let len = stats.len().max(1) as f64;
+    let top: Vec<String> = most_common(stats)
+        .into_iter()
+        .take(TOP_BYTES)
+        .filter(|&(_, count)| count > 0)
+        .map(|(byte, count)| format!("0x{byte:02X} ({:.1}%)", count as f64 * 100.0 / len))
+        .collect();
+    let longest_run = match stats.longest_run_byte {
+        Some(byte) => format!("{} x 0x{byte:02X}", stats.longest_run),
+        None => "none".to_string(),
+    };
+
+    format!(
+        "{path}:\n  \
+         dimensions:  {}x{}, {} words\n  \
+         entropy:     {:.3} bits per byte\n  \
+         most common: {}\n  \
+         longest run: {longest_run}\n  \
+         compression: {report}\n",
+        image.width(),
+        image.height(),
+        image.words.len(),
+        stats.entropy,
+        match top.is_empty() {
+            true => "none".to_string(),
+            false => top.join(", "),
+        },
+    )
+}
+
+fn to_json(
+    path: &str,
+    image: &RpegImage,
+    stats: &PayloadStats,
+    report: &CompressionReport,
+) -> String {
+    let histogram: Vec<String> = stats.histogram.iter().map(u64::to_string).collect();
+    let longest_run_byte = stats
+        .longest_run_byte
+        .map_or("null".to_string(), |byte| byte.to_string());
+
+    format!(
+        "{{\"file\":{},\"width\":{},\"height\":{},\"words\":{},\"entropy\":{},\
+         \"longest_run\":{},\"longest_run_byte\":{longest_run_byte},\"ppm_bytes\":{},\
+         \"rpeg_bytes\":{},\"compression_ratio\":{},\"bits_per_pixel\":{},\"histogram\":[{}]}}",
+        json_string(path),
+        image.width(),
+        image.height(),
+        image.words.len(),
+        json_number(stats.entropy),
+        stats.longest_run,
+        report.ppm_bytes,
+        report.rpeg_bytes,
+        json_number(report.ratio()),
+        json_number(report.bits_per_pixel()),
+        histogram.join(","),
+    )
+}
+
+/// A string as a JSON string literal.
+fn json_string(string: &str) -> String {
+    let mut json = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+
+    json
+}
+
+/// A number as JSON, which has no infinities or NaN (as from an empty image).
+fn json_number(number: f64) -> String {
+    match number.is_finite() {
+        true => number.to_string(),
+        false => "null".to_string(),
+    }
+}
@@ -0,0 +1,207 @@
+//! Checks whether a file is valid rpeg data, printing what's wrong with it if it isn't, and
+//! exiting with a code for the kind of problem, for Makefiles and autograders to branch on.
+//!
+//! Usage: `rpegvalidate [--quiet] [--reference REFERENCE] [--watch] [FILE]`
+//!
+//! Reads from stdin if no file (or `-`) is given. `--quiet` prints nothing, leaving only the
+//! exit code. `--reference` also compares valid data word by word against a reference file.
+//!
+//! `--watch` checks the file again whenever it changes, until interrupted (with Ctrl-C), for
+//! instant feedback while working on a compressor. It checks how the file has changed a few
+//! times a second, and waits for it to stop changing, so that a half-written file isn't
+//! reported as invalid.
+//!
+//! The exit codes are part of the crate's stability guarantees:
+//!
+//! | Code | Meaning |
+//! |------|---------|
+//! | 0 | The data is valid |
+//! | 1 | The input couldn't be read, or the arguments were wrong |
+//! | 2 | Bad magic: the data doesn't start with `Compressed image format` |
+//! | 3 | Bad dimensions: the rest of the header (such as the width and height) is invalid |
+//! | 4 | Misaligned payload: the payload isn't a whole number of words, or its trailers (such as a checksum) are wrong |
+//! | 5 | Size mismatch: the number of words isn't one for each 2x2 block of the image |
+//! | 6 | The data is valid, but differs from the `--reference` |
+
+use csc411_rpegio::{compare_rpeg, RpegImage, RpegReader, RpegSource};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+const USAGE: &str = "Usage: rpegvalidate [--quiet] [--reference REFERENCE] [--watch] [FILE]";
+
+/// The start of every rpeg file.
+const MAGIC: &[u8] = b"Compressed image format ";
+
+/// How long to wait between looking at a watched file.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The exit code for data which differs from the reference.
+const DIFFERS: u8 = 6;
+
+/// A kind of problem with the data, each with its own exit code.
+#[derive(Clone, Copy)]
+enum Failure {
+    BadMagic = 2,
+    BadDimensions = 3,
+    MisalignedPayload = 4,
+    SizeMismatch = 5,
+}
+
+impl Failure {
+    fn name(self) -> &'static str {
+        match self {
+            Failure::BadMagic => "bad magic",
+            Failure::BadDimensions => "bad dimensions",
+            Failure::MisalignedPayload => "misaligned payload",
+            Failure::SizeMismatch => "size mismatch",
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut quiet = false;
+    let mut watch = false;
+    let mut reference = None;
+    let mut path = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--quiet" => quiet = true,
+            "--watch" => watch = true,
+            "--reference" if reference.is_none() => reference = args.next(),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::from(1);
+            }
+        }
+    }
+    let path = path.filter(|path| path != "-");
+
+    if !watch {
+        return ExitCode::from(check(path.as_deref(), reference.as_deref(), quiet));
+    }
+    let Some(path) = path else {
+        eprintln!("--watch needs a file");
+        return ExitCode::from(1);
+    };
+
+    // The state of the file when it was last checked, and when it was last looked at
+    let mut checked = None;
+    let mut previous = None;
+    loop {
+        let current = file_state(&path);
+        // Only checks once the file has stayed the same for a poll, so it isn't half-written
+        if current != checked && current == previous {
+            check(Some(&path), reference.as_deref(), quiet);
+            if !quiet {
+                println!("--- watching {path} for changes (Ctrl-C to stop)");
+            }
+            checked = current;
+        }
+        previous = current;
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// When the file was last modified and how long it is, or None if it can't be found.
+fn file_state(path: &str) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Checks the data at the path (or stdin), and compares it against the reference if there is
+/// one, printing the results unless `quiet`. Returns the exit code for the results.
+fn check(path: Option<&str>, reference: Option<&str>, quiet: bool) -> u8 {
+    let name = path.unwrap_or("(stdin)");
+
+    let bytes = match path.read_bytes() {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("{name}: {err}");
+            return 1;
+        }
+    };
+
+    let image = match validate(&bytes) {
+        Ok(image) => image,
+        Err((failure, problem)) => {
+            if !quiet {
+                println!("{name}: invalid, {}: {problem}", failure.name());
+            }
+            return failure as u8;
+        }
+    };
+    let summary = format!(
+        "{}x{}, {} words",
+        image.width(),
+        image.height(),
+        image.words.len()
+    );
+
+    let Some(reference) = reference else {
+        if !quiet {
+            println!("{name}: valid ({summary})");
+        }
+        return 0;
+    };
+    let expected = match RpegImage::read(Some(reference)) {
+        Ok(expected) => expected,
+        Err(err) => {
+            eprintln!("{reference}: {err}");
+            return 1;
+        }
+    };
+    let diff = compare_rpeg(&expected, &image);
+    if !quiet {
+        match diff.is_identical() {
+            true => println!("{name}: valid ({summary}), and matches {reference}"),
+            false => println!("{name}: valid ({summary}), but differs from {reference}: {diff}"),
+        }
+    }
+
+    match diff.is_identical() {
+        true => 0,
+        false => DIFFERS,
+    }
+}
+
+/// Validates the data a stage at a time, so that a problem is put down to the first stage it
+/// happens in.
+fn validate(bytes: &[u8]) -> Result<RpegImage, (Failure, String)> {
+    let image = match RpegImage::from_bytes(bytes) {
+        Ok(image) => image,
+        Err(err) => {
+            let failure = if !bytes.starts_with(MAGIC) && !is_gzip(bytes) {
+                Failure::BadMagic
+            } else if RpegReader::new(bytes).is_err() {
+                Failure::BadDimensions
+            } else {
+                Failure::MisalignedPayload
+            };
+            return Err((failure, err.to_string()));
+        }
+    };
+
+    let (width, height) = (image.width(), image.height());
+    let words = image.words.len();
+    let blocks = (width / 2) as usize * (height / 2) as usize;
+    match words == blocks {
+        true => Ok(image),
+        false => Err((
+            Failure::SizeMismatch,
+            format!("a {width}x{height} image needs {blocks} words, but has {words}"),
+        )),
+    }
+}
+
+/// Whether the data is gzip-compressed, which the readers decompress (with the `gzip` feature).
+fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0x1F, 0x8B])
+}
@@ -0,0 +1,115 @@
+//! Draws a field of every word of an rpeg file in the terminal, as a heatmap with a character
+//! for each 2x2 block (see `RpegImage::field_heatmap`), so that spatial bugs, such as a
+//! transposed image or a field that's always zero, can be spotted over SSH.
+//!
+//! Usage: `rpegview [--layout LAYOUT] [--field NAME] [--shades] [--zoom N] [--rows START:END]
+//! FILE`
+//!
+//! The layout defaults to the fields of the usual arith codeword,
+//! `a:9, b:5s, c:5s, d:5s, pb:4, pr:4`, and the field to its first one. The heatmap's
+//! characters run from light to dark as the magnitude of the field grows. `--shades` draws it
+//! with Unicode shading characters instead of ASCII. `--zoom N` draws each block as `N`
+//! characters across and `N` lines down, and `--rows START:END` only draws the rows of blocks
+//! from `START` up to (but not including) `END`, either of which can be left out.
+
+use csc411_rpegio::{BitLayout, HeatmapStyle, RpegImage};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: rpegview [--layout LAYOUT] [--field NAME] [--shades] [--zoom N] \
+                     [--rows START:END] FILE";
+
+const DEFAULT_LAYOUT: &str = "a:9, b:5s, c:5s, d:5s, pb:4, pr:4";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut layout = DEFAULT_LAYOUT.to_string();
+    let mut field_name = None;
+    let mut style = HeatmapStyle::Ascii;
+    let mut zoom = 1;
+    let mut rows = (0, usize::MAX);
+    let mut path = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--layout" => layout = args.next().ok_or(USAGE)?,
+            "--field" => field_name = Some(args.next().ok_or(USAGE)?),
+            "--shades" => style = HeatmapStyle::Shades,
+            "--zoom" => {
+                let n = args.next().ok_or(USAGE)?;
+                zoom = n.parse().ok().filter(|&n| n > 0).ok_or(USAGE)?;
+            }
+            "--rows" => rows = parse_range(&args.next().ok_or(USAGE)?).ok_or(USAGE)?,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let path = path.ok_or(USAGE)?;
+
+    let layout: BitLayout = layout.parse()?;
+    let (field_name, field) = match &field_name {
+        Some(name) => {
+            let field = layout.get(name).ok_or_else(|| {
+                let names: Vec<&str> = layout.fields().map(|(name, _)| name).collect();
+                format!(
+                    "No field {name:?} in the layout (it has {})",
+                    names.join(", ")
+                )
+            })?;
+            (name.as_str(), field)
+        }
+        None => layout.fields().next().ok_or("The layout has no fields")?,
+    };
+
+    let image = RpegImage::read(Some(&path)).map_err(|err| format!("{path}: {err}"))?;
+    let heatmap = image
+        .field_heatmap(field, style)
+        .map_err(|err| format!("{path}: {err}"))?;
+    let stats = image.field_stats(field);
+
+    let lines: Vec<&str> = heatmap.lines().collect();
+    let (start, end) = (rows.0.min(lines.len()), rows.1.min(lines.len()));
+    println!(
+        "{path}: field {field_name}, rows {start} to {end} of {}{}",
+        lines.len(),
+        match stats {
+            Some(stats) => format!(", values {} to {}", stats.min, stats.max),
+            None => String::new(),
+        }
+    );
+    for line in &lines[start..end.max(start)] {
+        let line: String = line
+            .chars()
+            .flat_map(|c| std::iter::repeat_n(c, zoom))
+            .collect();
+        for _ in 0..zoom {
+            println!("{line}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a range of rows such as `10:20`, `10:`, or `:20`.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (start, end) = range.split_once(':')?;
+    let parse = |bound: &str, default| match bound {
+        "" => Some(default),
+        bound => bound.parse().ok(),
+    };
+
+    Some((parse(start, 0)?, parse(end, usize::MAX)?))
+}
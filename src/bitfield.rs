@@ -0,0 +1,350 @@
+use crate::{RpegError, RpegImage};
+use std::collections::BTreeMap;
+
+/// A field of bits within each word of an image, such as the `a` coefficient of a codeword,
+/// for [`RpegImage::field_to_pgm`]. Words are read as big-endian `u32`s, and the field's bits
+/// are counted from the least significant bit, as with Hanson's `Bitpack` interface.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::BitField;
+///
+/// // The 9-bit unsigned `a` field at the top of a codeword, and the 5-bit signed `b` below it
+/// let a = BitField::new(23, 9);
+/// let b = BitField::new(18, 5).signed();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitField {
+    /// The position of the field's least significant bit
+    pub lsb: u32,
+    /// The number of bits in the field
+    pub width: u32,
+    /// Whether the field holds a two's complement signed integer
+    pub signed: bool,
+}
+
+impl BitField {
+    /// Creates an unsigned field `width` bits wide, starting at bit `lsb`.
+    ///
+    /// # Panics
+    ///
+    /// * If the field is empty or doesn't fit in a 32-bit word
+    pub fn new(lsb: u32, width: u32) -> Self {
+        assert!(
+            (1..=32).contains(&width) && lsb <= 32 - width,
+            "A field must be at least 1 bit wide, and fit in a 32-bit word"
+        );
+
+        BitField {
+            lsb,
+            width,
+            signed: false,
+        }
+    }
+
+    /// Makes the field hold a two's complement signed integer.
+    pub fn signed(self) -> Self {
+        BitField {
+            signed: true,
+            ..self
+        }
+    }
+
+    /// Extracts the field from a word (read as a big-endian `u32`), sign-extending it if the
+    /// field is signed.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::BitField;
+    ///
+    /// let word = [0x00, 0x00, 0x00, 0xF4];
+    /// assert_eq!(BitField::new(4, 4).value(word), 15);
+    /// assert_eq!(BitField::new(4, 4).signed().value(word), -1);
+    /// ```
+    pub fn value(&self, word: [u8; 4]) -> i64 {
+        let value = self.extract(word) as i64;
+
+        match self.signed {
+            true => value - (1 << (self.width - 1)),
+            false => value,
+        }
+    }
+
+    /// The biggest magnitude (absolute value) the field can hold.
+    pub(crate) fn max_magnitude(&self) -> u64 {
+        match self.signed {
+            true => 1 << (self.width - 1),
+            false => (1 << self.width) - 1,
+        }
+    }
+
+    /// Extracts the field from a word, offset so that the smallest signed value is 0.
+    fn extract(&self, word: [u8; 4]) -> u32 {
+        let mask = u32::MAX >> (32 - self.width);
+        let value = (u32::from_be_bytes(word) >> self.lsb) & mask;
+
+        match self.signed {
+            true => value ^ (1 << (self.width - 1)),
+            false => value,
+        }
+    }
+}
+
+/// Statistics of the values of a field across every word of an image, from
+/// [`RpegImage::field_stats`]. Values outside the range a quantizer should produce (such as a
+/// `b` coefficient always at its limit) are the usual sign of a bug in quantizing or packing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldStats {
+    /// The smallest value of the field
+    pub min: i64,
+    /// The biggest value of the field
+    pub max: i64,
+    /// The mean value of the field
+    pub mean: f64,
+    /// The number of words with each value of the field, for the values which appear
+    pub histogram: BTreeMap<i64, u64>,
+}
+
+impl RpegImage {
+    /// Extracts a field from every word of the image, in order. See [`BitField::value`].
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{BitLayout, RpegImage};
+    ///
+    /// let layout: BitLayout = "a:9, b:5s".parse().unwrap();
+    /// let image = RpegImage::new(vec![[0x00, 0xFC, 0x00, 0x00], [0xFF, 0x80, 0x00, 0x00]], 4, 2);
+    ///
+    /// assert_eq!(image.field_values(layout.get("a").unwrap()), [1, 511]);
+    /// assert_eq!(image.field_values(layout.get("b").unwrap()), [-1, 0]);
+    /// ```
+    pub fn field_values(&self, field: BitField) -> Vec<i64> {
+        self.words.iter().map(|&word| field.value(word)).collect()
+    }
+
+    /// Works out the smallest, biggest, and mean values of a field across every word of the
+    /// image, and how often each value appears. Returns None if the image has no words.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{BitField, RpegImage};
+    ///
+    /// let words = vec![[0x10, 0, 0, 0], [0x30, 0, 0, 0], [0x30, 0, 0, 0], [0x80, 0, 0, 0]];
+    /// let image = RpegImage::new(words, 4, 4);
+    /// let stats = image.field_stats(BitField::new(28, 4).signed()).unwrap();
+    ///
+    /// assert_eq!((stats.min, stats.max, stats.mean), (-8, 3, -0.25));
+    /// assert_eq!(stats.histogram.into_iter().collect::<Vec<_>>(), [(-8, 1), (1, 1), (3, 2)]);
+    /// ```
+    pub fn field_stats(&self, field: BitField) -> Option<FieldStats> {
+        let values = self.field_values(field);
+        let mut histogram = BTreeMap::new();
+        for &value in &values {
+            *histogram.entry(value).or_insert(0) += 1;
+        }
+
+        Some(FieldStats {
+            min: *histogram.keys().next()?,
+            max: *histogram.keys().next_back()?,
+            mean: values.iter().sum::<i64>() as f64 / values.len() as f64,
+            histogram,
+        })
+    }
+
+    /// Extracts a field from every word of the image, and encodes it as a grayscale image in the
+    /// raw PGM format (`P5`), where each 2x2 block of the image becomes one pixel. Seeing a field
+    /// (such as the average brightness `a`) as an image makes mistakes in packing or ordering
+    /// words easy to spot.
+    ///
+    /// The PGM's maxval is the biggest value the field can hold. Signed fields are offset so that
+    /// their smallest value is black, zero is mid-gray, and their biggest value is white.
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::GridMismatch`] if the image doesn't have exactly `(width / 2) * (height / 2)`
+    ///   words
+    ///
+    /// # Panics
+    ///
+    /// * If the field isn't between 1 and 16 bits wide, or doesn't fit in a 32-bit word
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{BitField, RpegImage};
+    ///
+    /// let words = vec![[0xFF, 0x80, 0x00, 0x00], [0x00, 0x80, 0x00, 0x00]];
+    /// let image = RpegImage::new(words, 4, 2);
+    ///
+    /// let pgm = image.field_to_pgm(BitField::new(23, 9)).unwrap();
+    /// assert_eq!(pgm, b"P5\n2 1\n511\n\x01\xFF\x00\x01");
+    /// ```
+    pub fn field_to_pgm(&self, field: BitField) -> Result<Vec<u8>, RpegError> {
+        assert!(
+            (1..=16).contains(&field.width) && field.lsb + field.width <= 32,
+            "A field must be between 1 and 16 bits wide, and fit in a 32-bit word"
+        );
+
+        let (rows, cols) = self.block_grid()?;
+        let maxval = (1u32 << field.width) - 1;
+        let mut pgm = format!("P5\n{cols} {rows}\n{maxval}\n").into_bytes();
+        for &word in &self.words {
+            let value = field.extract(word);
+            match maxval > 255 {
+                true => pgm.extend((value as u16).to_be_bytes()),
+                false => pgm.push(value as u8),
+            }
+        }
+
+        Ok(pgm)
+    }
+
+    /// Draws a field of every word of the image as a heatmap of its magnitude, as text to print
+    /// to a terminal, with one character for each 2x2 block of the image and one line for each
+    /// row of blocks. Like [`RpegImage::field_to_pgm`], this makes spatial mistakes (such as
+    /// transposed rows) easy to spot without decompressing the image.
+    ///
+    /// The magnitude is the value of an unsigned field, or the absolute value of a signed one,
+    /// scaled from 0 up to the biggest magnitude the field can hold.
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - The field to draw
+    /// * `style` - The characters to draw magnitudes with
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::GridMismatch`] if the image doesn't have exactly `(width / 2) * (height / 2)`
+    ///   words
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{BitField, HeatmapStyle, RpegImage};
+    ///
+    /// let words = (0..8u32).map(|index| (index << 29).to_be_bytes()).collect();
+    /// let image = RpegImage::new(words, 8, 4);
+    ///
+    /// let heatmap = image.field_heatmap(BitField::new(29, 3), HeatmapStyle::Ascii).unwrap();
+    /// assert_eq!(heatmap, " .-=\n+*%@\n");
+    /// let heatmap = image.field_heatmap(BitField::new(29, 3).signed(), HeatmapStyle::Shades);
+    /// assert_eq!(heatmap.unwrap(), " ░▒▓\n█▓▒░\n");
+    /// ```
+    pub fn field_heatmap(&self, field: BitField, style: HeatmapStyle) -> Result<String, RpegError> {
+        let (_, cols) = self.block_grid()?;
+        let levels = style.levels();
+
+        let max_magnitude = field.max_magnitude();
+        let mut heatmap = String::with_capacity(self.words.len() * 3);
+        for (index, &word) in self.words.iter().enumerate() {
+            let magnitude = field.value(word).unsigned_abs();
+            let level = (magnitude * (levels.len() as u64 - 1) + max_magnitude / 2) / max_magnitude;
+            heatmap.push(levels[level as usize]);
+
+            if (index + 1) % cols as usize == 0 {
+                heatmap.push('\n');
+            }
+        }
+
+        Ok(heatmap)
+    }
+
+    /// The number of rows and columns of 2x2 blocks in the image, checking that it has a word
+    /// for each block.
+    pub(crate) fn block_grid(&self) -> Result<(u32, u32), RpegError> {
+        let (rows, cols) = (self.height() / 2, self.width() / 2);
+        if self.words.len() as u64 != rows as u64 * cols as u64 {
+            return Err(RpegError::GridMismatch {
+                rows,
+                cols,
+                word_count: self.words.len(),
+            });
+        }
+
+        Ok((rows, cols))
+    }
+}
+
+/// The characters [`RpegImage::field_heatmap`] draws magnitudes with, from smallest to biggest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum HeatmapStyle {
+    /// Plain ASCII characters, from ` ` through `.-=+*%` to `@`
+    #[default]
+    Ascii,
+    /// Unicode shading characters, from ` ` through `░▒▓` to `█`
+    Shades,
+}
+
+impl HeatmapStyle {
+    fn levels(self) -> &'static [char] {
+        match self {
+            HeatmapStyle::Ascii => &[' ', '.', '-', '=', '+', '*', '%', '@'],
+            HeatmapStyle::Shades => &[' ', '░', '▒', '▓', '█'],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        // 101 10011 0110 in binary
+        let word = 0xB36u32.to_be_bytes();
+
+        assert_eq!(BitField::new(0, 4).extract(word), 0b0110);
+        assert_eq!(BitField::new(4, 5).extract(word), 0b10011);
+        // -13 as a signed 5-bit integer, which is 3 above the smallest value (-16)
+        assert_eq!(BitField::new(4, 5).signed().extract(word), 3);
+        assert_eq!(BitField::new(9, 3).signed().extract(word), 0b001);
+        assert_eq!(BitField::new(0, 16).extract([0xFF; 4]), 0xFFFF);
+
+        assert_eq!(BitField::new(4, 5).signed().value(word), -13);
+        assert_eq!(BitField::new(0, 32).value([0xFF; 4]), u32::MAX as i64);
+        assert_eq!(BitField::new(0, 32).signed().value([0xFF; 4]), -1);
+        assert_eq!(BitField::new(31, 1).value([0x80, 0, 0, 0]), 1);
+
+        // Fields which are empty or don't fit in a word are never made
+        for (lsb, width) in [(0, 0), (0, 40), (30, 4), (32, 1), (u32::MAX, 2)] {
+            assert!(std::panic::catch_unwind(|| BitField::new(lsb, width)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_field_to_pgm() {
+        let words = (0..6u32).map(|index| (index << 28).to_be_bytes()).collect();
+        let image = RpegImage::new(words, 6, 4);
+
+        assert_eq!(
+            image.field_to_pgm(BitField::new(28, 4).signed()).unwrap(),
+            b"P5\n3 2\n15\n\x08\x09\x0A\x0B\x0C\x0D"
+        );
+        assert!(matches!(
+            RpegImage::new(vec![[0; 4]; 5], 6, 4).field_to_pgm(BitField::new(0, 1)),
+            Err(RpegError::GridMismatch { word_count: 5, .. })
+        ));
+
+        let stats = image.field_stats(BitField::new(28, 4)).unwrap();
+        assert_eq!((stats.min, stats.max, stats.mean), (0, 5, 2.5));
+        assert_eq!(stats.histogram.len(), 6);
+        assert_eq!(
+            RpegImage::new(vec![], 0, 0).field_stats(BitField::new(0, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_field_heatmap() {
+        let words = (0..6u32).map(|index| (index << 28).to_be_bytes()).collect();
+        let image = RpegImage::new(words, 6, 4);
+
+        let heatmap = image.field_heatmap(BitField::new(28, 4), HeatmapStyle::Shades);
+        assert_eq!(heatmap.unwrap(), "  ░\n░░░\n");
+        let heatmap = image.field_heatmap(BitField::new(0, 32), HeatmapStyle::Ascii);
+        assert_eq!(heatmap.unwrap(), "  .\n.--\n");
+        assert!(matches!(
+            RpegImage::new(vec![[0; 4]; 5], 6, 4)
+                .field_heatmap(BitField::new(0, 1), HeatmapStyle::Ascii),
+            Err(RpegError::GridMismatch { word_count: 5, .. })
+        ));
+    }
+}
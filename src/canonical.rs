@@ -0,0 +1,87 @@
+use crate::{RpegError, RpegImage};
+
+/// Rewrites rpeg data into the canonical form that every writer in this crate produces, so that
+/// two files holding the same image are byte-for-byte identical, and `diff` or `cmp` between a
+/// student's output and a reference file only reports real differences.
+///
+/// Anything the standard readers accept is accepted, including `\r\n` or `\r` line endings,
+/// several spaces or tabs between the width and height, spaces or tabs at the end of a line, and
+/// leading zeros in numbers. The canonical form is:
+///
+/// 1. `Compressed image format {version}`, followed by `c` if the data has a checksum and `t` if
+///    it has a word count (in that order), then `\n`. A binary header instead ends this line
+///    with ` [BINARY]\n`, followed by the width and height as big-endian `u32`s, and the payload
+/// 2. Otherwise, a `#@ {key}={value}\n` line for each piece of metadata, in order of key
+/// 3. A `# {comment}\n` line for each comment in order, or `#\n` for an empty comment
+/// 4. `{width} {height}\n`, in decimal without leading zeros, separated by a single space
+/// 5. The payload, exactly as it was
+/// 6. The word count trailer line (`{count}\n`) and then the checksum, if the header calls for
+///    them
+///
+/// Any signature (see `RpegImage::to_signed`, with the `crypto` feature) is dropped, since
+/// signing takes a key.
+///
+/// This form is part of the crate's stability guarantees, and won't change without a major
+/// version bump.
+///
+/// # Errors Returned
+///
+/// * If `bytes` isn't valid rpeg data, in exactly the same cases as
+///   [`RpegImage::from_bytes`]
+///
+/// # Examples
+/// ```
+/// let bytes = b"Compressed image format 2\r\n04  2 \r\n\x00\x11\x22\x33";
+///
+/// assert_eq!(
+///     csc411_rpegio::canonicalize(bytes).unwrap(),
+///     b"Compressed image format 2\n4 2\n\x00\x11\x22\x33"
+/// );
+/// ```
+pub fn canonicalize(bytes: &[u8]) -> Result<Vec<u8>, RpegError> {
+    Ok(RpegImage::from_bytes(bytes)?.to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WriteOptions;
+
+    #[test]
+    fn test_canonical_output_is_locked() {
+        let options = WriteOptions::new()
+            .version(3)
+            .checksum(true)
+            .word_count(true)
+            .metadata("z", "last")
+            .metadata("a", "first")
+            .comment("one\ntwo")
+            .comment("");
+        let mut writer = crate::RpegWriter::with_options(Vec::new(), 640, 480, &options).unwrap();
+        writer.write_word([0x0D, 0x0A, 0x20, 0x09]).unwrap();
+        let output = writer.finish().unwrap();
+
+        let mut expected = b"Compressed image format 3ct\n#@ a=first\n#@ z=last\n# one\n# two\n#\n\
+            640 480\n\x0D\x0A\x20\x091\n"
+            .to_vec();
+        expected.extend_from_slice(&output[output.len() - 4..]);
+        assert_eq!(output, expected);
+
+        // Canonical data is already canonical, and everything else written canonically
+        assert_eq!(canonicalize(&output).unwrap(), output);
+        let image = RpegImage::from_bytes(&output).unwrap();
+        assert_eq!(image.to_bytes(), output);
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let input =
+            b"Compressed image format 2 \r#  spaced\r\n#@ k = v\r\n0004\t2\t\r\n\x00\x11\x22\x33";
+
+        assert_eq!(
+            canonicalize(input).unwrap(),
+            b"Compressed image format 2\n#@ k=v\n#  spaced\n4 2\n\x00\x11\x22\x33"
+        );
+        assert!(canonicalize(b"Compressed image format 2\n4 2\n\x00").is_err());
+    }
+}
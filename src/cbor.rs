@@ -0,0 +1,277 @@
+use crate::{RpegError, RpegImage};
+
+// The major types of CBOR data items which are used (RFC 8949, section 3.1)
+const UNSIGNED: u8 = 0;
+const TEXT: u8 = 3;
+const ARRAY: u8 = 4;
+const MAP: u8 = 5;
+
+impl RpegImage {
+    /// Encodes the image as [CBOR](https://cbor.io), for passing it to other languages (such as
+    /// Python, with the `cbor2` package) without writing a parser for rpeg data. Enabled by the
+    /// `cbor` feature.
+    ///
+    /// The CBOR is a map with the `width` and `height` of the image, and its `words` as an array
+    /// of unsigned integers (reading the bytes of each word as a big-endian integer). Nothing
+    /// else from the header (such as comments) is kept. Every number is written in its shortest
+    /// form, so the same image always gives the same bytes.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let image = RpegImage::new(vec![[0x00, 0x00, 0x00, 0x07]], 2, 2);
+    /// let cbor = image.to_cbor();
+    ///
+    /// assert_eq!(cbor, b"\xA3\x65width\x02\x66height\x02\x65words\x81\x07");
+    /// assert_eq!(RpegImage::from_cbor(&cbor).unwrap(), image);
+    /// ```
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut cbor = Vec::with_capacity(32 + self.words.len() * 5);
+        write_head(&mut cbor, MAP, 3);
+
+        for (name, value) in [("width", self.width()), ("height", self.height())] {
+            write_text(&mut cbor, name);
+            write_head(&mut cbor, UNSIGNED, value as u64);
+        }
+
+        write_text(&mut cbor, "words");
+        write_head(&mut cbor, ARRAY, self.words.len() as u64);
+        for word in &self.words {
+            write_head(&mut cbor, UNSIGNED, u32::from_be_bytes(*word) as u64);
+        }
+
+        cbor
+    }
+
+    /// Parses an image from CBOR in the form written by [`RpegImage::to_cbor`]. Any other
+    /// entries in the map are ignored. Only definite-length items are supported.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the bytes aren't a single valid CBOR data item, or it isn't a map
+    /// * If `width`, `height`, or `words` is missing or the wrong type, or a word doesn't fit in
+    ///   four bytes
+    pub fn from_cbor(cbor: &[u8]) -> Result<Self, RpegError> {
+        let mut decoder = Decoder { cbor, position: 0 };
+        let root = decoder.decode()?;
+        if decoder.position != cbor.len() {
+            return Err(decoder.error("Expected the end of the CBOR"));
+        }
+
+        let Value::Map(entries) = root.value else {
+            return Err(type_error(&root, "The CBOR", "a map"));
+        };
+        let entry = |name: &str| {
+            entries
+                .iter()
+                .find(|(key, _)| matches!(&key.value, Value::Text(key) if key == name))
+                .map(|(_, value)| value)
+                .ok_or_else(|| RpegError::Format {
+                    message: format!("Missing the entry {name:?}"),
+                    offset: root.offset as u64,
+                })
+        };
+
+        let dimension = |name: &str| {
+            let value = entry(name)?;
+            as_u32(value).ok_or_else(|| type_error(value, name, "a u32"))
+        };
+        let width = dimension("width")?;
+        let height = dimension("height")?;
+
+        let words = entry("words")?;
+        let Value::Array(words) = &words.value else {
+            return Err(type_error(words, "words", "an array"));
+        };
+        let words = words
+            .iter()
+            .map(|word| {
+                as_u32(word)
+                    .map(u32::to_be_bytes)
+                    .ok_or_else(|| type_error(word, "A word", "a u32"))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(RpegImage::new(words, width, height))
+    }
+}
+
+/// Writes the head of a data item, with its argument in the shortest form it fits in.
+fn write_head(cbor: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+
+    if argument < 24 {
+        cbor.push(major | argument as u8);
+    } else if let Ok(argument) = u8::try_from(argument) {
+        cbor.extend([major | 24, argument]);
+    } else if let Ok(argument) = u16::try_from(argument) {
+        cbor.push(major | 25);
+        cbor.extend(argument.to_be_bytes());
+    } else if let Ok(argument) = u32::try_from(argument) {
+        cbor.push(major | 26);
+        cbor.extend(argument.to_be_bytes());
+    } else {
+        cbor.push(major | 27);
+        cbor.extend(argument.to_be_bytes());
+    }
+}
+
+fn write_text(cbor: &mut Vec<u8>, text: &str) {
+    write_head(cbor, TEXT, text.len() as u64);
+    cbor.extend(text.as_bytes());
+}
+
+fn as_u32(value: &Spanned) -> Option<u32> {
+    match value.value {
+        Value::Unsigned(value) => u32::try_from(value).ok(),
+        _ => None,
+    }
+}
+
+fn type_error(value: &Spanned, name: &str, expected: &str) -> RpegError {
+    RpegError::Format {
+        message: format!("{name} must be {expected}"),
+        offset: value.offset as u64,
+    }
+}
+
+/// A decoded CBOR data item, and the offset of its first byte in the CBOR.
+struct Spanned {
+    offset: usize,
+    value: Value,
+}
+
+enum Value {
+    Unsigned(u64),
+    Text(String),
+    Array(Vec<Spanned>),
+    Map(Vec<(Spanned, Spanned)>),
+    // Negative integers, byte strings, simple values, and floats, none of which are ever needed
+    Other,
+}
+
+struct Decoder<'a> {
+    cbor: &'a [u8],
+    position: usize,
+}
+
+impl Decoder<'_> {
+    fn error(&self, message: &str) -> RpegError {
+        RpegError::Format {
+            message: message.to_string(),
+            offset: self.position as u64,
+        }
+    }
+
+    fn take(&mut self, len: u64) -> Result<&[u8], RpegError> {
+        let remaining = (self.cbor.len() - self.position) as u64;
+        if len > remaining {
+            return Err(self.error("The CBOR ended partway through a data item"));
+        }
+
+        let start = self.position;
+        self.position += len as usize;
+        Ok(&self.cbor[start..self.position])
+    }
+
+    /// Decodes the head of a data item, returning its major type and argument.
+    fn decode_head(&mut self) -> Result<(u8, u64), RpegError> {
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1F);
+
+        let argument = match info {
+            0..=23 => info as u64,
+            24..=27 => {
+                let bytes = self.take(1 << (info - 24))?;
+                bytes
+                    .iter()
+                    .fold(0, |argument, &byte| argument << 8 | byte as u64)
+            }
+            31 => {
+                self.position -= 1;
+                return Err(self.error("Indefinite-length items aren't supported"));
+            }
+            _ => {
+                self.position -= 1;
+                return Err(self.error("Invalid additional information in a data item"));
+            }
+        };
+
+        Ok((major, argument))
+    }
+
+    fn decode(&mut self) -> Result<Spanned, RpegError> {
+        let offset = self.position;
+        let (major, argument) = self.decode_head()?;
+
+        let value = match major {
+            UNSIGNED => Value::Unsigned(argument),
+            TEXT => {
+                let text =
+                    std::str::from_utf8(self.take(argument)?).map_err(|_| RpegError::Format {
+                        message: "Text strings must be valid UTF-8".to_string(),
+                        offset: offset as u64,
+                    })?;
+                Value::Text(text.to_string())
+            }
+            ARRAY => Value::Array(
+                (0..argument)
+                    .map(|_| self.decode())
+                    .collect::<Result<_, _>>()?,
+            ),
+            MAP => Value::Map(
+                (0..argument)
+                    .map(|_| Ok((self.decode()?, self.decode()?)))
+                    .collect::<Result<_, RpegError>>()?,
+            ),
+            // A tag is followed by the item it applies to
+            6 => self.decode()?.value,
+            2 => {
+                self.take(argument)?;
+                Value::Other
+            }
+            _ => Value::Other,
+        };
+
+        Ok(Spanned { offset, value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let words = [0, 23, 24, 255, 256, 65535, 65536, u32::MAX];
+        let words = words.iter().map(|word| word.to_be_bytes()).collect();
+        let image = RpegImage::new(words, 8, 4);
+        let cbor = image.to_cbor();
+
+        assert!(cbor.ends_with(b"\x88\x00\x17\x18\x18\x18\xFF\x19\x01\x00\x19\xFF\xFF\x1A\x00\x01\x00\x00\x1A\xFF\xFF\xFF\xFF"));
+        assert_eq!(RpegImage::from_cbor(&cbor).unwrap(), image);
+    }
+
+    #[test]
+    fn test_cbor_extra_entries_and_errors() {
+        // {"name": h'00', 1: -1, "words": [1], "height": 2, "width": 0(2)}
+        let cbor = b"\xA5\x64name\x41\x00\x01\x20\x65words\x81\x01\x66height\x02\x65width\xC0\x02";
+        let image = RpegImage::from_cbor(cbor).unwrap();
+        assert_eq!(image.words, [[0, 0, 0, 1]]);
+        assert_eq!(image.width(), 2);
+
+        let error_offset = |cbor: &[u8]| match RpegImage::from_cbor(cbor) {
+            Err(RpegError::Format { offset, .. }) => offset,
+            result => panic!("Expected a format error, found {result:?}"),
+        };
+        assert_eq!(error_offset(b"\x81\x00"), 0);
+        assert_eq!(
+            error_offset(b"\xA1\x65width\x1B\x00\x00\x00\x01\x00\x00\x00\x00"),
+            7
+        );
+        assert_eq!(error_offset(b"\xA1\x65words\x9F\xFF"), 7);
+        assert_eq!(error_offset(b"\xA1\x65words\x82\x00"), 9);
+        assert_eq!(error_offset(b"\xA0\x00"), 1);
+    }
+}
@@ -0,0 +1,294 @@
+/// The number of bytes in a ChaCha20-Poly1305 key.
+pub(crate) const KEY_LEN: usize = 32;
+
+/// The number of bytes in a ChaCha20-Poly1305 nonce.
+pub(crate) const NONCE_LEN: usize = 12;
+
+/// The number of bytes in the authentication tag after the ciphertext.
+pub(crate) const TAG_LEN: usize = 16;
+
+/// Encrypts `plaintext` with ChaCha20-Poly1305 (RFC 8439), returning the ciphertext followed by
+/// a tag which authenticates both it and `aad` (additional data which isn't encrypted).
+pub(crate) fn seal(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Vec<u8> {
+    let mut sealed = plaintext.to_vec();
+    chacha20_xor(key, 1, nonce, &mut sealed);
+
+    let tag = tag(key, nonce, aad, &sealed);
+    sealed.extend(tag);
+
+    sealed
+}
+
+/// Decrypts the output of [`seal`], returning `None` if the tag doesn't match (because the key,
+/// nonce, or additional data is wrong, or the ciphertext was tampered with).
+pub(crate) fn open(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    sealed: &[u8],
+) -> Option<Vec<u8>> {
+    let (ciphertext, found) = sealed.split_at_checked(sealed.len().checked_sub(TAG_LEN)?)?;
+
+    // Compared without stopping at the first difference, so the time taken doesn't leak how
+    // much of a forged tag was right
+    let expected = tag(key, nonce, aad, ciphertext);
+    let difference = expected
+        .iter()
+        .zip(found)
+        .fold(0, |difference, (a, b)| difference | (a ^ b));
+    if difference != 0 {
+        return None;
+    }
+
+    let mut plaintext = ciphertext.to_vec();
+    chacha20_xor(key, 1, nonce, &mut plaintext);
+
+    Some(plaintext)
+}
+
+fn tag(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8], ciphertext: &[u8]) -> [u8; 16] {
+    let one_time_key = chacha20_block(key, 0, nonce);
+    let mut poly = Poly1305::new(one_time_key[..32].try_into().unwrap());
+
+    poly.update_padded(aad);
+    poly.update_padded(ciphertext);
+    let mut lengths = [0; 16];
+    lengths[..8].copy_from_slice(&(aad.len() as u64).to_le_bytes());
+    lengths[8..].copy_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    poly.update_padded(&lengths);
+
+    poly.finish()
+}
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+fn chacha20_block(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN]) -> [u8; 64] {
+    let le = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap());
+
+    let mut initial = [0; 16];
+    initial[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+    for (word, bytes) in initial[4..12].iter_mut().zip(key.chunks_exact(4)) {
+        *word = le(bytes);
+    }
+    initial[12] = counter;
+    for (word, bytes) in initial[13..].iter_mut().zip(nonce.chunks_exact(4)) {
+        *word = le(bytes);
+    }
+
+    let mut state = initial;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut block = [0; 64];
+    for ((bytes, word), initial) in block.chunks_exact_mut(4).zip(state).zip(initial) {
+        bytes.copy_from_slice(&word.wrapping_add(initial).to_le_bytes());
+    }
+
+    block
+}
+
+/// Encrypts (or decrypts) `bytes` in place with the ChaCha20 keystream, starting from block
+/// `counter`.
+fn chacha20_xor(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN], bytes: &mut [u8]) {
+    for (index, chunk) in bytes.chunks_mut(64).enumerate() {
+        let block = chacha20_block(key, counter.wrapping_add(index as u32), nonce);
+        for (byte, key) in chunk.iter_mut().zip(block) {
+            *byte ^= key;
+        }
+    }
+}
+
+/// The Poly1305 one-time authenticator, with the accumulator in five 26-bit limbs.
+struct Poly1305 {
+    r: [u32; 5],
+    h: [u32; 5],
+    pad: [u32; 4],
+}
+
+impl Poly1305 {
+    fn new(key: &[u8; 32]) -> Self {
+        let le = |index: usize| u32::from_le_bytes(key[index..index + 4].try_into().unwrap());
+
+        Poly1305 {
+            // The clamped first half of the key
+            r: [
+                le(0) & 0x3ff_ffff,
+                (le(3) >> 2) & 0x3ff_ff03,
+                (le(6) >> 4) & 0x3ff_c0ff,
+                (le(9) >> 6) & 0x3f0_3fff,
+                (le(12) >> 8) & 0x00f_ffff,
+            ],
+            h: [0; 5],
+            pad: [le(16), le(20), le(24), le(28)],
+        }
+    }
+
+    /// Adds `bytes` to the message, followed by zeros up to a multiple of 16 bytes.
+    fn update_padded(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(16) {
+            let mut block = [0; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            self.block(&block, 1 << 24);
+        }
+    }
+
+    /// Adds a block to the message, with `high_bit` being the 2^128 bit of the block (which is
+    /// always set apart from in final partial blocks, which aren't used in ChaCha20-Poly1305).
+    fn block(&mut self, block: &[u8; 16], high_bit: u32) {
+        let le = |index: usize| u32::from_le_bytes(block[index..index + 4].try_into().unwrap());
+        let [r0, r1, r2, r3, r4] = self.r.map(u64::from);
+        let [s1, s2, s3, s4] = [r1 * 5, r2 * 5, r3 * 5, r4 * 5];
+
+        let h = &mut self.h;
+        h[0] += le(0) & 0x3ff_ffff;
+        h[1] += (le(3) >> 2) & 0x3ff_ffff;
+        h[2] += (le(6) >> 4) & 0x3ff_ffff;
+        h[3] += (le(9) >> 6) & 0x3ff_ffff;
+        h[4] += (le(12) >> 8) | high_bit;
+
+        let [h0, h1, h2, h3, h4] = h.map(u64::from);
+        let mut d = [
+            h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1,
+            h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2,
+            h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3,
+            h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4,
+            h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0,
+        ];
+
+        for index in 0..4 {
+            d[index + 1] += d[index] >> 26;
+            h[index] = d[index] as u32 & 0x3ff_ffff;
+        }
+        h[4] = d[4] as u32 & 0x3ff_ffff;
+        h[0] += (d[4] >> 26) as u32 * 5;
+        h[1] += h[0] >> 26;
+        h[0] &= 0x3ff_ffff;
+    }
+
+    fn finish(self) -> [u8; 16] {
+        let mut h = self.h;
+
+        // Fully carry h
+        for index in 1..4 {
+            h[index + 1] += h[index] >> 26;
+            h[index] &= 0x3ff_ffff;
+        }
+        let carry = h[4] >> 26;
+        h[4] &= 0x3ff_ffff;
+        h[0] += carry * 5;
+        h[1] += h[0] >> 26;
+        h[0] &= 0x3ff_ffff;
+
+        // Compute h - p (where p = 2^130 - 5), and use it instead of h if it isn't negative
+        let mut g = [0u32; 5];
+        let mut carry = 5;
+        for index in 0..5 {
+            g[index] = h[index] + carry;
+            carry = g[index] >> 26;
+            g[index] &= 0x3ff_ffff;
+        }
+        g[4] = (g[4] | carry << 26).wrapping_sub(1 << 26);
+        let use_g = (g[4] >> 31).wrapping_sub(1);
+        for index in 0..5 {
+            h[index] = (h[index] & !use_g) | (g[index] & use_g);
+        }
+
+        // Repack h into 32-bit words, and add the second half of the key
+        let words = [
+            h[0] | h[1] << 26,
+            h[1] >> 6 | h[2] << 20,
+            h[2] >> 12 | h[3] << 14,
+            h[3] >> 18 | h[4] << 8,
+        ];
+        let mut tag = [0; 16];
+        let mut carry = 0;
+        for ((bytes, word), pad) in tag.chunks_exact_mut(4).zip(words).zip(self.pad) {
+            let sum = word as u64 + pad as u64 + carry;
+            bytes.copy_from_slice(&(sum as u32).to_le_bytes());
+            carry = sum >> 32;
+        }
+
+        tag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        let hex = hex.replace(char::is_whitespace, "");
+        (0..hex.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_poly1305() {
+        // RFC 8439, section 2.5.2
+        let key = unhex("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b");
+        let mut poly = Poly1305::new(key[..].try_into().unwrap());
+        let message = b"Cryptographic Forum Research Group";
+        poly.update_padded(&message[..32]);
+        let mut last = [0; 16];
+        last[..2].copy_from_slice(&message[32..]);
+        last[2] = 1;
+        // The final partial block is padded with a 1 byte rather than the usual high bit
+        poly.block(&last, 0);
+        assert_eq!(poly.finish(), *unhex("a8061dc1305136c6c22b8baf0c0127a9"));
+    }
+
+    #[test]
+    fn test_aead() {
+        // RFC 8439, section 2.8.2
+        let key = unhex("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f");
+        let key = key[..].try_into().unwrap();
+        let nonce = unhex("070000004041424344454647");
+        let nonce = nonce[..].try_into().unwrap();
+        let aad = unhex("50515253c0c1c2c3c4c5c6c7");
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only \
+                          one tip for the future, sunscreen would be it.";
+
+        let sealed = seal(key, nonce, &aad, plaintext);
+        assert_eq!(
+            sealed,
+            unhex(
+                "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6
+                 3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36
+                 92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc
+                 3ff4def08e4b7a9de576d26586cec64b6116
+                 1ae10b594f09e26a7e902ecbd0600691"
+            )
+        );
+        assert_eq!(open(key, nonce, &aad, &sealed).unwrap(), plaintext);
+
+        let mut tampered = sealed.clone();
+        tampered[0] ^= 1;
+        assert!(open(key, nonce, &aad, &tampered).is_none());
+        assert!(open(key, nonce, b"", &sealed).is_none());
+        assert!(open(key, nonce, &aad, &sealed[..10]).is_none());
+    }
+}
@@ -0,0 +1,187 @@
+//! Helpers for the course's command-line conventions: a program takes at most one argument, the
+//! file to read, and reads from stdin if it isn't given, and it reports errors on stderr before
+//! exiting with a status of 1.
+//!
+//! # Examples
+//! ```no_run
+//! use csc411_rpegio::{cli, RpegImage};
+//!
+//! let path = cli::input_path_from_args().unwrap_or_else(|err| err.exit());
+//! let image = RpegImage::read(path.as_deref()).unwrap_or_else(|err| cli::exit_with_error(err));
+//! ```
+
+use crate::RpegError;
+use std::fmt;
+
+/// The error for being given too many arguments, as from [`input_path_from_args`]. Its
+/// [`Display`](fmt::Display) implementation is the usage message, such as
+/// `Usage: ./40image [filename]`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UsageError {
+    /// The name the program was run by, from its first argument
+    pub program: String,
+    /// The number of arguments the program was given, not counting its name
+    pub arg_count: usize,
+}
+
+impl UsageError {
+    /// Prints the usage message to stderr and exits with a status of 1, as the course's
+    /// programs do when given too many arguments.
+    pub fn exit(&self) -> ! {
+        eprintln!("{self}");
+        std::process::exit(1)
+    }
+}
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Usage: {} [filename]", self.program)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// Gets the file to read from the program's arguments, or None if it should read from stdin.
+/// See [`input_path_from`].
+///
+/// # Errors Returned
+///
+/// * [`UsageError`] if the program was given more than one argument
+pub fn input_path_from_args() -> Result<Option<String>, UsageError> {
+    input_path_from(std::env::args())
+}
+
+/// Gets the file to read from a program's arguments, or None if it should read from stdin.
+/// The first argument is the program's name, as from [`std::env::args`].
+///
+/// Whatever one argument is given is taken as the path, even if it's empty or `-`.
+///
+/// # Arguments
+///
+/// * `args` - The program's arguments, starting with its name
+///
+/// # Errors Returned
+///
+/// * [`UsageError`] if there's more than one argument after the program's name
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::cli::input_path_from;
+///
+/// let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+///
+/// assert_eq!(input_path_from(args(&["./40image"])), Ok(None));
+/// assert_eq!(
+///     input_path_from(args(&["./40image", "in.rpeg"])),
+///     Ok(Some("in.rpeg".to_string()))
+/// );
+///
+/// let err = input_path_from(args(&["./40image", "a.rpeg", "b.rpeg"])).unwrap_err();
+/// assert_eq!(err.to_string(), "Usage: ./40image [filename]");
+/// ```
+pub fn input_path_from(
+    args: impl IntoIterator<Item = String>,
+) -> Result<Option<String>, UsageError> {
+    let mut args = args.into_iter();
+    let program = args.next().unwrap_or_else(|| "program".to_string());
+    let rest: Vec<String> = args.collect();
+
+    match <[String; 1]>::try_from(rest) {
+        Ok([path]) => Ok(Some(path)),
+        Err(rest) if rest.is_empty() => Ok(None),
+        Err(rest) => Err(UsageError {
+            program,
+            arg_count: rest.len(),
+        }),
+    }
+}
+
+/// Prints an error to stderr, with a hint about what to do about it if there is one, and exits
+/// with a status of 1 (`EXIT_FAILURE`).
+///
+/// The error is printed after the program's name, as in `./40image: <error>`, and its hint (see
+/// [`RpegError::hint`]) is on the next line, as in `./40image: hint: <hint>`.
+///
+/// # Arguments
+///
+/// * `err` - The error, or anything which converts into one, such as a [`std::io::Error`]
+///
+/// # Examples
+/// ```no_run
+/// use csc411_rpegio::{cli, RpegImage};
+///
+/// let image = RpegImage::read(None).unwrap_or_else(|err| cli::exit_with_error(err));
+/// ```
+pub fn exit_with_error(err: impl Into<RpegError>) -> ! {
+    let program = std::env::args()
+        .next()
+        .unwrap_or_else(|| "program".to_string());
+    eprint!("{}", error_report(&program, &err.into()));
+
+    std::process::exit(1)
+}
+
+/// The lines [`exit_with_error`] prints for an error.
+fn error_report(program: &str, err: &RpegError) -> String {
+    let mut report = format!("{program}: {err}\n");
+    if let Some(hint) = err.hint() {
+        report.push_str(&format!("{program}: hint: {hint}\n"));
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_input_path_from() {
+        assert_eq!(input_path_from(args(&["prog"])), Ok(None));
+        assert_eq!(
+            input_path_from(args(&["prog", "-"])),
+            Ok(Some("-".to_string()))
+        );
+        assert_eq!(
+            input_path_from(args(&["prog", ""])),
+            Ok(Some(String::new()))
+        );
+        // Without even a program name
+        assert_eq!(input_path_from(args(&[])), Ok(None));
+    }
+
+    #[test]
+    fn test_usage_error() {
+        let err = input_path_from(args(&["./ppmdiff", "a", "b", "c"])).unwrap_err();
+        assert_eq!(err.arg_count, 3);
+        assert_eq!(err.program, "./ppmdiff");
+        assert_eq!(err.to_string(), "Usage: ./ppmdiff [filename]");
+
+        let err = input_path_from(args(&["./ppmdiff", "a", "b"])).unwrap_err();
+        assert_eq!(err.arg_count, 2);
+    }
+
+    #[test]
+    fn test_error_report() {
+        let err =
+            crate::RpegImage::from_bytes(b"Compressed image format 2c\n2 2\n\0\0\0\0\0\0\0\0")
+                .unwrap_err();
+        let report = error_report("./40image", &err);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], format!("./40image: {err}"));
+        assert_eq!(
+            lines[1],
+            format!("./40image: hint: {}", err.hint().unwrap())
+        );
+
+        // Without a hint
+        assert_eq!(
+            error_report("./40image", &RpegError::PatchMismatch),
+            format!("./40image: {}\n", RpegError::PatchMismatch)
+        );
+    }
+}
@@ -0,0 +1,753 @@
+//! The core of the rpeg format: parsing and encoding rpeg data in memory, using nothing but
+//! `core` and `alloc`. This is all that's built without the `std` feature, such as for decoding
+//! rpeg data on a microcontroller.
+//!
+//! This is the grammar the rest of the crate reads rpeg data with: with the `std` feature,
+//! [`RpegImage::from_bytes`](crate::RpegImage::from_bytes) and the streaming readers parse
+//! headers and trailers through it. So [`decode`] accepts the same data they do: any of the
+//! [`SUPPORTED_FORMAT_VERSIONS`], with or without a checksum (`c`), word count (`t`), or
+//! signature (`s`), `#` comment and `#@` metadata lines, binary headers, and `\n` or `\r\n` line
+//! endings. Signatures are skipped over without being checked, as by readers without a key. Only
+//! compressed and armored data need [`RpegImage`](crate::RpegImage) (with the `std` feature).
+//! Decoding doesn't copy the payload, so it works on data in flash memory.
+//!
+//! # Examples
+//! ```
+//! use csc411_rpegio::codec;
+//!
+//! let bytes = codec::encode(&[[0x00, 0x11, 0x22, 0x33]], 2, 2);
+//! assert_eq!(bytes, b"Compressed image format 2\n2 2\n\x00\x11\x22\x33");
+//!
+//! let decoded = codec::decode(&bytes).unwrap();
+//! assert_eq!((decoded.header.width, decoded.header.height), (2, 2));
+//! assert_eq!(decoded.words().collect::<Vec<_>>(), [[0x00, 0x11, 0x22, 0x33]]);
+//! ```
+
+use crate::checksum::{Crc32, TRAILER_LEN};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Every version of the compressed image format with four-byte words, as found on the first line
+/// of the header ("Compressed image format 2"). These can all be read by the standard functions.
+///
+/// The legacy format 1, which has two-byte words, is read and written separately using
+/// [`Format1`](crate::Format1).
+pub const SUPPORTED_FORMAT_VERSIONS: &[u32] = &[2, 3];
+
+/// The version of the compressed image format written unless otherwise requested.
+pub const DEFAULT_FORMAT_VERSION: u32 = 2;
+
+/// The end of the first line of a header whose dimensions are stored in binary.
+pub(crate) const BINARY_SUFFIX: &str = " [BINARY]";
+
+/// The most bytes a word count trailer line can take up: the 20 digits of `u64::MAX`, and a
+/// newline.
+pub(crate) const MAX_WORD_COUNT_LEN: usize = 21;
+
+/// The number of bytes in the HMAC-SHA256 signature at the end of signed data.
+pub(crate) const SIGNATURE_LEN: usize = 32;
+
+/// The header of rpeg data, as parsed by [`parse_header`]. These are the same fields as the
+/// [`RpegHeader`](crate::RpegHeader) the `std` feature's readers give.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Header {
+    /// The version of the compressed image format, from the first line of the header
+    pub version: u32,
+    /// Whether the payload is followed by a CRC32 checksum (`c`)
+    pub checksum: bool,
+    /// Whether the payload is followed by a line containing the number of words in it (`t`)
+    pub word_count: bool,
+    /// Whether the data ends with an HMAC-SHA256 signature (`s`)
+    pub signed: bool,
+    /// Whether the width and height are stored in binary, after a first line ending in
+    /// `" [BINARY]"`
+    pub binary: bool,
+    /// The width of the image
+    pub width: u32,
+    /// The height of the image
+    pub height: u32,
+    /// The text of any `#` comment lines, without the `#` and a single space following it
+    pub comments: Vec<String>,
+    /// Any key/value metadata stored in `#@ key=value` comment lines
+    pub metadata: BTreeMap<String, String>,
+    /// The length of the header in bytes, which is where the payload starts
+    pub len: usize,
+}
+
+impl Header {
+    pub(crate) fn trailers(&self) -> Trailers {
+        Trailers {
+            checksum: self.checksum,
+            word_count: self.word_count,
+            signed: self.signed,
+        }
+    }
+}
+
+/// Which trailers follow the payload, as marked by the letters after the version in the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Trailers {
+    pub(crate) checksum: bool,
+    pub(crate) word_count: bool,
+    pub(crate) signed: bool,
+}
+
+/// Rpeg data parsed by [`decode`], borrowing its payload from the data.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Decoded<'a> {
+    /// The header of the data
+    pub header: Header,
+    /// The bytes of the payload's words, without any trailers
+    pub payload: &'a [u8],
+}
+
+impl<'a> Decoded<'a> {
+    /// The words of the payload, in order.
+    pub fn words(&self) -> impl ExactSizeIterator<Item = [u8; 4]> + 'a {
+        self.payload
+            .chunks_exact(4)
+            .map(|word| [word[0], word[1], word[2], word[3]])
+    }
+}
+
+/// Why rpeg data couldn't be decoded. With the `std` feature, each of these converts into the
+/// [`RpegError`](crate::RpegError) variant of the same name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DecodeError {
+    /// The data wasn't valid rpeg data.
+    Format {
+        /// A description of what was wrong with the data
+        message: String,
+        /// How far into the data (in bytes) the problem was found
+        offset: u64,
+    },
+    /// The CRC32 checksum at the end of the data didn't match its payload.
+    ChecksumMismatch {
+        /// The checksum recorded at the end of the data
+        expected: u32,
+        /// The checksum of the payload actually read
+        found: u32,
+    },
+    /// The word count at the end of the data didn't match the number of words in its payload.
+    WordCountMismatch {
+        /// The number of words recorded at the end of the data
+        expected: u64,
+        /// The number of words actually read
+        found: u64,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Format { message, offset } => write!(f, "{message} (at byte {offset})"),
+            DecodeError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "The payload's checksum (0x{found:08X}) doesn't match the one recorded in the data \
+                 (0x{expected:08X})"
+            ),
+            DecodeError::WordCountMismatch { expected, found } => write!(
+                f,
+                "Found {found} words in the payload, but the data says it has {expected} words"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+/// The bytes of a header being parsed, with one byte of lookahead. This is a slice for
+/// [`parse_header`], and with the `std` feature, any reader, so that a stream's header can be
+/// parsed without reading past it.
+pub(crate) trait HeaderSource {
+    fn peek(&mut self) -> Option<u8>;
+
+    fn next(&mut self) -> Option<u8>;
+}
+
+/// A cursor over a slice of bytes.
+struct SliceSource<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl HeaderSource for SliceSource<'_> {
+    fn peek(&mut self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.position += 1;
+
+        Some(byte)
+    }
+}
+
+pub(crate) fn expect(expected_bytes: &[u8], bytes: &mut impl HeaderSource) -> Result<(), String> {
+    for expected_byte in expected_bytes {
+        match &bytes.next() {
+            Some(byte) => {
+                if byte != expected_byte {
+                    return Err(format!(
+                        "Expected 0x{expected_byte:02X}, found 0x{byte:02X}"
+                    ));
+                }
+            }
+            None => {
+                return Err(format!(
+                    "Ran out of bytes before expected 0x{expected_byte:02X} byte"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn expect_newline(bytes: &mut impl HeaderSource) -> Result<(), String> {
+    match bytes.next() {
+        // \n - Mostly Unix
+        Some(0x0A) => Ok(()),
+        // \r[\n] - Mostly Windows
+        Some(0x0D) => {
+            // Check for a \n after the \r, consuming it if it exists
+            if bytes.peek() == Some(0x0A) {
+                bytes.next();
+            }
+
+            Ok(())
+        }
+        Some(byte) => Err(format!("Expected newline byte(s), found 0x{byte:02X}")),
+        None => Err("Ran out of bytes before expected newline byte(s)".to_string()),
+    }
+}
+
+fn is_blank(byte: u8) -> bool {
+    byte == b' ' || byte == b'\t'
+}
+
+/// Skips any spaces or tabs, such as those before the end of a line.
+pub(crate) fn skip_blanks(bytes: &mut impl HeaderSource) {
+    while bytes.peek().is_some_and(is_blank) {
+        bytes.next();
+    }
+}
+
+/// Expects the spaces or tabs (at least one) separating two numbers in the header.
+pub(crate) fn expect_separator(bytes: &mut impl HeaderSource) -> Result<(), String> {
+    match bytes.next() {
+        Some(byte) if is_blank(byte) => {
+            skip_blanks(bytes);
+            Ok(())
+        }
+        Some(byte) => Err(format!("Expected 0x20, found 0x{byte:02X}")),
+        None => Err("Ran out of bytes before expected 0x20 byte".to_string()),
+    }
+}
+
+fn parse_ascii_digit(digit: u8) -> Result<u32, String> {
+    if !digit.is_ascii_digit() {
+        Err(format!("Attempted to parse non-ascii digit {digit:?}"))
+    } else {
+        Ok((digit - b'0') as u32)
+    }
+}
+
+pub(crate) fn read_u32(bytes: &mut impl HeaderSource) -> Result<u32, String> {
+    // Read initial digit (there ought to be at least one)
+    let mut next_byte = match bytes.peek() {
+        Some(byte) => byte,
+        None => return Err("Didn't find a number where a number was expected in input".to_string()),
+    };
+
+    let mut num = parse_ascii_digit(next_byte)?;
+    bytes.next();
+
+    // Read any additional digits in the number
+    while bytes.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+        next_byte = bytes.next().unwrap();
+        let digit = parse_ascii_digit(next_byte).unwrap();
+
+        num = num
+            .checked_mul(10)
+            .and_then(|num| num.checked_add(digit))
+            .ok_or("Integer overflow while parsing u32".to_string())?;
+    }
+
+    Ok(num)
+}
+
+pub(crate) type Comments = (Vec<String>, BTreeMap<String, String>);
+
+/// Reads any number of PNM-style `#` comment lines, separating out `#@ key=value` metadata.
+pub(crate) fn read_comments(bytes: &mut impl HeaderSource) -> Result<Comments, String> {
+    let mut comments = Vec::new();
+    let mut metadata = BTreeMap::new();
+
+    while bytes.peek() == Some(b'#') {
+        bytes.next();
+
+        let mut line = Vec::new();
+        while let Some(byte) = bytes.peek().filter(|&byte| byte != b'\n' && byte != b'\r') {
+            line.push(byte);
+            bytes.next();
+        }
+        expect_newline(bytes)?;
+
+        let line = String::from_utf8_lossy(&line);
+
+        // Lines that look like "#@ key=value" are metadata, and anything else is a comment
+        let entry = line
+            .strip_prefix('@')
+            .and_then(|entry| entry.trim_start().split_once('='));
+
+        match entry {
+            Some((key, value)) => {
+                metadata.insert(key.trim().to_string(), value.trim().to_string());
+            }
+            // A single space after the # is conventional, and not part of the comment itself
+            None => comments.push(line.strip_prefix(' ').unwrap_or(&line).to_string()),
+        }
+    }
+
+    Ok((comments, metadata))
+}
+
+fn read_version(bytes: &mut impl HeaderSource, accepted_versions: &[u32]) -> Result<u32, String> {
+    let version = read_u32(bytes)?;
+
+    if version == 1 {
+        return Err(
+            "Compressed image format 1 has two-byte words, and must be read using Format1"
+                .to_string(),
+        );
+    }
+
+    if !SUPPORTED_FORMAT_VERSIONS.contains(&version) {
+        return Err(format!(
+            "Unsupported compressed image format version {version}"
+        ));
+    }
+
+    if !accepted_versions.contains(&version) {
+        let accepted: Vec<String> = accepted_versions.iter().map(u32::to_string).collect();
+
+        return Err(format!(
+            "Compressed image format {version} is not accepted here (accepted: {})",
+            accepted.join(", ")
+        ));
+    }
+
+    Ok(version)
+}
+
+/// Parses an rpeg header whose first line ends in `suffix` (such as " [TEXT]"), leaving `bytes`
+/// at the first byte of the payload. The header's `len` is left as 0, for the caller to fill in.
+pub(crate) fn parse_header_fields(
+    bytes: &mut impl HeaderSource,
+    accepted_versions: &[u32],
+    suffix: &str,
+) -> Result<Header, String> {
+    // Read "Compressed image format {version}\n" part of header
+    expect(b"Compressed image format ", bytes)?;
+    let version = read_version(bytes, accepted_versions)?;
+
+    // Read any letters after the version, which mark trailers after the payload
+    let (mut checksum, mut word_count, mut signed) = (false, false, false);
+    loop {
+        match bytes.peek() {
+            Some(b'c') if !checksum => checksum = true,
+            Some(b't') if !word_count => word_count = true,
+            Some(b's') if !signed => signed = true,
+            _ => break,
+        }
+        bytes.next();
+    }
+    expect(suffix.as_bytes(), bytes)?;
+    skip_blanks(bytes);
+
+    let mut header = Header {
+        version,
+        checksum,
+        word_count,
+        signed,
+        binary: false,
+        width: 0,
+        height: 0,
+        comments: Vec::new(),
+        metadata: BTreeMap::new(),
+        len: 0,
+    };
+
+    // Plain headers may instead be binary, which is detected from the end of the first line
+    if suffix.is_empty() && bytes.peek() == Some(b'[') {
+        expect(BINARY_SUFFIX.trim_start().as_bytes(), bytes)?;
+        expect(b"\n", bytes)?;
+
+        let mut dimensions = [0; 8];
+        for (index, byte) in dimensions.iter_mut().enumerate() {
+            *byte = bytes.next().ok_or_else(|| {
+                let field = if index < 4 { "width" } else { "height" };
+                format!("Ran out of bytes before the binary header's {field}")
+            })?;
+        }
+        let (width, height) = dimensions.split_at(4);
+
+        header.binary = true;
+        header.width = u32::from_be_bytes(width.try_into().unwrap());
+        header.height = u32::from_be_bytes(height.try_into().unwrap());
+        return Ok(header);
+    }
+    expect_newline(bytes)?;
+
+    // Read any "# {comment}\n" and "#@ {key}={value}\n" lines
+    (header.comments, header.metadata) = read_comments(bytes)?;
+
+    // Read "{width} {height}\n" part of header
+    header.width = read_u32(bytes)?;
+    expect_separator(bytes)?;
+    header.height = read_u32(bytes)?;
+    skip_blanks(bytes);
+    expect_newline(bytes)?;
+
+    Ok(header)
+}
+
+/// The size of a word, as it appears in error messages.
+pub(crate) fn word_size_name(word_size: usize) -> String {
+    match word_size {
+        2 => "two".to_string(),
+        4 => "four".to_string(),
+        8 => "eight".to_string(),
+        _ => word_size.to_string(),
+    }
+}
+
+/// The error for a payload of `payload_len` bytes which isn't a whole number of words, found at
+/// `offset`.
+pub(crate) fn length_error(payload_len: u64, word_size: usize, offset: u64) -> DecodeError {
+    DecodeError::Format {
+        message: format!(
+            "The number of raw bytes ({payload_len}) was not a multiple of {}",
+            word_size_name(word_size)
+        ),
+        offset,
+    }
+}
+
+/// Splits the signature (if any) off the very end of the data, after any other trailers. `rest`
+/// is everything after some part of the payload, and `end` is the offset of the end of the data.
+/// The signature isn't checked here, since that takes a key (see `crate::signature`).
+pub(crate) fn split_signature(
+    trailers: Trailers,
+    rest: &[u8],
+    end: u64,
+) -> Result<(&[u8], Option<[u8; SIGNATURE_LEN]>), DecodeError> {
+    if !trailers.signed {
+        return Ok((rest, None));
+    }
+
+    let Some(len) = rest.len().checked_sub(SIGNATURE_LEN) else {
+        return Err(DecodeError::Format {
+            message: "Ran out of bytes before the data's signature".to_string(),
+            offset: end,
+        });
+    };
+    let (rest, signature) = rest.split_at(len);
+
+    Ok((rest, Some(signature.try_into().unwrap())))
+}
+
+/// Splits the checksum and word count (if any) off the end of the data, checking that they match
+/// the payload. `rest` is everything after the first `words_read` words of the payload (and
+/// before any signature), whose checksum so far is `crc`, and `end` is the offset of the end of
+/// the data. Returns the rest of the payload.
+pub(crate) fn split_trailers(
+    trailers: Trailers,
+    rest: &[u8],
+    words_read: u64,
+    mut crc: Crc32,
+    word_size: usize,
+    end: u64,
+) -> Result<&[u8], DecodeError> {
+    let mut payload = rest;
+
+    let mut checksum = None;
+    if trailers.checksum {
+        let Some(payload_len) = payload.len().checked_sub(TRAILER_LEN) else {
+            return Err(DecodeError::Format {
+                message: "Ran out of bytes before the payload's checksum".to_string(),
+                offset: end,
+            });
+        };
+
+        let (rest, trailer) = payload.split_at(payload_len);
+        (payload, checksum) = (rest, Some(trailer.try_into().unwrap()));
+    }
+
+    if trailers.word_count {
+        payload = split_word_count(payload, words_read, word_size, end)?;
+    }
+
+    if let Some(trailer) = checksum {
+        crc.update(payload);
+        let (expected, found) = (u32::from_be_bytes(trailer), crc.finish());
+        if expected != found {
+            return Err(DecodeError::ChecksumMismatch { expected, found });
+        }
+    }
+
+    Ok(payload)
+}
+
+/// Splits the "{count}\n" line off the end of `rest`, which follows the first `words_read` words
+/// of the payload, checking that the count is right.
+fn split_word_count(
+    rest: &[u8],
+    words_read: u64,
+    word_size: usize,
+    end: u64,
+) -> Result<&[u8], DecodeError> {
+    // The payload's length decides how many digits the count has, so at most one split works
+    let min_words = rest
+        .len()
+        .saturating_sub(MAX_WORD_COUNT_LEN)
+        .div_ceil(word_size);
+    let max_words = rest.len().saturating_sub(2) / word_size;
+
+    for words in min_words..=max_words {
+        let (payload, line) = rest.split_at(words * word_size);
+
+        if line == format!("{}\n", words_read + words as u64).as_bytes() {
+            return Ok(payload);
+        }
+    }
+
+    // Otherwise, work out what the count ought to have been, for a helpful error. The last word
+    // may end in digits too, so the fewest digits which leave whole words are preferred
+    let line = rest.strip_suffix(b"\n").unwrap_or(rest);
+    let max_digits = line
+        .iter()
+        .rev()
+        .take(MAX_WORD_COUNT_LEN - 1)
+        .take_while(|byte| byte.is_ascii_digit())
+        .count();
+    let digits = (1..=max_digits)
+        .find(|digits| (line.len() - digits).is_multiple_of(word_size))
+        .unwrap_or(max_digits);
+
+    match core::str::from_utf8(&line[line.len() - digits..]).map(str::parse) {
+        Ok(Ok(expected)) if line.len() < rest.len() => Err(DecodeError::WordCountMismatch {
+            expected,
+            found: words_read + ((line.len() - digits) / word_size) as u64,
+        }),
+        _ => Err(DecodeError::Format {
+            message: "The data doesn't end with a line containing its number of words".to_string(),
+            offset: end,
+        }),
+    }
+}
+
+/// Splits the payload of data whose header is `header_len` bytes long off its trailers, checking
+/// them (other than the signature) and that the payload is a whole number of words.
+pub(crate) fn split_payload(
+    trailers: Trailers,
+    bytes: &[u8],
+    header_len: usize,
+    word_size: usize,
+) -> Result<&[u8], DecodeError> {
+    let end = bytes.len() as u64;
+    let (rest, _) = split_signature(trailers, &bytes[header_len..], end)?;
+    let payload = split_trailers(trailers, rest, 0, Crc32::new(), word_size, end)?;
+
+    if !payload.len().is_multiple_of(word_size) {
+        let offset = (header_len + payload.len()) as u64;
+        return Err(length_error(payload.len() as u64, word_size, offset));
+    }
+
+    Ok(payload)
+}
+
+/// Parses the header of rpeg data. See the [module documentation](self) for which data is
+/// supported.
+///
+/// # Errors Returned
+///
+/// * If the header is badly formatted, or is for a version of the format which isn't supported
+pub fn parse_header(bytes: &[u8]) -> Result<Header, DecodeError> {
+    let mut source = SliceSource { bytes, position: 0 };
+
+    match parse_header_fields(&mut source, SUPPORTED_FORMAT_VERSIONS, "") {
+        Ok(header) => Ok(Header {
+            len: source.position,
+            ..header
+        }),
+        Err(message) => Err(DecodeError::Format {
+            message,
+            offset: source.position as u64,
+        }),
+    }
+}
+
+/// Parses rpeg data and checks its payload, and any checksum or word count after it. See the
+/// [module documentation](self) for which data is supported.
+///
+/// # Errors Returned
+///
+/// * If the header is badly formatted, or is for a version of the format which isn't supported
+/// * If the payload isn't a whole number of words, or the data ends before its trailers
+/// * [`DecodeError::ChecksumMismatch`] or [`DecodeError::WordCountMismatch`] if the data's
+///   checksum or word count doesn't match its payload
+pub fn decode(bytes: &[u8]) -> Result<Decoded<'_>, DecodeError> {
+    let header = parse_header(bytes)?;
+    let payload = split_payload(header.trailers(), bytes, header.len, 4)?;
+
+    Ok(Decoded { header, payload })
+}
+
+/// Encodes words as rpeg data, in the same canonical form as the rest of the crate writes (with
+/// the [`DEFAULT_FORMAT_VERSION`] and no trailers).
+///
+/// # Arguments
+///
+/// * `words` - The words of the payload, one for each 2x2 block of the image
+/// * `width` - The width of the image
+/// * `height` - The height of the image
+pub fn encode(words: &[[u8; 4]], width: u32, height: u32) -> Vec<u8> {
+    use core::fmt::Write;
+
+    let mut header = String::new();
+    // Writing to a String can't fail
+    let _ = write!(
+        header,
+        "Compressed image format {DEFAULT_FORMAT_VERSION}\n{width} {height}\n"
+    );
+
+    let mut bytes = Vec::with_capacity(header.len() + words.len() * 4);
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend(words.iter().flatten());
+
+    bytes
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::generate::SplitMix64;
+    use crate::{generate_rpeg, RpegError, RpegImage};
+
+    /// Everything from_bytes gets from the data, from the decoded data.
+    fn image_from(decoded: &Decoded) -> RpegImage {
+        let header = &decoded.header;
+        let mut image = RpegImage::new(decoded.words().collect(), header.width, header.height);
+        image.header.version = header.version;
+        image.header.checksum = header.checksum;
+        image.header.word_count = header.word_count;
+        image.header.signed = header.signed;
+        image.header.binary = header.binary;
+        image.header.comments = header.comments.clone();
+        image.header.metadata = header.metadata.clone();
+        image
+    }
+
+    #[test]
+    fn test_matches_std_reader() {
+        let mut inputs: Vec<Vec<u8>> = vec![
+            [
+                &b"Compressed image format 3 \r\n# a comment\r\n#@ k = v\r\n4\t 2 \r\n"[..],
+                b"\x00\x11\x22\x33\x44\x55\x66\x77",
+            ]
+            .concat(),
+            b"Compressed image format 2s\n2 2\n\x00\x11\x22\x33".to_vec(),
+            b"Compressed image format 2c [BINARY]\n\0\0\0\x02\0\0\0\x02\x00\x11\x22\x33".to_vec(),
+            b"Compressed image format 2t\n2 2\n\x00\x11\x22\x33\n7\n".to_vec(),
+            b"Compressed image format 1\n2 2\n".to_vec(),
+            b"Compressed  image format 2\n2 2\n".to_vec(),
+            b"Compressed image format 2\n2\n".to_vec(),
+            b"Compressed image format 9\n2 2\n".to_vec(),
+            Vec::new(),
+        ];
+        for (width, height) in [(0, 0), (2, 2), (7, 5)] {
+            let mut image = generate_rpeg(width, height, 3);
+            inputs.push(image.to_bytes());
+            image.header.binary = true;
+            inputs.push(image.to_bytes());
+
+            image.header.binary = false;
+            image.header.checksum = true;
+            image.header.word_count = true;
+            image.header.comments.push("trailers".to_string());
+            let mut signed = image.to_bytes();
+            signed.splice(27..27, *b"s");
+            signed.extend([0xAB; SIGNATURE_LEN]);
+            for bytes in [image.to_bytes(), signed] {
+                inputs.extend((0..bytes.len()).map(|n| bytes[..n].to_vec()));
+                let mut random = SplitMix64::new(398);
+                for _ in 0..40 {
+                    let mut flipped = bytes.clone();
+                    let index = random.next_u64() as usize % bytes.len();
+                    flipped[index] ^= 1 << (random.next_u64() % 8);
+                    inputs.push(flipped);
+                }
+                inputs.push(bytes);
+            }
+        }
+
+        let mut decoded_count = 0;
+        for bytes in &inputs {
+            let std = RpegImage::from_bytes(bytes);
+            match (decode(bytes), std) {
+                (Ok(decoded), Ok(image)) => {
+                    assert_eq!(image_from(&decoded), image, "{bytes:?}");
+                    decoded_count += 1;
+                }
+                (Err(err), Err(std_err)) => {
+                    assert_eq!(RpegError::from(err).to_string(), std_err.to_string());
+                }
+                (decoded, std) => panic!("{bytes:?} gave {decoded:?}, but {std:?}"),
+            }
+        }
+        assert!(decoded_count >= 10, "{decoded_count}");
+    }
+
+    #[test]
+    fn test_encode_and_errors() {
+        for (width, height) in [(0, 0), (2, 2), (64, 48)] {
+            let image = generate_rpeg(width, height, 3);
+            let bytes = encode(&image.words, width, height);
+            assert_eq!(bytes, image.to_bytes());
+            assert_eq!(
+                decode(&bytes).unwrap().header.len,
+                bytes.len() - image.words.len() * 4
+            );
+        }
+
+        for (bytes, offset) in [
+            (&b"Compressed image format 1\n2 2\n"[..], 25),
+            (b"Compressed image format 2\n2\n", 28),
+            (b"Compressed image format 2\n2 2\n\x00\x11", 32),
+            (b"Compressed  image format 2\n2 2\n", 12),
+        ] {
+            let err = decode(bytes).unwrap_err();
+            assert!(
+                matches!(err, DecodeError::Format { offset: found, .. } if found == offset),
+                "{err}"
+            );
+        }
+        assert!(matches!(
+            decode(b"Compressed image format 2c\n2 2\n\x00\x11\x22\x33\x00\x00\x00\x00"),
+            Err(DecodeError::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            decode(b"Compressed image format 2t\n2 2\n\x00\x11\x22\x33\n2\n"),
+            Err(DecodeError::WordCountMismatch {
+                expected: 2,
+                found: 1
+            })
+        ));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::layout::RESET;
+use crate::{BitLayout, RpegImage};
+use std::fmt::{self, Write};
+
+/// Highlights the bytes (and fields) which differ between the images.
+pub(crate) const HIGHLIGHT: &str = "\x1b[1;31m";
+
+/// The differences between two images, from [`compare_rpeg`]. Its [`Display`](fmt::Display)
+/// implementation summarizes them in a line, for autograder feedback or `assert!` messages.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{compare_rpeg, RpegImage};
+///
+/// let expected = RpegImage::new(vec![[0x00; 4]; 6], 4, 6);
+/// let mut actual = expected.clone();
+/// actual.words[3][1] = 0xFF;
+///
+/// let diff = compare_rpeg(&expected, &actual);
+/// assert!(diff.dimensions_match());
+/// assert_eq!(diff.differing_words(), 1);
+///
+/// let first = diff.first_mismatch().unwrap();
+/// assert_eq!((first.index, first.row, first.column), (3, 1, 1));
+/// assert_eq!(first.differing_bytes(), [1]);
+///
+/// assert_eq!(
+///     diff.to_string(),
+///     "1 of 6 words differ, first at word 3 (row 1, column 1): 00000000 != 00FF0000"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RpegDiff {
+    /// The width and height of the left image
+    pub left_size: (u32, u32),
+    /// The width and height of the right image
+    pub right_size: (u32, u32),
+    /// The number of words in the longer image
+    pub word_count: usize,
+    /// Every word which differs between the images, in order
+    pub mismatches: Vec<WordMismatch>,
+}
+
+/// A word which differs between two images, as part of an [`RpegDiff`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WordMismatch {
+    /// The index of the word
+    pub index: usize,
+    /// The row of the 2x2 block the word is for, in the left image
+    pub row: u32,
+    /// The column of the 2x2 block the word is for, in the left image
+    pub column: u32,
+    /// The word in the left image, or None past the end of it
+    pub left: Option<[u8; 4]>,
+    /// The word in the right image, or None past the end of it
+    pub right: Option<[u8; 4]>,
+}
+
+impl RpegDiff {
+    /// Whether the images are exactly the same size, and have exactly the same words.
+    pub fn is_identical(&self) -> bool {
+        self.dimensions_match() && self.mismatches.is_empty()
+    }
+
+    /// Whether the images are the same width and height.
+    pub fn dimensions_match(&self) -> bool {
+        self.left_size == self.right_size
+    }
+
+    /// The number of words which differ (including any words past the end of the shorter image).
+    pub fn differing_words(&self) -> usize {
+        self.mismatches.len()
+    }
+
+    /// The first word which differs, if any do.
+    pub fn first_mismatch(&self) -> Option<&WordMismatch> {
+        self.mismatches.first()
+    }
+}
+
+impl WordMismatch {
+    /// The positions of the bytes of the word which differ (every byte, if the word is only in
+    /// one of the images).
+    pub fn differing_bytes(&self) -> Vec<usize> {
+        (0..4)
+            .filter(|&position| {
+                self.left.map(|word| word[position]) != self.right.map(|word| word[position])
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for RpegDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let word = |word: Option<[u8; 4]>| match word {
+            Some(word) => format!("{:08X}", u32::from_be_bytes(word)),
+            None => "(none)".to_string(),
+        };
+
+        if !self.dimensions_match() {
+            let (left, right) = (self.left_size, self.right_size);
+            write!(f, "{}x{} != {}x{}, ", left.0, left.1, right.0, right.1)?;
+        }
+        match self.first_mismatch() {
+            None => write!(f, "all {} words match", self.word_count),
+            Some(first) => write!(
+                f,
+                "{} of {} words differ, first at word {} (row {}, column {}): {} != {}",
+                self.differing_words(),
+                self.word_count,
+                first.index,
+                first.row,
+                first.column,
+                word(first.left),
+                word(first.right)
+            ),
+        }
+    }
+}
+
+/// Compares two images word by word, such as a student's output (`right`) against a reference
+/// (`left`). See [`RpegDiff`], and [`render_diff`] for a colored view of the same differences.
+///
+/// Only the sizes and words of the images are compared, not their comments or other header
+/// fields. The row and column of each word are worked out from the width of `left`.
+pub fn compare_rpeg(left: &RpegImage, right: &RpegImage) -> RpegDiff {
+    let row_len = (left.width() as usize / 2).max(1);
+    let word_count = left.words.len().max(right.words.len());
+
+    let mismatches = (0..word_count)
+        .map(|index| (index, left.words.get(index), right.words.get(index)))
+        .filter(|(_, left, right)| left != right)
+        .map(|(index, left, right)| WordMismatch {
+            index,
+            row: (index / row_len) as u32,
+            column: (index % row_len) as u32,
+            left: left.copied(),
+            right: right.copied(),
+        })
+        .collect();
+
+    RpegDiff {
+        left_size: (left.width(), left.height()),
+        right_size: (right.width(), right.height()),
+        word_count,
+        mismatches,
+    }
+}
+
+/// Shows two images side by side, word by word, with the bytes which differ highlighted (using
+/// ANSI escape codes), in place of comparing two hex dumps by eye.
+///
+/// The first line gives the size of each image. Each word of the longer image is then on its
+/// own line: its index, its bytes in `left` and in `right` (or dashes past the end of the
+/// shorter image), separated by `|` if they're the same or `!` if they differ. Given a layout,
+/// each line which differs also lists the fields which changed, with their values in each
+/// image.
+///
+/// # Arguments
+///
+/// * `left` - The image shown on the left, such as a reference output
+/// * `right` - The image shown on the right
+/// * `layout` - An optional layout of the fields of each word
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{render_diff, BitLayout, RpegImage};
+///
+/// let left = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]], 4, 2);
+/// let right = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33], [0x45, 0x55, 0x66, 0x77]], 4, 2);
+/// let layout: BitLayout = "a:9, b:5s".parse().unwrap();
+/// let diff = render_diff(&left, &right, Some(&layout));
+///
+/// let lines: Vec<&str> = diff.lines().collect();
+/// assert_eq!(lines[0], "# 4x2 | 4x2");
+/// assert_eq!(lines[1], "0: 00 11 22 33 | 00 11 22 33");
+/// assert_eq!(
+///     lines[2],
+///     "1: \x1b[1;31m44\x1b[0m 55 66 77 ! \x1b[1;31m45\x1b[0m 55 66 77  \x1b[1;31ma\x1b[0m: 136 -> 138"
+/// );
+/// ```
+pub fn render_diff(left: &RpegImage, right: &RpegImage, layout: Option<&BitLayout>) -> String {
+    let size = |image: &RpegImage| format!("{}x{}", image.width(), image.height());
+    let mut diff = String::new();
+    match size(left) == size(right) {
+        true => writeln!(diff, "# {} | {}", size(left), size(right)),
+        false => writeln!(
+            diff,
+            "# {HIGHLIGHT}{}{RESET} ! {HIGHLIGHT}{}{RESET}",
+            size(left),
+            size(right)
+        ),
+    }
+    .unwrap();
+
+    let len = left.words.len().max(right.words.len());
+    let label_len = len.saturating_sub(1).to_string().len();
+    for index in 0..len {
+        let (left_word, right_word) = (left.words.get(index), right.words.get(index));
+        let same = left_word == right_word;
+
+        write!(diff, "{index:>label_len$}: ").unwrap();
+        write_word(&mut diff, left_word, right_word);
+        diff.push_str(if same { " | " } else { " ! " });
+        write_word(&mut diff, right_word, left_word);
+
+        if let (Some(layout), Some(&left_word), Some(&right_word)) = (layout, left_word, right_word)
+        {
+            let changed = layout
+                .fields()
+                .filter(|(_, field)| field.value(left_word) != field.value(right_word));
+            for (position, (name, field)) in changed.enumerate() {
+                diff.push_str(if position == 0 { "  " } else { ", " });
+                let (old, new) = (field.value(left_word), field.value(right_word));
+                write!(diff, "{HIGHLIGHT}{name}{RESET}: {old} -> {new}").unwrap();
+            }
+        }
+        diff.push('\n');
+    }
+
+    diff
+}
+
+/// Writes the bytes of a word, highlighting those which differ from the other word.
+pub(crate) fn write_word(diff: &mut String, word: Option<&[u8; 4]>, other: Option<&[u8; 4]>) {
+    for position in 0..4 {
+        if position != 0 {
+            diff.push(' ');
+        }
+
+        let byte = word.map(|word| word[position]);
+        let text = match byte {
+            Some(byte) => format!("{byte:02X}"),
+            None => "--".to_string(),
+        };
+        match byte == other.map(|other| other[position]) {
+            true => diff.push_str(&text),
+            false => write!(diff, "{HIGHLIGHT}{text}{RESET}").unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_diff() {
+        let left = RpegImage::new(vec![[0x00; 4]; 10], 4, 10);
+        let mut right = RpegImage::new(vec![[0x00; 4]; 11], 4, 11);
+        right.words[9][3] = 0x0F;
+        let diff = render_diff(&left, &right, None);
+        let lines: Vec<&str> = diff.lines().collect();
+
+        assert_eq!(lines.len(), 12);
+        assert_eq!(lines[0], "# \x1b[1;31m4x10\x1b[0m ! \x1b[1;31m4x11\x1b[0m");
+        assert_eq!(lines[1], " 0: 00 00 00 00 | 00 00 00 00");
+        assert_eq!(
+            lines[10],
+            " 9: 00 00 00 \x1b[1;31m00\x1b[0m ! 00 00 00 \x1b[1;31m0F\x1b[0m"
+        );
+        assert!(lines[11].starts_with("10: \x1b[1;31m--\x1b[0m"));
+        assert!(lines[11].contains(" ! \x1b[1;31m00\x1b[0m "));
+    }
+
+    #[test]
+    fn test_compare_rpeg() {
+        let left = RpegImage::new(vec![[0x00; 4]; 4], 4, 4);
+        assert!(compare_rpeg(&left, &left).is_identical());
+        assert_eq!(compare_rpeg(&left, &left).to_string(), "all 4 words match");
+
+        let right = RpegImage::new(vec![[0x00; 4]; 6], 4, 6);
+        let diff = compare_rpeg(&left, &right);
+        assert!(!diff.dimensions_match());
+        assert_eq!(diff.differing_words(), 2);
+        assert_eq!(diff.mismatches[1].index, 5);
+        assert_eq!(diff.mismatches[1].differing_bytes(), [0, 1, 2, 3]);
+        assert_eq!(
+            diff.to_string(),
+            "4x4 != 4x6, 2 of 6 words differ, first at word 4 (row 2, column 0): (none) != 00000000"
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use crate::RpegError;
+
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+
+/// The most bytes needed from the start of the input to tell whether it's compressed.
+pub(crate) const MAGIC_LEN: usize = 4;
+
+/// Whether the input starts with the magic bytes of a compression format.
+pub(crate) fn is_compressed(start: &[u8]) -> bool {
+    start.starts_with(GZIP_MAGIC) || start.starts_with(ZSTD_MAGIC)
+}
+
+/// Decompresses the input if it's compressed (as detected by its magic bytes), returning `None`
+/// if it isn't. Gzip needs the `gzip` feature, and zstd isn't supported, but both are still
+/// detected so that they can be reported clearly.
+pub(crate) fn decompress(bytes: &[u8]) -> Result<Option<Vec<u8>>, RpegError> {
+    let unsupported = |message: &str| RpegError::Format {
+        message: message.to_string(),
+        offset: 0,
+    };
+
+    if bytes.starts_with(GZIP_MAGIC) {
+        #[cfg(feature = "gzip")]
+        return crate::gzip::decompress(bytes)
+            .map(Some)
+            .map_err(|message| RpegError::Format { message, offset: 0 });
+
+        #[cfg(not(feature = "gzip"))]
+        return Err(unsupported(
+            "The input is gzip-compressed, which needs the gzip feature to read",
+        ));
+    }
+
+    if bytes.starts_with(ZSTD_MAGIC) {
+        return Err(unsupported(
+            "The input is zstd-compressed, which isn't supported. Decompress it with `zstd -d` first",
+        ));
+    }
+
+    Ok(None)
+}
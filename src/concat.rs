@@ -0,0 +1,180 @@
+use crate::checksum::TRAILER_LEN;
+use crate::header::{self, ByteReader};
+use crate::trailer::{MAX_WORD_COUNT_LEN, SIGNATURE_LEN};
+use crate::{stdio, ReadOptions, RpegError, RpegImage};
+use std::io::{BufRead, BufReader, Read};
+
+/// Reads several standard rpeg images concatenated one after another, such as the output of
+/// `cat *.rpeg`. Each image is yielded in turn, stopping cleanly at the end of the input.
+///
+/// Since nothing marks where one image ends and the next begins, the length of each payload is
+/// worked out from its header: one word for each 2x2 block of the image, so
+/// `(width / 2) * (height / 2)` words, followed by any trailers. The images can't be compressed
+/// or ASCII-armored.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{RpegConcatReader, RpegImage};
+///
+/// let first = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+/// let second = RpegImage::new(vec![[0x44, 0x55, 0x66, 0x77]; 2], 4, 2);
+/// let bytes = [first.to_bytes(), second.to_bytes()].concat();
+///
+/// let images = RpegConcatReader::new(&bytes[..]).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(images, [first, second]);
+/// ```
+pub struct RpegConcatReader<R> {
+    reader: BufReader<R>,
+    options: ReadOptions,
+    position: u64,
+}
+
+impl RpegConcatReader<Box<dyn Read>> {
+    /// Opens concatenated rpeg images from either stdin or a file.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the provided file can't be opened
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - An optional file path to read from. If None, stdin will be read from
+    ///   instead
+    pub fn open(file_path: Option<&str>) -> Result<Self, RpegError> {
+        let reader: Box<dyn Read> = match stdio::input_path(file_path).as_deref() {
+            Some(file_path) => Box::new(std::fs::File::open(file_path)?),
+            None => Box::new(stdio::binary_stdin()),
+        };
+
+        Ok(RpegConcatReader::new(reader))
+    }
+}
+
+impl<R: Read> RpegConcatReader<R> {
+    /// Creates a reader of concatenated rpeg images from any source.
+    pub fn new(reader: R) -> Self {
+        RpegConcatReader::with_options(reader, &ReadOptions::default())
+    }
+
+    /// Like [`RpegConcatReader::new`], but with the given options, which apply to every image.
+    pub fn with_options(reader: R, options: &ReadOptions) -> Self {
+        RpegConcatReader {
+            reader: BufReader::with_capacity(options.buffer_capacity, reader),
+            options: options.clone(),
+            position: 0,
+        }
+    }
+
+    /// Reads the next image, returning `Ok(None)` once the input has run out.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If there is an error reading from the underlying source
+    /// * If an image's header is badly formatted, or the input ends partway through an image
+    /// * If an image has a checksum or word count at its end which doesn't match its payload
+    /// * If the options have a key to verify signatures with, and an image isn't signed with it
+    pub fn read_image(&mut self) -> Result<Option<RpegImage>, RpegError> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        let mut bytes = ByteReader::new(&mut self.reader);
+        let header = header::read_header(&mut bytes, &self.options);
+        let header_len = bytes.position();
+        let header = header.map_err(|err| self.shift(err))?;
+        self.position += header_len;
+
+        let word_count = (header.width / 2) as u64 * (header.height / 2) as u64;
+        let mut payload = Vec::new();
+        (&mut self.reader)
+            .take(word_count * 4)
+            .read_to_end(&mut payload)?;
+        if header.word_count {
+            (&mut self.reader)
+                .take(MAX_WORD_COUNT_LEN as u64)
+                .read_until(b'\n', &mut payload)?;
+        }
+        if header.checksum {
+            (&mut self.reader)
+                .take(TRAILER_LEN as u64)
+                .read_to_end(&mut payload)?;
+        }
+        if header.signed {
+            (&mut self.reader)
+                .take(SIGNATURE_LEN as u64)
+                .read_to_end(&mut payload)?;
+        }
+
+        if payload.len() < (word_count * 4) as usize {
+            return Err(RpegError::Format {
+                message: format!("The input ended partway through an image of {word_count} words"),
+                offset: self.position + payload.len() as u64,
+            });
+        }
+
+        #[cfg(feature = "crypto")]
+        if let Some(key) = &self.options.verify_key {
+            let end = payload.len() as u64;
+            crate::signature::verify(key, &header, &payload, end).map_err(|err| self.shift(err))?;
+        }
+
+        // Offsets within the image are relative to the whole input
+        let image = RpegImage::from_header_and_payload(header, &payload, 0)
+            .map_err(|err| self.shift(err))?;
+        self.position += payload.len() as u64;
+
+        Ok(Some(image))
+    }
+
+    fn shift(&self, err: RpegError) -> RpegError {
+        match err {
+            RpegError::Format { message, offset } => RpegError::Format {
+                message,
+                offset: self.position + offset,
+            },
+            err => err,
+        }
+    }
+}
+
+impl<R: Read> Iterator for RpegConcatReader<R> {
+    type Item = Result<RpegImage, RpegError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_image().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trailers() {
+        let mut first = RpegImage::new(vec![[0x33; 4]; 4], 4, 4);
+        first.header.checksum = true;
+        first.header.word_count = true;
+        let mut second = RpegImage::new(vec![[0x0A; 4]; 2], 2, 5);
+        second.header.word_count = true;
+        let bytes = [first.to_bytes(), second.to_bytes(), first.to_bytes()].concat();
+
+        let mut reader = RpegConcatReader::new(&bytes[..]);
+        assert_eq!(reader.read_image().unwrap(), Some(first.clone()));
+        assert_eq!(reader.read_image().unwrap(), Some(second));
+        assert_eq!(reader.read_image().unwrap(), Some(first));
+        assert_eq!(reader.read_image().unwrap(), None);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let image = RpegImage::new(vec![[0x00; 4]; 2], 4, 2);
+        let bytes = [image.to_bytes(), image.to_bytes()].concat();
+
+        let mut reader = RpegConcatReader::new(&bytes[..bytes.len() - 1]);
+        assert!(reader.read_image().unwrap().is_some());
+        assert!(matches!(
+            reader.read_image(),
+            Err(RpegError::Format { offset: 75, .. })
+        ));
+    }
+}
@@ -0,0 +1,170 @@
+//! Deliberately damages rpeg data in controlled ways, for testing that a decompressor handles
+//! bad input gracefully (with an error message, rather than a panic or garbage output).
+//!
+//! Each function encodes an image as rpeg data (see [`RpegImage::to_bytes`]), then damages it.
+//! Damage that depends on a seed is reproducible, as with [`generate_rpeg`](crate::generate_rpeg).
+//!
+//! # Examples
+//! ```
+//! use csc411_rpegio::{corrupt, RpegImage};
+//!
+//! let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]; 4], 4, 4);
+//!
+//! assert!(RpegImage::from_bytes(&corrupt::truncate(&image, 1)).is_err());
+//! let mangled = corrupt::mangle_header(&image, corrupt::HeaderDamage::BadMagic);
+//! assert!(RpegImage::from_bytes(&mangled).is_err());
+//!
+//! // Flipped bits in the payload still parse, but give different words
+//! let flipped = RpegImage::from_bytes(&corrupt::flip_bits(&image, 3, 42)).unwrap();
+//! assert_ne!(flipped.words, image.words);
+//! ```
+
+use crate::generate::SplitMix64;
+use crate::header;
+use crate::RpegImage;
+use std::collections::BTreeSet;
+
+/// A way of damaging the header of rpeg data, for [`mangle_header`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HeaderDamage {
+    /// Changes the first line to something other than "Compressed image format"
+    BadMagic,
+    /// Changes the version to one which doesn't exist (such as "Compressed image format 9")
+    UnsupportedVersion,
+    /// Replaces the width with text which isn't a number
+    NonNumericWidth,
+    /// Removes the height, leaving only the width on the dimensions line
+    MissingHeight,
+    /// Doubles the height, so the header claims more words than the payload holds. This still
+    /// parses (reading doesn't check the number of words), so it tests that a decompressor
+    /// doesn't trust the dimensions alone
+    DoubledHeight,
+    /// Removes the newline at the end of the header, joining it to the payload
+    MissingNewline,
+}
+
+/// Encodes the image as rpeg data without its last `n` bytes (or with no bytes at all, if it
+/// has fewer than `n`).
+pub fn truncate(image: &RpegImage, n: usize) -> Vec<u8> {
+    let mut bytes = image.to_bytes();
+    bytes.truncate(bytes.len().saturating_sub(n));
+    bytes
+}
+
+/// Encodes the image as rpeg data, then flips `count` different bits of its payload, chosen
+/// pseudo-randomly from `seed`. The header is left alone, so the result still parses, but with
+/// different words. If the payload has fewer than `count` bits, every bit of it is flipped.
+pub fn flip_bits(image: &RpegImage, count: usize, seed: u64) -> Vec<u8> {
+    let (mut bytes, start) = encode(image);
+
+    let payload_bits = (image.words.len() * 32) as u64;
+    let mut random = SplitMix64::new(seed);
+    let mut flipped = BTreeSet::new();
+    while flipped.len() < count.min(payload_bits as usize) {
+        // The bias from the modulo is far too small to matter here
+        flipped.insert(random.next_u64() % payload_bits);
+    }
+
+    for bit in flipped {
+        bytes[start + (bit / 8) as usize] ^= 1 << (bit % 8);
+    }
+
+    bytes
+}
+
+/// Encodes the image as rpeg data, then damages its header in the given way. See
+/// [`HeaderDamage`].
+pub fn mangle_header(image: &RpegImage, damage: HeaderDamage) -> Vec<u8> {
+    let (bytes, header_len) = encode(image);
+    let (header, payload) = bytes.split_at(header_len);
+    let header = String::from_utf8(header.to_vec()).expect("Headers are always valid UTF-8");
+
+    // The dimensions are always on the last line of a (text) header
+    let (first_line, rest) = header.split_once('\n').unwrap();
+    let dimensions_start = header.trim_end().rfind('\n').unwrap() + 1;
+    let (before_dimensions, dimensions) = header.split_at(dimensions_start);
+    let (width, height) = dimensions.trim_end().split_once(' ').unwrap();
+
+    let header = match damage {
+        HeaderDamage::BadMagic => format!("Compressed imaeg format 2\n{rest}"),
+        HeaderDamage::UnsupportedVersion => {
+            let flags = first_line.trim_start_matches(|c: char| !c.is_ascii_digit());
+            let flags = flags.trim_start_matches(|c: char| c.is_ascii_digit());
+            format!("Compressed image format 9{flags}\n{rest}")
+        }
+        HeaderDamage::NonNumericWidth => format!("{before_dimensions}wide {height}\n"),
+        HeaderDamage::MissingHeight => format!("{before_dimensions}{width}\n"),
+        HeaderDamage::DoubledHeight => {
+            let height: u64 = height.parse().unwrap();
+            format!("{before_dimensions}{width} {}\n", height * 2)
+        }
+        HeaderDamage::MissingNewline => header.trim_end_matches('\n').to_string(),
+    };
+
+    [header.as_bytes(), payload].concat()
+}
+
+/// Encodes the image as rpeg data, returning it and the length of its header. The header is
+/// always written as text, so that it can be damaged as text.
+fn encode(image: &RpegImage) -> (Vec<u8>, usize) {
+    let mut image = image.clone();
+    image.header.binary = false;
+
+    let mut header = Vec::new();
+    header::write_header(&mut header, &image.header).expect("Writing to a Vec can't fail");
+    (image.to_bytes(), header.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flip_bits() {
+        let image = RpegImage::new(vec![[0x00; 4]; 4], 4, 4);
+        let flipped = RpegImage::from_bytes(&flip_bits(&image, 5, 7)).unwrap();
+        let ones: u32 = flipped
+            .words
+            .iter()
+            .flatten()
+            .map(|byte| byte.count_ones())
+            .sum();
+        assert_eq!(ones, 5);
+        assert_eq!(flip_bits(&image, 5, 7), flip_bits(&image, 5, 7));
+        assert_ne!(flip_bits(&image, 5, 7), flip_bits(&image, 5, 8));
+
+        let flipped = RpegImage::from_bytes(&flip_bits(&image, 1000, 7)).unwrap();
+        assert_eq!(flipped.words, [[0xFF; 4]; 4]);
+        assert_eq!(truncate(&image, 1000), b"");
+    }
+
+    #[test]
+    fn test_mangle_header() {
+        let mut image = RpegImage::new(vec![[0x00; 4]; 4], 4, 4);
+        image.header.checksum = true;
+        image.header.comments.push("a comment".to_string());
+
+        let mangled = |damage| String::from_utf8_lossy(&mangle_header(&image, damage)).into_owned();
+        assert!(
+            mangled(HeaderDamage::UnsupportedVersion).starts_with("Compressed image format 9c\n")
+        );
+        assert!(mangled(HeaderDamage::NonNumericWidth).contains("# a comment\nwide 4\n"));
+        assert!(mangled(HeaderDamage::MissingHeight).contains("# a comment\n4\n"));
+        assert!(mangled(HeaderDamage::DoubledHeight).contains("# a comment\n4 8\n"));
+        assert!(mangled(HeaderDamage::MissingNewline).contains("# a comment\n4 4\0"));
+
+        for damage in [
+            HeaderDamage::BadMagic,
+            HeaderDamage::UnsupportedVersion,
+            HeaderDamage::NonNumericWidth,
+            HeaderDamage::MissingHeight,
+        ] {
+            assert!(
+                RpegImage::from_bytes(&mangle_header(&image, damage)).is_err(),
+                "{damage:?}"
+            );
+        }
+        let doubled = RpegImage::from_bytes(&mangle_header(&image, HeaderDamage::DoubledHeight));
+        assert_eq!(doubled.unwrap().height(), 8);
+    }
+}
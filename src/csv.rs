@@ -0,0 +1,152 @@
+use crate::text::parse_hex_word;
+use crate::{RpegError, RpegImage};
+
+/// The first line of CSV written by [`RpegImage::to_csv`].
+const CSV_HEADER: &str = "row,col,word_hex";
+
+impl RpegImage {
+    /// Encodes the words of the image as CSV, for inspecting where each word lands in the image
+    /// with a spreadsheet.
+    ///
+    /// After a `row,col,word_hex` header line, there is a line for each word with its row and
+    /// column (counting from 0) in the grid of 2x2 blocks that the words cover, and the word as
+    /// eight hexadecimal digits with a `0x` prefix, which keeps spreadsheets from reading it as
+    /// a number. Nothing from the header except the size of the image is kept.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let words = vec![[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]];
+    /// let image = RpegImage::new(words, 4, 2);
+    /// let csv = image.to_csv();
+    ///
+    /// assert_eq!(csv, "row,col,word_hex\n0,0,0x00112233\n0,1,0x44556677\n");
+    /// assert_eq!(RpegImage::from_csv(&csv).unwrap(), image);
+    /// ```
+    pub fn to_csv(&self) -> String {
+        use std::fmt::Write;
+
+        let mut csv = format!("{CSV_HEADER}\n");
+        let row_len = (self.width() as usize / 2).max(1);
+
+        for (index, word) in self.words.iter().enumerate() {
+            let (row, col) = (index / row_len, index % row_len);
+            let word = u32::from_be_bytes(*word);
+            writeln!(csv, "{row},{col},0x{word:08X}").unwrap();
+        }
+
+        csv
+    }
+
+    /// Parses the words of an image from CSV in the form written by [`RpegImage::to_csv`]. The
+    /// lines may be in any order (such as after sorting in a spreadsheet), and the header line
+    /// is optional.
+    ///
+    /// Since the CSV doesn't record the size of the image, it's worked out from the largest row
+    /// and column: every block of the image must have exactly one word.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If a line doesn't have three fields, a row or column isn't a number, or a word isn't
+    ///   (at most) eight hexadecimal digits, with or without a `0x` prefix
+    /// * If any block of the image is missing a word, or has more than one
+    pub fn from_csv(csv: &str) -> Result<Self, RpegError> {
+        let mut cells = Vec::new();
+        let mut offset = 0;
+
+        for line in csv.split_inclusive('\n') {
+            let fields = line.trim_end_matches(['\n', '\r']);
+            let line_offset = offset;
+            offset += line.len();
+
+            if fields.is_empty() || (line_offset == 0 && fields == CSV_HEADER) {
+                continue;
+            }
+
+            let cell = parse_line(fields).ok_or_else(|| RpegError::Format {
+                message: format!("Expected a line of the form {CSV_HEADER}, found {fields:?}"),
+                offset: line_offset as u64,
+            })?;
+            cells.push((cell, line_offset));
+        }
+
+        let rows = cells
+            .iter()
+            .map(|((row, _, _), _)| *row as u64 + 1)
+            .max()
+            .unwrap_or(0);
+        let cols = cells
+            .iter()
+            .map(|((_, col, _), _)| *col as u64 + 1)
+            .max()
+            .unwrap_or(0);
+        let (Ok(width), Ok(height)) = (u32::try_from(cols * 2), u32::try_from(rows * 2)) else {
+            return Err(RpegError::Format {
+                message: "The image is too large".to_string(),
+                offset: 0,
+            });
+        };
+
+        cells.sort_by_key(|((row, col, _), _)| (*row, *col));
+        let mut words = Vec::with_capacity(cells.len());
+        for (index, ((row, col, word), offset)) in cells.into_iter().enumerate() {
+            let expected = (index as u64 / cols, index as u64 % cols);
+            let found = (row as u64, col as u64);
+
+            if expected != found {
+                let ((row, col), problem) = if expected < found {
+                    (expected, "is missing a word")
+                } else {
+                    (found, "has more than one word")
+                };
+                return Err(RpegError::Format {
+                    message: format!("The block in row {row} and column {col} {problem}"),
+                    offset: offset as u64,
+                });
+            }
+            words.push(word);
+        }
+
+        Ok(RpegImage::new(words, width, height))
+    }
+}
+
+fn parse_line(line: &str) -> Option<(u32, u32, [u8; 4])> {
+    let mut fields = line.split(',').map(str::trim);
+    let row = fields.next()?.parse().ok()?;
+    let col = fields.next()?.parse().ok()?;
+    let word = parse_hex_word(fields.next()?)?;
+    if fields.next().is_some() {
+        return None;
+    }
+
+    Some((row, col, word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_any_order() {
+        let csv = "1,0,0x2\r\n0,1, 1\r\n\r\n1,1,0XFFFFFFFF\r\n0,0,0x00000000\r\n";
+        let image = RpegImage::from_csv(csv).unwrap();
+
+        assert_eq!((image.width(), image.height()), (4, 4));
+        assert_eq!(image.words, [[0; 4], [0, 0, 0, 1], [0, 0, 0, 2], [0xFF; 4]]);
+    }
+
+    #[test]
+    fn test_csv_errors() {
+        let error_offset = |csv: &str| match RpegImage::from_csv(csv) {
+            Err(RpegError::Format { offset, .. }) => offset,
+            result => panic!("Expected a format error, found {result:?}"),
+        };
+
+        assert_eq!(error_offset("row,col,word_hex\n0,0,0x1\n0,1\n"), 25);
+        assert_eq!(error_offset("0,0,0x1\n0,1,0x123456789\n"), 8);
+        assert_eq!(error_offset("0,0,0x1\n0,1,0x2\n1,1,0x3\n"), 16);
+        assert_eq!(error_offset("0,0,0x1\n0,0,0x2\n"), 8);
+    }
+}
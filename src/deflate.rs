@@ -0,0 +1,456 @@
+//! A small implementation of DEFLATE (RFC 1951), the compression used by gzip.
+//!
+//! Compression uses LZ77 with the fixed Huffman codes, which gets most of the benefit for rpeg
+//! payloads while keeping this module short. Decompression supports everything.
+
+/// The smallest and largest lengths of a match, and the farthest back one may be.
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const WINDOW_SIZE: usize = 32 * 1024;
+
+/// How many earlier positions with the same hash are tried when looking for a match.
+const MAX_CHAIN: usize = 64;
+
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Writes bits least-significant first, as DEFLATE requires.
+#[derive(Debug, Default)]
+pub(crate) struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, len: u32) {
+        self.bits |= (value as u64) << self.bit_count;
+        self.bit_count += len;
+
+        while self.bit_count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Huffman codes are the exception, and are written most-significant bit first.
+    fn put_code(&mut self, code: u32, len: u32) {
+        self.put(code.reverse_bits() >> (32 - len), len);
+    }
+
+    /// Takes the complete bytes written so far, leaving any partial byte behind.
+    pub(crate) fn take_bytes(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.bytes)
+    }
+
+    /// Pads the last partial byte with zeroes, and takes everything written.
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.put(0, 8 - self.bit_count);
+        }
+
+        self.bytes
+    }
+
+    fn put_literal(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+
+        match symbol {
+            0..=143 => self.put_code(0x30 + symbol, 8),
+            144..=255 => self.put_code(0x190 + symbol - 144, 9),
+            256..=279 => self.put_code(symbol - 256, 7),
+            _ => self.put_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn put_match(&mut self, length: usize, distance: usize) {
+        let code = LENGTH_BASES.partition_point(|&base| base as usize <= length) - 1;
+        self.put_literal(257 + code as u16);
+        let extra = LENGTH_EXTRA_BITS[code] as u32;
+        self.put((length - LENGTH_BASES[code] as usize) as u32, extra);
+
+        let code = DISTANCE_BASES.partition_point(|&base| base as usize <= distance) - 1;
+        self.put_code(code as u32, 5);
+        let extra = DISTANCE_EXTRA_BITS[code] as u32;
+        self.put((distance - DISTANCE_BASES[code] as usize) as u32, extra);
+    }
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let key = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+    (key.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compresses `data` as a single block with the fixed Huffman codes. Matches never reach back
+/// before the start of `data`, so each block can be decompressed on its own.
+pub(crate) fn compress_block(data: &[u8], is_final: bool, output: &mut BitWriter) {
+    output.put(is_final as u32, 1);
+    output.put(1, 2);
+
+    // The most recent position with each hash, and the previous one with the same hash as each
+    // position in the window (offset by one, so that zero means none)
+    let mut head = vec![0usize; 1 << HASH_BITS];
+    let mut prev = vec![0usize; WINDOW_SIZE];
+
+    let mut position = 0;
+    let insert = |position: usize, head: &mut [usize], prev: &mut [usize]| {
+        if position + MIN_MATCH <= data.len() {
+            let hash = hash(&data[position..]);
+            prev[position % WINDOW_SIZE] = head[hash];
+            head[hash] = position + 1;
+        }
+    };
+
+    while position < data.len() {
+        let (mut best_length, mut best_distance) = (0, 0);
+
+        if position + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - position);
+            let mut candidate = head[hash(&data[position..])];
+            let mut chain = 0;
+
+            while candidate != 0 && chain < MAX_CHAIN {
+                let start = candidate - 1;
+                let distance = position - start;
+                if distance > WINDOW_SIZE {
+                    break;
+                }
+
+                let length = data[start..]
+                    .iter()
+                    .zip(&data[position..position + max_length])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best_length {
+                    (best_length, best_distance) = (length, distance);
+                    if length == max_length {
+                        break;
+                    }
+                }
+
+                let next = prev[start % WINDOW_SIZE];
+                // Older entries in `prev` may have been overwritten by newer positions
+                if next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            output.put_match(best_length, best_distance);
+            for offset in 0..best_length {
+                insert(position + offset, &mut head, &mut prev);
+            }
+            position += best_length;
+        } else {
+            output.put_literal(data[position] as u16);
+            insert(position, &mut head, &mut prev);
+            position += 1;
+        }
+    }
+
+    // The end of the block
+    output.put_literal(256);
+}
+
+/// Reads bits least-significant first, as DEFLATE requires.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    bits: u32,
+    bit_count: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, len: u32) -> Result<u32, String> {
+        while self.bit_count < len {
+            let byte = *self
+                .bytes
+                .get(self.position)
+                .ok_or("The compressed data ended unexpectedly")?;
+            self.bits |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+            self.position += 1;
+        }
+
+        let value = self.bits & ((1u64 << len) - 1) as u32;
+        self.bits = self.bits.checked_shr(len).unwrap_or(0);
+        self.bit_count -= len;
+
+        Ok(value)
+    }
+
+    /// Skips to the start of the next byte, for stored blocks.
+    fn align(&mut self) {
+        self.bits = 0;
+        self.bit_count = 0;
+    }
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length and the symbols in
+/// order of their codes.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> Result<u16, String> {
+        // Codes of each length follow on from the (doubled) codes of the length before
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+
+        for len in 1..16 {
+            code |= bits.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err("The compressed data contains an invalid code".to_string())
+    }
+}
+
+/// The order in which the lengths of the code length code are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decompresses DEFLATE data, returning it and how many bytes of `bytes` it took up.
+pub(crate) fn decompress(bytes: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let mut bits = BitReader {
+        bytes,
+        position: 0,
+        bits: 0,
+        bit_count: 0,
+    };
+    let mut output = Vec::with_capacity(bytes.len() * 3);
+
+    loop {
+        let is_final = bits.bits(1)? == 1;
+
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let header = bits
+                    .bytes
+                    .get(bits.position..bits.position + 4)
+                    .ok_or("The compressed data ended unexpectedly")?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                if len != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err("A stored block's length is corrupted".to_string());
+                }
+
+                let start = bits.position + 4;
+                let block = bits
+                    .bytes
+                    .get(start..start + len as usize)
+                    .ok_or("The compressed data ended unexpectedly")?;
+                output.extend_from_slice(block);
+                bits.position = start + len as usize;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                decompress_block(&mut bits, &literals, &distances, &mut output)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(&mut bits)?;
+                decompress_block(&mut bits, &literals, &distances, &mut output)?;
+            }
+            _ => return Err("The compressed data contains an invalid block type".to_string()),
+        }
+
+        if is_final {
+            return Ok((output, bits.position));
+        }
+    }
+}
+
+fn read_dynamic_codes(bits: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_length_count = bits.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = bits.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code_length_code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or("The compressed data repeats a missing code length")?;
+                (previous, 3 + bits.bits(2)?)
+            }
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+
+    if lengths.len() != literal_count + distance_count {
+        return Err("The compressed data has too many code lengths".to_string());
+    }
+
+    let (literal_lengths, distance_lengths) = lengths.split_at(literal_count);
+    Ok((
+        Huffman::new(literal_lengths),
+        Huffman::new(distance_lengths),
+    ))
+}
+
+fn decompress_block(
+    bits: &mut BitReader,
+    literals: &Huffman,
+    distances: &Huffman,
+    output: &mut Vec<u8>,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let code = symbol - 257;
+                let length = LENGTH_BASES[code] as usize
+                    + bits.bits(LENGTH_EXTRA_BITS[code] as u32)? as usize;
+
+                let code = distances.decode(bits)? as usize;
+                if code >= DISTANCE_BASES.len() {
+                    return Err("The compressed data contains an invalid distance".to_string());
+                }
+                let distance = DISTANCE_BASES[code] as usize
+                    + bits.bits(DISTANCE_EXTRA_BITS[code] as u32)? as usize;
+
+                if distance > output.len() {
+                    return Err("The compressed data refers back past its start".to_string());
+                }
+
+                // Matches may overlap what they copy, so this has to go a byte at a time
+                let start = output.len() - distance;
+                for index in start..start + length {
+                    output.push(output[index]);
+                }
+            }
+            _ => return Err("The compressed data contains an invalid length".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) {
+        let mut bits = BitWriter::default();
+        compress_block(&data[..data.len() / 2], false, &mut bits);
+        compress_block(&data[data.len() / 2..], true, &mut bits);
+        let compressed = bits.finish();
+
+        let (decompressed, len) = decompress(&compressed).unwrap();
+        assert_eq!(decompressed, data);
+        assert_eq!(len, compressed.len());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(b"");
+        round_trip(b"a");
+        round_trip(&[0; 100_000]);
+        round_trip(
+            &(0..100_000u32)
+                .flat_map(|n| (n % 1000).to_be_bytes())
+                .collect::<Vec<_>>(),
+        );
+        round_trip(
+            &(0..100_000u32)
+                .map(|n| (n.wrapping_mul(2_654_435_761) >> 24) as u8)
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn test_decompress_stored_and_dynamic() {
+        // From `printf 'hello hello hello hello\n' | gzip -9`, minus the gzip framing
+        let fixed = [
+            0xCB, 0x48, 0xCD, 0xC9, 0xC9, 0x57, 0xC8, 0x40, 0x27, 0xB9, 0x00,
+        ];
+        assert_eq!(decompress(&fixed).unwrap().0, b"hello hello hello hello\n");
+
+        // From zlib, with the Huffman-only strategy (which always uses dynamic codes here)
+        let text = b"ababbaaaabbaabbaabaabbaabbbabbaaabbababbabbbbbaabbaaabbbbaaababbabbbabaaabaababbaababbabbbabababbabb\
+            ababababbbababaaaaaaaababaabbabaabbbaabbababaaabbaaabbababababbabaabbbbbabbbbabaababaabbbbaaaaababba";
+        let dynamic = [
+            0x05, 0xC1, 0x01, 0x01, 0x00, 0x00, 0x00, 0x80, 0x90, 0xAD, 0xF9, 0x3F, 0x22, 0x12,
+            0x55, 0x14, 0xA5, 0x28, 0x44, 0x45, 0x22, 0x50, 0x54, 0x50, 0x89, 0x90, 0x4A, 0x89,
+            0x12, 0x21, 0x89, 0x48, 0x12, 0x92, 0xAA, 0x2A, 0x29, 0x52, 0x28, 0x92, 0x8A, 0x8A,
+            0x24, 0x91, 0x02, 0x41, 0x4A, 0x0A, 0xAA, 0x12, 0x0D,
+        ];
+        assert_eq!(decompress(&dynamic).unwrap().0, text);
+
+        let stored = [0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c'];
+        assert_eq!(decompress(&stored).unwrap(), (b"abc".to_vec(), 8));
+
+        assert!(decompress(&[0x07]).is_err());
+        assert!(decompress(&stored[..6]).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+use crate::sha256::{sha256, DIGEST_LEN};
+use crate::{ContentHash, RpegError, RpegImage};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Finds groups of identical outputs among many submissions, such as a directory (or, with the
+/// `archive` feature, a zip) of every student's compressed images, for a TA to review.
+///
+/// Files are grouped by their [`ContentHash`], so outputs which differ only in the spelling of
+/// their headers (such as line endings) are still grouped together. Each group also notes
+/// whether its files are byte-for-byte identical. Since every correct, deterministic compressor
+/// gives the same output, groups matching a [`reference`](DuplicateFinder::reference) output
+/// are expected; any other group of identical outputs is suspicious.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{DuplicateFinder, RpegImage};
+///
+/// let reference = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+/// let odd = RpegImage::new(vec![[0xDE, 0xAD, 0xBE, 0xEF]], 2, 2);
+///
+/// let mut finder = DuplicateFinder::new().reference(reference.content_hash());
+/// finder.add("alice.rpeg", &reference.to_bytes());
+/// finder.add("bob.rpeg", &reference.to_bytes());
+/// finder.add("carol.rpeg", &odd.to_bytes());
+/// finder.add("dave.rpeg", &odd.to_bytes());
+/// finder.add("erin.rpeg", b"not rpeg data");
+/// let report = finder.report();
+///
+/// assert_eq!(report.groups.len(), 2);
+/// let suspicious: Vec<_> = report.suspicious().collect();
+/// assert_eq!(suspicious.len(), 1);
+/// assert_eq!(suspicious[0].paths, ["carol.rpeg", "dave.rpeg"]);
+/// assert_eq!(report.unreadable[0].0, "erin.rpeg");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DuplicateFinder {
+    references: BTreeSet<ContentHash>,
+    files: Vec<(String, Result<Hashes, String>)>,
+}
+
+/// A file's content hash, and the digest of its exact bytes.
+type Hashes = (ContentHash, [u8; DIGEST_LEN]);
+
+/// A group of two or more files with identical output, as part of a [`DuplicateReport`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DuplicateGroup {
+    /// The content hash every file in the group shares
+    pub hash: ContentHash,
+    /// The paths of the files in the group, in order
+    pub paths: Vec<String>,
+    /// Whether the files are byte-for-byte identical, not only canonically identical
+    pub byte_identical: bool,
+    /// Whether the output matches one of the reference outputs
+    pub matches_reference: bool,
+}
+
+/// The groups of identical outputs found by a [`DuplicateFinder`]. Its
+/// [`Display`](fmt::Display) implementation lists them, suspicious groups first.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DuplicateReport {
+    /// Every group of two or more identical outputs: suspicious groups first, then larger groups
+    /// before smaller ones
+    pub groups: Vec<DuplicateGroup>,
+    /// The path of each file which couldn't be read or parsed, with the reason why
+    pub unreadable: Vec<(String, String)>,
+}
+
+impl DuplicateFinder {
+    /// Creates a finder with no files and no reference outputs.
+    pub fn new() -> Self {
+        DuplicateFinder::default()
+    }
+
+    /// Marks outputs with this content hash (such as that of the reference solution's output)
+    /// as expected, so groups of them aren't reported as suspicious. May be given more than
+    /// once.
+    pub fn reference(mut self, hash: ContentHash) -> Self {
+        self.references.insert(hash);
+        self
+    }
+
+    /// Adds a file with the given path (used only to identify it in the report) and contents.
+    pub fn add(&mut self, path: &str, bytes: &[u8]) {
+        let hashes = RpegImage::from_bytes(bytes)
+            .map(|image| (image.content_hash(), sha256(bytes)))
+            .map_err(|error| error.to_string());
+
+        self.files.push((path.to_string(), hashes));
+    }
+
+    /// Adds every file in the directory `dir`, and in every directory inside it, whose name
+    /// ends in `.rpeg`. Each is identified by its path, starting with `dir`.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If a directory can't be listed. Files which can't be read are reported as unreadable
+    ///   instead
+    pub fn scan_dir(&mut self, dir: &str) -> Result<(), RpegError> {
+        for path in find_rpeg_files(dir)? {
+            match std::fs::read(&path) {
+                Ok(bytes) => self.add(&path, &bytes),
+                Err(error) => self.files.push((path, Err(error.to_string()))),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds every file in the archive whose path matches the glob `pattern` (as for
+    /// [`RpegArchive::images`](crate::RpegArchive::images)). Enabled by the `archive` feature.
+    #[cfg(feature = "archive")]
+    pub fn scan_archive(&mut self, archive: &crate::RpegArchive, pattern: &str) {
+        for (path, bytes) in archive.files(pattern) {
+            match bytes {
+                Ok(bytes) => self.add(path, &bytes),
+                Err(error) => self.files.push((path.to_string(), Err(error.to_string()))),
+            }
+        }
+    }
+
+    /// Groups the files added so far. See [`DuplicateReport`].
+    pub fn report(&self) -> DuplicateReport {
+        let mut by_hash: BTreeMap<ContentHash, Vec<(&str, [u8; DIGEST_LEN])>> = BTreeMap::new();
+        let mut unreadable = Vec::new();
+        for (path, hashes) in &self.files {
+            match hashes {
+                Ok((hash, digest)) => by_hash.entry(*hash).or_default().push((path, *digest)),
+                Err(error) => unreadable.push((path.clone(), error.clone())),
+            }
+        }
+
+        let mut groups: Vec<DuplicateGroup> = by_hash
+            .into_iter()
+            .filter(|(_, files)| files.len() > 1)
+            .map(|(hash, mut files)| {
+                files.sort();
+                DuplicateGroup {
+                    hash,
+                    paths: files.iter().map(|(path, _)| path.to_string()).collect(),
+                    byte_identical: files.iter().all(|(_, digest)| *digest == files[0].1),
+                    matches_reference: self.references.contains(&hash),
+                }
+            })
+            .collect();
+        groups.sort_by(|a, b| {
+            (a.matches_reference, b.paths.len(), &a.paths).cmp(&(
+                b.matches_reference,
+                a.paths.len(),
+                &b.paths,
+            ))
+        });
+
+        DuplicateReport { groups, unreadable }
+    }
+}
+
+/// Finds every file in the directory `dir`, and in every directory inside it, whose name ends
+/// in `.rpeg`, in order of path (so that results don't depend on the order the OS lists files
+/// in).
+pub(crate) fn find_rpeg_files(dir: &str) -> Result<Vec<String>, RpegError> {
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    let mut files = Vec::new();
+    for path in entries {
+        let name = path.to_string_lossy().into_owned();
+        if path.is_dir() {
+            files.extend(find_rpeg_files(&name)?);
+        } else if name.ends_with(".rpeg") {
+            files.push(name);
+        }
+    }
+
+    Ok(files)
+}
+
+impl DuplicateGroup {
+    /// Whether the group is worth a TA's attention: identical outputs which don't match any
+    /// reference output.
+    pub fn is_suspicious(&self) -> bool {
+        !self.matches_reference
+    }
+}
+
+impl DuplicateReport {
+    /// The groups which are suspicious. See [`DuplicateGroup::is_suspicious`].
+    pub fn suspicious(&self) -> impl Iterator<Item = &DuplicateGroup> {
+        self.groups.iter().filter(|group| group.is_suspicious())
+    }
+}
+
+impl fmt::Display for DuplicateReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |count: usize, noun: &str| match count {
+            1 => format!("1 {noun}"),
+            count => format!("{count} {noun}s"),
+        };
+
+        write!(
+            f,
+            "{} of identical outputs ({} suspicious), {}",
+            plural(self.groups.len(), "group"),
+            self.suspicious().count(),
+            plural(self.unreadable.len(), "unreadable file")
+        )?;
+
+        for group in &self.groups {
+            let kind = match group.is_suspicious() {
+                true => "suspicious",
+                false => "matches the reference",
+            };
+            let identical = match group.byte_identical {
+                true => "byte-identical",
+                false => "canonically identical",
+            };
+            write!(
+                f,
+                "\n{kind}: {}, {identical}, {}",
+                plural(group.paths.len(), "file"),
+                group.hash
+            )?;
+            for path in &group.paths {
+                write!(f, "\n  {path}")?;
+            }
+        }
+
+        for (path, error) in &self.unreadable {
+            write!(f, "\nunreadable: {path}: {error}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+        let bytes = image.to_bytes();
+        let messy = b"Compressed image format 2\r\n2 2\r\n\x00\x11\x22\x33";
+        let other = RpegImage::new(vec![[0x01; 4]], 2, 2).to_bytes();
+
+        let mut finder = DuplicateFinder::new();
+        finder.add("c", &bytes);
+        finder.add("a", &bytes);
+        finder.add("b", messy);
+        finder.add("d", &other);
+        finder.add("e", &other);
+        finder.add("f", &RpegImage::new(vec![], 0, 0).to_bytes());
+        let report = finder.report();
+
+        assert_eq!(report.groups.len(), 2);
+        assert_eq!(report.groups[0].paths, ["a", "b", "c"]);
+        assert!(!report.groups[0].byte_identical);
+        assert_eq!(report.groups[1].paths, ["d", "e"]);
+        assert!(report.groups[1].byte_identical);
+        assert_eq!(report.suspicious().count(), 2);
+
+        let report = finder.reference(image.content_hash()).report();
+        assert_eq!(report.groups[0].paths, ["d", "e"]);
+        assert!(report.groups[1].matches_reference);
+        assert_eq!(
+            report.to_string().lines().next().unwrap(),
+            "2 groups of identical outputs (1 suspicious), 0 unreadable files"
+        );
+    }
+
+    #[test]
+    fn test_scan_dir() {
+        let dir = std::env::temp_dir().join("csc411_rpegio_test_scan_dir");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("alice")).unwrap();
+        std::fs::create_dir_all(dir.join("bob")).unwrap();
+        let bytes = RpegImage::new(vec![[0xAB; 4]], 2, 2).to_bytes();
+        std::fs::write(dir.join("alice/out.rpeg"), &bytes).unwrap();
+        std::fs::write(dir.join("bob/out.rpeg"), &bytes).unwrap();
+        std::fs::write(dir.join("bob/notes.txt"), &bytes).unwrap();
+        std::fs::write(
+            dir.join("broken.rpeg"),
+            b"Compressed image format 2\n2 2\n\x00",
+        )
+        .unwrap();
+
+        let mut finder = DuplicateFinder::new();
+        let scanned = finder.scan_dir(dir.to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        scanned.unwrap();
+        let report = finder.report();
+
+        assert_eq!(report.groups.len(), 1);
+        assert!(report.groups[0].paths[0].ends_with("alice/out.rpeg"));
+        assert!(report.groups[0].paths[1].ends_with("bob/out.rpeg"));
+        assert_eq!(report.unreadable.len(), 1);
+        assert!(finder.scan_dir("/does/not/exist").is_err());
+    }
+}
@@ -0,0 +1,95 @@
+/// The start of every rpeg file, up to its format version.
+const MAGIC: &[u8] = b"Compressed image format ";
+
+/// Embeds an rpeg file in the program, as a `&'static RpegImage`, so that tests and examples
+/// don't depend on the directory they're run from.
+///
+/// As with `include_bytes!`, the path is relative to the file the macro is used in. The file
+/// is checked to be rpeg data when compiling (so naming the wrong file is a compile error),
+/// and parsed the first time the image is used; every later use returns the same image.
+///
+/// # Panics
+///
+/// * The first time the image is used, if the file can't be parsed
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{include_rpeg, RpegImage};
+///
+/// let image: &'static RpegImage =
+///     include_rpeg!(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/tiny.rpeg"));
+/// assert_eq!(image.words, [[0x00, 0x11, 0x22, 0x33]]);
+/// ```
+///
+/// ```compile_fail
+/// // Not rpeg data
+/// let image = csc411_rpegio::include_rpeg!(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"));
+/// ```
+#[macro_export]
+macro_rules! include_rpeg {
+    ($path:expr $(,)?) => {{
+        const _: () = ::std::assert!(
+            $crate::__is_rpeg_data(::std::include_bytes!($path)),
+            ::std::concat!($path, " isn't rpeg data")
+        );
+        static IMAGE: ::std::sync::OnceLock<$crate::RpegImage> = ::std::sync::OnceLock::new();
+
+        IMAGE.get_or_init(|| {
+            $crate::RpegImage::from_bytes(::std::include_bytes!($path))
+                .unwrap_or_else(|error| ::std::panic!("{} isn't valid rpeg data: {error}", $path))
+        })
+    }};
+}
+
+/// Whether the bytes start like rpeg data (or gzip-compressed data, with the `gzip` feature),
+/// for [`include_rpeg!`] to check when compiling. Whether they can actually be parsed is only
+/// checked once the program runs.
+#[doc(hidden)]
+pub const fn __is_rpeg_data(bytes: &[u8]) -> bool {
+    if cfg!(feature = "gzip") && bytes.len() >= 2 && bytes[0] == 0x1F && bytes[1] == 0x8B {
+        return true;
+    }
+    if bytes.len() <= MAGIC.len() {
+        return false;
+    }
+
+    let mut index = 0;
+    while index < MAGIC.len() {
+        if bytes[index] != MAGIC[index] {
+            return false;
+        }
+        index += 1;
+    }
+
+    bytes[index].is_ascii_digit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RpegImage;
+
+    #[test]
+    fn test_include_rpeg() {
+        let image = include_rpeg!("../fixtures/odd_sized.rpeg");
+        assert_eq!((image.width(), image.height()), (7, 5));
+        assert_eq!(image.words.len(), 6);
+
+        // Every use returns the same image
+        let first: *const RpegImage = image;
+        let again = (0..2).map(|_| include_rpeg!("../fixtures/empty.rpeg") as *const RpegImage);
+        let again: Vec<_> = again.collect();
+        assert_eq!(again[0], again[1]);
+        assert_ne!(first, again[0]);
+    }
+
+    #[test]
+    fn test_is_rpeg_data() {
+        assert!(__is_rpeg_data(b"Compressed image format 2\n2 2\n"));
+        assert!(__is_rpeg_data(b"Compressed image format 3"));
+        assert!(!__is_rpeg_data(b"Compressed image format "));
+        assert!(!__is_rpeg_data(b"Compressed image format x"));
+        assert!(!__is_rpeg_data(b"P6\n2 2\n255\n"));
+        assert!(!__is_rpeg_data(b""));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::chacha20poly1305::{self, KEY_LEN, NONCE_LEN};
+use crate::sha256::{hmac_sha256, DIGEST_LEN};
+use crate::{RpegError, RpegImage};
+
+/// The first line of an encrypted container, which also names the version of the container.
+const ENCRYPTED_MAGIC: &[u8] = b"Encrypted compressed image 1\n";
+
+/// The number of PBKDF2 iterations used to turn a passphrase into a key, following OWASP's
+/// recommendation for PBKDF2-HMAC-SHA256.
+const DEFAULT_ITERATIONS: u32 = 600_000;
+
+/// The most PBKDF2 iterations a container may ask for. The number is read before anything is
+/// authenticated, so without a limit a crafted container could take hours to reject.
+const MAX_ITERATIONS: u32 = 10 * DEFAULT_ITERATIONS;
+
+const SALT_LEN: usize = 16;
+
+/// The length of everything before the ciphertext: the magic line, the number of iterations, the
+/// salt, and the nonce.
+const PREAMBLE_LEN: usize = ENCRYPTED_MAGIC.len() + 4 + SALT_LEN + NONCE_LEN;
+
+impl RpegImage {
+    /// Encrypts the image with a passphrase, for sharing test inputs made from personal photos
+    /// without exposing them. Enabled by the `crypto` feature.
+    ///
+    /// The container starts with the line `Encrypted compressed image 1`, followed by (in
+    /// binary) the number of PBKDF2-HMAC-SHA256 iterations used to derive a key from the
+    /// passphrase (a big-endian `u32`), a random 16-byte salt, and a random 12-byte nonce. The
+    /// rest is the image's rpeg data encrypted with ChaCha20-Poly1305 (RFC 8439), authenticating
+    /// everything before it as well.
+    ///
+    /// The random salt and nonce come from the operating system: `BCryptGenRandom` on Windows,
+    /// and `/dev/urandom` everywhere else.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the operating system's random number generator can't be used
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let image = RpegImage::read(Some("path/to/file.rpeg")).unwrap();
+    /// let encrypted = image.to_encrypted("correct horse battery staple").unwrap();
+    ///
+    /// let decrypted = RpegImage::from_encrypted(&encrypted, "correct horse battery staple");
+    /// assert_eq!(decrypted.unwrap(), image);
+    /// ```
+    pub fn to_encrypted(&self, passphrase: &str) -> Result<Vec<u8>, RpegError> {
+        let mut random = [0; SALT_LEN + NONCE_LEN];
+        fill_random(&mut random)?;
+        let (salt, nonce) = random.split_at(SALT_LEN);
+
+        Ok(encrypt(
+            &self.to_bytes(),
+            passphrase,
+            DEFAULT_ITERATIONS,
+            salt.try_into().unwrap(),
+            nonce.try_into().unwrap(),
+        ))
+    }
+
+    /// Decrypts an image encrypted by [`RpegImage::to_encrypted`].
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the bytes aren't an encrypted container, or end before the end of its preamble
+    /// * If the container asks for no PBKDF2 iterations, or for more than ten times as many as
+    ///   [`RpegImage::to_encrypted`] uses
+    /// * [`RpegError::DecryptionFailed`] if the passphrase is wrong, or the container has been
+    ///   tampered with or corrupted
+    /// * If the decrypted data isn't valid rpeg data
+    pub fn from_encrypted(bytes: &[u8], passphrase: &str) -> Result<Self, RpegError> {
+        RpegImage::from_bytes(&decrypt(bytes, passphrase)?)
+    }
+}
+
+/// Fills `buf` with random bytes from the operating system.
+#[cfg(not(windows))]
+fn fill_random(buf: &mut [u8]) -> Result<(), RpegError> {
+    use std::io::Read;
+
+    std::fs::File::open("/dev/urandom")?.read_exact(buf)?;
+
+    Ok(())
+}
+
+/// Fills `buf` with random bytes from the operating system.
+#[cfg(windows)]
+fn fill_random(buf: &mut [u8]) -> Result<(), RpegError> {
+    use std::ffi::c_void;
+
+    // Uses the system's preferred generator, so no algorithm handle needs to be opened
+    const BCRYPT_USE_SYSTEM_PREFERRED_RNG: u32 = 0x0000_0002;
+
+    #[link(name = "bcrypt")]
+    extern "system" {
+        fn BCryptGenRandom(algorithm: *mut c_void, buf: *mut u8, len: u32, flags: u32) -> i32;
+    }
+
+    for chunk in buf.chunks_mut(u32::MAX as usize) {
+        // SAFETY: the pointer and length describe a writable chunk of `buf`, and a null algorithm
+        // handle is allowed with BCRYPT_USE_SYSTEM_PREFERRED_RNG
+        let status = unsafe {
+            BCryptGenRandom(
+                std::ptr::null_mut(),
+                chunk.as_mut_ptr(),
+                chunk.len() as u32,
+                BCRYPT_USE_SYSTEM_PREFERRED_RNG,
+            )
+        };
+
+        // An NTSTATUS is negative on failure
+        if status < 0 {
+            return Err(std::io::Error::other(format!(
+                "BCryptGenRandom failed with status {status:#010x}"
+            ))
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+fn encrypt(
+    plaintext: &[u8],
+    passphrase: &str,
+    iterations: u32,
+    salt: &[u8; SALT_LEN],
+    nonce: &[u8; NONCE_LEN],
+) -> Vec<u8> {
+    let mut container = Vec::with_capacity(PREAMBLE_LEN + plaintext.len() + 16);
+    container.extend(ENCRYPTED_MAGIC);
+    container.extend(iterations.to_be_bytes());
+    container.extend(salt);
+    container.extend(nonce);
+
+    let key = derive_key(passphrase, salt, iterations);
+    let sealed = chacha20poly1305::seal(&key, nonce, &container, plaintext);
+    container.extend(sealed);
+
+    container
+}
+
+fn decrypt(container: &[u8], passphrase: &str) -> Result<Vec<u8>, RpegError> {
+    if !container.starts_with(ENCRYPTED_MAGIC) {
+        return Err(RpegError::Format {
+            message: "Expected an encrypted container".to_string(),
+            offset: 0,
+        });
+    }
+    if container.len() < PREAMBLE_LEN {
+        return Err(RpegError::Format {
+            message: "The encrypted container ended early".to_string(),
+            offset: container.len() as u64,
+        });
+    }
+
+    let (preamble, sealed) = container.split_at(PREAMBLE_LEN);
+    let (iterations, rest) = preamble[ENCRYPTED_MAGIC.len()..].split_at(4);
+    let (salt, nonce) = rest.split_at(SALT_LEN);
+    let iterations = u32::from_be_bytes(iterations.try_into().unwrap());
+    if !(1..=MAX_ITERATIONS).contains(&iterations) {
+        return Err(RpegError::Format {
+            message: format!(
+                "The encrypted container asks for {iterations} PBKDF2 iterations, but at most \
+                 {MAX_ITERATIONS} are allowed (and at least 1)"
+            ),
+            offset: ENCRYPTED_MAGIC.len() as u64,
+        });
+    }
+
+    let key = derive_key(passphrase, salt.try_into().unwrap(), iterations);
+    chacha20poly1305::open(&key, nonce.try_into().unwrap(), preamble, sealed)
+        .ok_or(RpegError::DecryptionFailed)
+}
+
+/// Derives a key from a passphrase with PBKDF2-HMAC-SHA256 (RFC 8018). Since the key is no
+/// longer than one SHA-256 digest, only a single block is needed.
+fn derive_key(passphrase: &str, salt: &[u8; SALT_LEN], iterations: u32) -> [u8; KEY_LEN] {
+    let mut message = [0; SALT_LEN + 4];
+    message[..SALT_LEN].copy_from_slice(salt);
+    message[SALT_LEN..].copy_from_slice(&1u32.to_be_bytes());
+
+    let mut block: [u8; DIGEST_LEN] = hmac_sha256(passphrase.as_bytes(), &message);
+    let mut key = block;
+    for _ in 1..iterations {
+        block = hmac_sha256(passphrase.as_bytes(), &block);
+        for (key, byte) in key.iter_mut().zip(block) {
+            *key ^= byte;
+        }
+    }
+
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_derive_key() {
+        // From Python's hashlib.pbkdf2_hmac("sha256", b"passphrase", b"0123456789abcdef", 1000)
+        assert_eq!(
+            derive_key("passphrase", b"0123456789abcdef", 1000)[..],
+            unhex("79fb009af2209905f16f041902cbeb904a639320ff0965fd3c787458e830c779")
+        );
+    }
+
+    #[test]
+    fn test_encrypted_container() {
+        let salt = std::array::from_fn(|index| index as u8);
+        let nonce = std::array::from_fn(|index| 16 + index as u8);
+        let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+        let container = encrypt(&image.to_bytes(), "hunter2", 1000, &salt, &nonce);
+
+        // The same as Python's cryptography package gives
+        assert_eq!(container.len(), PREAMBLE_LEN + 34 + 16);
+        assert_eq!(
+            container[PREAMBLE_LEN..],
+            unhex(
+                "55ac589cb0dd00f25a012e84f391e087da3a8c01abbf6ccd50e0eae0f7dc3f7b8edbd9d643d4994a\
+                 5fde7d47b1a8c470167e"
+            )
+        );
+        assert_eq!(
+            RpegImage::from_encrypted(&container, "hunter2").unwrap(),
+            image
+        );
+
+        assert!(matches!(
+            RpegImage::from_encrypted(&container, "hunter3"),
+            Err(RpegError::DecryptionFailed)
+        ));
+        let mut tampered = container.clone();
+        tampered[ENCRYPTED_MAGIC.len() + 4] ^= 1;
+        assert!(matches!(
+            RpegImage::from_encrypted(&tampered, "hunter2"),
+            Err(RpegError::DecryptionFailed)
+        ));
+        assert!(RpegImage::from_encrypted(&container[..40], "hunter2").is_err());
+
+        // Iteration counts which are rejected before deriving a key
+        for iterations in [0, MAX_ITERATIONS + 1, u32::MAX] {
+            let mut crafted = container.clone();
+            crafted[ENCRYPTED_MAGIC.len()..][..4].copy_from_slice(&iterations.to_be_bytes());
+            assert!(matches!(
+                RpegImage::from_encrypted(&crafted, "hunter2"),
+                Err(RpegError::Format { offset, .. }) if offset == ENCRYPTED_MAGIC.len() as u64
+            ));
+        }
+    }
+}
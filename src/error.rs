@@ -0,0 +1,241 @@
+use crate::codec::DecodeError;
+use std::fmt;
+
+/// The error type returned by the fallible rpeg i/o functions.
+#[derive(Debug)]
+pub enum RpegError {
+    /// The reading or writing end of a pipe was closed before all rpeg data could be written.
+    /// This typically happens when output is piped into a program like `head`, or when a
+    /// downstream program exits (or crashes) early.
+    BrokenPipe,
+    /// Some other error occurred while reading or writing.
+    Io(std::io::Error),
+    /// The input was not valid rpeg data.
+    Format {
+        /// A description of what was wrong with the input
+        message: String,
+        /// How far into the input (in bytes) the problem was found
+        offset: u64,
+    },
+    /// An [`RpegFormat`](crate::RpegFormat) was used whose words are a different size than the
+    /// words being read or written.
+    WordSizeMismatch {
+        /// The size of the words being read or written
+        expected: usize,
+        /// The size of the words in the format
+        found: usize,
+    },
+    /// A word was too big to convert to a format with smaller words.
+    WordOutOfRange {
+        /// The index of the first word which didn't fit
+        index: usize,
+    },
+    /// An image was converted to a format which stores its words as a grid of blocks, but it
+    /// doesn't have exactly one word for each 2x2 block.
+    GridMismatch {
+        /// The number of rows of blocks (half the image's height)
+        rows: u32,
+        /// The number of columns of blocks (half the image's width)
+        cols: u32,
+        /// The number of words in the image
+        word_count: usize,
+    },
+    /// A frame was asked for which isn't in a sequence of images.
+    FrameOutOfRange {
+        /// The index of the frame asked for
+        index: u32,
+        /// The number of frames in the sequence
+        frame_count: u32,
+    },
+    /// A patch was applied to a different image than the one it was made from.
+    PatchMismatch,
+    /// Encrypted rpeg data couldn't be decrypted, because the passphrase was wrong or the data
+    /// was tampered with (or corrupted).
+    DecryptionFailed,
+    /// Signed rpeg data's signature didn't match its contents, meaning either the data was
+    /// modified after it was signed, or it was signed with a different key.
+    SignatureMismatch,
+    /// Rpeg data was expected to be signed, but wasn't.
+    SignatureMissing,
+    /// The CRC32 checksum at the end of checksummed ("format 2c") rpeg data didn't match its
+    /// payload, meaning the data was corrupted (or truncated) somewhere along the way.
+    ChecksumMismatch {
+        /// The checksum recorded at the end of the data
+        expected: u32,
+        /// The checksum of the payload actually read
+        found: u32,
+    },
+    /// The word count at the end of rpeg data with a word count trailer ("format 2t") didn't
+    /// match the number of words in its payload, meaning the data was truncated (or padded)
+    /// somewhere along the way.
+    WordCountMismatch {
+        /// The number of words recorded at the end of the data
+        expected: u64,
+        /// The number of words actually read
+        found: u64,
+    },
+}
+
+impl RpegError {
+    /// A short name for the kind of error, such as `format` or `checksum_mismatch`, for
+    /// programs (such as autograders) to match on in place of the error message. The codes are
+    /// part of the crate's stability guarantees, unlike the wording of the messages.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let error = RpegImage::from_bytes(b"Compressed image format 9\n2 2\n").unwrap_err();
+    /// assert_eq!(error.code(), "format");
+    /// assert_eq!(error.offset(), Some(25));
+    /// ```
+    pub fn code(&self) -> &'static str {
+        match self {
+            RpegError::BrokenPipe => "broken_pipe",
+            RpegError::Io(_) => "io",
+            RpegError::Format { .. } => "format",
+            RpegError::WordSizeMismatch { .. } => "word_size_mismatch",
+            RpegError::WordOutOfRange { .. } => "word_out_of_range",
+            RpegError::GridMismatch { .. } => "grid_mismatch",
+            RpegError::FrameOutOfRange { .. } => "frame_out_of_range",
+            RpegError::PatchMismatch => "patch_mismatch",
+            RpegError::DecryptionFailed => "decryption_failed",
+            RpegError::SignatureMismatch => "signature_mismatch",
+            RpegError::SignatureMissing => "signature_missing",
+            RpegError::ChecksumMismatch { .. } => "checksum_mismatch",
+            RpegError::WordCountMismatch { .. } => "word_count_mismatch",
+        }
+    }
+
+    /// How far into the input (in bytes) the problem was found, for errors about the input's
+    /// format.
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            RpegError::Format { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// A suggestion for what to check or do about the error, if there's a useful one.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            RpegError::BrokenPipe => {
+                Some("The program reading the output exited early, which is usually harmless")
+            }
+            RpegError::Io(_) => Some("Check that the file exists, and can be read or written"),
+            RpegError::Format { .. } => Some(
+                "Check that the input is rpeg data, and that it wasn't changed by being \
+                 written or read in text mode",
+            ),
+            RpegError::WordSizeMismatch { .. } => {
+                Some("Use a format with the same size of words as the image")
+            }
+            RpegError::GridMismatch { .. } => {
+                Some("An image needs one word for each 2x2 block: (width / 2) * (height / 2)")
+            }
+            RpegError::DecryptionFailed => Some("Check the passphrase"),
+            RpegError::SignatureMismatch => Some("Check that the data was signed with this key"),
+            RpegError::SignatureMissing => {
+                Some("Sign the data when writing it, with WriteOptions::signing_key")
+            }
+            RpegError::ChecksumMismatch { .. } | RpegError::WordCountMismatch { .. } => Some(
+                "The data was corrupted or cut short: check that all of it is written (and \
+                 flushed) before the program exits",
+            ),
+            RpegError::WordOutOfRange { .. }
+            | RpegError::FrameOutOfRange { .. }
+            | RpegError::PatchMismatch => None,
+        }
+    }
+}
+
+impl fmt::Display for RpegError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpegError::BrokenPipe => {
+                write!(
+                    f,
+                    "The output pipe was closed before all rpeg data was written"
+                )
+            }
+            RpegError::Io(err) => write!(f, "I/O error: {err}"),
+            RpegError::Format { message, .. } => write!(f, "{message}"),
+            RpegError::WordSizeMismatch { expected, found } => write!(
+                f,
+                "Expected a format with {expected}-byte words, but the format has {found}-byte words"
+            ),
+            RpegError::WordOutOfRange { index } => {
+                write!(f, "Word {index} is too big to fit in a smaller word")
+            }
+            RpegError::GridMismatch {
+                rows,
+                cols,
+                word_count,
+            } => write!(
+                f,
+                "The image has {word_count} words, which don't fill its {rows}x{cols} grid of blocks"
+            ),
+            RpegError::FrameOutOfRange { index, frame_count } => write!(
+                f,
+                "Frame {index} doesn't exist, since the sequence has {frame_count} frames"
+            ),
+            RpegError::PatchMismatch => write!(
+                f,
+                "The patch was made from a different image than the one it's being applied to"
+            ),
+            RpegError::DecryptionFailed => write!(
+                f,
+                "The data couldn't be decrypted: either the passphrase is wrong, or the data has \
+                 been tampered with"
+            ),
+            RpegError::SignatureMismatch => write!(
+                f,
+                "The data's signature doesn't match: either it has been modified since it was \
+                 signed, or it was signed with a different key"
+            ),
+            RpegError::SignatureMissing => write!(f, "Expected the data to be signed"),
+            RpegError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "The payload's checksum (0x{found:08X}) doesn't match the one recorded in the data \
+                 (0x{expected:08X})"
+            ),
+            RpegError::WordCountMismatch { expected, found } => write!(
+                f,
+                "Found {found} words in the payload, but the data says it has {expected} words"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RpegError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RpegError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for RpegError {
+    fn from(err: DecodeError) -> Self {
+        match err {
+            DecodeError::Format { message, offset } => RpegError::Format { message, offset },
+            DecodeError::ChecksumMismatch { expected, found } => {
+                RpegError::ChecksumMismatch { expected, found }
+            }
+            DecodeError::WordCountMismatch { expected, found } => {
+                RpegError::WordCountMismatch { expected, found }
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for RpegError {
+    fn from(err: std::io::Error) -> Self {
+        if err.kind() == std::io::ErrorKind::BrokenPipe {
+            RpegError::BrokenPipe
+        } else {
+            RpegError::Io(err)
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! A C interface to reading and writing rpeg data, so that C programs can use the same
+//! implementation as Rust ones. Enabled by the `ffi` feature.
+//!
+//! The declarations are in `include/rpegio.h`. To link against the crate from C, build it as a
+//! static or dynamic library:
+//!
+//! ```text
+//! cargo rustc --release --features ffi --crate-type staticlib
+//! cc -Iinclude main.c target/release/libcsc411_rpegio.a -o main
+//! ```
+//!
+//! Every function returns [`RPEG_OK`] on success, or one of the other `RPEG_*` codes on
+//! failure, in which case [`rpeg_last_error`] describes what went wrong.
+//!
+//! ```c
+//! #include <stdio.h>
+//! #include "rpegio.h"
+//!
+//! int main(int argc, char *argv[]) {
+//!     struct rpeg_data data;
+//!     if (rpeg_read(argc > 1 ? argv[1] : NULL, &data) != RPEG_OK) {
+//!         fprintf(stderr, "%s\n", rpeg_last_error());
+//!         return 1;
+//!     }
+//!     /* data.bytes[4 * i] to data.bytes[4 * i + 3] are word i, most significant byte first */
+//!     rpeg_write(NULL, &data);
+//!     rpeg_free(&data);
+//!     return 0;
+//! }
+//! ```
+
+use crate::{RpegError, RpegImage};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+
+/// The function succeeded.
+pub const RPEG_OK: c_int = 0;
+
+/// A pointer argument was null (where that isn't allowed), a path wasn't UTF-8, or the data's
+/// length wasn't a whole number of words.
+pub const RPEG_INVALID_ARGUMENT: c_int = 1;
+
+/// A file, stdin, or stdout couldn't be read or written.
+pub const RPEG_IO_ERROR: c_int = 2;
+
+/// The data read wasn't valid rpeg data.
+pub const RPEG_INVALID_DATA: c_int = 3;
+
+/// The words and dimensions of an image, as read by [`rpeg_read`] and written by
+/// [`rpeg_write`]. Data from [`rpeg_read`] must be freed with [`rpeg_free`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct RpegData {
+    /// The bytes of the words of the image, four to a word, with the most significant byte of
+    /// each word first
+    pub bytes: *mut u8,
+    /// The number of bytes, which is four times the number of words
+    pub len: usize,
+    /// The width of the image
+    pub width: u32,
+    /// The height of the image
+    pub height: u32,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Records an error for [`rpeg_last_error`], returning its code.
+fn fail(code: c_int, message: impl ToString) -> c_int {
+    // Interior nul bytes can't be in a C string, and there's no reason for a message to have any
+    let message = message.to_string().replace('\0', " ");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).unwrap_or_default());
+
+    code
+}
+
+fn fail_with(err: RpegError) -> c_int {
+    let code = match err {
+        RpegError::Io(_) | RpegError::BrokenPipe => RPEG_IO_ERROR,
+        _ => RPEG_INVALID_DATA,
+    };
+
+    fail(code, err)
+}
+
+/// Reads a path argument, which is None (for stdin or stdout) if it's null.
+///
+/// # Safety
+///
+/// `path` must be null or point to a nul-terminated string.
+unsafe fn path_arg<'a>(path: *const c_char) -> Result<Option<&'a str>, c_int> {
+    if path.is_null() {
+        return Ok(None);
+    }
+
+    // SAFETY: the caller guarantees `path` points to a nul-terminated string
+    match unsafe { CStr::from_ptr(path) }.to_str() {
+        Ok(path) => Ok(Some(path)),
+        Err(_) => Err(fail(RPEG_INVALID_ARGUMENT, "The path isn't valid UTF-8")),
+    }
+}
+
+/// Reads rpeg data from a file, or from stdin if `path` is null, into `*out`. On failure,
+/// `*out` is left as it was.
+///
+/// # Safety
+///
+/// `path` must be null or point to a nul-terminated string, and `out` must point to an
+/// `RpegData` which can be written to.
+#[no_mangle]
+pub unsafe extern "C" fn rpeg_read(path: *const c_char, out: *mut RpegData) -> c_int {
+    if out.is_null() {
+        return fail(RPEG_INVALID_ARGUMENT, "The output pointer is null");
+    }
+    // SAFETY: the caller guarantees `path` is null or a nul-terminated string
+    let path = match unsafe { path_arg(path) } {
+        Ok(path) => path,
+        Err(code) => return code,
+    };
+
+    let image = match RpegImage::read(path) {
+        Ok(image) => image,
+        Err(err) => return fail_with(err),
+    };
+    let (width, height) = (image.width(), image.height());
+    let bytes: Box<[u8]> = image.words.into_iter().flatten().collect();
+    let len = bytes.len();
+
+    // SAFETY: the caller guarantees `out` can be written to, and it was checked for null
+    unsafe {
+        out.write(RpegData {
+            bytes: Box::into_raw(bytes).cast(),
+            len,
+            width,
+            height,
+        });
+    }
+
+    RPEG_OK
+}
+
+/// Writes rpeg data to a file (which is created or replaced), or to stdout if `path` is null.
+///
+/// # Safety
+///
+/// `path` must be null or point to a nul-terminated string, and `data` must point to an
+/// `RpegData` whose `bytes` points to `len` readable bytes (or is null, if `len` is 0).
+#[no_mangle]
+pub unsafe extern "C" fn rpeg_write(path: *const c_char, data: *const RpegData) -> c_int {
+    // SAFETY: the caller guarantees `data` is null or points to an RpegData
+    let Some(data) = (unsafe { data.as_ref() }) else {
+        return fail(RPEG_INVALID_ARGUMENT, "The data pointer is null");
+    };
+    // SAFETY: the caller guarantees `path` is null or a nul-terminated string
+    let path = match unsafe { path_arg(path) } {
+        Ok(path) => path,
+        Err(code) => return code,
+    };
+
+    let bytes = match (data.bytes.is_null(), data.len) {
+        (_, 0) => &[][..],
+        (true, _) => return fail(RPEG_INVALID_ARGUMENT, "The data's bytes pointer is null"),
+        // SAFETY: the caller guarantees `bytes` points to `len` readable bytes
+        (false, len) => unsafe { std::slice::from_raw_parts(data.bytes, len) },
+    };
+    if !bytes.len().is_multiple_of(4) {
+        return fail(
+            RPEG_INVALID_ARGUMENT,
+            format!("The data's length ({}) isn't a multiple of 4", bytes.len()),
+        );
+    }
+
+    let words = bytes
+        .chunks_exact(4)
+        .map(|word| word.try_into().unwrap())
+        .collect();
+    let image = RpegImage::new(words, data.width, data.height);
+    let written = match path {
+        Some(path) => image.write_file(path),
+        None => image.write(),
+    };
+
+    match written {
+        Ok(()) => RPEG_OK,
+        Err(err) => fail_with(err),
+    }
+}
+
+/// Frees the bytes of data read by [`rpeg_read`], leaving it empty. Freeing empty data (or a
+/// null pointer) does nothing.
+///
+/// # Safety
+///
+/// `data` must be null or point to an `RpegData` filled in by [`rpeg_read`] (and not changed
+/// since), or already freed by `rpeg_free`.
+#[no_mangle]
+pub unsafe extern "C" fn rpeg_free(data: *mut RpegData) {
+    // SAFETY: the caller guarantees `data` is null or points to an RpegData
+    let Some(data) = (unsafe { data.as_mut() }) else {
+        return;
+    };
+
+    if !data.bytes.is_null() {
+        let bytes = std::ptr::slice_from_raw_parts_mut(data.bytes, data.len);
+        // SAFETY: the bytes were allocated as a Box<[u8]> of this length by rpeg_read
+        drop(unsafe { Box::from_raw(bytes) });
+    }
+    data.bytes = std::ptr::null_mut();
+    data.len = 0;
+}
+
+/// A description of the last error on this thread, which stays valid until the next call to a
+/// function of this interface on the same thread. It's empty if nothing has failed yet.
+#[no_mangle]
+pub extern "C" fn rpeg_last_error() -> *const c_char {
+    // The string lives in the thread local until it's replaced by the next error
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> CString {
+        let path = std::env::temp_dir().join(format!("rpegio-ffi-{name}-{}", std::process::id()));
+        CString::new(path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_read_write_free() {
+        let (input, output) = (temp_path("in"), temp_path("out"));
+        let image = RpegImage::new(
+            vec![[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]],
+            4,
+            2,
+        );
+        std::fs::write(input.to_str().unwrap(), image.to_bytes()).unwrap();
+
+        let mut data = RpegData {
+            bytes: std::ptr::null_mut(),
+            len: 0,
+            width: 0,
+            height: 0,
+        };
+        unsafe {
+            assert_eq!(rpeg_read(input.as_ptr(), &mut data), RPEG_OK);
+            assert_eq!((data.len, data.width, data.height), (8, 4, 2));
+            assert_eq!(*data.bytes.add(4), 0x44);
+
+            assert_eq!(rpeg_write(output.as_ptr(), &data), RPEG_OK);
+            rpeg_free(&mut data);
+            assert!(data.bytes.is_null());
+            rpeg_free(&mut data);
+        }
+
+        let written = std::fs::read(output.to_str().unwrap()).unwrap();
+        std::fs::remove_file(input.to_str().unwrap()).unwrap();
+        std::fs::remove_file(output.to_str().unwrap()).unwrap();
+        assert_eq!(written, image.to_bytes());
+    }
+
+    #[test]
+    fn test_errors() {
+        let path = temp_path("bad");
+        std::fs::write(path.to_str().unwrap(), b"Compressed image format 9\n2 2\n").unwrap();
+
+        let mut data = RpegData {
+            bytes: std::ptr::null_mut(),
+            len: 0,
+            width: 7,
+            height: 0,
+        };
+        unsafe {
+            assert_eq!(rpeg_read(path.as_ptr(), &mut data), RPEG_INVALID_DATA);
+            assert_eq!(data.width, 7);
+            let message = CStr::from_ptr(rpeg_last_error()).to_str().unwrap();
+            assert!(message.contains("version 9"), "{message}");
+
+            std::fs::remove_file(path.to_str().unwrap()).unwrap();
+            assert_eq!(rpeg_read(path.as_ptr(), &mut data), RPEG_IO_ERROR);
+            assert_eq!(
+                rpeg_read(path.as_ptr(), std::ptr::null_mut()),
+                RPEG_INVALID_ARGUMENT
+            );
+
+            let mut bytes = [0u8; 3];
+            data.bytes = bytes.as_mut_ptr();
+            data.len = bytes.len();
+            assert_eq!(rpeg_write(path.as_ptr(), &data), RPEG_INVALID_ARGUMENT);
+        }
+    }
+}
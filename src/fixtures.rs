@@ -0,0 +1,72 @@
+//! Known-good rpeg files built into the crate, for tests and examples which need some input
+//! without hunting for files. Enabled by the `fixtures` feature.
+//!
+//! Each fixture is available both as the raw bytes of the file (such as [`TINY`]), for testing
+//! code which reads rpeg data, and already parsed (such as [`tiny`]).
+//!
+//! # Examples
+//! ```
+//! use csc411_rpegio::{fixtures, RpegImage};
+//!
+//! let image = fixtures::large();
+//! assert_eq!((image.width(), image.height()), (320, 240));
+//! assert_eq!(RpegImage::from_bytes(fixtures::LARGE).unwrap(), image);
+//! ```
+
+use crate::RpegImage;
+
+/// The bytes of a 2x2 image with a single word, `0x00112233`.
+pub const TINY: &[u8] = include_bytes!("../fixtures/tiny.rpeg");
+
+/// The bytes of a 7x5 image, whose odd width and height leave a row and column of pixels
+/// outside every 2x2 block. It has 6 words (3 blocks across by 2 down), and a comment.
+pub const ODD_SIZED: &[u8] = include_bytes!("../fixtures/odd_sized.rpeg");
+
+/// The bytes of a 320x240 image with 19200 pseudo-random words.
+pub const LARGE: &[u8] = include_bytes!("../fixtures/large.rpeg");
+
+/// The bytes of a 0x0 image with no words.
+pub const EMPTY: &[u8] = include_bytes!("../fixtures/empty.rpeg");
+
+/// A 2x2 image with a single word, `0x00112233`. See [`TINY`].
+pub fn tiny() -> RpegImage {
+    parse(TINY)
+}
+
+/// A 7x5 image with 6 words. See [`ODD_SIZED`].
+pub fn odd_sized() -> RpegImage {
+    parse(ODD_SIZED)
+}
+
+/// A 320x240 image with 19200 pseudo-random words. See [`LARGE`].
+pub fn large() -> RpegImage {
+    parse(LARGE)
+}
+
+/// A 0x0 image with no words. See [`EMPTY`].
+pub fn empty() -> RpegImage {
+    parse(EMPTY)
+}
+
+fn parse(bytes: &[u8]) -> RpegImage {
+    RpegImage::from_bytes(bytes).expect("Fixtures are always valid rpeg data")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixtures() {
+        assert_eq!(tiny(), RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2));
+        assert_eq!(odd_sized().words.len(), 6);
+        assert_eq!(odd_sized().header.comments, ["odd width and height"]);
+        assert_eq!(large().words.len(), 160 * 120);
+        assert_eq!(empty(), RpegImage::new(vec![], 0, 0));
+
+        // Every fixture is in the canonical form, so writing it back gives the same bytes
+        for bytes in [TINY, ODD_SIZED, LARGE, EMPTY] {
+            assert_eq!(parse(bytes).to_bytes(), bytes);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+/// A description of a variant of the rpeg container format.
+///
+/// Every variant has the same overall shape: a "magic" first line identifying the variant, a
+/// second line of space-separated numbers (such as the width and height), and then a payload of
+/// fixed-size words. Implementing this trait for your own variant lets you reuse all of this
+/// crate's reading and writing machinery, via functions like
+/// [`RpegImage::from_bytes_in_format`](crate::RpegImage::from_bytes_in_format) and
+/// [`RpegReader::with_format`](crate::RpegReader::with_format).
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{RpegFormat, RpegImage};
+///
+/// /// A variant used by another section, which also records the compression quality
+/// struct QualityFormat;
+///
+/// impl RpegFormat for QualityFormat {
+///     fn magic(&self) -> &str {
+///         "Section B compressed image"
+///     }
+///
+///     fn version(&self) -> u32 {
+///         1
+///     }
+///
+///     fn word_size(&self) -> usize {
+///         4
+///     }
+///
+///     fn header_fields(&self) -> &[&str] {
+///         &["width", "height", "quality"]
+///     }
+/// }
+///
+/// let bytes = b"Section B compressed image\n2 2 75\n\x00\x11\x22\x33";
+/// let image: RpegImage = RpegImage::from_bytes_in_format(bytes, &QualityFormat).unwrap();
+///
+/// assert_eq!(image.width(), 2);
+/// assert_eq!(image.header.field("quality"), 75);
+/// assert_eq!(image.to_bytes_in_format(&QualityFormat).unwrap(), bytes);
+/// ```
+pub trait RpegFormat {
+    /// The first line of the header, without its line ending.
+    fn magic(&self) -> &str;
+
+    /// The version number reported in [`RpegHeader::version`](crate::RpegHeader::version) for
+    /// data in this format.
+    fn version(&self) -> u32;
+
+    /// The number of bytes in each word of the payload.
+    fn word_size(&self) -> usize;
+
+    /// The names of the numbers on the second line of the header, in the order they appear.
+    /// Fields named "width" and "height" are stored in the header's `width` and `height`, and
+    /// any others in its `extra_fields`. Defaults to just the width and height.
+    fn header_fields(&self) -> &[&str] {
+        &["width", "height"]
+    }
+}
+
+/// The standard "Compressed image format 2" variant of the rpeg format, with a width and height
+/// and four-byte words.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Format2;
+
+impl RpegFormat for Format2 {
+    fn magic(&self) -> &str {
+        "Compressed image format 2"
+    }
+
+    fn version(&self) -> u32 {
+        2
+    }
+
+    fn word_size(&self) -> usize {
+        4
+    }
+}
+
+/// The legacy "Compressed image format 1" variant of the rpeg format, with a width and height
+/// and two-byte words. Some older reference materials and test files use this format.
+///
+/// Read and write it using an [`RpegImage<2>`](crate::RpegImage), and convert to and from the
+/// standard format with [`RpegImage::into_format_2`](crate::RpegImage::into_format_2) and
+/// [`RpegImage::try_into_format_1`](crate::RpegImage::try_into_format_1).
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{Format1, RpegImage};
+///
+/// let bytes = b"Compressed image format 1\n4 2\n\x12\x34\x56\x78";
+/// let image = RpegImage::<2>::from_bytes_in_format(bytes, &Format1).unwrap();
+/// assert_eq!(image.words, vec![[0x12, 0x34], [0x56, 0x78]]);
+///
+/// let image = image.into_format_2();
+/// assert_eq!(image.words, vec![[0x00, 0x00, 0x12, 0x34], [0x00, 0x00, 0x56, 0x78]]);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Format1;
+
+impl RpegFormat for Format1 {
+    fn magic(&self) -> &str {
+        "Compressed image format 1"
+    }
+
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn word_size(&self) -> usize {
+        2
+    }
+}
+
+/// Checks that `format` uses words of the size the caller can handle.
+pub(crate) fn check_word_size(
+    format: &dyn RpegFormat,
+    word_size: usize,
+) -> Result<(), crate::RpegError> {
+    if format.word_size() == word_size {
+        Ok(())
+    } else {
+        Err(crate::RpegError::WordSizeMismatch {
+            expected: word_size,
+            found: format.word_size(),
+        })
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{RpegError, RpegImage};
+
+/// An entry point for fuzzing the parser, such as from a `cargo fuzz` target:
+///
+/// ```ignore
+/// fuzz_target!(|bytes: &[u8]| {
+///     let _ = csc411_rpegio::fuzz_parse(bytes);
+/// });
+/// ```
+///
+/// Parses the bytes as rpeg data (with [`RpegImage::from_bytes`], so compressed and armored data
+/// are tried too). If they parse, the image is written back out and parsed again, and the two
+/// are checked to match, so a fuzzer finds both panics in the parser and data which doesn't
+/// survive a round trip. Signed data is written back out without its signature (see
+/// [`RpegHeader::signed`](crate::RpegHeader::signed)), so that's the one difference allowed.
+///
+/// # Errors Returned
+///
+/// * If the bytes aren't valid rpeg data, exactly as [`RpegImage::from_bytes`] would
+///
+/// # Panics
+///
+/// * If the bytes parse, but writing the image and parsing it again fails or gives a different
+///   image
+/// * If the image written back out isn't in the canonical form (writing it again changes it)
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{corrupt, fuzz_parse, generate_rpeg};
+///
+/// let image = generate_rpeg(8, 8, 1);
+/// assert_eq!(fuzz_parse(&image.to_bytes()).unwrap(), image);
+/// assert!(fuzz_parse(&corrupt::truncate(&image, 3)).is_err());
+/// ```
+pub fn fuzz_parse(bytes: &[u8]) -> Result<RpegImage, RpegError> {
+    let image = RpegImage::from_bytes(bytes)?;
+
+    let written = image.to_bytes();
+    let reparsed = match RpegImage::from_bytes(&written) {
+        Ok(reparsed) => reparsed,
+        Err(error) => panic!("Parsed data couldn't be parsed again once written: {error}"),
+    };
+    // Writing never signs data, so a signed image comes back unsigned
+    let mut unsigned = image.clone();
+    unsigned.header.signed = false;
+    assert_eq!(
+        reparsed, unsigned,
+        "Parsed data changed once written and parsed again"
+    );
+    assert_eq!(reparsed.to_bytes(), written, "Written data isn't canonical");
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::corrupt::{self, HeaderDamage};
+    use crate::generate::SplitMix64;
+
+    #[test]
+    fn test_fuzz_parse_damaged() {
+        let mut image = crate::generate_rpeg(10, 6, 3);
+        image.header.checksum = true;
+        image.header.comments.push("fuzz".to_string());
+
+        for n in 0..image.to_bytes().len() {
+            let _ = fuzz_parse(&corrupt::truncate(&image, n));
+        }
+        for seed in 0..50 {
+            let _ = fuzz_parse(&corrupt::flip_bits(&image, 4, seed));
+        }
+        assert!(fuzz_parse(&corrupt::mangle_header(&image, HeaderDamage::BadMagic)).is_err());
+
+        // Signed data, whose signature isn't written back out
+        let mut signed = b"Compressed image format 2cs\n2 2\n\x00\x11\x22\x33".to_vec();
+        let mut crc = crate::checksum::Crc32::new();
+        crc.update(&signed[32..]);
+        signed.extend(crc.finish().to_be_bytes());
+        signed.extend([0xAB; 32]);
+        assert!(fuzz_parse(&signed).unwrap().header.signed);
+        for n in 0..signed.len() {
+            let _ = fuzz_parse(&signed[..n]);
+        }
+        #[cfg(feature = "crypto")]
+        assert!(fuzz_parse(&image.to_signed(b"key")).unwrap().header.signed);
+    }
+
+    #[test]
+    fn test_fuzz_parse_random() {
+        // Random payloads after a plausible header, and random bytes with nothing in front
+        let mut random = SplitMix64::new(411);
+        for len in 0..200 {
+            let noise: Vec<u8> = (0..len).map(|_| random.next_u64() as u8).collect();
+            let _ = fuzz_parse(&noise);
+            let _ = fuzz_parse(&[b"Compressed image format 2\n4 4\n", &noise[..]].concat());
+            let _ = fuzz_parse(&[b"Compressed image format 2", &noise[..]].concat());
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::RpegImage;
+
+/// A small pseudo-random number generator (SplitMix64), whose output for each seed is fixed
+/// forever, unlike a generator from an external crate.
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// Generates an image of the given size with pseudo-random words, one for each 2x2 block. The
+/// same size and seed always give exactly the same image (on any platform, and in any version of
+/// this crate), so sharing a seed is enough to test two programs on identical input.
+///
+/// Each word is the top 32 bits of the next output of a SplitMix64 generator started from
+/// `seed`.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::generate_rpeg;
+///
+/// let image = generate_rpeg(640, 480, 411);
+/// assert_eq!(image.words.len(), 320 * 240);
+/// assert_eq!(image, generate_rpeg(640, 480, 411));
+/// assert_ne!(image, generate_rpeg(640, 480, 412));
+/// ```
+pub fn generate_rpeg(width: u32, height: u32, seed: u64) -> RpegImage {
+    let mut random = SplitMix64::new(seed);
+    let len = (width / 2) as usize * (height / 2) as usize;
+    let words = (0..len)
+        .map(|_| ((random.next_u64() >> 32) as u32).to_be_bytes())
+        .collect();
+
+    RpegImage::new(words, width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_mix_64() {
+        // The first outputs for seed 1234567, from the reference implementation
+        let mut random = SplitMix64::new(1234567);
+        assert_eq!(random.next_u64(), 6457827717110365317);
+        assert_eq!(random.next_u64(), 3203168211198807973);
+        assert_eq!(random.next_u64(), 9817491932198370423);
+    }
+
+    #[test]
+    fn test_generate_rpeg() {
+        let image = generate_rpeg(7, 5, 0);
+        assert_eq!((image.width(), image.height()), (7, 5));
+        assert_eq!(image.words.len(), 6);
+        // The top half of SplitMix64's first output for seed 0, 0xE220A8397B1DCDAF
+        assert_eq!(image.words[0], [0xE2, 0x20, 0xA8, 0x39]);
+
+        assert_eq!(generate_rpeg(0, 100, 1).words.len(), 0);
+    }
+}
@@ -0,0 +1,317 @@
+//! Compares rpeg output against reference ("golden") files, reporting exactly what differs: the
+//! fields of the header, the words of the payload, and any bytes left over after the last whole
+//! word. For binary rpeg data, this says far more than `diff` or `cmp` can.
+//!
+//! Both sides are parsed leniently, so that broken data can still be compared: if the payload
+//! isn't a whole number of words, or its trailers (such as a checksum) are wrong, the whole
+//! words are compared as usual, the rest of the bytes are compared as trailing bytes, and the
+//! reason it couldn't be parsed normally is noted in the report.
+//!
+//! # Examples
+//! ```no_run
+//! use csc411_rpegio::golden;
+//!
+//! let output = std::process::Command::new("./rpeg")
+//!     .args(["-c", "tests/flowers.ppm"])
+//!     .output()
+//!     .unwrap();
+//!
+//! golden::assert_matches(output.stdout, "tests/flowers.rpeg");
+//! ```
+
+use crate::header::{self, ByteReader};
+use crate::{compare_rpeg, compression, ReadOptions, RpegDiff, RpegError, RpegHeader};
+use crate::{RpegImage, RpegSource};
+use std::fmt;
+
+/// A field of the header which differs between the output and the reference, as part of a
+/// [`GoldenReport`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldDifference {
+    /// The name of the field, such as `size` or `comments`
+    pub field: &'static str,
+    /// The value of the field in the reference
+    pub expected: String,
+    /// The value of the field in the output
+    pub actual: String,
+}
+
+/// How rpeg output compares to a reference, from [`check`]. Its [`Display`](fmt::Display)
+/// implementation describes every difference, a section at a time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GoldenReport {
+    /// Every field of the header which differs, in the order they're written
+    pub header: Vec<FieldDifference>,
+    /// The differences between the words of the payloads, with the reference on the left
+    pub payload: RpegDiff,
+    /// Any bytes of the reference after its last whole word, which couldn't be parsed
+    pub expected_trailing: Vec<u8>,
+    /// Any bytes of the output after its last whole word, which couldn't be parsed
+    pub actual_trailing: Vec<u8>,
+    /// Why the reference couldn't be parsed normally, if it couldn't be
+    pub expected_problem: Option<String>,
+    /// Why the output couldn't be parsed normally, if it couldn't be
+    pub actual_problem: Option<String>,
+}
+
+impl GoldenReport {
+    /// Whether the output matches the reference: the same header, the same words, and the same
+    /// trailing bytes (if any).
+    pub fn is_match(&self) -> bool {
+        self.header.is_empty()
+            && self.payload.is_identical()
+            && self.expected_trailing == self.actual_trailing
+    }
+}
+
+impl fmt::Display for GoldenReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = |bytes: &[u8]| match bytes.len() {
+            0 => "none".to_string(),
+            len => {
+                let hex: Vec<String> = bytes.iter().take(16).map(|b| format!("{b:02X}")).collect();
+                let more = if len > 16 { " ..." } else { "" };
+                format!("{len} ({}{more})", hex.join(" "))
+            }
+        };
+
+        match self.header.is_empty() {
+            true => writeln!(f, "header: matches")?,
+            false => {
+                writeln!(f, "header:")?;
+                for difference in &self.header {
+                    writeln!(
+                        f,
+                        "  {}: expected {}, found {}",
+                        difference.field, difference.expected, difference.actual
+                    )?;
+                }
+            }
+        }
+        writeln!(f, "payload: {}", self.payload)?;
+        match self.expected_trailing == self.actual_trailing {
+            true => write!(f, "trailing bytes: {}", bytes(&self.actual_trailing))?,
+            false => write!(
+                f,
+                "trailing bytes: expected {}, found {}",
+                bytes(&self.expected_trailing),
+                bytes(&self.actual_trailing)
+            )?,
+        }
+
+        if let Some(problem) = &self.expected_problem {
+            write!(f, "\nthe reference isn't valid rpeg data: {problem}")?;
+        }
+        if let Some(problem) = &self.actual_problem {
+            write!(f, "\nthe output isn't valid rpeg data: {problem}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares rpeg data (such as the output of a compressor) against the reference file at
+/// `expected_path`. See [`GoldenReport`].
+///
+/// # Arguments
+///
+/// * `actual` - The data to check, such as its bytes or the path of a file holding it
+/// * `expected_path` - The path of the reference file
+///
+/// # Errors Returned
+///
+/// * If either side can't be read
+/// * If either side's header is badly formatted, so there's nothing to compare
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{golden, RpegImage};
+///
+/// let path = std::env::temp_dir().join("csc411_rpegio_golden_example.rpeg");
+/// let expected = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]; 4], 4, 4);
+/// expected.write_file(path.to_str().unwrap()).unwrap();
+///
+/// let mut actual = expected.to_bytes();
+/// actual[30] = 0xFF;
+/// actual.push(0xAB);
+/// let report = golden::check(actual, path.to_str().unwrap()).unwrap();
+/// # std::fs::remove_file(&path).unwrap();
+///
+/// assert!(!report.is_match());
+/// assert_eq!(
+///     report.to_string(),
+///     "header: matches\n\
+///      payload: 1 of 4 words differ, first at word 0 (row 0, column 0): 00112233 != FF112233\n\
+///      trailing bytes: expected none, found 1 (AB)\n\
+///      the output isn't valid rpeg data: The number of raw bytes (17) was not a multiple of four"
+/// );
+/// ```
+pub fn check(actual: impl RpegSource, expected_path: &str) -> Result<GoldenReport, RpegError> {
+    let expected = parse_leniently(&expected_path.read_bytes()?)?;
+    let actual = parse_leniently(&actual.read_bytes()?)?;
+
+    Ok(GoldenReport {
+        header: compare_headers(&expected.image.header, &actual.image.header),
+        payload: compare_rpeg(&expected.image, &actual.image),
+        expected_trailing: expected.trailing,
+        actual_trailing: actual.trailing,
+        expected_problem: expected.problem,
+        actual_problem: actual.problem,
+    })
+}
+
+/// Like [`check`], but panics with the report if the output doesn't match the reference, for
+/// use in tests.
+///
+/// # Panics
+///
+/// * If the output doesn't match the reference
+/// * If either side can't be read, or its header is badly formatted
+#[track_caller]
+pub fn assert_matches(actual: impl RpegSource, expected_path: &str) {
+    match check(actual, expected_path) {
+        Ok(report) if report.is_match() => {}
+        Ok(report) => panic!("The output doesn't match {expected_path}:\n{report}"),
+        Err(error) => panic!("Couldn't compare the output to {expected_path}: {error}"),
+    }
+}
+
+/// Data parsed as well as it could be: its image, any bytes after its last whole word, and why
+/// it couldn't be parsed normally (if it couldn't).
+struct Lenient {
+    image: RpegImage,
+    trailing: Vec<u8>,
+    problem: Option<String>,
+}
+
+fn parse_leniently(bytes: &[u8]) -> Result<Lenient, RpegError> {
+    if let Some(bytes) = compression::decompress(bytes)? {
+        return parse_leniently(&bytes);
+    }
+
+    let mut byte_reader = ByteReader::new(bytes);
+    let header = header::read_header(&mut byte_reader, &ReadOptions::default())?;
+    let header_len = byte_reader.position() as usize;
+
+    match RpegImage::from_header_and_payload(header.clone(), bytes, header_len) {
+        Ok(image) => Ok(Lenient {
+            image,
+            trailing: Vec::new(),
+            problem: None,
+        }),
+        Err(error) => {
+            // Everything after the header is taken as the payload, trailers and all
+            let rest = &bytes[header_len..];
+            let (words, trailing) = rest.split_at(rest.len() / 4 * 4);
+            let words = words
+                .chunks_exact(4)
+                .map(|word| word.try_into().unwrap())
+                .collect();
+
+            Ok(Lenient {
+                image: RpegImage { header, words },
+                trailing: trailing.to_vec(),
+                problem: Some(error.to_string()),
+            })
+        }
+    }
+}
+
+/// The name of a field of the header, and how to write its value.
+type HeaderField = (&'static str, fn(&RpegHeader) -> String);
+
+fn compare_headers(expected: &RpegHeader, actual: &RpegHeader) -> Vec<FieldDifference> {
+    let fields: [HeaderField; 9] = [
+        ("size", |header| {
+            format!("{}x{}", header.width, header.height)
+        }),
+        ("version", |header| header.version.to_string()),
+        ("checksum", |header| header.checksum.to_string()),
+        ("word_count", |header| header.word_count.to_string()),
+        ("signed", |header| header.signed.to_string()),
+        ("binary", |header| header.binary.to_string()),
+        ("extra_fields", |header| {
+            format!("{:?}", header.extra_fields)
+        }),
+        ("comments", |header| format!("{:?}", header.comments)),
+        ("metadata", |header| format!("{:?}", header.metadata)),
+    ];
+
+    fields
+        .into_iter()
+        .map(|(field, value)| FieldDifference {
+            field,
+            expected: value(expected),
+            actual: value(actual),
+        })
+        .filter(|difference| difference.expected != difference.actual)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_reference(name: &str, image: &RpegImage) -> String {
+        let path = std::env::temp_dir().join(format!("csc411_rpegio_test_golden_{name}.rpeg"));
+        image.write_file(path.to_str().unwrap()).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_check() {
+        let mut expected = RpegImage::new(vec![[0x00; 4]; 6], 4, 6);
+        expected.header.checksum = true;
+        let path = write_reference("check", &expected);
+
+        let matching = check(expected.to_bytes(), &path).unwrap();
+        let mut actual = expected.clone();
+        actual.header.checksum = false;
+        actual.header.comments.push("extra".to_string());
+        actual.words[5] = [0x01; 4];
+        let differing = check(actual.to_bytes(), &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matching.is_match());
+        assert_eq!(
+            matching.to_string(),
+            "header: matches\npayload: all 6 words match\ntrailing bytes: none"
+        );
+
+        assert!(!differing.is_match());
+        assert_eq!(
+            differing.to_string(),
+            "header:\n  \
+             checksum: expected true, found false\n  \
+             comments: expected [], found [\"extra\"]\n\
+             payload: 1 of 6 words differ, first at word 5 (row 2, column 1): 00000000 != 01010101\n\
+             trailing bytes: none"
+        );
+    }
+
+    #[test]
+    fn test_check_broken_output() {
+        let expected = RpegImage::new(vec![[0x00; 4]; 2], 4, 2);
+        let path = write_reference("broken", &expected);
+
+        let mut bytes = expected.to_bytes();
+        bytes.extend([0xAB, 0xCD]);
+        let report = check(bytes, &path);
+        let bad_header = check(&b"Compressed image format 2\nfour 2\n"[..], &path);
+        std::fs::remove_file(&path).unwrap();
+
+        let report = report.unwrap();
+        assert!(!report.is_match());
+        assert!(report.payload.is_identical());
+        assert_eq!(report.actual_trailing, [0xAB, 0xCD]);
+        assert!(report.expected_problem.is_none());
+        assert!(report
+            .actual_problem
+            .unwrap()
+            .contains("not a multiple of four"));
+        assert!(bad_header.is_err());
+
+        let missing = std::panic::catch_unwind(|| assert_matches(vec![], "/does/not/exist.rpeg"));
+        assert!(missing.is_err());
+    }
+}
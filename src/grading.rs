@@ -0,0 +1,288 @@
+//! Grades rpeg output against a reference, producing results in the JSON format Gradescope's
+//! autograder expects in `results/results.json`. Enabled by the `grading` feature.
+//!
+//! A [`Grader`] holds the reference image and a list of named tests, each worth some points
+//! and running one [`Check`]. Grading a submission's output runs every test, giving each
+//! either all of its points or none.
+//!
+//! # Examples
+//! ```
+//! use csc411_rpegio::grading::{Check, Grader};
+//! use csc411_rpegio::RpegImage;
+//!
+//! let reference = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]; 4], 4, 4);
+//! let grader = Grader::new(reference.clone())
+//!     .test("Header is valid", 1.0, Check::HeaderValid)
+//!     .test("Dimensions match", 1.0, Check::DimensionsMatch)
+//!     .test("Payload matches", 2.0, Check::PayloadMatches { tolerance: 0 })
+//!     .test("Payload nearly matches", 1.0, Check::PayloadMatches { tolerance: 1 });
+//!
+//! let mut output = reference.clone();
+//! output.words[2] = [0xFF; 4];
+//! let results = grader.grade(output.to_bytes());
+//!
+//! assert_eq!((results.score(), results.max_score()), (3.0, 5.0));
+//! assert!(results.to_json().starts_with(r#"{"score":3,"tests":[{"name":"Header is valid""#));
+//! ```
+
+use crate::header::{self, ByteReader};
+use crate::json::json_string;
+use crate::{compare_rpeg, compression, ReadOptions, RpegImage, RpegSource};
+
+/// What a test checks about the output, as part of a [`Grader`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Check {
+    /// The output's header can be parsed
+    HeaderValid,
+    /// The output's width and height are the same as the reference's
+    DimensionsMatch,
+    /// The output can be parsed, is the same size as the reference, and has at most
+    /// `tolerance` words which differ from the reference's
+    PayloadMatches {
+        /// The most words which may differ
+        tolerance: usize,
+    },
+}
+
+/// Grades output against a reference image. See the [module documentation](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grader {
+    reference: RpegImage,
+    tests: Vec<(String, f64, Check)>,
+}
+
+/// The result of one test, as part of [`GradingResults`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    /// The name of the test
+    pub name: String,
+    /// The points the output earned: all of the test's points, or none
+    pub score: f64,
+    /// The points the test is worth
+    pub max_score: f64,
+    /// Feedback on the test, shown to the student
+    pub output: String,
+}
+
+/// The results of grading output, from [`Grader::grade`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradingResults {
+    /// The result of every test, in the order they were added
+    pub tests: Vec<TestResult>,
+}
+
+impl Grader {
+    /// Creates a grader for output which should match the reference image, without any tests.
+    pub fn new(reference: RpegImage) -> Self {
+        Grader {
+            reference,
+            tests: Vec::new(),
+        }
+    }
+
+    /// Adds a test, worth `points` points, which runs `check`.
+    pub fn test(mut self, name: &str, points: f64, check: Check) -> Self {
+        self.tests.push((name.to_string(), points, check));
+        self
+    }
+
+    /// Runs every test on the output. Output which can't be read (or parsed) fails every test
+    /// which depends on reading it, rather than being an error, so every submission gets a
+    /// complete set of results.
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - The output to grade, such as its bytes or the path of a file holding it
+    pub fn grade(&self, output: impl RpegSource) -> GradingResults {
+        let parsed = Parsed::new(output);
+
+        let tests = self
+            .tests
+            .iter()
+            .map(|(name, points, check)| {
+                let result = self.run(*check, &parsed);
+                TestResult {
+                    name: name.clone(),
+                    score: if result.is_ok() { *points } else { 0.0 },
+                    max_score: *points,
+                    output: result.unwrap_or_else(|problem| problem),
+                }
+            })
+            .collect();
+
+        GradingResults { tests }
+    }
+
+    /// Runs a check, giving feedback on why it passed or failed.
+    fn run(&self, check: Check, parsed: &Parsed) -> Result<String, String> {
+        let reference = &self.reference;
+        match check {
+            Check::HeaderValid => {
+                let (width, height) = parsed.size()?;
+                Ok(format!("The header is valid ({width}x{height})"))
+            }
+            Check::DimensionsMatch => {
+                let size = parsed.size()?;
+                let expected = (reference.width(), reference.height());
+                match size == expected {
+                    true => Ok(format!("The output is {}x{}", size.0, size.1)),
+                    false => Err(format!(
+                        "The output is {}x{}, but should be {}x{}",
+                        size.0, size.1, expected.0, expected.1
+                    )),
+                }
+            }
+            Check::PayloadMatches { tolerance } => {
+                let diff = compare_rpeg(reference, parsed.image()?);
+                match diff.dimensions_match() && diff.differing_words() <= tolerance {
+                    true => Ok(diff.to_string()),
+                    false => Err(diff.to_string()),
+                }
+            }
+        }
+    }
+}
+
+impl GradingResults {
+    /// The total points earned.
+    pub fn score(&self) -> f64 {
+        self.tests.iter().map(|test| test.score).sum()
+    }
+
+    /// The total points available.
+    pub fn max_score(&self) -> f64 {
+        self.tests.iter().map(|test| test.max_score).sum()
+    }
+
+    /// Encodes the results as JSON in the format of Gradescope's `results.json`: an object
+    /// with the total `score`, and the `name`, `score`, `max_score`, `status` (`passed` or
+    /// `failed`), and `output` of each test.
+    pub fn to_json(&self) -> String {
+        let tests: Vec<String> = self
+            .tests
+            .iter()
+            .map(|test| {
+                let status = if test.score == test.max_score {
+                    "passed"
+                } else {
+                    "failed"
+                };
+                format!(
+                    "{{\"name\":{},\"score\":{},\"max_score\":{},\"status\":\"{status}\",\"output\":{}}}",
+                    json_string(&test.name),
+                    test.score,
+                    test.max_score,
+                    json_string(&test.output)
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"score\":{},\"tests\":[{}]}}",
+            self.score(),
+            tests.join(",")
+        )
+    }
+}
+
+/// Output parsed as far as it could be: its header's size and its image, or why not.
+struct Parsed {
+    size: Result<(u32, u32), String>,
+    image: Result<RpegImage, String>,
+}
+
+impl Parsed {
+    fn new(output: impl RpegSource) -> Self {
+        let bytes = output
+            .read_bytes()
+            .map_err(|error| format!("The output couldn't be read: {error}"))
+            .and_then(|bytes| match compression::decompress(&bytes) {
+                Ok(decompressed) => Ok(decompressed.unwrap_or(bytes)),
+                Err(error) => Err(format!("The output couldn't be decompressed: {error}")),
+            });
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(problem) => {
+                return Parsed {
+                    size: Err(problem.clone()),
+                    image: Err(problem),
+                }
+            }
+        };
+
+        let mut byte_reader = ByteReader::new(&bytes[..]);
+        let size = header::read_header(&mut byte_reader, &ReadOptions::default())
+            .map(|header| (header.width, header.height))
+            .map_err(|error| format!("The header is invalid: {error}"));
+        let image = match &size {
+            Ok(_) => RpegImage::from_bytes(&bytes)
+                .map_err(|error| format!("The payload is invalid: {error}")),
+            Err(problem) => Err(problem.clone()),
+        };
+
+        Parsed { size, image }
+    }
+
+    fn size(&self) -> Result<(u32, u32), String> {
+        self.size.clone()
+    }
+
+    fn image(&self) -> Result<&RpegImage, String> {
+        self.image.as_ref().map_err(|problem| problem.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grader() -> Grader {
+        Grader::new(RpegImage::new(vec![[0x00; 4]; 4], 4, 4))
+            .test("header", 1.0, Check::HeaderValid)
+            .test("size", 1.0, Check::DimensionsMatch)
+            .test("payload", 2.5, Check::PayloadMatches { tolerance: 0 })
+    }
+
+    #[test]
+    fn test_grade() {
+        let results = grader().grade(RpegImage::new(vec![[0x00; 4]; 4], 4, 4).to_bytes());
+        assert_eq!(results.score(), 4.5);
+        assert_eq!(results.tests[2].output, "all 4 words match");
+
+        let results = grader().grade(RpegImage::new(vec![[0x00; 4]; 2], 4, 2).to_bytes());
+        assert_eq!(results.score(), 1.0);
+        assert_eq!(
+            results.tests[1].output,
+            "The output is 4x2, but should be 4x4"
+        );
+
+        // A broken payload still has a valid header
+        let results = grader().grade(&b"Compressed image format 2\n4 4\n\x00"[..]);
+        assert_eq!(results.score(), 2.0);
+        assert!(results.tests[2]
+            .output
+            .starts_with("The payload is invalid: "));
+
+        let results = grader().grade(Some("/does/not/exist.rpeg"));
+        assert_eq!((results.score(), results.max_score()), (0.0, 4.5));
+        assert!(results.tests[0]
+            .output
+            .starts_with("The output couldn't be read: "));
+    }
+
+    #[test]
+    fn test_results_json() {
+        let results = grader().grade(&b"Compressed image format 2\nfour 4\n"[..]);
+
+        assert_eq!(
+            results.to_json(),
+            "{\"score\":0,\"tests\":[\
+             {\"name\":\"header\",\"score\":0,\"max_score\":1,\"status\":\"failed\",\
+             \"output\":\"The header is invalid: Attempted to parse non-ascii digit 102\"},\
+             {\"name\":\"size\",\"score\":0,\"max_score\":1,\"status\":\"failed\",\
+             \"output\":\"The header is invalid: Attempted to parse non-ascii digit 102\"},\
+             {\"name\":\"payload\",\"score\":0,\"max_score\":2.5,\"status\":\"failed\",\
+             \"output\":\"The header is invalid: Attempted to parse non-ascii digit 102\"}]}"
+        );
+    }
+}
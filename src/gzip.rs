@@ -0,0 +1,256 @@
+use crate::checksum::Crc32;
+use crate::deflate::{self, BitWriter};
+use crate::{stdio, RpegError, RpegImage};
+use std::io::Write;
+
+/// How much input is compressed at a time. Each chunk is compressed in isolation, so bigger
+/// chunks compress (slightly) better, at the cost of memory.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// A gzip header with no file name or modification time, from an unknown operating system.
+const HEADER: [u8; 10] = [0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 0xFF];
+
+/// Compresses everything written to it in the gzip format, as understood by `gzip -d`, before
+/// passing it on to another writer. Useful for keeping compressed images small enough to submit,
+/// since rpeg payloads usually still compress noticeably.
+///
+/// Call [`GzipWriter::finish`] once everything has been written, to write the end of the gzip
+/// data and find out whether that succeeded. Dropping the writer does the same, but ignores any
+/// errors.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{GzipWriter, RpegWriter, WriteOptions};
+///
+/// let gzip = GzipWriter::new(Vec::new());
+/// let mut writer = RpegWriter::with_options(gzip, 2, 2, &WriteOptions::new()).unwrap();
+/// writer.write_word([0x00, 0x11, 0x22, 0x33]).unwrap();
+///
+/// let compressed: Vec<u8> = writer.finish().unwrap().finish().unwrap();
+/// assert_eq!(compressed[..2], [0x1F, 0x8B]);
+/// ```
+pub struct GzipWriter<W: Write> {
+    // Only `None` once finished
+    writer: Option<W>,
+    header_written: bool,
+    pending: Vec<u8>,
+    bits: BitWriter,
+    crc: Crc32,
+    len: u64,
+}
+
+impl<W: Write> GzipWriter<W> {
+    /// Creates a writer which compresses everything written to it before passing it on to
+    /// `writer`.
+    pub fn new(writer: W) -> Self {
+        GzipWriter {
+            writer: Some(writer),
+            header_written: false,
+            pending: Vec::with_capacity(CHUNK_SIZE),
+            bits: BitWriter::default(),
+            crc: Crc32::new(),
+            len: 0,
+        }
+    }
+
+    /// Compresses anything not yet compressed, writes the end of the gzip data, and flushes the
+    /// underlying writer, returning it.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If something goes wrong writing to the underlying writer
+    pub fn finish(mut self) -> std::io::Result<W> {
+        let result = self.try_finish();
+
+        // Taking the writer stops the drop from trying to finish again
+        let writer = self.writer.take().unwrap();
+        result.map(|()| writer)
+    }
+
+    fn try_finish(&mut self) -> std::io::Result<()> {
+        self.compress_pending(true)?;
+
+        let bits = std::mem::take(&mut self.bits).finish();
+        let writer = self.writer.as_mut().unwrap();
+        writer.write_all(&bits)?;
+        writer.write_all(&self.crc.finish().to_le_bytes())?;
+        // The length is only recorded modulo 2^32
+        writer.write_all(&(self.len as u32).to_le_bytes())?;
+        writer.flush()
+    }
+
+    fn compress_pending(&mut self, is_final: bool) -> std::io::Result<()> {
+        let writer = self.writer.as_mut().unwrap();
+
+        if !self.header_written {
+            writer.write_all(&HEADER)?;
+            self.header_written = true;
+        }
+
+        deflate::compress_block(&self.pending, is_final, &mut self.bits);
+        self.pending.clear();
+        writer.write_all(&self.bits.take_bytes())
+    }
+}
+
+impl<W: Write> Write for GzipWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let count = buf.len().min(CHUNK_SIZE - self.pending.len());
+        self.pending.extend_from_slice(&buf[..count]);
+        self.crc.update(&buf[..count]);
+        self.len += count as u64;
+
+        if self.pending.len() == CHUNK_SIZE {
+            self.compress_pending(false)?;
+        }
+
+        Ok(count)
+    }
+
+    /// Flushes the underlying writer. Data still waiting to be compressed isn't written until
+    /// there's enough of it, or the writer is finished.
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for GzipWriter<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+impl RpegImage {
+    /// Outputs the image to stdout as gzip-compressed rpeg data, in binary mode. See
+    /// [`GzipWriter`].
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::BrokenPipe`] if stdout is closed before all of the data is written
+    /// * [`RpegError::Io`] if anything else goes wrong writing to stdout
+    pub fn write_gzip(&self) -> Result<(), RpegError> {
+        let mut gzip = GzipWriter::new(stdio::output()?);
+        self.write_to(&mut gzip)?;
+        let _stdout = gzip.finish()?;
+
+        Ok(())
+    }
+}
+
+/// Decompresses gzip data (one or more concatenated members, as `gzip -d` does), checking the
+/// checksum and length of each.
+pub(crate) fn decompress(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    let truncated = || "The gzip data ended unexpectedly".to_string();
+    let mut output = Vec::new();
+    let mut rest = bytes;
+
+    while !rest.is_empty() {
+        if rest.len() < 10 || rest[..3] != HEADER[..3] {
+            return Err("The input isn't valid gzip data".to_string());
+        }
+
+        // Skip over the optional parts of the header
+        let flags = rest[3];
+        let mut position = 10;
+        if flags & FEXTRA != 0 {
+            let len = rest.get(position..position + 2).ok_or_else(truncated)?;
+            position += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+        }
+        for flag in [FNAME, FCOMMENT] {
+            if flags & flag != 0 {
+                let len = rest
+                    .get(position..)
+                    .and_then(|rest| rest.iter().position(|&byte| byte == 0))
+                    .ok_or_else(truncated)?;
+                position += len + 1;
+            }
+        }
+        if flags & FHCRC != 0 {
+            position += 2;
+        }
+
+        let (member, len) = deflate::decompress(rest.get(position..).ok_or_else(truncated)?)?;
+        position += len;
+
+        let trailer = rest.get(position..position + 8).ok_or_else(truncated)?;
+        let mut crc = Crc32::new();
+        crc.update(&member);
+        if trailer[..4] != crc.finish().to_le_bytes()
+            || trailer[4..] != (member.len() as u32).to_le_bytes()
+        {
+            return Err("The gzip data is corrupted (its checksum doesn't match)".to_string());
+        }
+
+        output.extend_from_slice(&member);
+        rest = &rest[position + 8..];
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gzip_framing() {
+        let data: Vec<u8> = (0..1000u32).flat_map(|n| (n % 7).to_be_bytes()).collect();
+
+        let mut gzip = GzipWriter::new(Vec::new());
+        gzip.write_all(&data).unwrap();
+        let compressed = gzip.finish().unwrap();
+
+        assert_eq!(compressed[..10], HEADER);
+        assert!(compressed.len() < data.len() / 10);
+
+        let mut crc = Crc32::new();
+        crc.update(&data);
+        let trailer = &compressed[compressed.len() - 8..];
+        assert_eq!(trailer[..4], crc.finish().to_le_bytes());
+        assert_eq!(trailer[4..], 4000u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_empty_gzip() {
+        // An empty final block with the fixed codes is just 10 bits
+        let compressed = GzipWriter::new(Vec::new()).finish().unwrap();
+        assert_eq!(compressed[10..12], [0x03, 0x00]);
+        assert_eq!(compressed.len(), 20);
+    }
+
+    #[test]
+    fn test_decompress() {
+        let data: Vec<u8> = (0..100_000u32)
+            .flat_map(|n| (n % 300).to_be_bytes())
+            .collect();
+
+        let mut gzip = GzipWriter::new(Vec::new());
+        gzip.write_all(&data).unwrap();
+        let mut compressed = gzip.finish().unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), data);
+
+        // From `printf 'hi\n' | gzip`, with the file name "hi.txt"
+        let named = [
+            0x1F, 0x8B, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x68, 0x69, 0x2E, 0x74,
+            0x78, 0x74, 0x00, 0xCB, 0xC8, 0xE4, 0x02, 0x00, 0x7A, 0x7A, 0x6F, 0xED, 0x03, 0x00,
+            0x00, 0x00,
+        ];
+        assert_eq!(decompress(&named).unwrap(), b"hi\n");
+
+        // Concatenated members decompress to the concatenation of their contents
+        let doubled = [named, named].concat();
+        assert_eq!(decompress(&doubled).unwrap(), b"hi\nhi\n");
+
+        let len = compressed.len();
+        compressed[len - 5] ^= 0x01;
+        assert!(decompress(&compressed).is_err());
+        assert!(decompress(&named[..20]).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::sha256::{sha256, DIGEST_LEN};
+use crate::{RpegError, RpegImage};
+use std::fmt;
+
+/// The SHA-256 digest of an image's canonical rpeg data, from [`RpegImage::content_hash`]. Two
+/// images have the same hash exactly when they'd be written as the same bytes, so a student's
+/// output can be checked against a reference by comparing hashes, and results can be archived
+/// as hashes rather than whole files.
+///
+/// Its [`Display`](fmt::Display) implementation writes the digest as 64 lowercase hexadecimal
+/// digits (as `sha256sum` does), which [`FromStr`](std::str::FromStr) parses back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ContentHash(pub [u8; DIGEST_LEN]);
+
+impl RpegImage {
+    /// Hashes the image's rpeg data in the canonical form (see
+    /// [`canonicalize`](crate::canonicalize)), header and payload, with SHA-256. See
+    /// [`ContentHash`].
+    ///
+    /// Since the canonical form is part of the crate's stability guarantees, so is the hash of
+    /// any image: hashes can be stored, and compared against images read by later versions of
+    /// the crate.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{ContentHash, RpegImage};
+    ///
+    /// let reference = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+    /// let hash = reference.content_hash();
+    /// assert_eq!(
+    ///     hash.to_string(),
+    ///     "12289fcb664d65d0017ff178af350920d38bce8abd8ccde990a8db9c259419af"
+    /// );
+    ///
+    /// // The same data with a messy (but valid) header hashes the same
+    /// let output = RpegImage::from_bytes(b"Compressed image format 2\r\n02  2\r\n\x00\x11\x22\x33");
+    /// assert_eq!(output.unwrap().content_hash(), hash);
+    /// assert_eq!(hash.to_string().parse::<ContentHash>().unwrap(), hash);
+    /// ```
+    pub fn content_hash(&self) -> ContentHash {
+        ContentHash(sha256(&self.to_bytes()))
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for ContentHash {
+    type Err = RpegError;
+
+    /// Parses a hash written as 64 hexadecimal digits, in either case.
+    fn from_str(hex: &str) -> Result<Self, Self::Err> {
+        let error = |message: &str, offset: usize| RpegError::Format {
+            message: message.to_string(),
+            offset: offset as u64,
+        };
+
+        if hex.len() != DIGEST_LEN * 2 {
+            return Err(error("Expected a hash of 64 hexadecimal digits", 0));
+        }
+
+        let mut digest = [0; DIGEST_LEN];
+        for (index, byte) in digest.iter_mut().enumerate() {
+            let digits = hex
+                .get(index * 2..index * 2 + 2)
+                .ok_or_else(|| error("Expected a hash of 64 hexadecimal digits", index * 2))?;
+            *byte = u8::from_str_radix(digits, 16)
+                .ok()
+                .filter(|_| digits.bytes().all(|digit| digit.is_ascii_hexdigit()))
+                .ok_or_else(|| error("Expected a hexadecimal digit", index * 2))?;
+        }
+
+        Ok(ContentHash(digest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_hash() {
+        let image = RpegImage::new(vec![[0x00; 4]; 4], 4, 4);
+        let mut commented = image.clone();
+        commented.header.comments.push("a comment".to_string());
+        let mut changed = image.clone();
+        changed.words[3][3] = 0x01;
+
+        // Everything written is part of the hash, comments included
+        assert_ne!(image.content_hash(), commented.content_hash());
+        assert_ne!(image.content_hash(), changed.content_hash());
+        assert_eq!(image.content_hash(), image.clone().content_hash());
+    }
+
+    #[test]
+    fn test_parse_content_hash() {
+        let hash = ContentHash([0xAB; DIGEST_LEN]);
+        assert_eq!(
+            hash.to_string()
+                .to_uppercase()
+                .parse::<ContentHash>()
+                .unwrap(),
+            hash
+        );
+
+        assert!("ab".parse::<ContentHash>().is_err());
+        assert!("+b".repeat(DIGEST_LEN).parse::<ContentHash>().is_err());
+        assert!("é".repeat(DIGEST_LEN).parse::<ContentHash>().is_err());
+    }
+}
@@ -0,0 +1,499 @@
+use crate::codec::{
+    self, expect, expect_newline, expect_separator, read_comments, read_u32, skip_blanks,
+    HeaderSource, Trailers, BINARY_SUFFIX,
+};
+pub(crate) use crate::codec::{DEFAULT_FORMAT_VERSION, SUPPORTED_FORMAT_VERSIONS};
+use crate::{ReadOptions, RpegError, RpegFormat};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::BufRead;
+
+/// The information stored in the header of rpeg data.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RpegHeader {
+    /// The version of the compressed image format, from the first line of the header
+    pub version: u32,
+    /// Whether the payload is followed by a CRC32 checksum, as marked by a `c` after the version
+    /// ("Compressed image format 2c"). Custom [`RpegFormat`]s never have checksums
+    pub checksum: bool,
+    /// Whether the payload is followed by a line containing the number of words in it, as marked
+    /// by a `t` after the version ("Compressed image format 2t"). When the data also has a
+    /// checksum, the checksum comes last. Custom [`RpegFormat`]s never have word counts
+    pub word_count: bool,
+    /// Whether the data ends with an HMAC-SHA256 signature, as marked by an `s` after the version
+    /// ("Compressed image format 2s"). The signature comes after any other trailers. Since
+    /// signing takes a key, data is only ever written signed when asked for (see
+    /// [`RpegImage::to_signed`](crate::RpegImage::to_signed), with the `crypto` feature);
+    /// otherwise this is ignored when writing
+    pub signed: bool,
+    /// Whether the width and height are stored in binary, as two big-endian `u32`s right after a
+    /// first line ending in " [BINARY]" ("Compressed image format 2 [BINARY]"), rather than as
+    /// text. Binary headers can't hold comments or metadata, so none are written
+    pub binary: bool,
+    /// The width of the image
+    pub width: u32,
+    /// The height of the image
+    pub height: u32,
+    /// Any numbers in the header other than the width and height, by name. These only appear in
+    /// custom [`RpegFormat`]s with extra [`header_fields`](RpegFormat::header_fields)
+    pub extra_fields: BTreeMap<String, u32>,
+    /// The text of any `#` comment lines between the first and second lines of the header, in
+    /// order. The `#`, and a single space following it, aren't included
+    pub comments: Vec<String>,
+    /// Any key/value metadata stored in `#@ key=value` comment lines of the header. Since these
+    /// are comments, readers which don't know about metadata simply ignore them
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl RpegHeader {
+    /// Creates a new header for an image of the given dimensions, using
+    /// [`DEFAULT_FORMAT_VERSION`].
+    pub fn new(width: u32, height: u32) -> Self {
+        RpegHeader {
+            version: DEFAULT_FORMAT_VERSION,
+            checksum: false,
+            word_count: false,
+            signed: false,
+            binary: false,
+            width,
+            height,
+            extra_fields: BTreeMap::new(),
+            comments: Vec::new(),
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// Looks up a number in the header by its name in
+    /// [`header_fields`](RpegFormat::header_fields). Fields missing from the header are 0.
+    pub fn field(&self, name: &str) -> u32 {
+        match name {
+            "width" => self.width,
+            "height" => self.height,
+            _ => self.extra_fields.get(name).copied().unwrap_or(0),
+        }
+    }
+
+    pub(crate) fn trailers(&self) -> Trailers {
+        Trailers {
+            checksum: self.checksum,
+            word_count: self.word_count,
+            signed: self.signed,
+        }
+    }
+
+    fn set_field(&mut self, name: &str, value: u32) {
+        match name {
+            "width" => self.width = value,
+            "height" => self.height = value,
+            _ => {
+                self.extra_fields.insert(name.to_string(), value);
+            }
+        }
+    }
+}
+
+impl From<codec::Header> for RpegHeader {
+    fn from(header: codec::Header) -> Self {
+        RpegHeader {
+            version: header.version,
+            checksum: header.checksum,
+            word_count: header.word_count,
+            signed: header.signed,
+            binary: header.binary,
+            width: header.width,
+            height: header.height,
+            extra_fields: BTreeMap::new(),
+            comments: header.comments,
+            metadata: header.metadata,
+        }
+    }
+}
+
+/// Summarizes the header in a line, such as "2048x1536, format 2".
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::RpegHeader;
+///
+/// assert_eq!(RpegHeader::new(2048, 1536).to_string(), "2048x1536, format 2");
+/// ```
+impl fmt::Display for RpegHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}, format {}", self.width, self.height, self.version)
+    }
+}
+
+/// A byte-at-a-time view of a [`BufRead`] with one byte of lookahead, used to parse headers
+/// without consuming any bytes past the end of the header.
+///
+/// Read errors are stashed away rather than returned, and look like the end of the input to the
+/// parsing functions. Whoever drives the parse must check [`ByteReader::take_error`] afterwards.
+pub(crate) struct ByteReader<R> {
+    reader: R,
+    position: u64,
+    error: Option<std::io::Error>,
+}
+
+impl<R: BufRead> ByteReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        ByteReader {
+            reader,
+            position: 0,
+            error: None,
+        }
+    }
+
+    pub(crate) fn peek(&mut self) -> Option<u8> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        loop {
+            match self.reader.fill_buf() {
+                Ok(buffer) => return buffer.first().copied(),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.error = Some(err);
+                    return None;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.reader.consume(1);
+        self.position += 1;
+
+        Some(byte)
+    }
+
+    /// The number of bytes consumed so far.
+    pub(crate) fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+
+    pub(crate) fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take()
+    }
+
+    /// Like [`BufRead::fill_buf`], but retrying on interruption.
+    pub(crate) fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        loop {
+            match self.reader.fill_buf() {
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                // Re-borrow to get around the borrow checker's conservatism with loops
+                Ok(_) => return self.reader.fill_buf(),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    pub(crate) fn consume(&mut self, amount: usize) {
+        self.reader.consume(amount);
+        self.position += amount as u64;
+    }
+}
+
+/// Headers read from streams are parsed with the same grammar as headers in memory.
+impl<R: BufRead> HeaderSource for ByteReader<R> {
+    fn peek(&mut self) -> Option<u8> {
+        ByteReader::peek(self)
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        ByteReader::next(self)
+    }
+}
+
+fn write_comments(writer: &mut impl std::io::Write, header: &RpegHeader) -> std::io::Result<()> {
+    // Line breaks or an = in the key would change the meaning of the metadata
+    for (key, value) in &header.metadata {
+        let key = key.replace(['\n', '\r'], " ").replace('=', "_");
+        let value = value.replace(['\n', '\r'], " ");
+
+        writer.write_all(format!("#@ {key}={value}\n").as_bytes())?;
+    }
+
+    // A comment containing a line break can only be written as several comments
+    // (and an empty comment as a single empty one, though `lines` would give no lines at all)
+    let lines = header.comments.iter().flat_map(|comment| {
+        if comment.is_empty() {
+            vec![""]
+        } else {
+            comment.lines().collect()
+        }
+    });
+
+    for line in lines {
+        if line.is_empty() {
+            writer.write_all(b"#\n")?;
+        } else {
+            writer.write_all(format!("# {line}\n").as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_header_fields_in_format(
+    bytes: &mut ByteReader<impl BufRead>,
+    format: &dyn RpegFormat,
+) -> Result<RpegHeader, String> {
+    // Read the magic line of the header
+    expect(format.magic().as_bytes(), bytes)?;
+    skip_blanks(bytes);
+    expect_newline(bytes)?;
+
+    // Read any "# {comment}\n" and "#@ {key}={value}\n" lines
+    let mut header = RpegHeader::new(0, 0);
+    header.version = format.version();
+    (header.comments, header.metadata) = read_comments(bytes)?;
+
+    // Read the space-separated fields of the header
+
+    for (index, name) in format.header_fields().iter().enumerate() {
+        if index != 0 {
+            expect_separator(bytes)?;
+        }
+
+        header.set_field(name, read_u32(bytes)?);
+    }
+    skip_blanks(bytes);
+    expect_newline(bytes)?;
+
+    Ok(header)
+}
+
+/// Parses an rpeg header, leaving `bytes` positioned at the first byte of the payload.
+pub(crate) fn read_header(
+    bytes: &mut ByteReader<impl BufRead>,
+    options: &ReadOptions,
+) -> Result<RpegHeader, RpegError> {
+    read_header_with_suffix(bytes, options, "")
+}
+
+/// Parses an rpeg header whose first line ends in `suffix` (such as " [TEXT]"), leaving `bytes`
+/// positioned at the first byte of the payload.
+pub(crate) fn read_header_with_suffix(
+    bytes: &mut ByteReader<impl BufRead>,
+    options: &ReadOptions,
+    suffix: &str,
+) -> Result<RpegHeader, RpegError> {
+    let result = codec::parse_header_fields(bytes, &options.accepted_versions, suffix);
+    finish_parse(bytes, result.map(RpegHeader::from))
+}
+
+/// Parses an rpeg header in a custom format, leaving `bytes` positioned at the first byte of the
+/// payload.
+pub(crate) fn read_header_in_format(
+    bytes: &mut ByteReader<impl BufRead>,
+    format: &dyn RpegFormat,
+) -> Result<RpegHeader, RpegError> {
+    let result = parse_header_fields_in_format(bytes, format);
+    finish_parse(bytes, result)
+}
+
+fn finish_parse(
+    bytes: &mut ByteReader<impl BufRead>,
+    result: Result<RpegHeader, String>,
+) -> Result<RpegHeader, RpegError> {
+    // A read error looks like running out of bytes to the parser, so it takes priority
+    if let Some(err) = bytes.take_error() {
+        return Err(err.into());
+    }
+
+    result.map_err(|message| RpegError::Format {
+        message,
+        offset: bytes.position(),
+    })
+}
+
+pub(crate) fn write_header(
+    writer: &mut impl std::io::Write,
+    header: &RpegHeader,
+) -> std::io::Result<()> {
+    write_header_with_suffix(writer, header, "")
+}
+
+/// Writes an rpeg header whose first line ends in `suffix` (such as " [TEXT]"). The header is
+/// never marked as signed, since only [`write_signed_header`] writes signatures.
+pub(crate) fn write_header_with_suffix(
+    writer: &mut impl std::io::Write,
+    header: &RpegHeader,
+    suffix: &str,
+) -> std::io::Result<()> {
+    write_header_with_flags(writer, header, suffix, false)
+}
+
+/// Writes an rpeg header marked as signed only if [`RpegHeader::signed`] says so, for data whose
+/// signature is copied through along with its payload.
+pub(crate) fn write_header_as_read(
+    writer: &mut impl std::io::Write,
+    header: &RpegHeader,
+) -> std::io::Result<()> {
+    write_header_with_flags(writer, header, "", header.signed)
+}
+
+/// Writes an rpeg header marked as signed, whatever [`RpegHeader::signed`] says.
+#[cfg(feature = "crypto")]
+pub(crate) fn write_signed_header(
+    writer: &mut impl std::io::Write,
+    header: &RpegHeader,
+) -> std::io::Result<()> {
+    write_header_with_flags(writer, header, "", true)
+}
+
+fn write_header_with_flags(
+    writer: &mut impl std::io::Write,
+    header: &RpegHeader,
+    suffix: &str,
+    signed: bool,
+) -> std::io::Result<()> {
+    // Letters after the version mark any trailers after the payload
+    let checksum = if header.checksum { "c" } else { "" };
+    let word_count = if header.word_count { "t" } else { "" };
+    let signed = if signed { "s" } else { "" };
+
+    // Only plain headers can be binary, since other suffixes mark other kinds of data
+    if header.binary && suffix.is_empty() {
+        let magic = format!(
+            "Compressed image format {}{checksum}{word_count}{signed}{BINARY_SUFFIX}\n",
+            header.version
+        );
+        writer.write_all(magic.as_bytes())?;
+        writer.write_all(&header.width.to_be_bytes())?;
+        return writer.write_all(&header.height.to_be_bytes());
+    }
+
+    let magic = format!(
+        "Compressed image format {}{checksum}{word_count}{signed}{suffix}\n",
+        header.version
+    );
+
+    writer.write_all(magic.as_bytes())?;
+    write_comments(writer, header)?;
+    writer.write_all(format!("{} {}\n", header.width, header.height).as_bytes())
+}
+
+pub(crate) fn write_header_in_format(
+    writer: &mut impl std::io::Write,
+    header: &RpegHeader,
+    format: &dyn RpegFormat,
+) -> std::io::Result<()> {
+    let fields: Vec<String> = format
+        .header_fields()
+        .iter()
+        .map(|name| header.field(name).to_string())
+        .collect();
+
+    writer.write_all(format.magic().as_bytes())?;
+    writer.write_all(b"\n")?;
+    write_comments(writer, header)?;
+    writer.write_all(fields.join(" ").as_bytes())?;
+    writer.write_all(b"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &[u8]) -> Result<RpegHeader, RpegError> {
+        read_header(&mut ByteReader::new(input), &ReadOptions::default())
+    }
+
+    #[test]
+    fn test_comments_are_collected() {
+        let input = b"Compressed image format 2\n# made by hand\n#\n#no space\r\n4 2\n";
+        let header = parse(input).unwrap();
+
+        assert_eq!(header.comments, vec!["made by hand", "", "no space"]);
+        assert_eq!((header.width, header.height), (4, 2));
+    }
+
+    #[test]
+    fn test_comments_round_trip() {
+        let mut header = RpegHeader::new(4, 2);
+        header.comments = vec!["made by hand".to_string(), "two\nlines".to_string()];
+
+        let mut output = Vec::new();
+        write_header(&mut output, &header).unwrap();
+        assert_eq!(
+            output,
+            b"Compressed image format 2\n# made by hand\n# two\n# lines\n4 2\n"
+        );
+
+        let parsed = parse(&output).unwrap();
+        assert_eq!(parsed.comments, vec!["made by hand", "two", "lines"]);
+    }
+
+    #[test]
+    fn test_lenient_whitespace() {
+        let header = parse(b"Compressed image format 2 \r\n4 \t 2\t\r\n").unwrap();
+        assert_eq!((header.width, header.height), (4, 2));
+
+        assert!(parse(b"Compressed image format 2\n42\n").is_err());
+        assert!(parse(b"Compressed image format 2\n 4 2\n").is_err());
+    }
+
+    #[test]
+    fn test_metadata() {
+        let input = b"Compressed image format 2\n#@ creator=ppm2rpeg\n# hi\n#@tag = a=b \n4 2\n";
+        let header = parse(input).unwrap();
+
+        assert_eq!(header.comments, vec!["hi"]);
+        assert_eq!(header.metadata.len(), 2);
+        assert_eq!(header.metadata["creator"], "ppm2rpeg");
+        assert_eq!(header.metadata["tag"], "a=b");
+
+        let mut output = Vec::new();
+        write_header(&mut output, &header).unwrap();
+        assert_eq!(
+            output,
+            b"Compressed image format 2\n#@ creator=ppm2rpeg\n#@ tag=a=b\n# hi\n4 2\n"
+        );
+    }
+
+    #[test]
+    fn test_comments_only_before_dimensions() {
+        assert!(parse(b"Compressed image format 2\n4 2\n# too late\n")
+            .is_ok_and(|header| header.comments.is_empty()));
+        assert!(parse(b"# too early\nCompressed image format 2\n4 2\n").is_err());
+    }
+
+    #[test]
+    fn test_binary_header() {
+        let mut header = RpegHeader::new(0x0A0D, 0x23);
+        header.binary = true;
+        header.checksum = true;
+        header.comments.push("not written".to_string());
+
+        let mut output = Vec::new();
+        write_header(&mut output, &header).unwrap();
+        assert_eq!(
+            output,
+            b"Compressed image format 2c [BINARY]\n\x00\x00\x0A\x0D\x00\x00\x00\x23"
+        );
+
+        let mut bytes = ByteReader::new(&output[..]);
+        let parsed = read_header(&mut bytes, &ReadOptions::default()).unwrap();
+        assert_eq!(
+            parsed,
+            RpegHeader {
+                comments: Vec::new(),
+                ..header
+            }
+        );
+        assert_eq!(bytes.position(), output.len() as u64);
+
+        assert!(matches!(
+            parse(&output[..output.len() - 1]),
+            Err(RpegError::Format { offset: 43, .. })
+        ));
+        assert!(parse(b"Compressed image format 2 [BINARY]\r\n\0\0\0\x02\0\0\0\x02").is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{RpegError, RpegImage};
+use std::fmt::Write;
+
+/// The number of bytes on each line of a hexdump, as `xxd` writes by default.
+const LINE_LEN: usize = 16;
+
+/// The most bytes [`parse_hexdump`] fills in with zeroes before a line, so that a mistyped offset
+/// is reported rather than taken as a request for gigabytes of zeroes.
+const MAX_GAP: usize = 64 * 1024;
+
+/// Writes bytes as a hexdump in the style of `xxd`, for inspecting or hand-editing a whole file
+/// (header and all) with familiar tools. [`parse_hexdump`] (or `xxd -r`) turns it back into
+/// bytes.
+///
+/// Each line starts with the offset of its first byte (eight hexadecimal digits and a colon),
+/// followed by up to 16 bytes in groups of two, and then the same bytes as ASCII, with a `.` in
+/// place of each byte which isn't a printable character.
+///
+/// # Examples
+/// ```
+/// let dump = csc411_rpegio::hexdump(b"Compressed image format 2\n2 2\n\x00\x11\x22\x33");
+///
+/// assert_eq!(
+///     dump,
+///     "00000000: 436f 6d70 7265 7373 6564 2069 6d61 6765  Compressed image\n\
+///      00000010: 2066 6f72 6d61 7420 320a 3220 320a 0011   format 2.2 2...\n\
+///      00000020: 2233                                     \"3\n"
+/// );
+/// let bytes = csc411_rpegio::parse_hexdump(&dump).unwrap();
+/// assert_eq!(bytes, b"Compressed image format 2\n2 2\n\x00\x11\x22\x33");
+/// ```
+pub fn hexdump(bytes: &[u8]) -> String {
+    let mut dump = String::with_capacity(bytes.len() / LINE_LEN * 68 + 68);
+
+    for (index, line) in bytes.chunks(LINE_LEN).enumerate() {
+        write!(dump, "{:08x}:", index * LINE_LEN).unwrap();
+
+        let mut hex = String::with_capacity(40);
+        for (position, byte) in line.iter().enumerate() {
+            if position % 2 == 0 {
+                hex.push(' ');
+            }
+            write!(hex, "{byte:02x}").unwrap();
+        }
+        write!(dump, "{hex:40}  ").unwrap();
+
+        let ascii = line.iter().map(|&byte| match byte {
+            b' '..=b'~' => byte as char,
+            _ => '.',
+        });
+        dump.extend(ascii);
+        dump.push('\n');
+    }
+
+    dump
+}
+
+/// Parses a hexdump in the style of `xxd` (as written by [`hexdump`]) back into bytes, as
+/// `xxd -r` does.
+///
+/// The bytes on each line are put at the offset at the start of the line, so lines may be
+/// edited, removed, or reordered. Any gaps (of up to 64 KiB past the end of the bytes so far) are
+/// filled with zeroes, and later lines overwrite earlier ones. Everything after two spaces in a
+/// row (the ASCII column) is ignored, so edits must be made to the hexadecimal bytes. Blank lines
+/// are skipped.
+///
+/// # Errors Returned
+///
+/// * If a line doesn't start with a hexadecimal offset and a colon
+/// * If the bytes of a line aren't pairs of hexadecimal digits
+/// * If a line's offset is more than 64 KiB past the end of the bytes before it
+pub fn parse_hexdump(dump: &str) -> Result<Vec<u8>, RpegError> {
+    let mut bytes = Vec::new();
+    let mut line_offset = 0;
+
+    for line in dump.split_inclusive('\n') {
+        let start = line_offset;
+        let error = |message: &str| RpegError::Format {
+            message: message.to_string(),
+            offset: start as u64,
+        };
+        let text = line.trim_end_matches(['\n', '\r']);
+        line_offset += line.len();
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let (offset, hex) = text
+            .split_once(':')
+            .ok_or_else(|| error("Expected a line starting with an offset and a colon"))?;
+        let offset = usize::from_str_radix(offset.trim(), 16)
+            .map_err(|_| error("Expected a hexadecimal offset at the start of the line"))?;
+        let hex = match hex.trim_start().split_once("  ") {
+            Some((hex, _ascii)) => hex,
+            None => hex,
+        };
+
+        let digits: Vec<u8> = hex.bytes().filter(|byte| *byte != b' ').collect();
+        if !digits.len().is_multiple_of(2) {
+            return Err(error(
+                "Expected the bytes of the line as pairs of hex digits",
+            ));
+        }
+        let line_bytes = digits
+            .chunks(2)
+            .map(|pair| {
+                let pair = std::str::from_utf8(pair).ok()?;
+                u8::from_str_radix(pair, 16).ok()
+            })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| error("Expected the bytes of the line as pairs of hex digits"))?;
+
+        if offset > bytes.len().saturating_add(MAX_GAP) {
+            return Err(error(
+                "The offset leaves too large a gap after the bytes before it",
+            ));
+        }
+        let end = offset
+            .checked_add(line_bytes.len())
+            .ok_or_else(|| error("The offset leaves too large a gap after the bytes before it"))?;
+        if bytes.len() < end {
+            bytes.resize(end, 0);
+        }
+        bytes[offset..end].copy_from_slice(&line_bytes);
+    }
+
+    Ok(bytes)
+}
+
+impl RpegImage {
+    /// Encodes the image as rpeg data (see [`RpegImage::to_bytes`]), written as an `xxd`-style
+    /// hexdump. See [`hexdump`].
+    pub fn to_hexdump(&self) -> String {
+        hexdump(&self.to_bytes())
+    }
+
+    /// Parses an image from a hexdump of its rpeg data, such as one written by
+    /// [`RpegImage::to_hexdump`] and then edited by hand. See [`parse_hexdump`].
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the hexdump is badly formatted
+    /// * If the bytes it holds aren't valid rpeg data
+    pub fn from_hexdump(dump: &str) -> Result<Self, RpegError> {
+        RpegImage::from_bytes(&parse_hexdump(dump)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hexdump_round_trip() {
+        let mut image = RpegImage::new((0..=255u8).map(|byte| [byte; 4]).collect(), 32, 32);
+        image.header.checksum = true;
+        let dump = image.to_hexdump();
+
+        assert!(dump.lines().all(|line| line.len() <= 67));
+        assert_eq!(RpegImage::from_hexdump(&dump).unwrap(), image);
+        assert_eq!(parse_hexdump("").unwrap(), b"");
+    }
+
+    #[test]
+    fn test_parse_edited_hexdump() {
+        // Editing a word, and adding a line out of order with a gap before it
+        let dump = "00000000: 00ff 1 1  ascii is ignored: 00\n\n00000006: abcd\r\n00000001: 22\n";
+        assert_eq!(
+            parse_hexdump(dump).unwrap(),
+            [0x00, 0x22, 0x11, 0x00, 0x00, 0x00, 0xAB, 0xCD]
+        );
+
+        assert!(matches!(
+            parse_hexdump("00000000: 0011\n00000002 2233\n"),
+            Err(RpegError::Format { offset: 15, .. })
+        ));
+        assert!(parse_hexdump("00000000: 001\n").is_err());
+        assert!(parse_hexdump("0000000g: 0011\n").is_err());
+        assert!(parse_hexdump("00000000: 00xy\n").is_err());
+
+        // Offsets far past the end, which would need gigabytes of zeroes (or overflow)
+        for dump in ["ffffffffffffffff: 0011\n", "00000000: 0011\nffffffff: 22\n"] {
+            assert!(matches!(parse_hexdump(dump), Err(RpegError::Format { .. })));
+        }
+        assert_eq!(parse_hexdump("00010000: 22\n").unwrap().len(), 0x10001);
+    }
+}
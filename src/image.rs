@@ -0,0 +1,727 @@
+use crate::armor;
+use crate::checksum::Crc32;
+use crate::compression;
+use crate::header::{self, ByteReader};
+use crate::timing::{timed, Stage};
+use crate::trailer;
+use crate::{format, stdio, ReadOptions, RpegError, RpegFormat, RpegHeader, RpegSink, RpegSource};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A complete rpeg image: its header, along with every word of its payload.
+///
+/// # Examples
+/// ```no_run
+/// use csc411_rpegio::RpegImage;
+///
+/// let image = RpegImage::read(Some("path/to/file.rpeg")).unwrap();
+/// println!("Image size: {}x{}", image.width(), image.height());
+///
+/// for word in &image.words {
+///     // Do something with each word
+/// }
+/// ```
+///
+/// Images are generic over the number of bytes `N` in each word, which defaults to the 4 bytes
+/// used by the standard format, so `RpegImage` on its own means `RpegImage<4>`. Images with other
+/// word sizes can be read and written using a custom [`RpegFormat`].
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct RpegImage<const N: usize = 4> {
+    /// The header of the image
+    pub header: RpegHeader,
+    /// The words of compressed image data, in order
+    pub words: Vec<[u8; N]>,
+}
+
+impl<const N: usize> RpegImage<N> {
+    /// Creates an image from its words and dimensions.
+    pub fn new(words: Vec<[u8; N]>, width: u32, height: u32) -> Self {
+        RpegImage {
+            header: RpegHeader::new(width, height),
+            words,
+        }
+    }
+
+    /// The width of the image.
+    pub fn width(&self) -> u32 {
+        self.header.width
+    }
+
+    /// The height of the image.
+    pub fn height(&self) -> u32 {
+        self.header.height
+    }
+
+    /// The image's key/value metadata, from `#@ key=value` lines in the header. Empty if the
+    /// header has none.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.header.metadata
+    }
+
+    /// The image's key/value metadata, for modification before the image is written.
+    pub fn metadata_mut(&mut self) -> &mut BTreeMap<String, String> {
+        &mut self.header.metadata
+    }
+
+    /// Consumes the image, returning its words as a boxed slice with no excess capacity. Use
+    /// this when the words will be kept around for a long time without being added to, to make
+    /// sure no more memory is held than is actually needed.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let mut words = Vec::with_capacity(1000);
+    /// words.push([0x00, 0x11, 0x22, 0x33]);
+    ///
+    /// let words: Box<[[u8; 4]]> = RpegImage::new(words, 2, 2).into_boxed_words();
+    /// assert_eq!(words.len(), 1);
+    /// ```
+    pub fn into_boxed_words(self) -> Box<[[u8; N]]> {
+        self.words.into_boxed_slice()
+    }
+
+    /// Shrinks the capacity of the image's words as much as possible, freeing any memory that
+    /// isn't needed to hold them.
+    pub fn shrink_to_fit(&mut self) {
+        self.words.shrink_to_fit();
+    }
+
+    /// Parses data in a custom variant of the rpeg format (header and payload) from a slice of
+    /// bytes. See [`RpegFormat`] for details.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the format's words aren't `N` bytes long
+    /// * If the header is badly formatted
+    /// * If the number of raw bytes following the header is not a multiple of `N`
+    pub fn from_bytes_in_format(bytes: &[u8], format: &dyn RpegFormat) -> Result<Self, RpegError> {
+        format::check_word_size(format, N)?;
+
+        let mut byte_reader = ByteReader::new(bytes);
+        let header = timed(Stage::HeaderParse, || {
+            header::read_header_in_format(&mut byte_reader, format)
+        })?;
+
+        RpegImage::from_header_and_payload(header, bytes, byte_reader.position() as usize)
+    }
+
+    /// Reads and parses data in a custom variant of the rpeg format from either stdin or a file.
+    /// See [`RpegFormat`] for details.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the format's words aren't `N` bytes long
+    /// * If there is an unexpected error reading from the provided file or stdin
+    /// * If the header is badly formatted
+    /// * If the number of raw bytes following the header is not a multiple of `N`
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - An optional file path to read from. If None, stdin will be read from
+    ///   instead
+    /// * `format` - The variant of the rpeg format to read
+    pub fn read_in_format(
+        file_path: Option<&str>,
+        format: &dyn RpegFormat,
+    ) -> Result<Self, RpegError> {
+        format::check_word_size(format, N)?;
+
+        let bytes = timed(Stage::PayloadRead, || crate::read_raw_bytes(file_path))?;
+
+        RpegImage::from_bytes_in_format(&bytes, format)
+    }
+
+    pub(crate) fn from_header_and_payload(
+        header: RpegHeader,
+        bytes: &[u8],
+        header_len: usize,
+    ) -> Result<Self, RpegError> {
+        // The rest of the bytes (after the header) are the payload, and perhaps some trailers
+        let raw_bytes = trailer::split_payload(&header, bytes, header_len, N)?;
+
+        // Group the bytes in groups of N
+        let words = timed(Stage::Grouping, || {
+            raw_bytes
+                .chunks_exact(N)
+                .map(|x| x.try_into().unwrap())
+                .collect()
+        });
+
+        Ok(RpegImage { header, words })
+    }
+
+    /// Encodes the image in a custom variant of the rpeg format (header and payload). See
+    /// [`RpegFormat`] for details.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the format's words aren't `N` bytes long
+    pub fn to_bytes_in_format(&self, format: &dyn RpegFormat) -> Result<Vec<u8>, RpegError> {
+        format::check_word_size(format, N)?;
+
+        let mut bytes = Vec::with_capacity(32 + self.words.len() * N);
+        header::write_header_in_format(&mut bytes, &self.header, format)?;
+        for word in &self.words {
+            bytes.extend_from_slice(word);
+        }
+
+        Ok(bytes)
+    }
+
+    /// Outputs the image to stdout in a custom variant of the rpeg format, in binary mode. See
+    /// [`RpegFormat`] for details.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the format's words aren't `N` bytes long
+    /// * [`RpegError::BrokenPipe`] if stdout is closed before all of the data is written
+    /// * [`RpegError::Io`] if anything else goes wrong writing to stdout
+    pub fn write_in_format(&self, format: &dyn RpegFormat) -> Result<(), RpegError> {
+        let bytes = self.to_bytes_in_format(format)?;
+
+        let mut stdout = stdio::output()?;
+        timed(Stage::Output, || {
+            std::io::Write::write_all(&mut stdout, &bytes)?;
+            std::io::Write::flush(&mut stdout)
+        })?;
+
+        Ok(())
+    }
+}
+
+impl RpegImage<2> {
+    /// Converts an image with two-byte words (such as one in the legacy [`Format1`]) to one with
+    /// the standard four-byte words, by zero-extending each word. The header's version becomes
+    /// [`DEFAULT_FORMAT_VERSION`](crate::DEFAULT_FORMAT_VERSION).
+    ///
+    /// [`Format1`]: crate::Format1
+    pub fn into_format_2(self) -> RpegImage<4> {
+        let words = self.words.iter().map(|&[a, b]| [0, 0, a, b]).collect();
+
+        RpegImage {
+            header: RpegHeader {
+                version: crate::DEFAULT_FORMAT_VERSION,
+                ..self.header
+            },
+            words,
+        }
+    }
+}
+
+impl RpegImage {
+    /// Converts an image with the standard four-byte words to one with two-byte words, as used
+    /// by the legacy [`Format1`]. This is the reverse of [`RpegImage::into_format_2`].
+    ///
+    /// [`Format1`]: crate::Format1
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::WordOutOfRange`] if any word is too big to fit in two bytes
+    pub fn try_into_format_1(self) -> Result<RpegImage<2>, RpegError> {
+        let words = self
+            .words
+            .iter()
+            .enumerate()
+            .map(|(index, word)| match word {
+                [0, 0, a, b] => Ok([*a, *b]),
+                _ => Err(RpegError::WordOutOfRange { index }),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(RpegImage {
+            header: RpegHeader {
+                version: 1,
+                ..self.header
+            },
+            words,
+        })
+    }
+}
+
+impl RpegImage {
+    /// Reads and parses rpeg data from either stdin or a file. This is equivalent to
+    /// [`read_in_rpeg_data`](crate::read_in_rpeg_data), with a structured error type.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If there is an unexpected error reading from the provided file or stdin
+    /// * If the rpeg data header is badly formatted
+    /// * If the number of raw bytes following the header is not a multiple of 4
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - An optional file path to read from. If None, stdin will be read from
+    ///   instead
+    pub fn read(file_path: Option<&str>) -> Result<Self, RpegError> {
+        RpegImage::read_with_options(file_path, &ReadOptions::default())
+    }
+
+    /// Like [`RpegImage::read`], but with the given options.
+    pub fn read_with_options(
+        file_path: Option<&str>,
+        options: &ReadOptions,
+    ) -> Result<Self, RpegError> {
+        let bytes = timed(Stage::PayloadRead, || crate::read_raw_bytes(file_path))?;
+
+        RpegImage::from_bytes_with_options(&bytes, options)
+    }
+
+    /// Like [`RpegImage::read`], but reads from any [`RpegSource`], such as bytes in memory in a
+    /// test.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If there is an unexpected error reading from the source
+    /// * If the rpeg data header is badly formatted
+    /// * If the number of raw bytes following the header is not a multiple of 4
+    pub fn read_from(source: impl RpegSource) -> Result<Self, RpegError> {
+        let bytes = timed(Stage::PayloadRead, || source.read_bytes())?;
+
+        RpegImage::from_bytes(&bytes)
+    }
+
+    /// Parses rpeg data (header and payload) from a slice of bytes.
+    ///
+    /// Gzip-compressed data (with the `gzip` feature) and [ASCII-armored](RpegImage::to_armored)
+    /// data are detected automatically, and decoded before parsing.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the rpeg data header is badly formatted
+    /// * If the number of raw bytes following the header is not a multiple of 4
+    /// * If the data is compressed, but can't be decompressed. Only gzip is supported
+    /// * If the data is compressed or armored more than once, such as gzip data inside gzip data
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RpegError> {
+        RpegImage::from_bytes_with_options(bytes, &ReadOptions::default())
+    }
+
+    /// Like [`RpegImage::from_bytes`], but with the given options.
+    pub fn from_bytes_with_options(bytes: &[u8], options: &ReadOptions) -> Result<Self, RpegError> {
+        let nested = |container: &str| RpegError::Format {
+            message: format!("The data is {container} more than once"),
+            offset: 0,
+        };
+
+        // Compressed data is detected from its magic bytes, and decompressed first. Only one layer
+        // is unwrapped, so that nested containers can't make reading arbitrarily expensive
+        let decompressed = compression::decompress(bytes)?;
+        let bytes = decompressed.as_deref().unwrap_or(bytes);
+        if decompressed.is_some() && compression::is_compressed(bytes) {
+            return Err(nested("compressed"));
+        }
+
+        // ASCII-armored data is detected from its header, and decoded back to binary next
+        let dearmored = match armor::is_armored(bytes) {
+            true => Some(armor::dearmor(bytes, options)?),
+            false => None,
+        };
+        let bytes = dearmored.as_deref().unwrap_or(bytes);
+        if dearmored.is_some() && (compression::is_compressed(bytes) || armor::is_armored(bytes)) {
+            return Err(nested("armored"));
+        }
+
+        let mut byte_reader = ByteReader::new(bytes);
+        let header = timed(Stage::HeaderParse, || {
+            header::read_header(&mut byte_reader, options)
+        })?;
+        let header_len = byte_reader.position() as usize;
+
+        #[cfg(feature = "crypto")]
+        if let Some(key) = &options.verify_key {
+            let end = bytes.len() as u64;
+            crate::signature::verify(key, &header, &bytes[header_len..], end)?;
+        }
+
+        RpegImage::from_header_and_payload(header, bytes, header_len)
+    }
+
+    /// Encodes the image as rpeg data (header and payload).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32 + self.words.len() * 4);
+        self.write_to(&mut bytes)
+            .expect("Writing to a Vec can't fail");
+
+        bytes
+    }
+
+    /// Outputs the image to stdout, in binary mode. This is equivalent to
+    /// [`try_output_rpeg_data`](crate::try_output_rpeg_data), except that the header's format
+    /// version is used.
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::BrokenPipe`] if stdout is closed before all of the data is written
+    /// * [`RpegError::Io`] if anything else goes wrong writing to stdout
+    pub fn write(&self) -> Result<(), RpegError> {
+        let mut stdout = std::io::BufWriter::new(stdio::output()?);
+        timed(Stage::Output, || self.write_to(&mut stdout))?;
+
+        Ok(())
+    }
+
+    /// Like [`RpegImage::write`], but writes to any [`RpegSink`], such as a `Vec<u8>` in a test.
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::BrokenPipe`] if the sink is a pipe which is closed before all of the data
+    ///   is written
+    /// * [`RpegError::Io`] if anything else goes wrong writing to the sink
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+    /// let mut output = Vec::new();
+    /// image.write_into(&mut output).unwrap();
+    ///
+    /// assert_eq!(RpegImage::read_from(output).unwrap(), image);
+    /// ```
+    pub fn write_into(&self, sink: &mut impl RpegSink) -> Result<(), RpegError> {
+        timed(Stage::Output, || self.write_to(&mut sink.writer()?))?;
+
+        Ok(())
+    }
+
+    /// Writes the image to a file, creating it or replacing its contents. With the `gzip`
+    /// feature enabled, the data is gzip-compressed if the path ends in `.gz`.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the file can't be created, or something goes wrong writing to it
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file to write
+    pub fn write_file(&self, path: &str) -> Result<(), RpegError> {
+        let file = std::fs::File::create(path)?;
+
+        #[cfg(feature = "gzip")]
+        if path.ends_with(".gz") {
+            let mut gzip = crate::GzipWriter::new(file);
+            self.write_to(&mut gzip)?;
+            gzip.finish()?;
+
+            return Ok(());
+        }
+
+        self.write_to(&mut std::io::BufWriter::new(file))?;
+
+        Ok(())
+    }
+
+    pub(crate) fn write_to(&self, writer: &mut impl std::io::Write) -> std::io::Result<()> {
+        header::write_header(writer, &self.header)?;
+
+        let mut crc = Crc32::new();
+        for word in &self.words {
+            writer.write_all(word)?;
+            crc.update(word);
+        }
+
+        trailer::write(writer, &self.header, self.words.len() as u64, &crc)?;
+
+        writer.flush()
+    }
+}
+
+/// The number of words shown by the [`Debug`](fmt::Debug) implementation of [`RpegImage`].
+const DEBUG_WORDS: usize = 4;
+
+/// Shows the header in full, but only the number of words and the first few of them (in hex),
+/// rather than every word of a payload which may have hundreds of thousands.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::RpegImage;
+///
+/// let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]; 19200], 320, 240);
+/// let debug = format!("{image:?}");
+///
+/// assert!(debug.starts_with("RpegImage { header: RpegHeader { version: 2, "));
+/// assert!(debug.ends_with("words: 19200 words [00112233, 00112233, 00112233, 00112233, ...] }"));
+/// ```
+impl<const N: usize> fmt::Debug for RpegImage<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RpegImage")
+            .field("header", &self.header)
+            .field("words", &WordsPreview(&self.words))
+            .finish()
+    }
+}
+
+/// The words of an image, shown as their number and the first few of them.
+struct WordsPreview<'a, const N: usize>(&'a [[u8; N]]);
+
+impl<const N: usize> fmt::Debug for WordsPreview<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words = if self.0.len() == 1 { "word" } else { "words" };
+        write!(f, "{} {words} [", self.0.len())?;
+
+        for (index, word) in self.0.iter().take(DEBUG_WORDS).enumerate() {
+            if index != 0 {
+                f.write_str(", ")?;
+            }
+            for byte in word {
+                write!(f, "{byte:02X}")?;
+            }
+        }
+        if self.0.len() > DEBUG_WORDS {
+            f.write_str(", ...")?;
+        }
+
+        f.write_str("]")
+    }
+}
+
+/// Summarizes the image in a line: its header (see [`RpegHeader`]), and the number of words and
+/// bytes in its payload.
+///
+/// The alternate form (`{:#}`) instead writes the whole image in the plain-text format, with
+/// the words in hexadecimal (see [`RpegImage::to_text`]), which parses back into the same image
+/// (see [`FromStr`](std::str::FromStr)).
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::RpegImage;
+///
+/// let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]; 6], 6, 4);
+/// assert_eq!(image.to_string(), "6x4, format 2, 6 words (24 bytes)");
+///
+/// let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]; 2], 4, 2);
+/// assert_eq!(
+///     format!("{image:#}"),
+///     "Compressed image format 2 [TEXT]\n4 2\n0x00112233 0x00112233\n"
+/// );
+/// ```
+impl<const N: usize> fmt::Display for RpegImage<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return crate::text::write_hex_text(f, self);
+        }
+
+        let len = self.words.len();
+        let words = if len == 1 { "word" } else { "words" };
+        write!(f, "{}, {len} {words} ({} bytes)", self.header, len * N)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SUPPORTED_FORMAT_VERSIONS;
+
+    #[test]
+    fn test_debug_shows_few_words() {
+        let debug = |words: Vec<[u8; 4]>| format!("{:?}", WordsPreview(&words));
+
+        assert_eq!(debug(vec![]), "0 words []");
+        assert_eq!(debug(vec![[0xAB; 4]]), "1 word [ABABABAB]");
+        assert_eq!(
+            debug(vec![[0x01; 4]; 4]),
+            "4 words [01010101, 01010101, 01010101, 01010101]"
+        );
+        assert!(debug(vec![[0; 4]; 5]).ends_with("00000000, ...]"));
+    }
+
+    #[test]
+    fn test_detects_version() {
+        for version in SUPPORTED_FORMAT_VERSIONS {
+            let bytes = format!("Compressed image format {version}\n2 2\n\x00\x11\x22\x33");
+            let image = RpegImage::from_bytes(bytes.as_bytes()).unwrap();
+
+            assert_eq!(image.header.version, *version);
+            assert_eq!(image.to_bytes(), bytes.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let mut image = RpegImage::new(vec![[0x00, 0x11]], 2, 2);
+        image.header.version = 1;
+        assert_eq!(image.to_string(), "2x2, format 1, 1 word (2 bytes)");
+        assert_eq!(
+            RpegImage::<4>::new(vec![], 0, 0).to_string(),
+            "0x0, format 2, 0 words (0 bytes)"
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_version() {
+        let bytes = b"Compressed image format 4\n2 2\n\x00\x11\x22\x33";
+
+        assert!(matches!(
+            RpegImage::from_bytes(bytes),
+            Err(RpegError::Format { offset: 25, .. })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_unaccepted_version() {
+        let bytes = b"Compressed image format 3\n2 2\n\x00\x11\x22\x33";
+        let options = ReadOptions::new().accepted_versions(&[2]);
+
+        assert_eq!(RpegImage::from_bytes(bytes).unwrap().header.version, 3);
+        let result = RpegImage::from_bytes_with_options(bytes, &options);
+        assert!(matches!(
+            result,
+            Err(RpegError::Format { message, .. }) if message.contains("not accepted")
+        ));
+    }
+
+    struct EightByteFormat;
+
+    impl RpegFormat for EightByteFormat {
+        fn magic(&self) -> &str {
+            "Eight byte words"
+        }
+
+        fn version(&self) -> u32 {
+            1
+        }
+
+        fn word_size(&self) -> usize {
+            8
+        }
+    }
+
+    #[test]
+    fn test_eight_byte_words() {
+        let bytes = b"Eight byte words\n4 2\n\x00\x11\x22\x33\x44\x55\x66\x77";
+        let image = RpegImage::<8>::from_bytes_in_format(bytes, &EightByteFormat).unwrap();
+
+        assert_eq!(
+            image.words,
+            vec![[0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77]]
+        );
+        assert_eq!(image.to_bytes_in_format(&EightByteFormat).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_eight_byte_words_misaligned() {
+        let bytes = b"Eight byte words\n4 2\n\x00\x11\x22\x33";
+        let err = RpegImage::<8>::from_bytes_in_format(bytes, &EightByteFormat).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "The number of raw bytes (4) was not a multiple of eight"
+        );
+    }
+
+    #[test]
+    fn test_word_size_must_match_format() {
+        let bytes = b"Eight byte words\n4 2\n";
+
+        assert!(matches!(
+            RpegImage::<4>::from_bytes_in_format(bytes, &EightByteFormat),
+            Err(RpegError::WordSizeMismatch {
+                expected: 4,
+                found: 8
+            })
+        ));
+    }
+
+    #[test]
+    fn test_format_1_conversions() {
+        let bytes = b"Compressed image format 1\n4 2\n\x12\x34\x56\x78";
+        let image = RpegImage::<2>::from_bytes_in_format(bytes, &crate::Format1).unwrap();
+        assert_eq!(image.header.version, 1);
+
+        let converted = image.clone().into_format_2();
+        assert_eq!(
+            converted.to_bytes(),
+            b"Compressed image format 2\n4 2\n\x00\x00\x12\x34\x00\x00\x56\x78"
+        );
+        assert_eq!(converted.try_into_format_1().unwrap(), image);
+
+        let too_wide = RpegImage::new(vec![[0, 0, 0, 1], [0, 1, 0, 0]], 4, 2);
+        assert!(matches!(
+            too_wide.try_into_format_1(),
+            Err(RpegError::WordOutOfRange { index: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_standard_reader_points_to_format_1() {
+        let bytes = b"Compressed image format 1\n4 2\n\x12\x34\x56\x78";
+        let err = RpegImage::from_bytes(bytes).unwrap_err();
+
+        assert!(err.to_string().contains("Format1"));
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+        image.header.checksum = true;
+        let mut bytes = image.to_bytes();
+
+        assert_eq!(&bytes[..31], b"Compressed image format 2c\n2 2\n");
+        assert_eq!(RpegImage::from_bytes(&bytes).unwrap(), image);
+
+        bytes[31] = 0xFF;
+        assert!(matches!(
+            RpegImage::from_bytes(&bytes),
+            Err(RpegError::ChecksumMismatch { .. })
+        ));
+
+        assert!(RpegImage::from_bytes(b"Compressed image format 2c\n2 2\n\x00").is_err());
+    }
+
+    #[test]
+    fn test_word_count() {
+        let mut image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]; 3], 2, 2);
+        image.header.word_count = true;
+        image.header.checksum = true;
+        let bytes = image.to_bytes();
+
+        assert_eq!(&bytes[..32], b"Compressed image format 2ct\n2 2\n");
+        assert_eq!(&bytes[44..46], b"3\n");
+        assert_eq!(RpegImage::from_bytes(&bytes).unwrap(), image);
+
+        // Losing a whole word is noticed, though the length is still a multiple of four
+        let truncated = b"Compressed image format 2t\n2 2\n\x00\x11\x22\x33\x00\x11\x22\x333\n";
+        assert!(matches!(
+            RpegImage::from_bytes(truncated),
+            Err(RpegError::WordCountMismatch {
+                expected: 3,
+                found: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_detects_compression() {
+        let zstd = b"\x28\xB5\x2F\xFD\x00\x00";
+        let result = RpegImage::from_bytes(zstd);
+        assert!(
+            matches!(result, Err(RpegError::Format { message, .. }) if message.contains("zstd"))
+        );
+
+        #[cfg(feature = "gzip")]
+        {
+            use std::io::Write;
+
+            let image = RpegImage::new(vec![[0x1F, 0x8B, 0x28, 0xB5]; 5], 6, 2);
+            let mut gzip = crate::GzipWriter::new(Vec::new());
+            image.write_to(&mut gzip).unwrap();
+            let compressed = gzip.finish().unwrap();
+
+            assert_eq!(RpegImage::from_bytes(&compressed).unwrap(), image);
+
+            let mut gzip = crate::GzipWriter::new(Vec::new());
+            gzip.write_all(image.to_armored().as_bytes()).unwrap();
+            let armored = gzip.finish().unwrap();
+            assert_eq!(RpegImage::from_bytes(&armored).unwrap(), image);
+
+            let mut gzip = crate::GzipWriter::new(Vec::new());
+            gzip.write_all(&compressed).unwrap();
+            let twice = gzip.finish().unwrap();
+            let result = RpegImage::from_bytes(&twice);
+            assert!(matches!(
+                result,
+                Err(RpegError::Format { message, offset: 0 }) if message.contains("more than once")
+            ));
+        }
+    }
+}
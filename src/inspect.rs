@@ -0,0 +1,355 @@
+use crate::compare::{self, HIGHLIGHT};
+use crate::header;
+use crate::layout::RESET;
+use crate::{BitLayout, RpegError, RpegImage};
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+
+/// The number of words shown on each page, unless otherwise requested.
+const DEFAULT_PAGE_LEN: usize = 16;
+
+const HELP: &str = "\
+Commands (press enter after each):
+  n, or nothing   next page
+  p               previous page
+  g ROW COL       go to the word for block (ROW, COL)
+  g INDEX         go to word INDEX
+  v hex|bin|bits  show words in hex, binary, or colored by the fields of the layout
+  l LAYOUT        set the layout, such as \"a:9, b:5s, c:5s, d:5s, pb:4, pr:4\"
+  d               toggle the diff against the second image
+  h               show the header
+  ?               show this help
+  q               quit
+";
+
+/// How the words of an image are shown by an [`RpegInspector`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum InspectView {
+    /// The bytes of each word in hexadecimal
+    #[default]
+    Hex,
+    /// The bits of each word, a byte at a time
+    Binary,
+    /// The bits of each word, colored by the fields of the inspector's layout
+    Fields,
+}
+
+/// An interactive, line-based inspector for the words of an image, for use over SSH where GUI
+/// tools aren't available. Enabled by the `inspect` feature, which also builds it into the
+/// `rpeg-inspect` binary.
+///
+/// The inspector shows a page of words at a time, each labelled with its index and the row and
+/// column of its 2x2 block, then reads a command from its input (type `?` for a list). Commands
+/// page through the words, jump to a block, switch between hex, binary, and bitfield views, and
+/// toggle a diff against a second image.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{InspectView, RpegImage, RpegInspector};
+///
+/// let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]; 40], 20, 8);
+/// let mut inspector = RpegInspector::new(image).page_len(4).view(InspectView::Hex);
+///
+/// let mut output = Vec::new();
+/// inspector.run(&b"g 2 3\nq\n"[..], &mut output).unwrap();
+///
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.contains("words 23-26 of 40"));
+/// assert!(output.contains("23 (2, 3): 00 11 22 33"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RpegInspector {
+    image: RpegImage,
+    other: Option<RpegImage>,
+    layout: BitLayout,
+    view: InspectView,
+    diff: bool,
+    page_len: usize,
+    position: usize,
+}
+
+impl RpegInspector {
+    /// Creates an inspector for an image, starting at its first word in the hex view.
+    pub fn new(image: RpegImage) -> Self {
+        RpegInspector {
+            image,
+            other: None,
+            layout: BitLayout::new(),
+            view: InspectView::default(),
+            diff: false,
+            page_len: DEFAULT_PAGE_LEN,
+            position: 0,
+        }
+    }
+
+    /// Sets a second image to diff against, and starts with the diff shown.
+    pub fn compare_with(mut self, other: RpegImage) -> Self {
+        self.other = Some(other);
+        self.diff = true;
+        self
+    }
+
+    /// Sets the layout of the fields of each word, for the bitfield view.
+    pub fn layout(mut self, layout: BitLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Sets the view to start in. Defaults to [`InspectView::Hex`].
+    pub fn view(mut self, view: InspectView) -> Self {
+        self.view = view;
+        self
+    }
+
+    /// Sets the number of words shown on each page. Defaults to 16.
+    ///
+    /// # Panics
+    ///
+    /// * If `page_len` is 0
+    pub fn page_len(mut self, page_len: usize) -> Self {
+        assert!(page_len > 0, "A page must show at least one word");
+        self.page_len = page_len;
+        self
+    }
+
+    /// Runs the inspector, showing pages on `output` and reading commands from `input`, until
+    /// the `q` command or the end of the input.
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::BrokenPipe`] if the output is closed
+    /// * [`RpegError::Io`] if anything else goes wrong reading commands or writing pages
+    pub fn run(
+        &mut self,
+        mut input: impl BufRead,
+        mut output: impl Write,
+    ) -> Result<(), RpegError> {
+        writeln!(output, "{}", self.summary())?;
+        output.write_all(self.page().as_bytes())?;
+
+        let mut line = String::new();
+        loop {
+            write!(output, "> ")?;
+            output.flush()?;
+
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            match self.command(line.trim()) {
+                Ok(Some(text)) => output.write_all(text.as_bytes())?,
+                Ok(None) => return Ok(()),
+                Err(message) => writeln!(output, "{message} (type ? for help)")?,
+            }
+        }
+    }
+
+    /// Carries out a command, returning what it shows, or None to quit.
+    fn command(&mut self, command: &str) -> Result<Option<String>, String> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("n");
+        let args: Vec<&str> = words.collect();
+        let number = |arg: &str| {
+            arg.parse::<usize>()
+                .map_err(|_| format!("Expected a number, got \"{arg}\""))
+        };
+
+        match (name, &args[..]) {
+            ("n", []) => {
+                let last_page = self.image.words.len().saturating_sub(1) / self.page_len;
+                self.position = (self.position + self.page_len).min(last_page * self.page_len);
+            }
+            ("p", []) => self.position = self.position.saturating_sub(self.page_len),
+            ("g", [index]) => self.go_to(number(index)?)?,
+            ("g", [row, column]) => {
+                let (row, column) = (number(row)?, number(column)?);
+                let row_len = self.row_len();
+                if column >= row_len {
+                    return Err(format!("Column {column} is past the end of the row"));
+                }
+                self.go_to(row * row_len + column)?;
+            }
+            ("v", ["hex"]) => self.view = InspectView::Hex,
+            ("v", ["bin"]) => self.view = InspectView::Binary,
+            ("v", ["bits"]) => self.view = InspectView::Fields,
+            ("l", _) if !args.is_empty() => self.layout = args.join(" ").parse()?,
+            ("d", []) if self.other.is_some() => self.diff = !self.diff,
+            ("d", []) => return Err("There's no second image to diff against".to_string()),
+            ("h", []) => return Ok(Some(self.header())),
+            ("?", []) => return Ok(Some(HELP.to_string())),
+            ("q", []) => return Ok(None),
+            _ => return Err(format!("Unknown command \"{command}\"")),
+        }
+
+        Ok(Some(self.page()))
+    }
+
+    fn go_to(&mut self, index: usize) -> Result<(), String> {
+        if index >= self.image.words.len() {
+            return Err(format!("Word {index} is past the end of the image"));
+        }
+
+        self.position = index;
+        Ok(())
+    }
+
+    fn row_len(&self) -> usize {
+        (self.image.width() as usize / 2).max(1)
+    }
+
+    fn summary(&self) -> String {
+        let mut summary = format!(
+            "{}x{} image, {} words",
+            self.image.width(),
+            self.image.height(),
+            self.image.words.len()
+        );
+        if let Some(other) = &self.other {
+            write!(summary, ", ").unwrap();
+            write!(summary, "{}", compare::compare_rpeg(&self.image, other)).unwrap();
+        }
+
+        summary
+    }
+
+    fn header(&self) -> String {
+        // Binary headers are shown as text, so that they can be read
+        let header = header::RpegHeader {
+            binary: false,
+            ..self.image.header.clone()
+        };
+        let mut bytes = Vec::new();
+        header::write_header(&mut bytes, &header).expect("Writing to a Vec can't fail");
+
+        String::from_utf8(bytes).expect("Headers are always valid UTF-8")
+    }
+
+    fn page(&self) -> String {
+        let len = self.image.words.len();
+        let end = (self.position + self.page_len).min(len);
+        let view = match self.view {
+            InspectView::Hex => "hex",
+            InspectView::Binary => "binary",
+            InspectView::Fields => "bitfield",
+        };
+
+        let mut page = match len {
+            0 => format!("no words ({view} view)\n"),
+            _ => format!(
+                "words {}-{} of {len} ({view} view)\n",
+                self.position,
+                end - 1
+            ),
+        };
+        if self.view == InspectView::Fields {
+            writeln!(page, "{}", self.layout.legend()).unwrap();
+        }
+
+        let row_len = self.row_len();
+        let label_len = len.saturating_sub(1).to_string().len();
+        for index in self.position..end {
+            let word = self.image.words[index];
+            let (row, column) = (index / row_len, index % row_len);
+            write!(page, "{index:>label_len$} ({row}, {column}): ").unwrap();
+
+            let other = self.other.as_ref().filter(|_| self.diff);
+            let other_word = other.and_then(|other| other.words.get(index));
+            match self.view {
+                InspectView::Hex if other.is_some() => {
+                    compare::write_word(&mut page, Some(&word), other_word);
+                }
+                InspectView::Hex => compare::write_word(&mut page, Some(&word), Some(&word)),
+                InspectView::Binary => {
+                    let bytes: Vec<String> =
+                        word.iter().map(|byte| format!("{byte:08b}")).collect();
+                    page.push_str(&bytes.join(" "));
+                }
+                InspectView::Fields => self.layout.write_colored_word(&mut page, word),
+            }
+
+            if other.is_some() {
+                match other_word {
+                    Some(other_word) if *other_word == word => page.push_str(" | same"),
+                    Some(other_word) => {
+                        page.push_str(" ! ");
+                        compare::write_word(&mut page, Some(other_word), Some(&word));
+                    }
+                    None => write!(page, " ! {HIGHLIGHT}missing{RESET}").unwrap(),
+                }
+            }
+            page.push('\n');
+        }
+
+        page
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(inspector: &mut RpegInspector, commands: &str) -> String {
+        let mut output = Vec::new();
+        inspector.run(commands.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_inspector_paging() {
+        let words = (0..10u32).map(|index| index.to_be_bytes()).collect();
+        let mut inspector = RpegInspector::new(RpegImage::new(words, 4, 10)).page_len(4);
+
+        let output = run(&mut inspector, "\nn\nn\np\n");
+        let pages: Vec<&str> = output
+            .lines()
+            .filter(|line| line.contains("words "))
+            .collect();
+        assert_eq!(pages.len(), 5);
+        assert!(pages[1].starts_with("> words 4-7 of 10"));
+        // Paging stops at the last page
+        assert!(pages[2].starts_with("> words 8-9 of 10"));
+        assert!(pages[3].starts_with("> words 8-9 of 10"));
+        assert!(pages[4].starts_with("> words 4-7 of 10"));
+
+        let output = run(&mut inspector, "g 4 1\nv bin\nh\nbad\ng 4 2\nq\nn\n");
+        assert!(output.contains("> words 9-9 of 10 (hex view)\n9 (4, 1): 00 00 00 09\n"));
+        assert!(output.contains("9 (4, 1): 00000000 00000000 00000000 00001001\n"));
+        assert!(output.contains("> Compressed image format 2\n4 10\n"));
+        assert!(output.contains("> Unknown command \"bad\" (type ? for help)"));
+        assert!(output.contains("> Column 2 is past the end of the row"));
+        assert!(output.ends_with("> "));
+    }
+
+    #[test]
+    fn test_inspector_diff() {
+        let image = RpegImage::new(vec![[0x00; 4]; 3], 2, 6);
+        let mut other = image.clone();
+        other.words[1] = [0x00, 0x00, 0x00, 0x0F];
+        other.words.pop();
+        let layout = "x:4".parse().unwrap();
+        let mut inspector = RpegInspector::new(image).compare_with(other).layout(layout);
+
+        let output = run(&mut inspector, "v bits\nd\nd\n");
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].ends_with(
+            "2 of 3 words differ, first at word 1 (row 1, column 0): 00000000 != 0000000F"
+        ));
+        assert_eq!(lines[2], "0 (0, 0): 00 00 00 00 | same");
+        assert_eq!(
+            lines[3],
+            "1 (1, 0): 00 00 00 \x1b[1;31m00\x1b[0m ! 00 00 00 \x1b[1;31m0F\x1b[0m"
+        );
+        assert!(lines[4].starts_with("2 (2, 0): \x1b[1;31m00\x1b[0m "));
+        assert!(lines[4].ends_with(" ! \x1b[1;31mmissing\x1b[0m"));
+        assert_eq!(lines[6], "\x1b[31mx\x1b[0m [31:28]");
+        assert!(lines[7].starts_with("0 (0, 0): \x1b[31m0000\x1b[0m 0000"));
+        assert!(lines[7].ends_with("  x=0 | same"));
+        assert_eq!(
+            lines[12],
+            "0 (0, 0): \x1b[31m0000\x1b[0m 0000000000000000000000000000  x=0"
+        );
+
+        let mut inspector = RpegInspector::new(RpegImage::new(vec![], 0, 0));
+        assert!(run(&mut inspector, "d\n").contains("no second image"));
+    }
+}
@@ -0,0 +1,422 @@
+use crate::text::parse_hex_word;
+use crate::{RpegError, RpegImage};
+
+impl RpegImage {
+    /// Encodes the image as JSON, for inspecting it in a browser or notebook, or diffing it with
+    /// tools like `jq`. Enabled by the `json` feature.
+    ///
+    /// The JSON is a single object with the `width` and `height` of the image, and its `words` as
+    /// an array of strings of eight hexadecimal digits (the bytes of the word, in order). Nothing
+    /// else from the header (such as comments) is kept.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let words = vec![[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0xFF]];
+    /// let image = RpegImage::new(words, 4, 2);
+    /// let json = image.to_json();
+    ///
+    /// assert_eq!(json, r#"{"width":4,"height":2,"words":["00112233","445566ff"]}"#);
+    /// assert_eq!(RpegImage::from_json(&json).unwrap(), image);
+    /// ```
+    pub fn to_json(&self) -> String {
+        let words = self
+            .words
+            .iter()
+            .map(|word| format!("\"{:08x}\"", u32::from_be_bytes(*word)))
+            .collect::<Vec<_>>();
+
+        format!(
+            "{{\"width\":{},\"height\":{},\"words\":[{}]}}",
+            self.width(),
+            self.height(),
+            words.join(",")
+        )
+    }
+
+    /// Parses an image from JSON in the form written by [`RpegImage::to_json`]. Any other fields
+    /// in the object are ignored, and the digits of the words may be in either case, with or
+    /// without a `0x` prefix.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the text isn't valid JSON, or isn't an object
+    /// * If `width`, `height`, or `words` is missing or the wrong type, or a word isn't a string
+    ///   of at most eight hexadecimal digits
+    pub fn from_json(json: &str) -> Result<Self, RpegError> {
+        let mut parser = Parser { json, position: 0 };
+        let root = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.position != json.len() {
+            return Err(parser.error("Expected the end of the JSON"));
+        }
+
+        let Value::Object(fields) = root.value else {
+            return Err(type_error(&root, "The JSON", "an object"));
+        };
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value)
+                .ok_or_else(|| RpegError::Format {
+                    message: format!("Missing the field {name:?}"),
+                    offset: root.offset as u64,
+                })
+        };
+
+        let dimension = |name: &str| {
+            let value = field(name)?;
+            match &value.value {
+                Value::Number(number) => number.parse::<u32>().ok(),
+                _ => None,
+            }
+            .ok_or_else(|| type_error(value, name, "a u32"))
+        };
+        let width = dimension("width")?;
+        let height = dimension("height")?;
+
+        let words = field("words")?;
+        let Value::Array(words) = &words.value else {
+            return Err(type_error(words, "words", "an array"));
+        };
+        let words = words
+            .iter()
+            .map(|word| match &word.value {
+                Value::String(hex) => parse_hex_word(hex).ok_or_else(|| RpegError::Format {
+                    message: format!("Expected a word as eight hexadecimal digits, found {hex:?}"),
+                    offset: word.offset as u64,
+                }),
+                _ => Err(type_error(word, "A word", "a string")),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(RpegImage::new(words, width, height))
+    }
+}
+
+impl RpegError {
+    /// Encodes the error as a JSON object, for autograders and other programs which need to
+    /// report errors without matching on their messages. Enabled by the `json` feature.
+    ///
+    /// The object has the error's [`code`](RpegError::code), its `message` (as written by its
+    /// [`Display`](std::fmt::Display) implementation), and its [`offset`](RpegError::offset) and
+    /// [`hint`](RpegError::hint), which are `null` if the error doesn't have them.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let error = RpegImage::from_bytes(b"Compressed image format 2\n2 x\n").unwrap_err();
+    /// assert_eq!(
+    ///     error.to_json(),
+    ///     r#"{"code":"format","message":"Attempted to parse non-ascii digit 120","offset":28,"hint":"Check that the input is rpeg data, and that it wasn't changed by being written or read in text mode"}"#
+    /// );
+    /// ```
+    pub fn to_json(&self) -> String {
+        let offset = match self.offset() {
+            Some(offset) => offset.to_string(),
+            None => "null".to_string(),
+        };
+        let hint = match self.hint() {
+            Some(hint) => json_string(hint),
+            None => "null".to_string(),
+        };
+
+        format!(
+            "{{\"code\":\"{}\",\"message\":{},\"offset\":{offset},\"hint\":{hint}}}",
+            self.code(),
+            json_string(&self.to_string())
+        )
+    }
+}
+
+/// Writes a string as a JSON string literal, quotes and all.
+pub(crate) fn json_string(string: &str) -> String {
+    let mut json = String::with_capacity(string.len() + 2);
+    json.push('"');
+    for char in string.chars() {
+        match char {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            char if char.is_control() => json.push_str(&format!("\\u{:04x}", char as u32)),
+            char => json.push(char),
+        }
+    }
+    json.push('"');
+
+    json
+}
+
+fn type_error(value: &Spanned, name: &str, expected: &str) -> RpegError {
+    RpegError::Format {
+        message: format!("{name} must be {expected}"),
+        offset: value.offset as u64,
+    }
+}
+
+/// A parsed JSON value, and the offset of its first byte in the JSON.
+struct Spanned {
+    offset: usize,
+    value: Value,
+}
+
+enum Value {
+    // `null`, `true`, or `false`, none of which are ever needed
+    Literal,
+    // Numbers are kept as written, to be parsed as whichever type is wanted
+    Number(String),
+    String(String),
+    Array(Vec<Spanned>),
+    Object(Vec<(String, Spanned)>),
+}
+
+struct Parser<'a> {
+    json: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> RpegError {
+        RpegError::Format {
+            message: message.to_string(),
+            offset: self.position as u64,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.json.as_bytes().get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), RpegError> {
+        self.skip_whitespace();
+        if self.peek() == Some(byte) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", byte as char)))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Spanned, RpegError> {
+        self.skip_whitespace();
+        let offset = self.position;
+
+        let value = match self.peek() {
+            Some(b'{') => self.parse_object()?,
+            Some(b'[') => self.parse_array()?,
+            Some(b'"') => Value::String(self.parse_string()?),
+            Some(b'-' | b'0'..=b'9') => {
+                while matches!(
+                    self.peek(),
+                    Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+                ) {
+                    self.position += 1;
+                }
+                Value::Number(self.json[offset..self.position].to_string())
+            }
+            _ => {
+                let rest = &self.json[offset..];
+                let Some(literal) = ["null", "true", "false"]
+                    .into_iter()
+                    .find(|literal| rest.starts_with(literal))
+                else {
+                    return Err(self.error("Expected a JSON value"));
+                };
+                self.position += literal.len();
+                Value::Literal
+            }
+        };
+
+        Ok(Spanned { offset, value })
+    }
+
+    fn parse_object(&mut self) -> Result<Value, RpegError> {
+        self.expect(b'{')?;
+        let mut fields = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(Value::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("Expected the name of a field"));
+            }
+            let key = self.parse_string()?;
+            self.expect(b':')?;
+            fields.push((key, self.parse_value()?));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(Value::Object(fields));
+                }
+                _ => return Err(self.error("Expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Value, RpegError> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Value::Array(values));
+        }
+
+        loop {
+            values.push(self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Value::Array(values));
+                }
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, RpegError> {
+        self.position += 1;
+        let mut string = String::new();
+
+        loop {
+            let rest = &self.json[self.position..];
+            let Some(ch) = rest.chars().next() else {
+                return Err(self.error("Unterminated string"));
+            };
+            self.position += ch.len_utf8();
+
+            match ch {
+                '"' => return Ok(string),
+                '\\' => {
+                    let escape = self
+                        .peek()
+                        .ok_or_else(|| self.error("Unterminated string"))?;
+                    self.position += 1;
+                    string.push(match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{C}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("Invalid escape in string")),
+                    });
+                }
+                _ => string.push(ch),
+            }
+        }
+    }
+
+    /// Parses the digits of a `\u` escape, including the second half of a surrogate pair.
+    fn parse_unicode_escape(&mut self) -> Result<char, RpegError> {
+        let first = self.parse_hex4()?;
+
+        let code = if (0xD800..0xDC00).contains(&first) {
+            if !self.json[self.position..].starts_with("\\u") {
+                return Err(self.error("Expected the second half of a surrogate pair"));
+            }
+            self.position += 2;
+            let second = self.parse_hex4()?;
+            if !(0xDC00..0xE000).contains(&second) {
+                return Err(self.error("Invalid surrogate pair"));
+            }
+            0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+        } else {
+            first
+        };
+
+        char::from_u32(code).ok_or_else(|| self.error("Invalid unicode escape"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, RpegError> {
+        let digits = self
+            .json
+            .get(self.position..self.position + 4)
+            .filter(|digits| digits.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("Expected four hexadecimal digits"))?;
+        self.position += 4;
+
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_whitespace_and_extra_fields() {
+        let json = " {\n  \"name\": \"t\\u00e9st \\ud83d\\ude00\",\n  \"words\": [\"0xDEADBEEF\", \"1\"],\n  \
+                    \"height\": 2, \"extra\": [null, true, {\"a\": -1.5e3}], \"width\": 4\n}\n";
+        let image = RpegImage::from_json(json).unwrap();
+
+        assert_eq!(image.width(), 4);
+        assert_eq!(image.height(), 2);
+        assert_eq!(image.words, [[0xDE, 0xAD, 0xBE, 0xEF], [0, 0, 0, 1]]);
+    }
+
+    #[test]
+    fn test_json_errors() {
+        let error_offset = |json: &str| match RpegImage::from_json(json) {
+            Err(RpegError::Format { offset, .. }) => offset,
+            result => panic!("Expected a format error, found {result:?}"),
+        };
+
+        assert_eq!(
+            error_offset(r#"{"width":4,"height":2,"words":["123456789"]}"#),
+            31
+        );
+        assert_eq!(error_offset(r#"{"width":-4,"height":2,"words":[]}"#), 9);
+        assert_eq!(error_offset(r#"{"width":4,"words":[]}"#), 0);
+        assert_eq!(error_offset(r#"{"width":4,"height":2,"words":[]} x"#), 34);
+        assert_eq!(error_offset(r#"{"width":4,"height":2,"words":["#), 31);
+        assert_eq!(error_offset("[]"), 0);
+    }
+
+    #[test]
+    fn test_error_to_json() {
+        let error = RpegError::Io(std::io::Error::other("a \"quoted\"\tname\u{1}"));
+        assert_eq!(
+            error.to_json(),
+            r#"{"code":"io","message":"I/O error: a \"quoted\"\tname\u0001","offset":null,"hint":"Check that the file exists, and can be read or written"}"#
+        );
+
+        let error = RpegError::PatchMismatch.to_json();
+        assert!(error.starts_with(r#"{"code":"patch_mismatch","message":"#));
+        assert!(error.ends_with(r#","offset":null,"hint":null}"#));
+
+        // The JSON written for any error can be parsed
+        let mut parser = Parser {
+            json: &error,
+            position: 0,
+        };
+        assert!(parser.parse_value().is_ok());
+        assert_eq!(parser.position, error.len());
+    }
+}
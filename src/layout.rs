@@ -0,0 +1,444 @@
+use crate::{stdio, BitField, RpegError, RpegImage};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// The ANSI colors given to the fields of a layout, in turn.
+const FIELD_COLORS: [u8; 6] = [31, 32, 33, 34, 35, 36];
+
+/// Resets the color after a colored span of text.
+pub(crate) const RESET: &str = "\x1b[0m";
+
+/// The ANSI color of the field of a layout with the given index.
+fn field_color(index: usize) -> u8 {
+    FIELD_COLORS[index % FIELD_COLORS.len()]
+}
+
+/// A layout of named fields within each word of an image, such as the `a`, `b`, `c`, `d`,
+/// `pb`, and `pr` fields of a codeword, for [`RpegImage::to_colored_bits`].
+///
+/// A layout can be built up field by field, or parsed from a list of `name:width` pairs
+/// separated by commas, starting from the most significant bit of the word. A width followed
+/// by `s` (such as `b:5s`) makes the field signed. Fields don't have to fill the whole word.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{BitField, BitLayout};
+///
+/// let layout: BitLayout = "a:9, b:5s, c:5s, d:5s, pb:4, pr:4".parse().unwrap();
+///
+/// let built = BitLayout::new()
+///     .field("a", BitField::new(23, 9))
+///     .field("b", BitField::new(18, 5).signed())
+///     .field("c", BitField::new(13, 5).signed())
+///     .field("d", BitField::new(8, 5).signed())
+///     .field("pb", BitField::new(4, 4))
+///     .field("pr", BitField::new(0, 4));
+/// assert_eq!(layout, built);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BitLayout {
+    fields: Vec<(String, BitField)>,
+}
+
+impl BitLayout {
+    /// Creates a layout without any fields.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a named field to the layout.
+    ///
+    /// # Panics
+    ///
+    /// * If the field is empty or doesn't fit in a 32-bit word
+    /// * If the field overlaps another field of the layout
+    pub fn field(mut self, name: &str, field: BitField) -> Self {
+        assert!(
+            field.width > 0 && field.lsb + field.width <= 32,
+            "A field must be at least 1 bit wide, and fit in a 32-bit word"
+        );
+        assert!(
+            self.fields.iter().all(|(_, other)| {
+                field.lsb >= other.lsb + other.width || other.lsb >= field.lsb + field.width
+            }),
+            "The field \"{name}\" overlaps another field of the layout"
+        );
+
+        self.fields.push((name.to_string(), field));
+        self
+    }
+
+    /// Returns the named fields of the layout, in the order they were added.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, BitField)> {
+        self.fields
+            .iter()
+            .map(|(name, field)| (name.as_str(), *field))
+    }
+
+    /// Returns the field of the layout with the given name, if it has one.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{BitField, BitLayout};
+    ///
+    /// let layout: BitLayout = "a:9, b:5s".parse().unwrap();
+    /// assert_eq!(layout.get("b"), Some(BitField::new(18, 5).signed()));
+    /// assert_eq!(layout.get("pb"), None);
+    /// ```
+    pub fn get(&self, name: &str) -> Option<BitField> {
+        self.fields()
+            .find(|(other, _)| *other == name)
+            .map(|(_, field)| field)
+    }
+
+    /// Lists the fields of the layout in their colors, and the bits they cover.
+    pub(crate) fn legend(&self) -> String {
+        let mut legend = String::new();
+        for (index, (name, field)) in self.fields().enumerate() {
+            if index != 0 {
+                legend.push_str("  ");
+            }
+            let msb = field.lsb + field.width - 1;
+            let color = field_color(index);
+            write!(legend, "\x1b[{color}m{name}{RESET} [{msb}:{}]", field.lsb).unwrap();
+        }
+
+        legend
+    }
+
+    /// Writes the bits of a word colored by field, followed by the value of each field.
+    pub(crate) fn write_colored_word(&self, bits: &mut String, word: [u8; 4]) {
+        // Each run of bits in the same field (or outside every field) is written at once
+        let value = u32::from_be_bytes(word);
+        let mut bit = 32;
+        while bit > 0 {
+            let field = self.field_at(bit - 1);
+            let lsb = match field {
+                Some((_, field)) => field.lsb,
+                // A run outside every field ends just above the next field down
+                None => (0..bit)
+                    .rev()
+                    .find(|&bit| self.field_at(bit).is_some())
+                    .map_or(0, |bit| bit + 1),
+            };
+            let run: String = (lsb..bit)
+                .rev()
+                .map(|bit| if value >> bit & 1 == 1 { '1' } else { '0' })
+                .collect();
+
+            if bit != 32 {
+                bits.push(' ');
+            }
+            match field {
+                Some((index, _)) => write!(bits, "\x1b[{}m{run}{RESET}", field_color(index)),
+                None => write!(bits, "{run}"),
+            }
+            .unwrap();
+            bit = lsb;
+        }
+
+        bits.push(' ');
+        for (name, field) in self.fields() {
+            write!(bits, " {name}={}", field.value(word)).unwrap();
+        }
+    }
+
+    /// Returns the field of the layout which holds the given bit, and its index.
+    fn field_at(&self, bit: u32) -> Option<(usize, BitField)> {
+        self.fields
+            .iter()
+            .enumerate()
+            .map(|(index, (_, field))| (index, *field))
+            .find(|(_, field)| (field.lsb..field.lsb + field.width).contains(&bit))
+    }
+}
+
+impl FromStr for BitLayout {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut layout = BitLayout::new();
+        let mut next_bit = 32;
+
+        for pair in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let error = || format!("Expected a field like \"name:width\", got \"{pair}\"");
+            let (name, width) = pair.split_once(':').ok_or_else(error)?;
+            let (name, width) = (name.trim(), width.trim());
+            let (width, signed) = match width.strip_suffix('s') {
+                Some(width) => (width, true),
+                None => (width, false),
+            };
+            let width: u32 = width.parse().map_err(|_| error())?;
+
+            if name.is_empty() || width == 0 {
+                return Err(error());
+            }
+            if layout.fields().any(|(other, _)| other == name) {
+                return Err(format!("The field \"{name}\" is in the layout twice"));
+            }
+            if width > next_bit {
+                return Err(format!(
+                    "The fields don't fit in a 32-bit word at \"{pair}\""
+                ));
+            }
+
+            next_bit -= width;
+            let field = BitField::new(next_bit, width);
+            layout = layout.field(name, if signed { field.signed() } else { field });
+        }
+
+        Ok(layout)
+    }
+}
+
+impl RpegImage {
+    /// Shows the bits of every word of the image, with the bits of each field of a layout in
+    /// their own color (using ANSI escape codes), to make mistakes in shifting and masking fields
+    /// easy to spot. Bits outside every field are left uncolored.
+    ///
+    /// The first line is a legend of the fields and the bits they cover. Each word is then on its
+    /// own line: its index, its bits from the most significant, with a space between fields,
+    /// and the value of each field.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{BitLayout, RpegImage};
+    ///
+    /// let layout: BitLayout = "hi:4, lo:4s".parse().unwrap();
+    /// let image = RpegImage::new(vec![[0x5F, 0x00, 0x00, 0x00]], 2, 2);
+    /// let bits = image.to_colored_bits(&layout);
+    ///
+    /// let lines: Vec<&str> = bits.lines().collect();
+    /// assert_eq!(lines[0], "\x1b[31mhi\x1b[0m [31:28]  \x1b[32mlo\x1b[0m [27:24]");
+    /// assert_eq!(
+    ///     lines[1],
+    ///     "0: \x1b[31m0101\x1b[0m \x1b[32m1111\x1b[0m 000000000000000000000000  hi=5 lo=-1"
+    /// );
+    /// ```
+    pub fn to_colored_bits(&self, layout: &BitLayout) -> String {
+        let mut bits = layout.legend();
+        bits.push('\n');
+
+        let label_len = self.words.len().saturating_sub(1).to_string().len();
+        for (index, &word) in self.words.iter().enumerate() {
+            write!(bits, "{index:>label_len$}: ").unwrap();
+            layout.write_colored_word(&mut bits, word);
+            bits.push('\n');
+        }
+
+        bits
+    }
+
+    /// Outputs the bits of every word of the image to stdout, colored by the fields of a layout.
+    /// See [`RpegImage::to_colored_bits`].
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::BrokenPipe`] if stdout is closed before all of the output is written
+    /// * [`RpegError::Io`] if anything else goes wrong writing to stdout
+    pub fn write_colored_bits(&self, layout: &BitLayout) -> Result<(), RpegError> {
+        use std::io::Write;
+
+        let mut stdout = stdio::output()?;
+        stdout.write_all(self.to_colored_bits(layout).as_bytes())?;
+        stdout.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Counts of the values of a field across every word of an image, grouped into buckets, from
+/// [`RpegImage::histogram`].
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{BitLayout, RpegImage};
+///
+/// let layout: BitLayout = "a:9, b:5s".parse().unwrap();
+/// let words = [0x00, 0x04, 0x04, 0x7C, 0x7C, 0x7C]
+///     .iter()
+///     .map(|&byte| [0x00, byte, 0x00, 0x00])
+///     .collect();
+/// let image = RpegImage::new(words, 6, 4);
+///
+/// // The b field is 0, 1, 1, -1, -1, and -1
+/// let histogram = image.histogram(&layout, "b", 2).unwrap();
+/// assert_eq!(histogram.buckets, [(-2, 3), (0, 3)]);
+/// assert_eq!(histogram.to_bar_chart(6), "-2..-1 |###### 3\n 0..1  |###### 3\n");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldHistogram {
+    /// The number of values in each bucket
+    pub bucket_width: u64,
+    /// The smallest value in each bucket, and the number of words with a value in it, in order.
+    /// The buckets cover every value from the smallest value found to the biggest, and are lined
+    /// up with the smallest value the field can hold
+    pub buckets: Vec<(i64, u64)>,
+}
+
+impl FieldHistogram {
+    /// Draws the histogram as a text bar chart, with a line for each bucket showing its range of
+    /// values, a bar of `#`s, and its count. The longest bar is `max_bar_len` characters long.
+    pub fn to_bar_chart(&self, max_bar_len: usize) -> String {
+        let end = |start: i64| start + self.bucket_width as i64 - 1;
+        let len = |values: &mut dyn Iterator<Item = i64>| {
+            values
+                .map(|value| value.to_string().len())
+                .max()
+                .unwrap_or(0)
+        };
+        let start_len = len(&mut self.buckets.iter().map(|&(start, _)| start));
+        let end_len = len(&mut self.buckets.iter().map(|&(start, _)| end(start)));
+        let biggest = self
+            .buckets
+            .iter()
+            .map(|&(_, count)| count)
+            .max()
+            .unwrap_or(0);
+
+        let mut chart = String::new();
+        for &(start, count) in &self.buckets {
+            match self.bucket_width {
+                1 => write!(chart, "{start:>start_len$} |"),
+                _ => write!(chart, "{start:>start_len$}..{:<end_len$} |", end(start)),
+            }
+            .unwrap();
+
+            // Any bucket with words in it gets at least one #
+            let bar_len = match biggest {
+                0 => 0,
+                _ => (count as u128 * max_bar_len as u128).div_ceil(biggest as u128) as usize,
+            };
+            writeln!(chart, "{} {count}", "#".repeat(bar_len)).unwrap();
+        }
+
+        chart
+    }
+}
+
+impl RpegImage {
+    /// Counts the values of a named field of a layout across every word of the image, grouping
+    /// them into buckets of `bucket_width` values. This makes it easy to check that a field stays
+    /// in the range its quantizer should give (such as -15 to 15). See [`FieldHistogram`].
+    ///
+    /// Returns None if the layout doesn't have a field with that name.
+    ///
+    /// # Panics
+    ///
+    /// * If `bucket_width` is 0
+    pub fn histogram(
+        &self,
+        layout: &BitLayout,
+        name: &str,
+        bucket_width: u64,
+    ) -> Option<FieldHistogram> {
+        assert!(bucket_width > 0, "A bucket must hold at least one value");
+        let field = layout.get(name)?;
+
+        // Buckets are counted from the smallest value the field can hold
+        let min_value = match field.signed {
+            true => -(1i64 << (field.width - 1)),
+            false => 0,
+        };
+        let bucket = |value: i64| (value - min_value) as u64 / bucket_width;
+
+        let mut counts = BTreeMap::new();
+        for value in self.field_values(field) {
+            *counts.entry(bucket(value)).or_insert(0) += 1;
+        }
+
+        let buckets = match (counts.keys().next(), counts.keys().next_back()) {
+            (Some(&first), Some(&last)) => (first..=last)
+                .map(|bucket| {
+                    let start = min_value + (bucket * bucket_width) as i64;
+                    (start, counts.get(&bucket).copied().unwrap_or(0))
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Some(FieldHistogram {
+            bucket_width,
+            buckets,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_layout() {
+        let layout: BitLayout = "a:9,b:5s , pb:4".parse().unwrap();
+        let fields: Vec<_> = layout.fields().collect();
+        assert_eq!(
+            fields,
+            [
+                ("a", BitField::new(23, 9)),
+                ("b", BitField::new(18, 5).signed()),
+                ("pb", BitField::new(14, 4)),
+            ]
+        );
+        assert_eq!("".parse::<BitLayout>().unwrap(), BitLayout::new());
+
+        assert!("a:9, b".parse::<BitLayout>().is_err());
+        assert!("a:0".parse::<BitLayout>().is_err());
+        assert!("a:x".parse::<BitLayout>().is_err());
+        assert!("a:9, a:9".parse::<BitLayout>().is_err());
+        assert!("a:16, b:16, c:1".parse::<BitLayout>().is_err());
+        assert!("a:32".parse::<BitLayout>().is_ok());
+    }
+
+    #[test]
+    fn test_colored_bits() {
+        // A gap between the fields, and unused bits below them
+        let layout = BitLayout::new()
+            .field("x", BitField::new(28, 4))
+            .field("y", BitField::new(20, 4).signed());
+        let image = RpegImage::new(vec![[0x8F, 0xF0, 0x00, 0x01]; 11], 2, 2);
+        let bits = image.to_colored_bits(&layout);
+        let lines: Vec<&str> = bits.lines().collect();
+
+        assert_eq!(lines.len(), 12);
+        assert_eq!(
+            lines[11],
+            "10: \x1b[31m1000\x1b[0m 1111 \x1b[32m1111\x1b[0m 00000000000000000001  x=8 y=-1"
+        );
+        assert!(lines[1].starts_with(" 0: "));
+    }
+
+    #[test]
+    fn test_histogram() {
+        let layout: BitLayout = "a:4, b:4s".parse().unwrap();
+        let words = [0x00, 0x01, 0x01, 0x0F, 0x07, 0xF8]
+            .iter()
+            .map(|&byte| [byte, 0x00, 0x00, 0x00])
+            .collect();
+        let image = RpegImage::new(words, 6, 4);
+
+        let histogram = image.histogram(&layout, "b", 1).unwrap();
+        assert_eq!(histogram.buckets.len(), 7 + 8 + 1);
+        assert_eq!(histogram.buckets[0], (-8, 1));
+        assert_eq!(histogram.buckets[15], (7, 1));
+        let chart = histogram.to_bar_chart(4);
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(lines[0], "-8 |## 1");
+        assert_eq!(lines[1], "-7 | 0");
+        assert_eq!(lines[9], " 1 |#### 2");
+
+        let histogram = image.histogram(&layout, "a", 4).unwrap();
+        assert_eq!(histogram.buckets, [(0, 5), (4, 0), (8, 0), (12, 1)]);
+        assert!(image.histogram(&layout, "c", 1).is_none());
+        let empty = RpegImage::new(vec![], 0, 0)
+            .histogram(&layout, "a", 1)
+            .unwrap();
+        assert_eq!(empty.buckets, []);
+        assert_eq!(empty.to_bar_chart(10), "");
+    }
+}
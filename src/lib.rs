@@ -1,104 +1,289 @@
 //! # csc411_rpegio
 //!
 //! A collection functions to handle rpeg data i/o. Intended for use in URI's CSC 411 class.
+//!
+//! # Canonical Output
+//!
+//! Every function in this crate that writes rpeg data writes it in the same canonical form,
+//! byte for byte (described under [`canonicalize`], which converts other data to it). This makes
+//! it meaningful to `diff` a student's output against a reference file.
+//!
+//! # Redirecting Input and Output
+//!
+//! The functions which read from stdin when they aren't given a file, such as
+//! [`read_in_rpeg_data`] and [`RpegImage::read`], read from the file named by the `RPEG_INPUT`
+//! environment variable instead, if it's set. Likewise, the functions which write to stdout,
+//! such as [`output_rpeg_data`] and [`RpegImage::write`], write to the file named by
+//! `RPEG_OUTPUT` (which is created or replaced), if it's set. This lets a grading harness point
+//! a program at its input and output without changing its arguments. A file given explicitly
+//! is always used, whatever the variables say.
+//!
+//! # WebAssembly
+//!
+//! The crate builds for `wasm32-unknown-unknown`, for reading rpeg files in a browser. There, the
+//! functions which work on bytes in memory, such as [`RpegImage::from_bytes`],
+//! [`RpegImage::to_bytes`], [`RpegReader::new`] over a byte slice, and everything in [`codec`],
+//! work as usual. But there are no files, so reading or writing one is an error, stdin is
+//! always empty, and anything written to stdout is thrown away. There are no threads either, so
+//! [`spawn_rpeg_reader`] and the `parallel` feature can't be used. There's also no clock, so the
+//! `timing` feature and [`IoStats`] report every duration as zero.
+//!
+//! On WASI (`wasm32-wasip1` and `wasm32-wasip2`), stdin and stdout are the host's, and are read
+//! and written exactly as on other platforms, so a program using this crate runs the same way
+//! under a WASI runtime, as in `wasmtime student.wasm < image.rpeg > image.ppm`. Files can be
+//! read and written in the directories the runtime grants access to (such as with
+//! `wasmtime --dir .`). There are no threads on `wasm32-wasip1`.
+//!
+//! # Optional Features
+//!
+//! * `archive` - Adds `RpegArchive`, for reading the rpeg files in zip or tar archives (such as
+//!   a zip of submissions) without extracting them. Also enables `gzip`
+//! * `cbor` - Adds `RpegImage::to_cbor` and `RpegImage::from_cbor`, for converting images to
+//!   and from CBOR
+//! * `cli` - Builds the command-line tools: `rpeginfo`, which prints a summary of an rpeg file
+//!   and whether it's valid; `rpegdiff`, which compares two rpeg files word by word (and can
+//!   write a patch from one to the other); `rpegpatch`, which applies such a patch;
+//!   `rpegcat`, which converts rpeg data between the binary, text, and debug formats;
+//!   `rpeggen`, which generates test images; `rpegfmt`, which rewrites rpeg data in canonical
+//!   form; `rpegvalidate`, which checks rpeg data (again whenever it changes, with `--watch`)
+//!   and exits with a code for what's wrong; `rpeghexdump`, which dumps rpeg data in hex a word
+//!   at a time; `rpegstat`, which prints statistics of rpeg files' payloads;
+//!   `rpeg-passthru`, which copies rpeg data from stdin to stdout only if it's valid; and
+//!   `rpegview`, which draws a field of rpeg data in the terminal
+//! * `crypto` - Adds `RpegImage::to_encrypted` and `RpegImage::from_encrypted`, for encrypting
+//!   images with a passphrase, and `RpegImage::to_signed`, `ReadOptions::verify_key`, and
+//!   `WriteOptions::signing_key`, for signing rpeg data and checking its signature
+//! * `ffi` - Adds the `ffi` module, a C interface (`rpeg_read`, `rpeg_write`, and `rpeg_free`)
+//!   for reading and writing rpeg data from C programs, declared in `include/rpegio.h`
+//! * `fixtures` - Adds the `fixtures` module of known-good rpeg files built into the crate, for
+//!   tests and examples
+//! * `grading` - Adds the `grading` module, for grading rpeg output against a reference and
+//!   writing the results as Gradescope's autograder JSON. Also enables `json`
+//! * `gzip` - Adds `GzipWriter` and `RpegImage::write_gzip`, for writing gzip-compressed rpeg
+//!   data, makes `RpegImage::write_file` compress files whose names end in `.gz`, and makes the
+//!   readers decompress gzip-compressed input automatically
+//! * `image` - Adds `RpegImage::field_to_png`, for drawing a field of every word as a PNG
+//!   heatmap. Also enables `gzip`
+//! * `inspect` - Adds `RpegInspector`, an interactive line-based inspector for the words of an
+//!   image, and builds it into the `rpeg-inspect` binary
+//! * `json` - Adds `RpegImage::to_json` and `RpegImage::from_json`, for converting images to
+//!   and from JSON, and `RpegError::to_json`, for reporting errors to autograders as JSON
+//! * `npy` - Adds `RpegImage::to_npy` and `RpegImage::from_npy`, for converting the words of
+//!   images to and from NumPy `.npy` arrays
+//! * `parallel` - Adds `RpegImage::par_map_words` and `RpegImage::par_chunks`, for processing
+//!   the words of an image on several threads at once, and `validate_dir`, for validating a
+//!   directory of rpeg files on several threads at once
+//! * `std` - On by default, and turned on by every other feature. Everything but the `codec`
+//!   module (and the format version constants) needs it. Without it, the crate is `no_std`,
+//!   needing only `alloc`, for decoding rpeg data on embedded systems
+//! * `timing` - Adds `last_timings`, which reports how long each stage of reading and writing
+//!   rpeg data took
 
-use std::iter::Peekable;
-
-fn expect(
-    expected_bytes: &[u8],
-    peekable_bytes_iter: &mut Peekable<impl Iterator<Item = u8>>,
-) -> Result<(), String> {
-    for expected_byte in expected_bytes {
-        match &peekable_bytes_iter.next() {
-            Some(byte) => {
-                if byte != expected_byte {
-                    return Err(format!(
-                        "Expected 0x{expected_byte:02X}, found 0x{byte:02X}"
-                    ));
-                }
-            }
-            None => {
-                return Err(format!(
-                    "Ran out of bytes before expected 0x{expected_byte:02X} byte"
-                ));
-            }
-        }
-    }
-    Ok(())
-}
-
-fn expect_newline(
-    peekable_bytes_iter: &mut Peekable<impl Iterator<Item = u8>>,
-) -> Result<(), String> {
-    match peekable_bytes_iter.next() {
-        // \n - Mostly Unix
-        Some(0x0A) => Ok(()),
-        // \r[\n] - Mostly Windows
-        Some(0x0D) => {
-            // Check for a \n after the \r, consuming it if it exists
-            if peekable_bytes_iter.peek() == Some(&0x0A) {
-                peekable_bytes_iter.next();
-            }
-
-            Ok(())
-        }
-        Some(byte) => Err(format!("Expected newline byte(s), found 0x{byte:02X}")),
-        None => Err("Ran out of bytes before expected newline byte(s)".to_string()),
-    }
-}
-
-fn is_ascii_digit(byte: u8) -> bool {
-    (b'0'..=b'9').contains(&byte)
-}
-
-fn parse_ascii_digit(digit: u8) -> Result<u32, String> {
-    if !is_ascii_digit(digit) {
-        Err(format!("Attempted to parse non-ascii digit {digit:?}"))
-    } else {
-        Ok((digit - b'0') as u32)
-    }
-}
-
-fn read_u32(peekable_bytes_iter: &mut Peekable<impl Iterator<Item = u8>>) -> Result<u32, String> {
-    // Read initial digit (there ought to be at least one)
-    let mut next_byte = match peekable_bytes_iter.peek() {
-        Some(&byte) => byte,
-        None => return Err("Didn't find a number where a number was expected in input".to_string()),
-    };
-
-    let mut num = parse_ascii_digit(next_byte)?;
-    peekable_bytes_iter.next();
+#![cfg_attr(not(feature = "std"), no_std)]
 
-    // Read any additional digits in the number
-    while peekable_bytes_iter.peek().is_some()
-        && is_ascii_digit(*peekable_bytes_iter.peek().unwrap())
-    {
-        next_byte = peekable_bytes_iter.next().unwrap();
-        let digit = parse_ascii_digit(next_byte).unwrap();
+extern crate alloc;
 
-        num = num
-            .checked_mul(10)
-            .and_then(|num| num.checked_add(digit))
-            .ok_or("Integer overflow while parsing u32".to_string())?;
-    }
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(feature = "std")]
+mod armor;
+#[cfg(feature = "std")]
+mod assert;
+#[cfg(feature = "std")]
+mod base64;
+#[cfg(feature = "std")]
+mod bitfield;
+#[cfg(feature = "std")]
+mod canonical;
+#[cfg(feature = "cbor")]
+mod cbor;
+#[cfg(feature = "crypto")]
+mod chacha20poly1305;
+mod checksum;
+#[cfg(feature = "std")]
+pub mod cli;
+pub mod codec;
+#[cfg(feature = "std")]
+mod compare;
+#[cfg(feature = "std")]
+mod compression;
+#[cfg(feature = "std")]
+mod concat;
+#[cfg(feature = "std")]
+pub mod corrupt;
+#[cfg(feature = "std")]
+mod csv;
+#[cfg(feature = "gzip")]
+mod deflate;
+#[cfg(feature = "std")]
+mod duplicates;
+#[cfg(feature = "std")]
+mod embed;
+#[cfg(feature = "crypto")]
+mod encryption;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+#[cfg(feature = "std")]
+mod format;
+#[cfg(feature = "std")]
+mod fuzz;
+#[cfg(feature = "std")]
+mod generate;
+#[cfg(feature = "std")]
+pub mod golden;
+#[cfg(feature = "grading")]
+pub mod grading;
+#[cfg(feature = "gzip")]
+mod gzip;
+#[cfg(feature = "std")]
+mod hash;
+#[cfg(feature = "std")]
+mod header;
+#[cfg(feature = "std")]
+mod hexdump;
+#[cfg(feature = "std")]
+mod image;
+#[cfg(feature = "inspect")]
+mod inspect;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "std")]
+mod layout;
+#[cfg(feature = "npy")]
+mod npy;
+#[cfg(feature = "std")]
+mod options;
+#[cfg(feature = "parallel")]
+mod parallel;
+#[cfg(feature = "std")]
+mod passthrough;
+#[cfg(feature = "std")]
+mod patch;
+#[cfg(feature = "image")]
+mod png;
+#[cfg(feature = "std")]
+mod ppm;
+#[cfg(feature = "std")]
+mod roundtrip;
+#[cfg(feature = "std")]
+mod selftest;
+#[cfg(feature = "std")]
+mod sequence;
+#[cfg(feature = "std")]
+mod sha256;
+#[cfg(feature = "crypto")]
+mod signature;
+#[cfg(feature = "std")]
+mod similarity;
+#[cfg(feature = "std")]
+mod snapshot;
+#[cfg(feature = "std")]
+mod source;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
+mod stdio;
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "std")]
+mod text;
+#[cfg(feature = "std")]
+mod threaded;
+#[cfg(feature = "std")]
+mod timing;
+#[cfg(feature = "std")]
+mod trailer;
 
-    Ok(num)
-}
+#[cfg(feature = "archive")]
+pub use archive::RpegArchive;
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub use assert::{__assert_rpeg_eq, RpegOperand};
+#[cfg(feature = "std")]
+pub use bitfield::{BitField, FieldStats, HeatmapStyle};
+#[cfg(feature = "std")]
+pub use canonical::canonicalize;
+pub use codec::{DEFAULT_FORMAT_VERSION, SUPPORTED_FORMAT_VERSIONS};
+#[cfg(feature = "std")]
+pub use compare::{compare_rpeg, render_diff, RpegDiff, WordMismatch};
+#[cfg(feature = "std")]
+pub use concat::RpegConcatReader;
+#[cfg(feature = "std")]
+pub use duplicates::{DuplicateFinder, DuplicateGroup, DuplicateReport};
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub use embed::__is_rpeg_data;
+#[cfg(feature = "std")]
+pub use error::RpegError;
+#[cfg(feature = "std")]
+pub use format::{Format1, Format2, RpegFormat};
+#[cfg(feature = "std")]
+pub use fuzz::fuzz_parse;
+#[cfg(feature = "std")]
+pub use generate::generate_rpeg;
+#[cfg(feature = "gzip")]
+pub use gzip::GzipWriter;
+#[cfg(feature = "std")]
+pub use hash::ContentHash;
+#[cfg(feature = "std")]
+pub use header::RpegHeader;
+#[cfg(feature = "std")]
+pub use hexdump::{hexdump, parse_hexdump};
+#[cfg(feature = "std")]
+pub use image::RpegImage;
+#[cfg(feature = "inspect")]
+pub use inspect::{InspectView, RpegInspector};
+#[cfg(feature = "std")]
+pub use layout::{BitLayout, FieldHistogram};
+#[cfg(feature = "std")]
+pub use options::{DebugFormatOptions, ReadOptions, WriteOptions, DEFAULT_BUFFER_CAPACITY};
+#[cfg(feature = "parallel")]
+pub use parallel::{validate_dir, DirReport, FileReport};
+#[cfg(feature = "std")]
+pub use passthrough::passthrough;
+#[cfg(feature = "std")]
+pub use patch::{apply, diff, RpegPatch};
+#[cfg(feature = "std")]
+pub use ppm::{read_ppm, write_ppm, PpmImage};
+#[cfg(feature = "std")]
+pub use roundtrip::assert_roundtrip;
+#[cfg(feature = "std")]
+pub use selftest::{selftest, SelfTestCheck, SelfTestReport};
+#[cfg(feature = "std")]
+pub use sequence::{RpegSequenceReader, RpegSequenceWriter};
+#[cfg(feature = "std")]
+pub use similarity::{similarity, FieldSimilarity, Similarity};
+#[cfg(feature = "std")]
+pub use source::{RpegSink, RpegSource};
+#[cfg(feature = "std")]
+pub use stats::{CompressionReport, PayloadStats};
+#[cfg(feature = "std")]
+pub use stream::{stream_in_rpeg_data, stream_out_rpeg_data, IoStats, RpegReader, RpegWriter};
+#[cfg(feature = "std")]
+pub use text::TextStyle;
+#[cfg(feature = "std")]
+pub use threaded::{spawn_rpeg_reader, RpegReaderThread};
+#[cfg(feature = "timing")]
+pub use timing::{last_timings, Timings};
 
+#[cfg(feature = "std")]
 fn read_raw_bytes(file_path: Option<&str>) -> Result<Vec<u8>, std::io::Error> {
-    let mut raw_reader: Box<dyn std::io::BufRead> = match file_path {
-        Some(file_path) => Box::new(std::io::BufReader::new(std::fs::File::open(file_path)?)),
-        None => Box::new(std::io::BufReader::new(std::io::stdin())),
-    };
-
-    // read the entire contents into a buffer
-    let mut buffer = Vec::new();
-    raw_reader.read_to_end(&mut buffer)?;
-
-    Ok(buffer)
+    file_path.read_bytes()
 }
 
-/// Reads and parses rpeg data from either stdin or a file.
+/// Reads and parses rpeg data from either stdin or a file. Any of the
+/// [`SUPPORTED_FORMAT_VERSIONS`] is accepted (see [`Format1`] for the legacy format 1); use
+/// [`RpegImage::read_with_options`] to find out which version was read, or to restrict which
+/// versions are accepted.
+///
+/// Input is always read in binary mode. In particular, on Windows, payload bytes read from a
+/// redirected stdin are never subject to CRLF translation, and a `0x1A` (Ctrl-Z) byte is not
+/// treated as the end of the input.
+///
 /// Returns a Result<tuple, String> where the tuple contains, in order:
 /// 1. A `Vec<[u8; 4]>` (Vector of four-byte arrays) representing the raw image data
 /// 2. A `u32` representing the width of the image
@@ -125,53 +310,86 @@ fn read_raw_bytes(file_path: Option<&str>) -> Result<Vec<u8>, std::io::Error> {
 /// // Do something with raw_bytes
 /// // you will likely first want to convert the four-byte arrays to u32s
 /// ```
+#[cfg(feature = "std")]
 pub fn read_in_rpeg_data(file_path: Option<&str>) -> Result<(Vec<[u8; 4]>, u32, u32), String> {
-    // Read stdin as bytes
-    let bytes = read_raw_bytes(file_path)
+    read_in_rpeg_data_from(file_path)
+}
+
+/// Like [`read_in_rpeg_data`], but reads from any [`RpegSource`], such as bytes in memory in a
+/// test.
+///
+/// # Errors Returned
+///
+/// * If there is an unexpected error reading from the source
+/// * If the rpeg data header is badly formatted
+/// * If the number of raw bytes following the header is not a multiple of 4
+///
+/// # Arguments
+///
+/// * `source` - Where to read from
+///
+/// # Examples
+/// ```
+/// let input = b"Compressed image format 2\n2 1\n\x00\x11\x22\x33\x44\x55\x66\x77".to_vec();
+/// let (raw_bytes, width, height) = csc411_rpegio::read_in_rpeg_data_from(input).unwrap();
+///
+/// assert_eq!((width, height), (2, 1));
+/// assert_eq!(raw_bytes, vec![[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]]);
+/// ```
+#[cfg(feature = "std")]
+pub fn read_in_rpeg_data_from(source: impl RpegSource) -> Result<(Vec<[u8; 4]>, u32, u32), String> {
+    let bytes = timing::timed(timing::Stage::PayloadRead, || source.read_bytes())
         .map_err(|_| "Error reading raw bytes from the input".to_string())?;
-    let mut peekable_bytes_iter = bytes.into_iter().peekable();
 
-    // Read "Compressed image format 2\n" part of header
-    expect(b"Compressed image format 2", &mut peekable_bytes_iter)?;
-    expect_newline(&mut peekable_bytes_iter)?;
+    parse_rpeg_data(bytes)
+}
 
-    // Read "{width} {height}\n" part of header
-    let width = read_u32(&mut peekable_bytes_iter)?;
-    expect(b" ", &mut peekable_bytes_iter)?;
-    let height = read_u32(&mut peekable_bytes_iter)?;
-    expect_newline(&mut peekable_bytes_iter)?;
+#[cfg(feature = "std")]
+fn parse_rpeg_data(bytes: Vec<u8>) -> Result<(Vec<[u8; 4]>, u32, u32), String> {
+    let image = RpegImage::from_bytes(&bytes).map_err(|err| err.to_string())?;
 
-    // Collect the rest of the bytes (after the header) as a vector of u8s
-    let raw_bytes: Vec<u8> = peekable_bytes_iter.collect();
+    Ok((image.words, image.header.width, image.header.height))
+}
 
-    // Group the bytes in groups of 4
-    if raw_bytes.len() % 4 != 0 {
-        return Err(format!(
-            "The number of raw bytes ({}) was not a multiple of four",
-            raw_bytes.len()
-        ));
-    }
+#[cfg(feature = "std")]
+fn write_rpeg_data(
+    writer: &mut impl std::io::Write,
+    raw_bytes: &[[u8; 4]],
+    width: u32,
+    height: u32,
+) -> std::io::Result<()> {
+    header::write_header(writer, &RpegHeader::new(width, height))?;
 
-    let grouped_bytes: Vec<[u8; 4]> = raw_bytes
-        .chunks_exact(4)
-        .map(|x| x.try_into().unwrap())
-        .collect();
+    for bytes in raw_bytes {
+        writer.write_all(bytes)?;
+    }
 
-    Ok((grouped_bytes, width, height))
+    writer.flush()
 }
 
 /// Outputs rpeg data to stdout.
 ///
+/// The header is always written with `\n` (LF) line endings and the raw bytes are written
+/// exactly as given, on every platform. In particular, on Windows the output is written in
+/// binary mode, so payload bytes that happen to be `0x0A` are never translated to `\r\n`. Note
+/// that some shells (such as Windows PowerShell 5) re-encode anything redirected with `>` as
+/// text; use `cmd` or a newer PowerShell to redirect rpeg data into a file.
+///
+/// If stdout is closed early (for example, because the output was piped into `head` or into a
+/// decompressor that crashed), this function exits the process quietly with status 141 (the
+/// status a shell reports for a process killed by `SIGPIPE`) rather than panicking. Use
+/// [`try_output_rpeg_data`] to handle that case yourself instead.
+///
 /// # Arguments
 ///
 /// * `raw_bytes` - A slice of four-byte arrays, each array representing a single word of
-///    compressed image data
+///   compressed image data
 /// * `width` - The width of the image
 /// * `height` - The height of the image
 ///
 /// # Panics
 ///
-/// * If something goes wrong writing raw bytes to stdout
+/// * If something other than a closed pipe goes wrong writing raw bytes to stdout
 ///
 /// # Examples
 /// ```
@@ -184,26 +402,105 @@ pub fn read_in_rpeg_data(file_path: Option<&str>) -> Result<(Vec<[u8; 4]>, u32,
 /// // Output the rpeg data to stdout
 /// csc411_rpegio::output_rpeg_data(&raw_bytes, width, height);
 /// ```
+#[cfg(feature = "std")]
 pub fn output_rpeg_data(raw_bytes: &[[u8; 4]], width: u32, height: u32) {
-    use std::io::Write;
-
-    println!("Compressed image format 2");
-    println!("{width} {height}");
+    match try_output_rpeg_data(raw_bytes, width, height) {
+        Ok(()) => {}
+        Err(RpegError::BrokenPipe) => std::process::exit(141),
+        Err(err) => panic!("Failed to write raw bytes to stdout: {err}"),
+    }
+}
 
-    for bytes in raw_bytes {
-        std::io::stdout()
-            .write_all(bytes)
-            .expect("Failed to write raw bytes to stdout");
+/// Outputs rpeg data to stdout, returning an error instead of panicking or exiting if something
+/// goes wrong.
+///
+/// # Errors Returned
+///
+/// * [`RpegError::BrokenPipe`] if stdout is closed before all of the data is written, for
+///   example because the output was piped into `head`
+/// * [`RpegError::Io`] if anything else goes wrong writing to stdout
+///
+/// # Arguments
+///
+/// * `raw_bytes` - A slice of four-byte arrays, each array representing a single word of
+///   compressed image data
+/// * `width` - The width of the image
+/// * `height` - The height of the image
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::RpegError;
+///
+/// let raw_bytes: Vec<[u8; 4]> = vec![[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]];
+///
+/// match csc411_rpegio::try_output_rpeg_data(&raw_bytes, 2, 1) {
+///     Ok(()) => {}
+///     // Whoever was reading our output has stopped listening, so just stop quietly
+///     Err(RpegError::BrokenPipe) => std::process::exit(0),
+///     Err(err) => panic!("{err}"),
+/// }
+/// ```
+#[cfg(feature = "std")]
+pub fn try_output_rpeg_data(
+    raw_bytes: &[[u8; 4]],
+    width: u32,
+    height: u32,
+) -> Result<(), RpegError> {
+    match stdio::output_path() {
+        Some(path) => try_output_rpeg_data_to(&mut path.as_str(), raw_bytes, width, height),
+        None => try_output_rpeg_data_to(&mut std::io::stdout(), raw_bytes, width, height),
     }
 }
 
+/// Like [`try_output_rpeg_data`], but writes to any [`RpegSink`], such as a `Vec<u8>` in a
+/// test.
+///
+/// # Errors Returned
+///
+/// * [`RpegError::BrokenPipe`] if the sink is a pipe which is closed before all of the data is
+///   written
+/// * [`RpegError::Io`] if anything else goes wrong writing to the sink
+///
+/// # Arguments
+///
+/// * `sink` - Where to write the rpeg data
+/// * `raw_bytes` - A slice of four-byte arrays, each array representing a single word of
+///   compressed image data
+/// * `width` - The width of the image
+/// * `height` - The height of the image
+///
+/// # Examples
+/// ```
+/// let mut output = Vec::new();
+/// csc411_rpegio::try_output_rpeg_data_to(&mut output, &[[0x00, 0x11, 0x22, 0x33]], 2, 2)
+///     .unwrap();
+///
+/// assert_eq!(output, b"Compressed image format 2\n2 2\n\x00\x11\x22\x33");
+/// ```
+#[cfg(feature = "std")]
+pub fn try_output_rpeg_data_to(
+    sink: &mut impl RpegSink,
+    raw_bytes: &[[u8; 4]],
+    width: u32,
+    height: u32,
+) -> Result<(), RpegError> {
+    timing::timed(timing::Stage::Output, || {
+        write_rpeg_data(&mut sink.writer()?, raw_bytes, width, height)
+    })?;
+
+    Ok(())
+}
+
 /// Outputs rpeg data to stdout in a human-readable form. This should NOT be used outside of
-/// debugging and testing.
+/// debugging and testing. The output can be read back with [`read_in_debug_rpeg_data`].
+///
+/// If stdout is closed early, this function exits the process quietly with status 141, just like
+/// [`output_rpeg_data`].
 ///
 /// # Arguments
 ///
 /// * `raw_bytes` - A slice of four-byte arrays, each array representing a single word of
-///    compressed image data
+///   compressed image data
 /// * `width` - The width of the image
 /// * `height` - The height of the image
 ///
@@ -223,29 +520,470 @@ pub fn output_rpeg_data(raw_bytes: &[[u8; 4]], width: u32, height: u32) {
 /// // 2 1
 /// // 00 11 22 33 44 55 66 77
 /// ```
+#[cfg(feature = "std")]
 pub fn debug_output_rpeg_data(raw_bytes: &[[u8; 4]], width: u32, height: u32) {
-    println!("Compressed image format 2 [DEBUG]");
-    println!("{width} {height}");
+    debug_output_rpeg_data_with_options(raw_bytes, width, height, &DebugFormatOptions::default());
+}
+
+/// Like [`debug_output_rpeg_data`], but with the payload laid out as the options say. See
+/// [`DebugFormatOptions`].
+///
+/// # Arguments
+///
+/// * `raw_bytes` - A slice of four-byte arrays, each array representing a single word of
+///   compressed image data
+/// * `width` - The width of the image
+/// * `height` - The height of the image
+/// * `options` - How to lay out the payload
+#[cfg(feature = "std")]
+pub fn debug_output_rpeg_data_with_options(
+    raw_bytes: &[[u8; 4]],
+    width: u32,
+    height: u32,
+    options: &DebugFormatOptions,
+) {
+    let written = stdio::output()
+        .map_err(RpegError::from)
+        .and_then(|mut output| write_rpeg_debug(&mut output, raw_bytes, width, height, options));
+    match written {
+        Ok(()) => {}
+        Err(RpegError::BrokenPipe) => std::process::exit(141),
+        Err(err) => panic!("Failed to write debug output to stdout: {err}"),
+    }
+}
+
+/// Writes rpeg data in the human-readable form of [`debug_output_rpeg_data`] to any
+/// destination, such as a log file.
+///
+/// # Errors Returned
+///
+/// * [`RpegError::BrokenPipe`] if `writer` is a pipe which is closed before all of the output
+///   is written
+/// * [`RpegError::Io`] if anything else goes wrong writing to `writer`
+///
+/// # Arguments
+///
+/// * `writer` - Where to write the output
+/// * `raw_bytes` - A slice of four-byte arrays, each array representing a single word of
+///   compressed image data
+/// * `width` - The width of the image
+/// * `height` - The height of the image
+/// * `options` - How to lay out the payload
+#[cfg(feature = "std")]
+pub fn write_rpeg_debug(
+    writer: &mut impl std::io::Write,
+    raw_bytes: &[[u8; 4]],
+    width: u32,
+    height: u32,
+    options: &DebugFormatOptions,
+) -> Result<(), RpegError> {
+    let debug_output = format_rpeg_debug(raw_bytes, width, height, options);
 
-    let mut first = true;
+    // Emit everything in one go, rather than formatting once per byte
+    writer.write_all(debug_output.as_bytes())?;
+    writer.flush()?;
 
-    for bytes in raw_bytes {
-        for byte in bytes {
-            if first {
-                first = false;
-            } else {
-                print!(" ");
+    Ok(())
+}
+
+/// Reads and parses the human-readable output of [`debug_output_rpeg_data`] from either stdin or
+/// a file, giving back exactly the data that was output. This makes it possible to keep test
+/// fixtures in a readable form, while still testing code that works with the binary data.
+///
+/// The bytes of the payload may be separated by any whitespace (including line breaks), or run
+/// together (such as a whole word at a time, as [`DebugFormatOptions::group_words`] writes
+/// them), and their hexadecimal digits may be in either case. The row labels and column header
+/// written by [`DebugFormatOptions::annotate`] are skipped, as is anything else after a `#` on
+/// a line of the payload.
+///
+/// Returns a Result<tuple, String> where the tuple contains, in order:
+/// 1. A `Vec<[u8; 4]>` (Vector of four-byte arrays) representing the raw image data
+/// 2. A `u32` representing the width of the image
+/// 3. A `u32` representing the height of the image
+///
+/// # Errors Returned
+///
+/// * If there is an unexpected error reading from the provided file or stdin
+/// * If the header is badly formatted, or its first line doesn't end in ` [DEBUG]`
+/// * If anything in the payload isn't made of two-digit hexadecimal bytes
+/// * If the number of bytes in the payload is not a multiple of 4
+///
+/// # Arguments
+///
+/// * `file_path` - An optional file path to read from. If None, stdin will be read from instead
+///
+/// # Examples
+/// ```no_run
+/// let (raw_bytes, width, height) =
+///     csc411_rpegio::read_in_debug_rpeg_data(Some("tests/fixtures/small.debug")).unwrap();
+///
+/// assert_eq!((width, height), (2, 1));
+/// assert_eq!(raw_bytes, vec![[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]]);
+/// ```
+#[cfg(feature = "std")]
+pub fn read_in_debug_rpeg_data(
+    file_path: Option<&str>,
+) -> Result<(Vec<[u8; 4]>, u32, u32), String> {
+    read_in_debug_rpeg_data_from(file_path)
+}
+
+/// Like [`read_in_debug_rpeg_data`], but reads from any [`RpegSource`], such as the output of
+/// [`write_rpeg_debug`] in a test.
+///
+/// # Errors Returned
+///
+/// * If there is an unexpected error reading from the source
+/// * If the header is badly formatted, or its first line doesn't end in ` [DEBUG]`
+/// * If anything in the payload isn't made of two-digit hexadecimal bytes
+/// * If the number of bytes in the payload is not a multiple of 4
+///
+/// # Arguments
+///
+/// * `source` - Where to read from
+#[cfg(feature = "std")]
+pub fn read_in_debug_rpeg_data_from(
+    source: impl RpegSource,
+) -> Result<(Vec<[u8; 4]>, u32, u32), String> {
+    let bytes = source
+        .read_bytes()
+        .map_err(|_| "Error reading raw bytes from the input".to_string())?;
+
+    parse_debug_rpeg_data(&bytes)
+}
+
+#[cfg(feature = "std")]
+fn parse_debug_rpeg_data(bytes: &[u8]) -> Result<(Vec<[u8; 4]>, u32, u32), String> {
+    let mut byte_reader = header::ByteReader::new(bytes);
+    let header =
+        header::read_header_with_suffix(&mut byte_reader, &ReadOptions::default(), " [DEBUG]")
+            .map_err(|err| err.to_string())?;
+
+    // Row labels ("12:") and anything after a "#" (such as column labels) are annotations
+    let lines = bytes[byte_reader.position() as usize..]
+        .split(|&byte| byte == b'\n' || byte == b'\r')
+        .map(|line| line.split(|&byte| byte == b'#').next().unwrap());
+    let tokens = lines.flat_map(|line| line.split(u8::is_ascii_whitespace));
+    let is_label = |token: &[u8]| {
+        token
+            .strip_suffix(b":")
+            .is_some_and(|label| !label.is_empty() && label.iter().all(u8::is_ascii_digit))
+    };
+
+    let mut payload = Vec::new();
+    for token in tokens {
+        if token.is_empty() || is_label(token) {
+            continue;
+        }
+
+        if !token.len().is_multiple_of(2) {
+            return Err(format!(
+                "Expected hexadecimal bytes, found {:?}",
+                String::from_utf8_lossy(token)
+            ));
+        }
+
+        for digits in token.chunks_exact(2) {
+            let byte = std::str::from_utf8(digits)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| {
+                    format!(
+                        "Expected hexadecimal bytes, found {:?}",
+                        String::from_utf8_lossy(token)
+                    )
+                })?;
+            payload.push(byte);
+        }
+    }
+
+    if !payload.len().is_multiple_of(4) {
+        return Err(format!(
+            "The number of raw bytes ({}) was not a multiple of four",
+            payload.len()
+        ));
+    }
+
+    let words = payload
+        .chunks_exact(4)
+        .map(|word| word.try_into().unwrap())
+        .collect();
+
+    Ok((words, header.width, header.height))
+}
+
+/// Formats rpeg data in the human-readable form of [`debug_output_rpeg_data`], such as for
+/// comparing against in a test.
+///
+/// # Arguments
+///
+/// * `raw_bytes` - A slice of four-byte arrays, each array representing a single word of
+///   compressed image data
+/// * `width` - The width of the image
+/// * `height` - The height of the image
+/// * `options` - How to lay out the payload
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::DebugFormatOptions;
+///
+/// let raw_bytes = [[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]];
+/// let debug_output = csc411_rpegio::format_rpeg_debug(&raw_bytes, 4, 2, &DebugFormatOptions::new());
+///
+/// assert_eq!(debug_output, "Compressed image format 2 [DEBUG]\n4 2\n00 11 22 33 44 55 66 77");
+/// ```
+#[cfg(feature = "std")]
+pub fn format_rpeg_debug(
+    raw_bytes: &[[u8; 4]],
+    width: u32,
+    height: u32,
+    options: &DebugFormatOptions,
+) -> String {
+    use std::fmt::Write;
+
+    let hex_digits = match options.lowercase {
+        true => b"0123456789abcdef",
+        false => b"0123456789ABCDEF",
+    };
+    let group_by_row = options.group_by_row || options.annotate;
+    let row_len = (width as usize / 2).max(1);
+
+    // Each line is some of the words of a row of blocks (or of all of the words), and whether
+    // it's the first of the row
+    let rows = match group_by_row {
+        true => raw_bytes.chunks(row_len).collect(),
+        false => vec![raw_bytes],
+    };
+    let lines: Vec<(usize, bool, &[[u8; 4]])> = rows
+        .iter()
+        .enumerate()
+        .flat_map(|(row, words)| {
+            let line_len = match options.words_per_line {
+                0 => words.len().max(1),
+                words_per_line => words_per_line,
+            };
+            words
+                .chunks(line_len)
+                .enumerate()
+                .map(move |(index, line)| (row, index == 0, line))
+        })
+        .collect();
+
+    let mut debug_output = String::with_capacity(64 + raw_bytes.len() * 12);
+    writeln!(debug_output, "Compressed image format 2 [DEBUG]").unwrap();
+    writeln!(debug_output, "{width} {height}").unwrap();
+
+    // Annotations are lined up with the words, so labels are all as wide as the biggest
+    let word_len = if options.group_words { 8 } else { 11 };
+    let label_len = rows.len().saturating_sub(1).to_string().len();
+    if options.annotate && options.column_header {
+        let columns = lines.first().map_or(0, |(_, _, line)| line.len());
+        let labels: Vec<String> = (0..columns)
+            .map(|column| format!("{column:<word_len$}"))
+            .collect();
+        let header = format!("#{:label_len$} {}", "", labels.join(" "));
+        writeln!(debug_output, "{}", header.trim_end()).unwrap();
+    }
+
+    for (index, (row, first, line)) in lines.iter().enumerate() {
+        if index != 0 {
+            debug_output.push('\n');
+        }
+        if options.annotate {
+            match first {
+                true => write!(debug_output, "{row:>label_len$}: ").unwrap(),
+                false => write!(debug_output, "{:label_len$}  ", "").unwrap(),
+            }
+        }
+
+        for (position, word) in line.iter().enumerate() {
+            if position != 0 {
+                debug_output.push(' ');
             }
 
-            print!("{byte:02X}");
+            for (position, byte) in word.iter().enumerate() {
+                if position != 0 && !options.group_words {
+                    debug_output.push(' ');
+                }
+
+                debug_output.push(hex_digits[(byte >> 4) as usize] as char);
+                debug_output.push(hex_digits[(byte & 0xF) as usize] as char);
+            }
         }
     }
+
+    if options.trailing_newline && !raw_bytes.is_empty() {
+        debug_output.push('\n');
+    }
+
+    debug_output
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
+    use super::*;
+
+    struct ClosedPipe;
+
+    impl std::io::Write for ClosedPipe {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_rpeg_data() {
+        let mut output = Vec::new();
+        write_rpeg_data(&mut output, &[[0x00, 0x11, 0x22, 0x33]], 2, 2).unwrap();
+
+        assert_eq!(output, b"Compressed image format 2\n2 2\n\x00\x11\x22\x33");
+    }
+
+    #[test]
+    fn test_write_rpeg_data_is_binary_safe() {
+        let mut output = Vec::new();
+        write_rpeg_data(&mut output, &[[0x0A, 0x0D, 0x0A, 0x1A]], 2, 2).unwrap();
+
+        assert_eq!(output, b"Compressed image format 2\n2 2\n\x0A\x0D\x0A\x1A");
+    }
+
+    #[test]
+    fn test_parse_rpeg_data_is_binary_safe() {
+        let bytes = b"Compressed image format 2\n2 4\n\x0D\x0A\x1A\x00\x1A\x0D\x0A\x0A".to_vec();
+
+        assert_eq!(
+            parse_rpeg_data(bytes),
+            Ok((
+                vec![[0x0D, 0x0A, 0x1A, 0x00], [0x1A, 0x0D, 0x0A, 0x0A]],
+                2,
+                4
+            ))
+        );
+    }
+
+    #[test]
+    fn test_format_rpeg_debug() {
+        let raw_bytes = [[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0xAB, 0xFF]];
+
+        assert_eq!(
+            format_rpeg_debug(&raw_bytes, 2, 1, &DebugFormatOptions::default()),
+            "Compressed image format 2 [DEBUG]\n2 1\n00 11 22 33 44 55 AB FF"
+        );
+        assert_eq!(
+            format_rpeg_debug(&[], 0, 0, &DebugFormatOptions::default()),
+            "Compressed image format 2 [DEBUG]\n0 0\n"
+        );
+
+        // Rows of three words, wrapped after two words
+        let raw_bytes = [
+            [0xAB; 4], [0x01; 4], [0x02; 4], [0x03; 4], [0x04; 4], [0x05; 4],
+        ];
+        let options = DebugFormatOptions::new()
+            .words_per_line(2)
+            .group_by_row(true)
+            .group_words(true)
+            .lowercase(true)
+            .trailing_newline(true);
+        assert_eq!(
+            format_rpeg_debug(&raw_bytes, 6, 4, &options),
+            "Compressed image format 2 [DEBUG]\n6 4\nabababab 01010101\n02020202\n\
+             03030303 04040404\n05050505\n"
+        );
+        let options = DebugFormatOptions::new().words_per_line(4);
+        assert_eq!(
+            format_rpeg_debug(&raw_bytes[..2], 6, 4, &options),
+            "Compressed image format 2 [DEBUG]\n6 4\nAB AB AB AB 01 01 01 01"
+        );
+
+        let raw_bytes = [[0x00; 4]; 22];
+        let options = DebugFormatOptions::new()
+            .group_words(true)
+            .annotate(true)
+            .column_header(true);
+        let debug_output = format_rpeg_debug(&raw_bytes, 4, 22, &options);
+        let lines: Vec<&str> = debug_output.lines().collect();
+        assert_eq!(lines[2], "#   0        1");
+        assert_eq!(lines[3], " 0: 00000000 00000000");
+        assert_eq!(lines[13], "10: 00000000 00000000");
+    }
+
+    #[test]
+    fn test_parse_debug_rpeg_data() {
+        let raw_bytes = vec![[0x00, 0x0A, 0x0D, 0x1A], [0xFF, 0xEE, 0xDD, 0xCC]];
+        let options = DebugFormatOptions::new()
+            .group_words(true)
+            .annotate(true)
+            .column_header(true);
+        let debug_output = format_rpeg_debug(&raw_bytes, 2, 1, &options);
+
+        assert_eq!(
+            parse_debug_rpeg_data(debug_output.as_bytes()).unwrap(),
+            (raw_bytes, 2, 1)
+        );
+
+        let input = b"Compressed image format 2 [DEBUG]\r\n2 1\r\n00 0a\n0d 1A  \n";
+        assert_eq!(
+            parse_debug_rpeg_data(input).unwrap(),
+            (vec![[0x00, 0x0A, 0x0D, 0x1A]], 2, 1)
+        );
+
+        assert!(parse_debug_rpeg_data(b"Compressed image format 2\n2 1\n00 11 22 33").is_err());
+        assert!(
+            parse_debug_rpeg_data(b"Compressed image format 2 [DEBUG]\n2 1\n00 11 22").is_err()
+        );
+        assert!(
+            parse_debug_rpeg_data(b"Compressed image format 2 [DEBUG]\n2 1\n00 11 2 33").is_err()
+        );
+    }
+
+    #[test]
+    fn test_broken_pipe_is_not_an_io_error() {
+        let err: RpegError = write_rpeg_data(&mut ClosedPipe, &[[0; 4]], 2, 2)
+            .unwrap_err()
+            .into();
+
+        assert!(matches!(err, RpegError::BrokenPipe));
+
+        let options = DebugFormatOptions::default();
+        let err = write_rpeg_debug(&mut ClosedPipe, &[[0; 4]], 2, 2, &options).unwrap_err();
+        assert!(matches!(err, RpegError::BrokenPipe));
+    }
+
+    #[test]
+    fn test_write_rpeg_debug() {
+        let mut output = Vec::new();
+        let options = DebugFormatOptions::new().trailing_newline(true);
+        write_rpeg_debug(&mut output, &[[0x0A, 0x0D, 0x1A, 0xFF]], 2, 2, &options).unwrap();
+
+        assert_eq!(
+            output,
+            b"Compressed image format 2 [DEBUG]\n2 2\n0A 0D 1A FF\n"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_in_memory() {
+        let raw_bytes = vec![[0x0A, 0x0D, 0x1A, 0x00], [0x44, 0x55, 0x66, 0x77]];
+        let mut output = std::io::Cursor::new(Vec::new());
+        try_output_rpeg_data_to(&mut output, &raw_bytes, 4, 2).unwrap();
+
+        output.set_position(0);
+        assert_eq!(read_in_rpeg_data_from(output).unwrap(), (raw_bytes, 4, 2));
+        assert!(read_in_rpeg_data_from(&b"Compressed image format 2\n2 2\n\x00"[..]).is_err());
+    }
+
     #[test]
-    fn test_no_tests() {
-        panic!("I don't know how to test this because it is very dependent on exact i/o to stdio");
+    fn test_debug_round_trip_in_memory() {
+        let raw_bytes = vec![[0x00, 0x11, 0x22, 0x33]];
+        let mut output = Vec::new();
+        write_rpeg_debug(&mut output, &raw_bytes, 2, 2, &DebugFormatOptions::new()).unwrap();
+
+        assert_eq!(
+            read_in_debug_rpeg_data_from(output).unwrap(),
+            (raw_bytes, 2, 2)
+        );
     }
 }
@@ -0,0 +1,215 @@
+use crate::{RpegError, RpegImage};
+
+/// The magic string at the start of every `.npy` file.
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// The total length of the preamble and header is padded to a multiple of this, so that the data
+/// is aligned.
+const NPY_ALIGNMENT: usize = 64;
+
+impl RpegImage {
+    /// Encodes the words of the image as a [NumPy `.npy` file][npy], which can be loaded in Python
+    /// with `numpy.load`. Enabled by the `npy` feature.
+    ///
+    /// The array is a 2-D `uint32` array with a row for each row of 2x2 blocks of the image, and a
+    /// column for each column of blocks, where each element is a word (reading its bytes as a
+    /// big-endian integer). Nothing from the header except the size of the image is kept.
+    ///
+    /// [npy]: https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::GridMismatch`] if the image doesn't have exactly `(width / 2) * (height / 2)`
+    ///   words
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let words = vec![[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]];
+    /// let image = RpegImage::new(words, 4, 2);
+    /// let npy = image.to_npy().unwrap();
+    ///
+    /// assert_eq!(npy.len(), 128 + 2 * 4);
+    /// assert_eq!(RpegImage::from_npy(&npy).unwrap(), image);
+    /// ```
+    pub fn to_npy(&self) -> Result<Vec<u8>, RpegError> {
+        let (rows, cols) = (self.height() / 2, self.width() / 2);
+        if self.words.len() as u64 != rows as u64 * cols as u64 {
+            return Err(RpegError::GridMismatch {
+                rows,
+                cols,
+                word_count: self.words.len(),
+            });
+        }
+
+        let mut header =
+            format!("{{'descr': '<u4', 'fortran_order': False, 'shape': ({rows}, {cols}), }}");
+        let preamble_len = NPY_MAGIC.len() + 4;
+        let padded_len = (preamble_len + header.len() + 1).next_multiple_of(NPY_ALIGNMENT);
+        while preamble_len + header.len() + 1 < padded_len {
+            header.push(' ');
+        }
+        header.push('\n');
+
+        let mut npy = Vec::with_capacity(padded_len + self.words.len() * 4);
+        npy.extend(NPY_MAGIC);
+        npy.extend([1, 0]);
+        npy.extend((header.len() as u16).to_le_bytes());
+        npy.extend(header.as_bytes());
+        for word in &self.words {
+            npy.extend(u32::from_be_bytes(*word).to_le_bytes());
+        }
+
+        Ok(npy)
+    }
+
+    /// Parses the words of an image from a `.npy` file in the form written by
+    /// [`RpegImage::to_npy`], such as one saved from Python with `numpy.save`. The array may be
+    /// in either byte order, and in either C or Fortran order.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the bytes aren't a `.npy` file, or its header is badly formatted
+    /// * If the array isn't a 2-D array of `uint32`s, or is too big for the image's size to fit in
+    ///   a `u32`
+    /// * If the file ends before all of the array's data
+    pub fn from_npy(npy: &[u8]) -> Result<Self, RpegError> {
+        let error = |message: &str, offset: usize| RpegError::Format {
+            message: message.to_string(),
+            offset: offset as u64,
+        };
+
+        if !npy.starts_with(NPY_MAGIC) {
+            return Err(error("Expected the magic string of a .npy file", 0));
+        }
+        let (header_start, header_len) = match npy.get(NPY_MAGIC.len()) {
+            Some(1) if npy.len() >= 10 => (10, u16::from_le_bytes([npy[8], npy[9]]) as usize),
+            Some(2 | 3) if npy.len() >= 12 => (
+                12,
+                u32::from_le_bytes(npy[8..12].try_into().unwrap()) as usize,
+            ),
+            _ => return Err(error("Expected a supported .npy format version", 6)),
+        };
+        let header = npy
+            .get(header_start..header_start + header_len)
+            .and_then(|header| std::str::from_utf8(header).ok())
+            .ok_or_else(|| error("Expected a .npy header", header_start))?;
+
+        let (big_endian, fortran_order, rows, cols) = parse_header(header)
+            .ok_or_else(|| error("Expected the header of a 2-D uint32 array", header_start))?;
+        let (Some(width), Some(height)) = (cols.checked_mul(2), rows.checked_mul(2)) else {
+            return Err(error("The array is too big", header_start));
+        };
+
+        let data_start = header_start + header_len;
+        let len = rows as usize * cols as usize;
+        let data = npy
+            .get(data_start..)
+            .filter(|data| data.len() / 4 >= len)
+            .ok_or_else(|| error("The file ended before all of the array's data", npy.len()))?;
+
+        let element = |index: usize| {
+            let bytes = data[index * 4..index * 4 + 4].try_into().unwrap();
+            match big_endian {
+                true => bytes,
+                false => u32::from_le_bytes(bytes).to_be_bytes(),
+            }
+        };
+        let words = (0..len)
+            .map(|index| match fortran_order {
+                false => element(index),
+                true => element((index % cols as usize) * rows as usize + index / cols as usize),
+            })
+            .collect();
+
+        Ok(RpegImage::new(words, width, height))
+    }
+}
+
+/// Parses the header of a `.npy` file (a Python dictionary literal), returning whether the array
+/// is big-endian and in Fortran order, and its shape.
+fn parse_header(header: &str) -> Option<(bool, bool, u32, u32)> {
+    // The value after each key, and whatever follows it
+    let value = |key: &str| {
+        let start = header.find(&format!("'{key}'"))? + key.len() + 2;
+        header[start..]
+            .trim_start()
+            .strip_prefix(':')
+            .map(str::trim_start)
+    };
+
+    let descr = value("descr")?;
+    let big_endian = if descr.starts_with("'<u4'") {
+        false
+    } else if descr.starts_with("'>u4'") {
+        true
+    } else {
+        return None;
+    };
+
+    let fortran_order = value("fortran_order")?;
+    let fortran_order = if fortran_order.starts_with("False") {
+        false
+    } else if fortran_order.starts_with("True") {
+        true
+    } else {
+        return None;
+    };
+
+    let shape = value("shape")?.strip_prefix('(')?;
+    let shape = &shape[..shape.find(')')?];
+    let mut dimensions = shape.split(',').map(str::trim);
+    let rows = dimensions.next()?.parse().ok()?;
+    let cols = dimensions.next()?.parse().ok()?;
+    if !dimensions.all(str::is_empty) {
+        return None;
+    }
+
+    Some((big_endian, fortran_order, rows, cols))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_npy_header() {
+        let words = (0..6u32).map(u32::to_be_bytes).collect();
+        let npy = RpegImage::new(words, 6, 4).to_npy().unwrap();
+
+        let header = "{'descr': '<u4', 'fortran_order': False, 'shape': (2, 3), }";
+        assert_eq!(&npy[..10], b"\x93NUMPY\x01\x00\x76\x00");
+        assert_eq!(
+            npy[10..128],
+            *format!("{header}{}\n", " ".repeat(58)).as_bytes()
+        );
+        assert_eq!(&npy[128..136], [0, 0, 0, 0, 1, 0, 0, 0]);
+
+        assert!(matches!(
+            RpegImage::new(vec![[0; 4]; 5], 6, 4).to_npy(),
+            Err(RpegError::GridMismatch { word_count: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn test_npy_fortran_order() {
+        // A version 2.0 file with a big-endian array, as numpy.save(np.asfortranarray(...)) might
+        let header = b"{'shape': (2, 3), 'fortran_order': True, 'descr': '>u4'}\n";
+        let mut npy = b"\x93NUMPY\x02\x00".to_vec();
+        npy.extend((header.len() as u32).to_le_bytes());
+        npy.extend(header);
+        for element in [0u32, 3, 1, 4, 2, 5] {
+            npy.extend(element.to_be_bytes());
+        }
+
+        let image = RpegImage::from_npy(&npy).unwrap();
+        assert_eq!((image.width(), image.height()), (6, 4));
+        assert_eq!(
+            image.words,
+            (0..6u32).map(u32::to_be_bytes).collect::<Vec<_>>()
+        );
+
+        assert!(RpegImage::from_npy(&npy[..npy.len() - 1]).is_err());
+    }
+}
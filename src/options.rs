@@ -0,0 +1,495 @@
+use crate::header::{DEFAULT_FORMAT_VERSION, SUPPORTED_FORMAT_VERSIONS};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The default capacity (in bytes) of the buffers used by [`RpegReader`](crate::RpegReader) and
+/// [`RpegWriter`](crate::RpegWriter).
+///
+/// This is larger than the standard library's 8 KiB default, which means far fewer system calls
+/// for the multi-megabyte images typical of the course's assignments.
+pub const DEFAULT_BUFFER_CAPACITY: usize = 64 * 1024;
+
+/// Options controlling how rpeg data is read.
+///
+/// # Examples
+/// ```no_run
+/// use csc411_rpegio::{ReadOptions, RpegReader};
+///
+/// let options = ReadOptions::new().buffer_capacity(1024 * 1024);
+/// let reader = RpegReader::open_with_options(Some("path/to/file.rpeg"), &options).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReadOptions {
+    pub(crate) buffer_capacity: usize,
+    pub(crate) accepted_versions: Vec<u32>,
+    #[cfg(feature = "crypto")]
+    pub(crate) verify_key: Option<Vec<u8>>,
+}
+
+impl ReadOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        ReadOptions {
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+            accepted_versions: SUPPORTED_FORMAT_VERSIONS.to_vec(),
+            #[cfg(feature = "crypto")]
+            verify_key: None,
+        }
+    }
+
+    /// Restricts which versions of the compressed image format are accepted. Reading data in
+    /// any other version is an error. Defaults to every one of the
+    /// [`SUPPORTED_FORMAT_VERSIONS`].
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{ReadOptions, RpegImage};
+    ///
+    /// let options = ReadOptions::new().accepted_versions(&[2]);
+    ///
+    /// let format_2 = b"Compressed image format 2\n2 2\n\x00\x11\x22\x33";
+    /// assert_eq!(RpegImage::from_bytes_with_options(format_2, &options).unwrap().header.version, 2);
+    ///
+    /// let format_3 = b"Compressed image format 3\n2 2\n\x00\x11\x22\x33";
+    /// assert!(RpegImage::from_bytes_with_options(format_3, &options).is_err());
+    /// ```
+    pub fn accepted_versions(mut self, versions: &[u32]) -> Self {
+        self.accepted_versions = versions.to_vec();
+        self
+    }
+
+    /// Sets the capacity (in bytes) of the buffer used while reading. Defaults to
+    /// [`DEFAULT_BUFFER_CAPACITY`]. A capacity of 0 is treated as 1.
+    pub fn buffer_capacity(mut self, capacity: usize) -> Self {
+        self.buffer_capacity = capacity.max(1);
+        self
+    }
+
+    /// Requires the data to be signed with `key` (see
+    /// [`RpegImage::to_signed`](crate::RpegImage::to_signed)), reporting unsigned data as
+    /// [`RpegError::SignatureMissing`](crate::RpegError::SignatureMissing) and data whose
+    /// signature doesn't match as
+    /// [`RpegError::SignatureMismatch`](crate::RpegError::SignatureMismatch). Streaming readers
+    /// only know this once the whole payload has been read. Enabled by the `crypto` feature.
+    #[cfg(feature = "crypto")]
+    pub fn verify_key(mut self, key: &[u8]) -> Self {
+        self.verify_key = Some(key.to_vec());
+        self
+    }
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions::new()
+    }
+}
+
+/// Options controlling how rpeg data is written.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{RpegWriter, WriteOptions};
+///
+/// let options = WriteOptions::new().buffer_capacity(1024 * 1024);
+/// let mut writer = RpegWriter::with_options(Vec::new(), 2, 2, &options).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WriteOptions {
+    pub(crate) buffer_capacity: usize,
+    pub(crate) version: u32,
+    pub(crate) comments: Vec<String>,
+    pub(crate) metadata: BTreeMap<String, String>,
+    pub(crate) checksum: bool,
+    pub(crate) word_count: bool,
+    pub(crate) binary_header: bool,
+    #[cfg(feature = "crypto")]
+    pub(crate) signing_key: Option<Vec<u8>>,
+}
+
+impl WriteOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        WriteOptions {
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+            version: DEFAULT_FORMAT_VERSION,
+            comments: Vec::new(),
+            metadata: BTreeMap::new(),
+            checksum: false,
+            word_count: false,
+            binary_header: false,
+            #[cfg(feature = "crypto")]
+            signing_key: None,
+        }
+    }
+
+    /// Sets the version of the compressed image format written in the header, which must be one
+    /// of the [`SUPPORTED_FORMAT_VERSIONS`] (format 1 is written with
+    /// [`Format1`](crate::Format1)). Defaults to [`DEFAULT_FORMAT_VERSION`].
+    ///
+    /// # Panics
+    ///
+    /// * If `version` isn't one of the [`SUPPORTED_FORMAT_VERSIONS`], since the data written
+    ///   couldn't be read back
+    pub fn version(mut self, version: u32) -> Self {
+        assert!(
+            SUPPORTED_FORMAT_VERSIONS.contains(&version),
+            "Can't write compressed image format {version}, which isn't supported"
+        );
+        self.version = version;
+        self
+    }
+
+    /// Sets whether to follow the payload with a CRC32 checksum of it, marked by a `c` after the
+    /// version in the header ("Compressed image format 2c"). Readers check the checksum once the
+    /// whole payload has been read, reporting corrupted or truncated data as
+    /// [`RpegError::ChecksumMismatch`](crate::RpegError::ChecksumMismatch). Defaults to false.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{RpegImage, RpegWriter, WriteOptions};
+    ///
+    /// let options = WriteOptions::new().checksum(true);
+    /// let mut writer = RpegWriter::with_options(Vec::new(), 2, 2, &options).unwrap();
+    /// writer.write_word([0x00, 0x11, 0x22, 0x33]).unwrap();
+    /// let mut bytes = writer.finish().unwrap();
+    ///
+    /// assert!(bytes.starts_with(b"Compressed image format 2c\n2 2\n"));
+    /// assert!(RpegImage::from_bytes(&bytes).is_ok());
+    ///
+    /// bytes[31] ^= 0xFF;
+    /// assert!(RpegImage::from_bytes(&bytes).is_err());
+    /// ```
+    pub fn checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets whether to follow the payload with a line containing the number of words in it,
+    /// marked by a `t` after the version in the header ("Compressed image format 2t"). Unlike
+    /// the payload's length, this catches data which was cut off at a multiple of four bytes,
+    /// which readers report as
+    /// [`RpegError::WordCountMismatch`](crate::RpegError::WordCountMismatch). Can be combined
+    /// with [`WriteOptions::checksum`]. Defaults to false.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{RpegWriter, WriteOptions};
+    ///
+    /// let options = WriteOptions::new().word_count(true);
+    /// let mut writer = RpegWriter::with_options(Vec::new(), 2, 2, &options).unwrap();
+    /// writer.write_word([0x00, 0x11, 0x22, 0x33]).unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer.finish().unwrap(),
+    ///     b"Compressed image format 2t\n2 2\n\x00\x11\x22\x331\n"
+    /// );
+    /// ```
+    pub fn word_count(mut self, word_count: bool) -> Self {
+        self.word_count = word_count;
+        self
+    }
+
+    /// Sets whether to store the width and height in binary, as two big-endian `u32`s after a
+    /// first line ending in " [BINARY]", rather than as text. A binary header leaves no room
+    /// for doubt about where the numbers end and the payload begins, but can't hold comments or
+    /// metadata, so any given are ignored. Readers detect binary headers automatically. Defaults
+    /// to false.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{RpegImage, RpegWriter, WriteOptions};
+    ///
+    /// let options = WriteOptions::new().binary_header(true);
+    /// let mut writer = RpegWriter::with_options(Vec::new(), 640, 480, &options).unwrap();
+    /// writer.write_word([0x00, 0x11, 0x22, 0x33]).unwrap();
+    /// let bytes = writer.finish().unwrap();
+    ///
+    /// assert_eq!(
+    ///     bytes,
+    ///     b"Compressed image format 2 [BINARY]\n\x00\x00\x02\x80\x00\x00\x01\xE0\x00\x11\x22\x33"
+    /// );
+    /// assert_eq!(RpegImage::from_bytes(&bytes).unwrap().width(), 640);
+    /// ```
+    pub fn binary_header(mut self, binary_header: bool) -> Self {
+        self.binary_header = binary_header;
+        self
+    }
+
+    /// Adds a `#` comment line to the header, such as the name and version of the program that
+    /// wrote the data. Comments are written between the first and second lines of the header, in
+    /// the order they were added. A comment containing line breaks is written as several
+    /// comments.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{RpegWriter, WriteOptions};
+    ///
+    /// let options = WriteOptions::new().comment("Generated by ppm2rpeg 1.0");
+    /// let writer = RpegWriter::with_options(Vec::new(), 2, 2, &options).unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer.finish().unwrap(),
+    ///     b"Compressed image format 2\n# Generated by ppm2rpeg 1.0\n2 2\n"
+    /// );
+    /// ```
+    pub fn comment(mut self, comment: &str) -> Self {
+        self.comments.push(comment.to_string());
+        self
+    }
+
+    /// Adds a `#@ key=value` metadata line to the header, replacing any earlier value for `key`.
+    /// Metadata is only written when asked for, and since it's stored in comment lines, readers
+    /// which don't know about it simply ignore it. Line breaks in `key` or `value`, and any `=`
+    /// in `key`, are replaced when written.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{RpegImage, RpegWriter, WriteOptions};
+    ///
+    /// let options = WriteOptions::new().metadata("quality", "high");
+    /// let writer = RpegWriter::with_options(Vec::new(), 2, 2, &options).unwrap();
+    /// let bytes = writer.finish().unwrap();
+    ///
+    /// assert_eq!(bytes, b"Compressed image format 2\n#@ quality=high\n2 2\n");
+    /// assert_eq!(RpegImage::from_bytes(&bytes).unwrap().metadata()["quality"], "high");
+    /// ```
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Records the name (and perhaps version) of the program writing the data, as the `creator`
+    /// metadata. See [`WriteOptions::metadata`].
+    pub fn creator(self, creator: &str) -> Self {
+        self.metadata("creator", creator)
+    }
+
+    /// Records the current time as the `timestamp` metadata, in RFC 3339 format (UTC, to the
+    /// second). See [`WriteOptions::metadata`]. On `wasm32-unknown-unknown`, which has no clock,
+    /// this records nothing.
+    pub fn timestamp(self) -> Self {
+        if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+            return self;
+        }
+
+        let timestamp = format_timestamp(SystemTime::now());
+        self.metadata("timestamp", &timestamp)
+    }
+
+    /// Sets the capacity (in bytes) of the buffer used while writing. Defaults to
+    /// [`DEFAULT_BUFFER_CAPACITY`]. A capacity of 0 is treated as 1.
+    pub fn buffer_capacity(mut self, capacity: usize) -> Self {
+        self.buffer_capacity = capacity.max(1);
+        self
+    }
+
+    /// Signs the data with `key`, as [`RpegImage::to_signed`](crate::RpegImage::to_signed)
+    /// does. Custom formats are never signed. Enabled by the `crypto` feature.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{ReadOptions, RpegReader, RpegWriter, WriteOptions};
+    ///
+    /// let options = WriteOptions::new().signing_key(b"grader's key");
+    /// let mut writer = RpegWriter::with_options(Vec::new(), 2, 2, &options).unwrap();
+    /// writer.write_word([0x00, 0x11, 0x22, 0x33]).unwrap();
+    /// let bytes = writer.finish().unwrap();
+    ///
+    /// let options = ReadOptions::new().verify_key(b"grader's key");
+    /// let reader = RpegReader::with_options(&bytes[..], &options).unwrap();
+    /// assert_eq!(reader.count(), 1);
+    /// ```
+    #[cfg(feature = "crypto")]
+    pub fn signing_key(mut self, key: &[u8]) -> Self {
+        self.signing_key = Some(key.to_vec());
+        self
+    }
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions::new()
+    }
+}
+
+/// Options controlling how [`debug_output_rpeg_data_with_options`] lays out the payload. The
+/// defaults give the same output as [`debug_output_rpeg_data`]: every byte on one line, in
+/// uppercase hexadecimal, separated by spaces.
+///
+/// Whatever the options, the output can still be read back with
+/// [`read_in_debug_rpeg_data`](crate::read_in_debug_rpeg_data).
+///
+/// [`debug_output_rpeg_data`]: crate::debug_output_rpeg_data
+/// [`debug_output_rpeg_data_with_options`]: crate::debug_output_rpeg_data_with_options
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::DebugFormatOptions;
+///
+/// // One line for each row of 2x2 blocks, with each word as a single lowercase number
+/// let options = DebugFormatOptions::new()
+///     .group_by_row(true)
+///     .group_words(true)
+///     .lowercase(true)
+///     .trailing_newline(true);
+///
+/// let raw_bytes = [[0xAA, 0xBB, 0xCC, 0xDD]; 4];
+/// csc411_rpegio::debug_output_rpeg_data_with_options(&raw_bytes, 4, 4, &options);
+///
+/// // Standard Output:
+/// // Compressed image format 2 [DEBUG]
+/// // 4 4
+/// // aabbccdd aabbccdd
+/// // aabbccdd aabbccdd
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DebugFormatOptions {
+    pub(crate) words_per_line: usize,
+    pub(crate) group_by_row: bool,
+    pub(crate) group_words: bool,
+    pub(crate) lowercase: bool,
+    pub(crate) trailing_newline: bool,
+    pub(crate) annotate: bool,
+    pub(crate) column_header: bool,
+}
+
+impl DebugFormatOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        DebugFormatOptions {
+            words_per_line: 0,
+            group_by_row: false,
+            group_words: false,
+            lowercase: false,
+            trailing_newline: false,
+            annotate: false,
+            column_header: false,
+        }
+    }
+
+    /// Sets the most words to put on each line, or 0 for no limit. Defaults to 0.
+    pub fn words_per_line(mut self, words_per_line: usize) -> Self {
+        self.words_per_line = words_per_line;
+        self
+    }
+
+    /// Sets whether to start a new line for each row of 2x2 blocks of the image (every
+    /// `width / 2` words), so that the layout of the output matches the image. Rows longer than
+    /// [`DebugFormatOptions::words_per_line`] are wrapped. Defaults to false.
+    pub fn group_by_row(mut self, group_by_row: bool) -> Self {
+        self.group_by_row = group_by_row;
+        self
+    }
+
+    /// Sets whether to write each word as a single eight-digit number (`00112233`), rather than
+    /// as four separate bytes (`00 11 22 33`). Defaults to false.
+    pub fn group_words(mut self, group_words: bool) -> Self {
+        self.group_words = group_words;
+        self
+    }
+
+    /// Sets whether to write hexadecimal digits in lowercase. Defaults to false.
+    pub fn lowercase(mut self, lowercase: bool) -> Self {
+        self.lowercase = lowercase;
+        self
+    }
+
+    /// Sets whether to end the output with a newline. Defaults to false.
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    /// Sets whether to start each row of 2x2 blocks on a new line (as
+    /// [`DebugFormatOptions::group_by_row`] does), labelled with the index of the row, so that
+    /// the word for block `(row, column)` can be found without counting. Defaults to false.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::DebugFormatOptions;
+    ///
+    /// let options = DebugFormatOptions::new().annotate(true).column_header(true);
+    /// let raw_bytes = [[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]];
+    ///
+    /// assert_eq!(
+    ///     csc411_rpegio::format_rpeg_debug(&raw_bytes, 2, 4, &options),
+    ///     "Compressed image format 2 [DEBUG]\n2 4\n#  0\n0: 00 11 22 33\n1: 44 55 66 77"
+    /// );
+    /// ```
+    pub fn annotate(mut self, annotate: bool) -> Self {
+        self.annotate = annotate;
+        self
+    }
+
+    /// Sets whether to start the payload of annotated output (see
+    /// [`DebugFormatOptions::annotate`]) with a `#` line labelling the column of each word.
+    /// Defaults to false.
+    pub fn column_header(mut self, column_header: bool) -> Self {
+        self.column_header = column_header;
+        self
+    }
+}
+
+impl Default for DebugFormatOptions {
+    fn default() -> Self {
+        DebugFormatOptions::new()
+    }
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp, like "2024-01-31T12:00:00Z". Times before the
+/// Unix epoch are clamped to it.
+fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+
+    // Converts days since the epoch to a civil date (see Howard Hinnant's `civil_from_days`)
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_version() {
+        for version in SUPPORTED_FORMAT_VERSIONS {
+            assert_eq!(WriteOptions::new().version(*version).version, *version);
+        }
+        for version in [0, 1, 4, 7, u32::MAX] {
+            assert!(std::panic::catch_unwind(|| WriteOptions::new().version(version)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        let at = |seconds| format_timestamp(UNIX_EPOCH + Duration::from_secs(seconds));
+
+        assert_eq!(at(0), "1970-01-01T00:00:00Z");
+        assert_eq!(at(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(at(1_706_702_400), "2024-01-31T12:00:00Z");
+        assert_eq!(at(4_107_542_399), "2100-02-28T23:59:59Z");
+    }
+}
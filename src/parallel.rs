@@ -0,0 +1,270 @@
+use crate::duplicates::find_rpeg_files;
+use crate::{ReadOptions, RpegError, RpegImage};
+use std::fmt;
+
+/// The number of threads to split work across.
+fn thread_count() -> usize {
+    std::thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1)
+}
+
+/// Applies `f` to contiguous runs of `items` spread across threads, returning each run's results
+/// in the same order as the runs themselves.
+fn par_runs<T, R, F>(items: &[T], run_len: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&[T]) -> R + Sync,
+{
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(run_len.max(1))
+            .map(|run| scope.spawn(|| f(run)))
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| match handle.join() {
+                Ok(result) => result,
+                Err(panic) => std::panic::resume_unwind(panic),
+            })
+            .collect()
+    })
+}
+
+impl<const N: usize> RpegImage<N> {
+    /// Applies `f` to every word of the image in parallel, returning the results.
+    ///
+    /// The results are always in the same order as the words they came from, so the result at
+    /// index `i` is `f(self.words[i])`. However, the order in which `f` is *called* is
+    /// unspecified, and calls happen concurrently on several threads.
+    ///
+    /// # Panics
+    ///
+    /// * If `f` panics. The panic is propagated once every thread has finished
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let image = RpegImage::new(vec![[0, 0, 0, 1], [0, 0, 0, 2], [0, 0, 0, 3]], 6, 2);
+    ///
+    /// let codewords: Vec<u32> = image.par_map_words(u32::from_be_bytes);
+    /// assert_eq!(codewords, vec![1, 2, 3]);
+    /// ```
+    pub fn par_map_words<T, F>(&self, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn([u8; N]) -> T + Sync,
+    {
+        let run_len = self.words.len().div_ceil(thread_count());
+
+        par_runs(&self.words, run_len, |run| {
+            run.iter().map(|&word| f(word)).collect::<Vec<_>>()
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Applies `f` to consecutive chunks of `chunk_size` words in parallel, returning the result
+    /// for each chunk. The last chunk may be shorter than `chunk_size` if the number of words
+    /// isn't a multiple of it. This is handy for working on a whole row of blocks at a time, by
+    /// passing the number of blocks in a row as the `chunk_size`.
+    ///
+    /// The results are always in the same order as the chunks they came from. However, the order
+    /// in which `f` is *called* is unspecified, and calls happen concurrently on several threads.
+    ///
+    /// # Panics
+    ///
+    /// * If `chunk_size` is 0
+    /// * If `f` panics. The panic is propagated once every thread has finished
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let image = RpegImage::new(vec![[0, 0, 0, 1], [0, 0, 0, 2], [0, 0, 0, 3]], 6, 2);
+    ///
+    /// let chunk_lengths: Vec<usize> = image.par_chunks(2, |chunk| chunk.len());
+    /// assert_eq!(chunk_lengths, vec![2, 1]);
+    /// ```
+    pub fn par_chunks<T, F>(&self, chunk_size: usize, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(&[[u8; N]]) -> T + Sync,
+    {
+        assert!(chunk_size != 0, "chunk_size must be greater than 0");
+
+        // Hand each thread a whole number of chunks, so that no chunk is split between threads
+        let chunk_count = self.words.len().div_ceil(chunk_size);
+        let run_len = chunk_count.div_ceil(thread_count()) * chunk_size;
+
+        par_runs(&self.words, run_len, |run| {
+            run.chunks(chunk_size).map(&f).collect::<Vec<_>>()
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+/// How one file fared in [`validate_dir`], as part of a [`DirReport`].
+#[derive(Debug)]
+pub struct FileReport {
+    /// The path of the file, starting with the directory that was validated
+    pub path: String,
+    /// The image's width, height, and number of words, or why it isn't valid
+    pub result: Result<(u32, u32, usize), RpegError>,
+}
+
+/// The results of [`validate_dir`]. Its [`Display`](fmt::Display) implementation gives the
+/// number of valid and invalid files, then lists the invalid ones with what's wrong with them.
+#[derive(Debug)]
+pub struct DirReport {
+    /// Every file validated, in order of path
+    pub files: Vec<FileReport>,
+}
+
+impl DirReport {
+    /// The number of files which are valid rpeg data.
+    pub fn valid_count(&self) -> usize {
+        self.files.len() - self.invalid().count()
+    }
+
+    /// The files which aren't valid rpeg data (or couldn't be read).
+    pub fn invalid(&self) -> impl Iterator<Item = &FileReport> {
+        self.files.iter().filter(|file| file.result.is_err())
+    }
+}
+
+impl fmt::Display for DirReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} files: {} valid, {} invalid",
+            self.files.len(),
+            self.valid_count(),
+            self.invalid().count()
+        )?;
+
+        for file in self.invalid() {
+            if let Err(error) = &file.result {
+                write!(f, "\n{}: {error}", file.path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Validates every file in the directory `dir` (and in every directory inside it) whose name
+/// ends in `.rpeg`, reading and parsing them on several threads at once. This is much faster
+/// than checking a directory of submissions one file at a time. Enabled by the `parallel`
+/// feature.
+///
+/// Each file is read as by [`RpegImage::read_with_options`], so it's valid if it would be read
+/// successfully.
+///
+/// # Arguments
+///
+/// * `dir` - The directory to search for rpeg files
+/// * `options` - The options to read each file with
+///
+/// # Errors Returned
+///
+/// * If a directory can't be listed. Files which can't be read are reported as invalid
+///   instead
+///
+/// # Examples
+/// ```no_run
+/// use csc411_rpegio::{validate_dir, ReadOptions};
+///
+/// let report = validate_dir("submissions", &ReadOptions::default()).unwrap();
+/// println!("{report}");
+/// ```
+pub fn validate_dir(dir: &str, options: &ReadOptions) -> Result<DirReport, RpegError> {
+    let paths = find_rpeg_files(dir)?;
+    let run_len = paths.len().div_ceil(thread_count());
+
+    let files = par_runs(&paths, run_len, |run| {
+        run.iter()
+            .map(|path| FileReport {
+                path: path.clone(),
+                result: RpegImage::read_with_options(Some(path), options).map(|image| {
+                    let word_count = image.words.len();
+                    (image.width(), image.height(), word_count)
+                }),
+            })
+            .collect::<Vec<_>>()
+    })
+    .into_iter()
+    .flatten()
+    .collect();
+
+    Ok(DirReport { files })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_par_map_words_preserves_order() {
+        let words: Vec<[u8; 4]> = (0..10_000u32).map(u32::to_be_bytes).collect();
+        let image = RpegImage::new(words, 200, 200);
+
+        let codewords = image.par_map_words(u32::from_be_bytes);
+        assert_eq!(codewords, (0..10_000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_par_chunks_preserves_order() {
+        let words: Vec<[u8; 4]> = (0..1_001u32).map(u32::to_be_bytes).collect();
+        let image = RpegImage::new(words, 2, 2002);
+
+        let firsts = image.par_chunks(10, |chunk| u32::from_be_bytes(chunk[0]));
+        assert_eq!(firsts, (0..1_001).step_by(10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_par_map_words_empty() {
+        let image = RpegImage::new(Vec::new(), 0, 0);
+
+        assert!(image.par_map_words(u32::from_be_bytes).is_empty());
+        assert!(image.par_chunks(4, |chunk| chunk.len()).is_empty());
+    }
+
+    #[test]
+    fn test_validate_dir() {
+        let dir = std::env::temp_dir().join("csc411_rpegio_test_validate_dir");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        for index in 0..20 {
+            let image = RpegImage::new(vec![[index; 4]; 4], 4, 4);
+            let path = dir.join(format!("nested/{index:02}.rpeg"));
+            image.write_file(path.to_str().unwrap()).unwrap();
+        }
+        std::fs::write(
+            dir.join("bad.rpeg"),
+            b"Compressed image format 2\n4 4\n\x00",
+        )
+        .unwrap();
+        std::fs::write(dir.join("ignored.txt"), b"not rpeg data").unwrap();
+
+        let report = validate_dir(dir.to_str().unwrap(), &ReadOptions::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+        let report = report.unwrap();
+
+        assert_eq!(report.files.len(), 21);
+        assert_eq!(report.valid_count(), 20);
+        assert!(report.files[0].path.ends_with("bad.rpeg"));
+        assert!(report.files[1].path.ends_with("nested/00.rpeg"));
+        assert_eq!(report.files[20].result.as_ref().unwrap(), &(4, 4, 4));
+        assert!(report
+            .to_string()
+            .starts_with("21 files: 20 valid, 1 invalid\n"));
+        assert!(validate_dir("/does/not/exist", &ReadOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use crate::header::{self, ByteReader};
+use crate::{stdio, ReadOptions, RpegError, RpegHeader};
+use std::io::{BufReader, Read, Write};
+
+/// Input whose header has been read and validated, but whose payload hasn't been read yet.
+struct Validated<R> {
+    header: RpegHeader,
+    header_len: u64,
+    bytes: ByteReader<BufReader<R>>,
+}
+
+/// Reads and validates the header of `input`, so that the output can be left alone if it's bad.
+fn validate<R: Read>(input: R) -> Result<Validated<R>, RpegError> {
+    let mut bytes = ByteReader::new(BufReader::new(input));
+    let header = header::read_header(&mut bytes, &ReadOptions::default())?;
+    let header_len = bytes.position();
+
+    Ok(Validated {
+        header,
+        header_len,
+        bytes,
+    })
+}
+
+/// Copies the header (in canonical form) and the payload of validated input to `output`.
+fn relay<R: Read>(input: Validated<R>, mut output: impl Write) -> Result<RpegHeader, RpegError> {
+    let Validated {
+        header,
+        header_len,
+        bytes,
+    } = input;
+
+    // The signature (if any) is copied along with the payload, and it covers the header as
+    // written rather than as read, so it still holds once the header is in canonical form
+    header::write_header_as_read(&mut output, &header)?;
+    output.flush()?;
+
+    // When `input` and `output` are files or pipes, the standard library's `io::copy` moves the
+    // data with `copy_file_range`/`splice`/`sendfile` on Linux (after first draining whatever
+    // BufReader already buffered), and falls back to a buffered copy everywhere else
+    let payload_len = std::io::copy(&mut bytes.into_inner(), &mut output)?;
+    output.flush()?;
+
+    // A word count trailer can make the payload any length, so only the reader can check it
+    if !header.word_count && !payload_len.is_multiple_of(4) {
+        return Err(RpegError::Format {
+            message: format!("The number of raw bytes ({payload_len}) was not a multiple of four"),
+            offset: header_len + payload_len,
+        });
+    }
+
+    Ok(header)
+}
+
+/// Validates rpeg data from either stdin or a file while copying it to either stdout or a file,
+/// returning its header. This is useful for building programs which sit in the middle of a
+/// pipeline, checking that the data passing through is well-formed.
+///
+/// On Linux, when both ends are files or pipes, the payload is moved by the kernel (using
+/// `copy_file_range` or `splice`) without ever being copied into this program's memory. Elsewhere,
+/// it is copied through a fixed-size buffer.
+///
+/// The header is written back out in canonical form (with `\n` line endings). The payload is
+/// copied as-is, and is only known to be the wrong length once it has all been copied, in which
+/// case an error is returned after the fact. The checksum of checksummed data and the signature
+/// of signed data are copied along with the payload (keeping the header's `s` flag), but aren't
+/// checked, and neither is the length of a payload followed by a word count.
+///
+/// # Errors Returned
+///
+/// * If the input file can't be opened, or the output file can't be created
+/// * If there is an error reading from the input or writing to the output. Writing to a closed
+///   pipe is reported as [`RpegError::BrokenPipe`]
+/// * If the rpeg data header is badly formatted, in which case nothing is written (and the output
+///   file isn't created or truncated)
+/// * If the number of raw bytes following the header is not a multiple of 4
+///
+/// # Arguments
+///
+/// * `input` - An optional file path to read from. If None, stdin will be read from instead
+/// * `output` - An optional file path to write to, which will be created or truncated once the
+///   input's header has been validated. If None, stdout will be written to instead
+///
+/// # Examples
+/// ```no_run
+/// // Relay stdin to stdout, complaining about bad data
+/// if let Err(err) = csc411_rpegio::passthrough(None, None) {
+///     eprintln!("Invalid rpeg data: {err}");
+///     std::process::exit(1);
+/// }
+/// ```
+pub fn passthrough(input: Option<&str>, output: Option<&str>) -> Result<RpegHeader, RpegError> {
+    let (input, output) = (stdio::input_path(input), output.map(String::from));
+    let output = output.or_else(stdio::output_path);
+
+    // Each combination is spelled out so that `relay` sees concrete standard library types,
+    // which is what lets `io::copy` use the kernel's zero-copy paths. The output file is only
+    // created once the header is known to be good, so a bad input doesn't wipe it out
+    match (input.as_deref(), output.as_deref()) {
+        (Some(input), Some(output)) => {
+            let input = validate(std::fs::File::open(input)?)?;
+            relay(input, std::fs::File::create(output)?)
+        }
+        (Some(input), None) => {
+            let input = validate(std::fs::File::open(input)?)?;
+            relay(input, stdio::binary_stdout())
+        }
+        (None, Some(output)) => {
+            let input = validate(stdio::binary_stdin())?;
+            relay(input, std::fs::File::create(output)?)
+        }
+        (None, None) => relay(validate(stdio::binary_stdin())?, stdio::binary_stdout()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay() {
+        let input: &[u8] = b"Compressed image format 2\r\n4 2\r\n\x00\x11\x22\x33";
+        let mut output = Vec::new();
+
+        let header = relay(validate(input).unwrap(), &mut output).unwrap();
+        assert_eq!(header, RpegHeader::new(4, 2));
+        assert_eq!(output, b"Compressed image format 2\n4 2\n\x00\x11\x22\x33");
+    }
+
+    #[test]
+    fn test_relay_rejects_bad_header() {
+        let input: &[u8] = b"Compressed image format 9\n4 2\n\x00\x11\x22\x33";
+
+        assert!(matches!(validate(input), Err(RpegError::Format { .. })));
+    }
+
+    #[test]
+    fn test_relay_keeps_signature() {
+        let mut input = b"Compressed image format 2s\r\n2 2\r\n\x00\x11\x22\x33".to_vec();
+        input.extend([0xAB; 32]);
+        let mut output = Vec::new();
+
+        let header = relay(validate(&input[..]).unwrap(), &mut output).unwrap();
+        assert!(header.signed);
+        assert_eq!(output[..27], *b"Compressed image format 2s\n");
+        assert_eq!(output[31..], input[33..]);
+
+        // A signature still holds once relayed
+        #[cfg(feature = "crypto")]
+        {
+            let image = crate::RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+            let signed = image.to_signed(b"key");
+            let mut output = Vec::new();
+            relay(validate(&signed[..]).unwrap(), &mut output).unwrap();
+            assert_eq!(output, signed);
+
+            let options = ReadOptions::new().verify_key(b"key");
+            let read = crate::RpegImage::from_bytes_with_options(&output, &options).unwrap();
+            assert_eq!(read.words, image.words);
+        }
+    }
+
+    #[test]
+    fn test_passthrough_files() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("rpegio-passthrough-in-{}", std::process::id()));
+        let output = dir.join(format!("rpegio-passthrough-out-{}", std::process::id()));
+        let data = b"Compressed image format 2\n4 2\n\x00\x11\x22\x33\x44\x55\x66\x77\x88";
+        std::fs::write(&input, data).unwrap();
+
+        let result = passthrough(input.to_str(), output.to_str());
+        let copied = std::fs::read(&output).unwrap();
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        assert!(matches!(result, Err(RpegError::Format { offset: 39, .. })));
+        assert_eq!(copied, data);
+    }
+
+    #[test]
+    fn test_passthrough_keeps_output_on_bad_header() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("rpegio-passthrough-bad-in-{}", std::process::id()));
+        let output = dir.join(format!("rpegio-passthrough-bad-out-{}", std::process::id()));
+        std::fs::write(&input, b"Compressed image format 9\n4 2\n\x00\x11\x22\x33").unwrap();
+        std::fs::write(&output, b"precious").unwrap();
+
+        let result = passthrough(input.to_str(), output.to_str());
+        let kept = std::fs::read(&output).unwrap();
+        std::fs::remove_file(&input).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        assert!(matches!(result, Err(RpegError::Format { .. })));
+        assert_eq!(kept, b"precious");
+    }
+}
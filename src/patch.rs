@@ -0,0 +1,298 @@
+use crate::checksum::Crc32;
+use crate::header::{self, ByteReader, RpegHeader};
+use crate::{ReadOptions, RpegError, RpegImage};
+
+/// The first line of an encoded [`RpegPatch`].
+const PATCH_MAGIC: &str = "Rpeg patch";
+
+/// The differences between two images, word by word, as made by [`diff`] and used by [`apply`].
+///
+/// A patch stores the header of the new image and only the runs of words which changed, so
+/// patches between nearly identical images are small. It also records a checksum of the old
+/// image's words, so that it can't be applied to the wrong image by mistake.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{apply, diff, RpegImage, RpegPatch};
+///
+/// let old = RpegImage::new(vec![[0x00; 4]; 1000], 100, 40);
+/// let mut new = old.clone();
+/// new.words[500] = [0xFF; 4];
+///
+/// let patch = diff(&old, &new);
+/// let bytes = patch.to_bytes();
+/// assert!(bytes.len() < 64);
+///
+/// let patch = RpegPatch::from_bytes(&bytes).unwrap();
+/// assert_eq!(patch.changed_words(), 1);
+/// assert_eq!(apply(&old, &patch).unwrap(), new);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RpegPatch {
+    header: RpegHeader,
+    base_crc: u32,
+    base_len: u64,
+    target_len: u64,
+    // Each run of changed words, and the index of its first word
+    runs: Vec<(u64, Vec<[u8; 4]>)>,
+}
+
+/// Finds the differences between the images `old` and `new`. See [`RpegPatch`].
+pub fn diff(old: &RpegImage, new: &RpegImage) -> RpegPatch {
+    let mut runs: Vec<(u64, Vec<[u8; 4]>)> = Vec::new();
+
+    for (index, word) in new.words.iter().enumerate() {
+        if old.words.get(index) == Some(word) {
+            continue;
+        }
+
+        match runs.last_mut() {
+            Some((start, words)) if *start + words.len() as u64 == index as u64 => {
+                words.push(*word);
+            }
+            _ => runs.push((index as u64, vec![*word])),
+        }
+    }
+
+    RpegPatch {
+        header: new.header.clone(),
+        base_crc: crc_of_words(old),
+        base_len: old.words.len() as u64,
+        target_len: new.words.len() as u64,
+        runs,
+    }
+}
+
+/// Applies a patch made by [`diff`] to the image it was made from, giving back the other image.
+///
+/// # Errors Returned
+///
+/// * [`RpegError::PatchMismatch`] if `old` isn't the image the patch was made from
+pub fn apply(old: &RpegImage, patch: &RpegPatch) -> Result<RpegImage, RpegError> {
+    if old.words.len() as u64 != patch.base_len || crc_of_words(old) != patch.base_crc {
+        return Err(RpegError::PatchMismatch);
+    }
+
+    let mut words = old.words.clone();
+    words.resize(patch.target_len as usize, [0; 4]);
+    for (start, run) in &patch.runs {
+        let start = *start as usize;
+        words[start..start + run.len()].copy_from_slice(run);
+    }
+
+    Ok(RpegImage {
+        header: patch.header.clone(),
+        words,
+    })
+}
+
+fn crc_of_words(image: &RpegImage) -> u32 {
+    let mut crc = Crc32::new();
+    for word in &image.words {
+        crc.update(word);
+    }
+
+    crc.finish()
+}
+
+impl RpegPatch {
+    /// The number of words which are different in the new image (including any words added to
+    /// the end of it).
+    pub fn changed_words(&self) -> u64 {
+        self.runs.iter().map(|(_, run)| run.len() as u64).sum()
+    }
+
+    /// Encodes the patch, for storing or sending it.
+    ///
+    /// The first line is `Rpeg patch`, followed by the header of the new image as in rpeg data.
+    /// The rest is binary: the CRC32 (big-endian) and number of the old image's words, the
+    /// number of the new image's words, and the number of runs of changed words, followed by each
+    /// run. A run is the number of unchanged words before it, the number of words in it, and its
+    /// words. All of the numbers other than the CRC32 are LEB128 variable-length integers.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = format!("{PATCH_MAGIC}\n").into_bytes();
+        header::write_header(&mut bytes, &self.header).expect("Writing to a Vec can't fail");
+
+        bytes.extend(self.base_crc.to_be_bytes());
+        for number in [self.base_len, self.target_len, self.runs.len() as u64] {
+            write_varint(&mut bytes, number);
+        }
+
+        let mut end = 0;
+        for (start, run) in &self.runs {
+            write_varint(&mut bytes, start - end);
+            write_varint(&mut bytes, run.len() as u64);
+            bytes.extend(run.iter().flatten());
+            end = start + run.len() as u64;
+        }
+
+        bytes
+    }
+
+    /// Parses a patch encoded by [`RpegPatch::to_bytes`].
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the bytes don't start with `Rpeg patch` and a valid rpeg header
+    /// * If the bytes end early, any run of words goes past the end of the new image, or words
+    ///   added to the end of the new image are missing
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RpegError> {
+        let magic = format!("{PATCH_MAGIC}\n");
+        let Some(rest) = bytes.strip_prefix(magic.as_bytes()) else {
+            return Err(RpegError::Format {
+                message: format!("Expected {PATCH_MAGIC:?}"),
+                offset: 0,
+            });
+        };
+
+        let mut reader = ByteReader::new(rest);
+        let header =
+            header::read_header(&mut reader, &ReadOptions::default()).map_err(|err| match err {
+                RpegError::Format { message, offset } => RpegError::Format {
+                    message,
+                    offset: magic.len() as u64 + offset,
+                },
+                err => err,
+            })?;
+
+        let mut decoder = Decoder {
+            bytes,
+            position: magic.len() + reader.position() as usize,
+        };
+        let base_crc = u32::from_be_bytes(decoder.take(4)?.try_into().unwrap());
+        let base_len = decoder.varint()?;
+        let target_len = decoder.varint()?;
+        let run_count = decoder.varint()?;
+
+        let mut runs = Vec::new();
+        let mut end: u64 = 0;
+        for _ in 0..run_count {
+            let start = end.saturating_add(decoder.varint()?);
+            let len = decoder.varint()?;
+            if start.saturating_add(len) > target_len {
+                return Err(decoder.error("A run of words goes past the end of the new image"));
+            }
+
+            let run = decoder
+                .take(len.saturating_mul(4))?
+                .chunks_exact(4)
+                .map(|word| word.try_into().unwrap())
+                .collect();
+            runs.push((start, run));
+            end = start + len;
+        }
+
+        if decoder.position != bytes.len() {
+            return Err(decoder.error("Expected the end of the patch"));
+        }
+
+        let patch = RpegPatch {
+            header,
+            base_crc,
+            base_len,
+            target_len,
+            runs,
+        };
+
+        // Any words added to the end of the new image must all be in runs
+        if target_len > base_len.saturating_add(patch.changed_words()) {
+            return Err(decoder.error("The patch is missing words at the end of the new image"));
+        }
+
+        Ok(patch)
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut number: u64) {
+    while number >= 0x80 {
+        bytes.push(number as u8 | 0x80);
+        number >>= 7;
+    }
+    bytes.push(number as u8);
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Decoder<'_> {
+    fn error(&self, message: &str) -> RpegError {
+        RpegError::Format {
+            message: message.to_string(),
+            offset: self.position as u64,
+        }
+    }
+
+    fn take(&mut self, len: u64) -> Result<&[u8], RpegError> {
+        if len > (self.bytes.len() - self.position) as u64 {
+            return Err(self.error("The patch ended early"));
+        }
+
+        let start = self.position;
+        self.position += len as usize;
+        Ok(&self.bytes[start..self.position])
+    }
+
+    fn varint(&mut self) -> Result<u64, RpegError> {
+        let start = self.position;
+        let mut number = 0;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            number |= ((byte & 0x7F) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(number);
+            }
+        }
+
+        self.position = start;
+        Err(self.error("A number in the patch is too big"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patch_round_trip() {
+        let words = |words: &[u8]| words.iter().map(|&word| [word; 4]).collect::<Vec<_>>();
+        let old = RpegImage::new(words(&[1, 2, 3, 4, 5, 6]), 4, 6);
+        let mut new = RpegImage::new(words(&[1, 9, 9, 4, 5, 9, 7, 8]), 4, 8);
+        new.header.comments.push("regenerated".to_string());
+        new.header.checksum = true;
+
+        let patch = diff(&old, &new);
+        assert_eq!(patch.changed_words(), 5);
+        let patch = RpegPatch::from_bytes(&patch.to_bytes()).unwrap();
+        assert_eq!(apply(&old, &patch).unwrap(), new);
+
+        // Shrinking works too, and patches only apply to the image they were made from
+        let patch = diff(&new, &old);
+        assert_eq!(apply(&new, &patch).unwrap(), old);
+        assert!(matches!(apply(&old, &patch), Err(RpegError::PatchMismatch)));
+    }
+
+    #[test]
+    fn test_patch_errors() {
+        let image = RpegImage::new(vec![[0; 4]; 2], 4, 2);
+        let bytes = diff(&RpegImage::new(vec![], 0, 0), &image).to_bytes();
+        assert_eq!(bytes.len(), 11 + 30 + 4 + 5 + 8);
+
+        assert!(RpegPatch::from_bytes(&bytes[1..]).is_err());
+        assert!(matches!(
+            RpegPatch::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(RpegError::Format { offset: 50, .. })
+        ));
+
+        // A run which is longer than the new image
+        let mut bytes = bytes;
+        bytes[49] = 3;
+        assert!(matches!(
+            RpegPatch::from_bytes(&bytes),
+            Err(RpegError::Format { offset: 50, .. })
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+use crate::checksum::Crc32;
+use crate::deflate::{self, BitWriter};
+use crate::{BitField, RpegError, RpegImage};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// How much of the filtered image is compressed as each DEFLATE block.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// The colors magnitudes are drawn with, from smallest to biggest, blending between each pair.
+const COLORMAP: [[u8; 3]; 5] = [
+    [0, 0, 255],
+    [0, 255, 255],
+    [0, 255, 0],
+    [255, 255, 0],
+    [255, 0, 0],
+];
+
+impl RpegImage {
+    /// Draws a field of every word of the image as a heatmap of its magnitude, encoded as a PNG
+    /// image, for putting in a report. Enabled by the `image` feature.
+    ///
+    /// Like [`RpegImage::field_heatmap`], each 2x2 block of the image becomes a square of pixels,
+    /// whose color shows the magnitude of the field: the value of an unsigned field, or the
+    /// absolute value of a signed one. Magnitudes run from blue (zero) through cyan, green, and
+    /// yellow to red (the biggest magnitude the field can hold).
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - The field to draw
+    /// * `scale` - The width and height of the square of pixels each block becomes
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::GridMismatch`] if the image doesn't have exactly `(width / 2) * (height / 2)`
+    ///   words
+    ///
+    /// # Panics
+    ///
+    /// * If `scale` is 0
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use csc411_rpegio::{BitField, RpegImage};
+    ///
+    /// let image = RpegImage::read(Some("path/to/file.rpeg")).unwrap();
+    /// let png = image.field_to_png(BitField::new(23, 9), 4).unwrap();
+    ///
+    /// std::fs::write("a.png", png).unwrap();
+    /// ```
+    pub fn field_to_png(&self, field: BitField, scale: u32) -> Result<Vec<u8>, RpegError> {
+        assert!(scale > 0, "The scale of a PNG heatmap must be at least 1");
+
+        let (rows, cols) = self.block_grid()?;
+        let max_magnitude = field.max_magnitude() as f64;
+        let colors: Vec<[u8; 3]> = self
+            .words
+            .iter()
+            .map(|&word| color(field.value(word).unsigned_abs() as f64 / max_magnitude))
+            .collect();
+
+        // Each row of pixels starts with its filter type, which is always 0 (none)
+        let scale = scale as usize;
+        let row_len = 1 + cols as usize * scale * 3;
+        let mut raw = Vec::with_capacity(row_len * rows as usize * scale);
+        for row in colors.chunks(cols.max(1) as usize) {
+            let start = raw.len();
+            raw.push(0);
+            for color in row {
+                for _ in 0..scale {
+                    raw.extend_from_slice(color);
+                }
+            }
+            for _ in 1..scale {
+                raw.extend_from_within(start..start + row_len);
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend((cols * scale as u32).to_be_bytes());
+        header.extend((rows * scale as u32).to_be_bytes());
+        // 8-bit RGB, with the default compression and filtering, and no interlacing
+        header.extend([8, 2, 0, 0, 0]);
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", &zlib(&raw));
+        write_chunk(&mut png, b"IEND", &[]);
+
+        Ok(png)
+    }
+}
+
+/// The color of a magnitude between 0 and 1.
+fn color(magnitude: f64) -> [u8; 3] {
+    let position = magnitude.clamp(0.0, 1.0) * (COLORMAP.len() - 1) as f64;
+    let index = (position as usize).min(COLORMAP.len() - 2);
+    let blend = position - index as f64;
+
+    let (low, high) = (COLORMAP[index], COLORMAP[index + 1]);
+    std::array::from_fn(|channel| {
+        let (low, high) = (low[channel] as f64, high[channel] as f64);
+        (low + (high - low) * blend).round() as u8
+    })
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+
+    // The checksum covers the chunk's type and data, but not its length
+    let mut crc = Crc32::new();
+    crc.update(&png[start..]);
+    png.extend(crc.finish().to_be_bytes());
+}
+
+/// Compresses data in the zlib format (RFC 1950), which wraps DEFLATE data with a header and
+/// an Adler-32 checksum.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    let mut chunks = data.chunks(CHUNK_SIZE).peekable();
+    if chunks.peek().is_none() {
+        deflate::compress_block(&[], true, &mut bits);
+    }
+    while let Some(chunk) = chunks.next() {
+        deflate::compress_block(chunk, chunks.peek().is_none(), &mut bits);
+    }
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    zlib.extend(bits.finish());
+    zlib.extend((b << 16 | a).to_be_bytes());
+    zlib
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colormap() {
+        assert_eq!(color(0.0), [0, 0, 255]);
+        assert_eq!(color(0.125), [0, 128, 255]);
+        assert_eq!(color(0.5), [0, 255, 0]);
+        assert_eq!(color(1.0), [255, 0, 0]);
+    }
+
+    #[test]
+    fn test_field_to_png() {
+        let words = (0..6u32).map(|index| (index << 28).to_be_bytes()).collect();
+        let image = RpegImage::new(words, 6, 4);
+        let png = image
+            .field_to_png(BitField::new(28, 4).signed(), 2)
+            .unwrap();
+
+        assert_eq!(png[..8], PNG_SIGNATURE);
+        assert_eq!(png[8..16], [0, 0, 0, 13, b'I', b'H', b'D', b'R']);
+        assert_eq!(png[16..29], [0, 0, 0, 6, 0, 0, 0, 4, 8, 2, 0, 0, 0]);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+
+        let idat_len = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(png[37..41], *b"IDAT");
+        let zlib = &png[41..41 + idat_len];
+        let (raw, _) = deflate::decompress(&zlib[2..]).unwrap();
+        assert_eq!(raw.len(), 4 * (1 + 6 * 3));
+
+        // Each block is doubled across and down, and 4 (of up to 8) is half of the colormap
+        assert_eq!(raw[..19], raw[19..38]);
+        assert_eq!(raw[0], 0);
+        assert_eq!(raw[1..7], [0, 0, 255, 0, 0, 255]);
+        assert_eq!(raw[19 * 2 + 7..19 * 2 + 10], [0, 255, 0]);
+
+        assert!(matches!(
+            RpegImage::new(vec![[0; 4]; 5], 6, 4).field_to_png(BitField::new(0, 1), 1),
+            Err(RpegError::GridMismatch { word_count: 5, .. })
+        ));
+    }
+}
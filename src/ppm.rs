@@ -0,0 +1,283 @@
+use crate::{stdio, RpegError};
+use std::io::Write;
+
+/// The longest a line of a plain PPM file may be, not counting its newline.
+const PLAIN_LINE_LEN: usize = 70;
+
+/// An uncompressed image in the [PPM format][ppm], as read by [`read_ppm`] and written by
+/// [`write_ppm`]. These are the images an rpeg compressor starts from, and its decompressor
+/// ends with.
+///
+/// [ppm]: https://netpbm.sourceforge.net/doc/ppm.html
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::PpmImage;
+///
+/// let image = PpmImage::new(vec![[255, 0, 0], [0, 0, 255]], 2, 1, 255);
+/// assert_eq!(image.to_bytes(), b"P6\n2 1\n255\n\xFF\x00\x00\x00\x00\xFF");
+/// assert_eq!(image.to_plain_bytes(), b"P3\n2 1\n255\n255 0 0 0 0 255\n");
+///
+/// assert_eq!(PpmImage::from_bytes(&image.to_plain_bytes()).unwrap(), image);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PpmImage {
+    /// The width of the image, in pixels
+    pub width: u32,
+    /// The height of the image, in pixels
+    pub height: u32,
+    /// The largest value of a sample (between 1 and 65535), which stands for full intensity
+    pub maxval: u16,
+    /// The red, green, and blue samples of each pixel, row by row from the top left
+    pub pixels: Vec<[u16; 3]>,
+}
+
+impl PpmImage {
+    /// Creates an image from its pixels, row by row from the top left.
+    pub fn new(pixels: Vec<[u16; 3]>, width: u32, height: u32, maxval: u16) -> Self {
+        PpmImage {
+            width,
+            height,
+            maxval,
+            pixels,
+        }
+    }
+
+    /// Parses a PPM image from a slice of bytes, in either the raw (`P6`) or plain (`P3`)
+    /// format. Only the first image is read, if there are several.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the header is badly formatted, or its maxval isn't between 1 and 65535
+    /// * If the bytes end before every pixel, or any sample is bigger than the maxval
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RpegError> {
+        let mut parser = Parser { bytes, position: 0 };
+
+        let plain = match bytes.get(..2) {
+            Some(b"P6") => false,
+            Some(b"P3") => true,
+            _ => return Err(parser.error("Expected a PPM image (starting with P6 or P3)")),
+        };
+        parser.position = 2;
+
+        let width = parser.number()?;
+        let height = parser.number()?;
+        let maxval = parser.number()?;
+        let maxval = match u16::try_from(maxval) {
+            Ok(maxval) if maxval > 0 => maxval,
+            _ => return Err(parser.error("Expected a maxval between 1 and 65535")),
+        };
+        let len = width as u64 * height as u64 * 3;
+
+        let mut samples = Vec::with_capacity(len.min(bytes.len() as u64) as usize);
+        if plain {
+            for _ in 0..len {
+                let sample = parser.number()?;
+                samples.push(parser.sample(sample, maxval)?);
+            }
+        } else {
+            // A single whitespace character separates the header from the raster
+            if !parser.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+                return Err(parser.error("Expected whitespace after the maxval"));
+            }
+            parser.position += 1;
+
+            let sample_len = if maxval > 255 { 2 } else { 1 };
+            for _ in 0..len {
+                let sample = match bytes.get(parser.position..parser.position + sample_len) {
+                    Some([byte]) => *byte as u32,
+                    Some([high, low]) => u16::from_be_bytes([*high, *low]) as u32,
+                    _ => return Err(parser.error("The image ended before all of its pixels")),
+                };
+                parser.position += sample_len;
+                samples.push(parser.sample(sample, maxval)?);
+            }
+        }
+
+        let pixels = samples
+            .chunks_exact(3)
+            .map(|pixel| pixel.try_into().unwrap())
+            .collect();
+
+        Ok(PpmImage::new(pixels, width, height, maxval))
+    }
+
+    /// Encodes the image in the raw PPM format (`P6`), with one byte per sample if the maxval is
+    /// at most 255, or two (big-endian) otherwise.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            format!("P6\n{} {}\n{}\n", self.width, self.height, self.maxval).into_bytes();
+
+        for sample in self.pixels.iter().flatten() {
+            match self.maxval > 255 {
+                true => bytes.extend(sample.to_be_bytes()),
+                false => bytes.push(*sample as u8),
+            }
+        }
+
+        bytes
+    }
+
+    /// Encodes the image in the plain PPM format (`P3`), with the samples in decimal. Lines are
+    /// at most 70 characters long, as the format requires.
+    pub fn to_plain_bytes(&self) -> Vec<u8> {
+        let mut bytes = format!("P3\n{} {}\n{}\n", self.width, self.height, self.maxval);
+        let mut line_len = 0;
+
+        for sample in self.pixels.iter().flatten() {
+            let sample = sample.to_string();
+            if line_len > 0 && line_len + 1 + sample.len() > PLAIN_LINE_LEN {
+                bytes.push('\n');
+                line_len = 0;
+            }
+            if line_len > 0 {
+                bytes.push(' ');
+                line_len += 1;
+            }
+            bytes.push_str(&sample);
+            line_len += sample.len();
+        }
+        if line_len > 0 {
+            bytes.push('\n');
+        }
+
+        bytes.into_bytes()
+    }
+}
+
+/// Reads and parses a PPM image (raw `P6` or plain `P3`) from either stdin or a file, just as
+/// [`read_in_rpeg_data`](crate::read_in_rpeg_data) does for rpeg data.
+///
+/// # Errors Returned
+///
+/// * If there is an unexpected error reading from the provided file or stdin
+/// * If the input isn't a valid PPM image (see [`PpmImage::from_bytes`])
+///
+/// # Arguments
+///
+/// * `file_path` - An optional file path to read from. If None, stdin will be read from instead
+///
+/// # Examples
+/// ```no_run
+/// let image = csc411_rpegio::read_ppm(Some("path/to/file.ppm")).unwrap();
+///
+/// println!("Image size: {}x{}", image.width, image.height);
+/// ```
+pub fn read_ppm(file_path: Option<&str>) -> Result<PpmImage, RpegError> {
+    let bytes = crate::read_raw_bytes(file_path)?;
+
+    PpmImage::from_bytes(&bytes)
+}
+
+/// Outputs a PPM image to stdout in the raw format (`P6`), in binary mode. Use
+/// [`PpmImage::to_plain_bytes`] for the plain format instead.
+///
+/// # Errors Returned
+///
+/// * [`RpegError::BrokenPipe`] if stdout is closed before all of the image is written
+/// * [`RpegError::Io`] if anything else goes wrong writing to stdout
+///
+/// # Arguments
+///
+/// * `image` - The image to write
+pub fn write_ppm(image: &PpmImage) -> Result<(), RpegError> {
+    let mut stdout = stdio::output()?;
+    stdout.write_all(&image.to_bytes())?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> RpegError {
+        RpegError::Format {
+            message: message.to_string(),
+            offset: self.position as u64,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    /// Skips whitespace and comments, then reads a decimal number.
+    fn number(&mut self) -> Result<u32, RpegError> {
+        loop {
+            match self.peek() {
+                Some(byte) if byte.is_ascii_whitespace() => self.position += 1,
+                Some(b'#') => {
+                    while !matches!(self.peek(), None | Some(b'\n' | b'\r')) {
+                        self.position += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        let start = self.position;
+        while self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+            self.position += 1;
+        }
+
+        let digits = std::str::from_utf8(&self.bytes[start..self.position]).unwrap();
+        digits.parse().map_err(|_| {
+            self.position = start;
+            match self.peek() {
+                None => self.error("The image ended before all of its pixels"),
+                _ => self.error("Expected a number"),
+            }
+        })
+    }
+
+    fn sample(&self, sample: u32, maxval: u16) -> Result<u16, RpegError> {
+        match u16::try_from(sample) {
+            Ok(sample) if sample <= maxval => Ok(sample),
+            _ => Err(self.error("A sample is bigger than the maxval")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ppm_round_trip() {
+        let pixels = (0..12).map(|index| [index, 1000 + index, 65535]).collect();
+        let image = PpmImage::new(pixels, 4, 3, 65535);
+
+        let bytes = image.to_bytes();
+        assert_eq!(bytes.len(), 13 + 12 * 6);
+        assert_eq!(PpmImage::from_bytes(&bytes).unwrap(), image);
+
+        let plain = image.to_plain_bytes();
+        assert!(plain
+            .split(|&byte| byte == b'\n')
+            .all(|line| line.len() <= PLAIN_LINE_LEN));
+        assert_eq!(PpmImage::from_bytes(&plain).unwrap(), image);
+    }
+
+    #[test]
+    fn test_ppm_header() {
+        let bytes = b"P6 # made by hand\n2\t1 #comment\n\n255\n\x01\x02\x03\x04\x05\x06";
+        let image = PpmImage::from_bytes(bytes).unwrap();
+        assert_eq!(image.pixels, [[1, 2, 3], [4, 5, 6]]);
+        assert_eq!(image.maxval, 255);
+
+        assert!(matches!(
+            PpmImage::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(RpegError::Format { offset: 41, .. })
+        ));
+        assert!(PpmImage::from_bytes(b"P5\n2 1\n255\n\x01\x02").is_err());
+        assert!(PpmImage::from_bytes(b"P3\n1 1\n0\n0 0 0\n").is_err());
+        assert!(matches!(
+            PpmImage::from_bytes(b"P3\n1 1\n15\n1 2 16\n"),
+            Err(RpegError::Format { offset: 16, .. })
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{PpmImage, RpegImage};
+
+/// Tests a compressor and decompressor together: compresses a PPM image with `compress`, checks
+/// that the result is valid rpeg data of the right size, decompresses it with `decompress`, and
+/// checks that the PPM image which comes out is close enough to the one which went in. This is
+/// the round-trip test every compressor needs, with every step checked.
+///
+/// The images are compared as `ppmdiff` compares them: by the root mean square difference of
+/// their samples, each scaled to between 0 and 1 by its image's maxval. Since rpeg data only
+/// holds whole 2x2 blocks, the original image is trimmed to an even width and height first (as
+/// the compressor should trim it), and the rpeg data and decompressed image must both be that
+/// size.
+///
+/// # Arguments
+///
+/// * `ppm_bytes` - The bytes of the PPM image to start from
+/// * `compress` - Compresses the bytes of a PPM image into rpeg data
+/// * `decompress` - Decompresses rpeg data back into the bytes of a PPM image
+/// * `tolerance` - The largest root mean square difference allowed between the images
+///
+/// Returns the root mean square difference, so tests can report it (or check it more strictly).
+///
+/// # Panics
+///
+/// * If `ppm_bytes` isn't a valid PPM image
+/// * If the output of `compress` isn't valid rpeg data, or isn't the size of the trimmed image,
+///   or doesn't have a word for every 2x2 block
+/// * If the output of `decompress` isn't a valid PPM image, or isn't the size of the trimmed
+///   image
+/// * If the images differ by more than `tolerance`
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{assert_roundtrip, PpmImage, RpegImage};
+///
+/// // A "compressor" which keeps only the red sample of the top left pixel of each block
+/// let compress = |ppm: &[u8]| {
+///     let ppm = PpmImage::from_bytes(ppm).unwrap();
+///     let (width, height) = (ppm.width & !1, ppm.height & !1);
+///     let words = (0..height / 2)
+///         .flat_map(|row| (0..width / 2).map(move |column| (row, column)))
+///         .map(|(row, column)| {
+///             let pixel = ppm.pixels[(row * 2 * ppm.width + column * 2) as usize];
+///             (pixel[0] as u32).to_be_bytes()
+///         })
+///         .collect();
+///     RpegImage::new(words, width, height).to_bytes()
+/// };
+/// let decompress = |rpeg: &[u8]| {
+///     let rpeg = RpegImage::from_bytes(rpeg).unwrap();
+///     let pixels = (0..rpeg.height() * rpeg.width())
+///         .map(|index| (index / rpeg.width() / 2, index % rpeg.width() / 2))
+///         .map(|(row, column)| rpeg.words[(row * rpeg.width() / 2 + column) as usize])
+///         .map(|word| [u32::from_be_bytes(word) as u16; 3])
+///         .collect();
+///     PpmImage::new(pixels, rpeg.width(), rpeg.height(), 255).to_bytes()
+/// };
+///
+/// let gray = PpmImage::new(vec![[100, 100, 100]; 5 * 3], 5, 3, 255);
+/// assert_eq!(assert_roundtrip(&gray.to_bytes(), compress, decompress, 0.01), 0.0);
+/// ```
+#[track_caller]
+pub fn assert_roundtrip(
+    ppm_bytes: &[u8],
+    compress: impl FnOnce(&[u8]) -> Vec<u8>,
+    decompress: impl FnOnce(&[u8]) -> Vec<u8>,
+    tolerance: f64,
+) -> f64 {
+    let original = PpmImage::from_bytes(ppm_bytes)
+        .unwrap_or_else(|error| panic!("The original image isn't a valid PPM image: {error}"));
+    let (width, height) = (original.width & !1, original.height & !1);
+
+    let rpeg_bytes = compress(ppm_bytes);
+    let rpeg = RpegImage::from_bytes(&rpeg_bytes)
+        .unwrap_or_else(|error| panic!("The compressed image isn't valid rpeg data: {error}"));
+    assert!(
+        (rpeg.width(), rpeg.height()) == (width, height),
+        "The compressed image is {}x{}, but the original image trimmed to whole 2x2 blocks is \
+         {width}x{height}",
+        rpeg.width(),
+        rpeg.height()
+    );
+    let blocks = (width / 2) as usize * (height / 2) as usize;
+    assert!(
+        rpeg.words.len() == blocks,
+        "The compressed image has {} words, but a {width}x{height} image has {blocks} 2x2 blocks",
+        rpeg.words.len()
+    );
+
+    let decompressed = PpmImage::from_bytes(&decompress(&rpeg_bytes))
+        .unwrap_or_else(|error| panic!("The decompressed image isn't a valid PPM image: {error}"));
+    assert!(
+        (decompressed.width, decompressed.height) == (width, height),
+        "The decompressed image is {}x{}, but the compressed image is {width}x{height}",
+        decompressed.width,
+        decompressed.height
+    );
+
+    let difference = rms_difference(&original, &decompressed, width, height);
+    assert!(
+        difference <= tolerance,
+        "The decompressed image differs from the original by {difference:.4} (root mean \
+         square), more than the tolerance of {tolerance}"
+    );
+
+    difference
+}
+
+/// The root mean square difference between the samples of the top left `width` by `height`
+/// pixels of two images, each scaled to between 0 and 1 by its image's maxval.
+fn rms_difference(left: &PpmImage, right: &PpmImage, width: u32, height: u32) -> f64 {
+    let samples = width as usize * height as usize * 3;
+    if samples == 0 {
+        return 0.0;
+    }
+
+    let pixel = |image: &PpmImage, row: u32, column: u32| {
+        let pixel = image.pixels[(row * image.width + column) as usize];
+        pixel.map(|sample| sample as f64 / image.maxval as f64)
+    };
+
+    let mut total = 0.0;
+    for row in 0..height {
+        for column in 0..width {
+            let (left, right) = (pixel(left, row, column), pixel(right, row, column));
+            total += (0..3)
+                .map(|channel| (left[channel] - right[channel]).powi(2))
+                .sum::<f64>();
+        }
+    }
+
+    (total / samples as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Compresses each 2x2 block to the red sample of its top left pixel
+    fn compress(ppm: &[u8]) -> Vec<u8> {
+        let ppm = PpmImage::from_bytes(ppm).unwrap();
+        let (width, height) = (ppm.width & !1, ppm.height & !1);
+        let mut words = Vec::new();
+        for row in 0..height / 2 {
+            for column in 0..width / 2 {
+                let pixel = ppm.pixels[(row * 2 * ppm.width + column * 2) as usize];
+                words.push((pixel[0] as u32).to_be_bytes());
+            }
+        }
+        RpegImage::new(words, width, height).to_bytes()
+    }
+
+    // Decompresses each word to a 2x2 block of gray, at a maxval of 100
+    fn decompress(rpeg: &[u8]) -> Vec<u8> {
+        let rpeg = RpegImage::from_bytes(rpeg).unwrap();
+        let mut pixels = Vec::new();
+        for row in 0..rpeg.height() {
+            for column in 0..rpeg.width() {
+                let word = rpeg.words[(row / 2 * (rpeg.width() / 2) + column / 2) as usize];
+                pixels.push([u32::from_be_bytes(word) as u16; 3]);
+            }
+        }
+        PpmImage::new(pixels, rpeg.width(), rpeg.height(), 100).to_bytes()
+    }
+
+    #[test]
+    fn test_assert_roundtrip() {
+        // Only the blue samples are lost, each coming back 0.5 too bright, so a third of the
+        // samples are wrong by 0.5
+        let ppm = PpmImage::new(vec![[50, 50, 0]; 6 * 4], 6, 4, 100).to_bytes();
+        let difference = assert_roundtrip(&ppm, compress, decompress, 0.5);
+        assert!((difference - (0.25f64 / 3.0).sqrt()).abs() < 1e-9);
+
+        let empty = PpmImage::new(vec![[0; 3]], 1, 1, 255).to_bytes();
+        assert_eq!(assert_roundtrip(&empty, compress, decompress, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_assert_roundtrip_failures() {
+        let ppm = PpmImage::new(vec![[50, 50, 0]; 3 * 3], 3, 3, 100).to_bytes();
+        let message =
+            |result: std::thread::Result<f64>| *result.unwrap_err().downcast::<String>().unwrap();
+
+        let failure =
+            std::panic::catch_unwind(|| assert_roundtrip(&ppm, compress, decompress, 0.1));
+        assert!(message(failure).contains("more than the tolerance of 0.1"));
+
+        let untrimmed = |_: &[u8]| RpegImage::new(vec![[0; 4]], 3, 3).to_bytes();
+        let failure =
+            std::panic::catch_unwind(|| assert_roundtrip(&ppm, untrimmed, decompress, 1.0));
+        assert!(message(failure).starts_with("The compressed image is 3x3"));
+
+        let garbage = |_: &[u8]| b"not rpeg data".to_vec();
+        let failure = std::panic::catch_unwind(|| assert_roundtrip(&ppm, garbage, decompress, 1.0));
+        assert!(message(failure).starts_with("The compressed image isn't valid rpeg data"));
+    }
+}
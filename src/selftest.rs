@@ -0,0 +1,205 @@
+use crate::{generate_rpeg, read_in_rpeg_data_from, try_output_rpeg_data_to, RpegImage, TextStyle};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+/// The result of one check made by [`selftest`], as part of a [`SelfTestReport`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SelfTestCheck {
+    /// What was checked, such as `binary round trip`
+    pub name: &'static str,
+    /// What went wrong, or None if the check passed
+    pub problem: Option<String>,
+}
+
+/// The results of every check made by [`selftest`]. Its [`Display`](fmt::Display)
+/// implementation lists them, one to a line, then a summary.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SelfTestReport {
+    /// Every check, in the order they were made
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestReport {
+    /// Whether every check passed.
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// The checks which failed.
+    pub fn failures(&self) -> impl Iterator<Item = &SelfTestCheck> {
+        self.checks.iter().filter(|check| check.problem.is_some())
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            match &check.problem {
+                None => writeln!(f, "ok   {}", check.name)?,
+                Some(problem) => writeln!(f, "FAIL {}: {problem}", check.name)?,
+            }
+        }
+
+        match self.failures().count() {
+            0 => write!(f, "all {} checks passed", self.checks.len()),
+            failed => write!(f, "{failed} of {} checks failed", self.checks.len()),
+        }
+    }
+}
+
+/// Checks that this crate works in the current environment, by generating images and writing
+/// and reading them back in every format, and checking that every byte survives. Call it before
+/// anything else when output looks wrong for no reason: a failure points at the environment
+/// (such as newlines being translated on Windows) or a broken installation, not at your code.
+///
+/// Everything happens in memory, except for one round trip through a temporary file. A check
+/// which panics counts as failing, so this never panics.
+///
+/// # Examples
+/// ```
+/// let report = csc411_rpegio::selftest();
+/// assert!(report.passed(), "{report}");
+/// ```
+pub fn selftest() -> SelfTestReport {
+    // A pseudo-random image, with the bytes most often mangled in transit at the front:
+    // newlines, carriage returns, Ctrl-Z (end of file on Windows), NUL, and 0xFF
+    let mut image = generate_rpeg(64, 48, 411);
+    image.words[0] = [b'\r', b'\n', b'\n', b'\r'];
+    image.words[1] = [0x1A, 0x00, 0xFF, b'\n'];
+
+    let checks = vec![
+        check("binary round trip", || {
+            let bytes = image.to_bytes();
+            if !bytes.starts_with(b"Compressed image format 2\n64 48\n") {
+                return Err(format!("wrote the header as {:?}", header_of(&bytes)));
+            }
+            let expected_len = 32 + image.words.len() * 4;
+            if bytes.len() != expected_len {
+                return Err(format!("wrote {} bytes, not {expected_len}", bytes.len()));
+            }
+
+            same(&image, RpegImage::from_bytes(&bytes))
+        }),
+        check("read_in_rpeg_data and output_rpeg_data", || {
+            let mut bytes = Vec::new();
+            try_output_rpeg_data_to(&mut bytes, &image.words, 64, 48).map_err(|e| e.to_string())?;
+            let read = read_in_rpeg_data_from(bytes);
+
+            same(
+                &image,
+                read.map(|(words, w, h)| RpegImage::new(words, w, h)),
+            )
+        }),
+        check("text round trip (decimal)", || {
+            let text = image.to_text(TextStyle::Decimal);
+            same(&image, RpegImage::from_text(&text))
+        }),
+        check("text round trip (hex)", || {
+            let text = image.to_text(TextStyle::Hex);
+            same(&image, RpegImage::from_text(&text))
+        }),
+        check("large dimensions", || {
+            // Numbers are written without any locale's digit grouping, and read back exactly
+            let large = RpegImage::new(vec![], 4_000_000_000, 1_234_567);
+            let bytes = large.to_bytes();
+            if !bytes.ends_with(b"\n4000000000 1234567\n") {
+                return Err(format!("wrote the header as {:?}", header_of(&bytes)));
+            }
+
+            same(&large, RpegImage::from_bytes(&bytes))
+        }),
+        check("temporary file", || {
+            let path = std::env::temp_dir().join(format!(
+                "csc411_rpegio_selftest_{}.rpeg",
+                std::process::id()
+            ));
+            let path = path.to_str().ok_or("the temporary directory isn't UTF-8")?;
+            let written = image.write_file(path);
+            let on_disk = std::fs::read(path);
+            let _ = std::fs::remove_file(path);
+
+            written.map_err(|error| format!("couldn't write {path}: {error}"))?;
+            let on_disk = on_disk.map_err(|error| format!("couldn't read {path}: {error}"))?;
+            if on_disk != image.to_bytes() {
+                return Err(format!(
+                    "{path} held {} bytes, not the {} written (are newlines being translated?)",
+                    on_disk.len(),
+                    image.to_bytes().len()
+                ));
+            }
+
+            same(&image, RpegImage::from_bytes(&on_disk))
+        }),
+    ];
+
+    SelfTestReport { checks }
+}
+
+/// Runs a check, taking a panic as a failure.
+fn check(name: &'static str, check: impl FnOnce() -> Result<(), String>) -> SelfTestCheck {
+    let problem = match panic::catch_unwind(AssertUnwindSafe(check)) {
+        Ok(result) => result.err(),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Some(format!("panicked: {message}"))
+        }
+    };
+
+    SelfTestCheck { name, problem }
+}
+
+/// Checks that an image came back the same as it was written.
+fn same(expected: &RpegImage, actual: Result<RpegImage, impl fmt::Display>) -> Result<(), String> {
+    let actual = actual.map_err(|error| format!("couldn't read it back: {error}"))?;
+
+    match *expected == actual {
+        true => Ok(()),
+        false => Err(format!(
+            "read back a different image: {}",
+            crate::compare_rpeg(expected, &actual)
+        )),
+    }
+}
+
+/// The first two lines of rpeg data, for describing a wrongly written header.
+fn header_of(bytes: &[u8]) -> String {
+    let lines = bytes.split_inclusive(|&byte| byte == b'\n').take(2);
+
+    String::from_utf8_lossy(&lines.flatten().copied().collect::<Vec<u8>>()).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selftest() {
+        let report = selftest();
+
+        assert!(report.passed(), "{report}");
+        assert_eq!(report.checks.len(), 6);
+        assert!(report.to_string().ends_with("\nall 6 checks passed"));
+    }
+
+    #[test]
+    fn test_failed_checks() {
+        let report = SelfTestReport {
+            checks: vec![
+                check("fine", || Ok(())),
+                check("broken", || Err("it broke".to_string())),
+                check("panicky", || panic!("oh no")),
+            ],
+        };
+
+        assert!(!report.passed());
+        assert_eq!(report.failures().count(), 2);
+        assert_eq!(
+            report.to_string(),
+            "ok   fine\nFAIL broken: it broke\nFAIL panicky: panicked: oh no\n2 of 3 checks failed"
+        );
+    }
+}
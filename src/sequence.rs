@@ -0,0 +1,575 @@
+use crate::{stdio, RpegError, RpegImage};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+/// The first line of a sequence of rpeg images.
+const SEQUENCE_MAGIC: &str = "Compressed image sequence";
+
+/// The start of the second line, which holds the number of frames.
+const FRAMES_PREFIX: &str = "frames ";
+
+/// The number of digits the frame count is always written with, so that it can be updated in
+/// place as frames are appended. Enough for any `u32`.
+const FRAME_COUNT_DIGITS: usize = 10;
+
+/// The start of the line before each frame, which holds the frame's length in bytes.
+const FRAME_PREFIX: &str = "frame ";
+
+/// The first line of the optional index of frames.
+const INDEX_MAGIC: &str = "index";
+
+/// The start of the last line of an indexed sequence, which holds the offset of the index.
+const FOOTER_PREFIX: &str = "index at ";
+
+/// The number of digits the offset of the index is always written with, so that the last line
+/// of an indexed sequence is always the same length. Enough for any `u64`.
+const INDEX_OFFSET_DIGITS: usize = 20;
+
+const FOOTER_LEN: usize = FOOTER_PREFIX.len() + INDEX_OFFSET_DIGITS + 1;
+
+/// Writes a sequence of rpeg images (such as the frames of a video) into a single file.
+///
+/// A sequence starts with the line `Compressed image sequence`, then a line with the number of
+/// frames (as `frames {count}`, zero-padded to ten digits). Each frame follows as a line with
+/// its length in bytes (`frame {length}`), and then the frame itself as standard rpeg data.
+///
+/// The number of frames is updated after every frame is written, so a sequence is always
+/// complete, and more frames can later be added with [`RpegSequenceWriter::append`].
+///
+/// Sequences created with [`RpegSequenceWriter::new_indexed`] end with an index of where each
+/// frame starts, so that [`RpegSequenceReader::read_frame`] can go straight to any frame. The
+/// index is a line `index`, then a line with the offset (in bytes, from the start of the
+/// sequence) of each frame, and finally a line `index at {offset}` with the offset of the index,
+/// zero-padded to twenty digits. The index is only written by [`RpegSequenceWriter::finish`];
+/// without it, finding a frame means skipping over every frame before it.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{RpegImage, RpegSequenceReader, RpegSequenceWriter};
+/// use std::io::Cursor;
+///
+/// let frames = vec![
+///     RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2),
+///     RpegImage::new(vec![[0x44, 0x55, 0x66, 0x77]], 2, 2),
+/// ];
+///
+/// let mut writer = RpegSequenceWriter::new(Cursor::new(Vec::new())).unwrap();
+/// for frame in &frames {
+///     writer.write_frame(frame).unwrap();
+/// }
+/// let bytes = writer.finish().unwrap().into_inner();
+///
+/// let reader = RpegSequenceReader::new(bytes.as_slice()).unwrap();
+/// assert_eq!(reader.frame_count(), 2);
+/// assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), frames);
+/// ```
+pub struct RpegSequenceWriter<W: Write + Seek> {
+    writer: W,
+    count_offset: u64,
+    frame_count: u32,
+    // Where the next frame will go, and where each frame so far went (if indexed)
+    end: u64,
+    offsets: Option<Vec<u64>>,
+}
+
+impl<W: Write + Seek> RpegSequenceWriter<W> {
+    /// Starts a new, empty sequence at the current position of `writer`.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If something goes wrong writing to `writer`
+    pub fn new(writer: W) -> Result<Self, RpegError> {
+        RpegSequenceWriter::create(writer, None)
+    }
+
+    /// Like [`RpegSequenceWriter::new`], but with an index of frames written when the sequence
+    /// is finished.
+    pub fn new_indexed(writer: W) -> Result<Self, RpegError> {
+        RpegSequenceWriter::create(writer, Some(Vec::new()))
+    }
+
+    fn create(mut writer: W, offsets: Option<Vec<u64>>) -> Result<Self, RpegError> {
+        writer.write_all(format!("{SEQUENCE_MAGIC}\n{FRAMES_PREFIX}").as_bytes())?;
+        let count_offset = writer.stream_position()?;
+        writer.write_all(format!("{:0FRAME_COUNT_DIGITS$}\n", 0).as_bytes())?;
+        let end = writer.stream_position()?;
+
+        Ok(RpegSequenceWriter {
+            writer,
+            count_offset,
+            frame_count: 0,
+            end,
+            offsets,
+        })
+    }
+
+    /// The number of frames in the sequence so far.
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    /// Adds a frame to the end of the sequence.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If something goes wrong writing to the underlying writer
+    /// * [`RpegError::Format`] if the sequence already has `u32::MAX` frames
+    pub fn write_frame(&mut self, frame: &RpegImage) -> Result<(), RpegError> {
+        let frame_count = self
+            .frame_count
+            .checked_add(1)
+            .ok_or_else(|| RpegError::Format {
+                message: "A sequence can't have more than u32::MAX frames".to_string(),
+                offset: self.count_offset,
+            })?;
+
+        let bytes = frame.to_bytes();
+        let line = format!("{FRAME_PREFIX}{}\n", bytes.len());
+        self.writer.seek(SeekFrom::Start(self.end))?;
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(&bytes)?;
+
+        if let Some(offsets) = &mut self.offsets {
+            offsets.push(self.end);
+        }
+        self.end += (line.len() + bytes.len()) as u64;
+
+        // Keep the frame count up to date, so the sequence is complete after every frame
+        self.writer.seek(SeekFrom::Start(self.count_offset))?;
+        self.writer
+            .write_all(format!("{frame_count:0FRAME_COUNT_DIGITS$}").as_bytes())?;
+        self.frame_count = frame_count;
+
+        Ok(())
+    }
+
+    /// Writes the index of frames (if the sequence has one), flushes the underlying writer, and
+    /// returns it.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If something goes wrong writing to the underlying writer
+    pub fn finish(mut self) -> Result<W, RpegError> {
+        if let Some(offsets) = &self.offsets {
+            let mut index = format!("{INDEX_MAGIC}\n");
+            for offset in offsets {
+                index.push_str(&format!("{offset}\n"));
+            }
+            index.push_str(&format!(
+                "{FOOTER_PREFIX}{:0INDEX_OFFSET_DIGITS$}\n",
+                self.end
+            ));
+
+            self.writer.seek(SeekFrom::Start(self.end))?;
+            self.writer.write_all(index.as_bytes())?;
+        }
+
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+impl<W: Read + Write + Seek> RpegSequenceWriter<W> {
+    /// Opens an existing sequence (starting at the beginning of `writer`) to add more frames to
+    /// its end. If the sequence has an index, it's replaced by an updated one when the sequence
+    /// is finished.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If something goes wrong reading from or seeking in `writer`
+    /// * If `writer` doesn't start with the header of a sequence, or its frames are cut off
+    pub fn append(mut writer: W) -> Result<Self, RpegError> {
+        writer.seek(SeekFrom::Start(0))?;
+        let (frame_count, header_len) = read_sequence_header(&mut BufReader::new(&mut writer))?;
+
+        // New frames go where the index was, or otherwise after the last frame
+        let (offsets, end) = match read_index(&mut writer, frame_count)? {
+            Some((offsets, index_offset)) => (Some(offsets), index_offset),
+            None => (None, scan_offsets(&mut writer, header_len, frame_count)?.1),
+        };
+
+        Ok(RpegSequenceWriter {
+            writer,
+            count_offset: (SEQUENCE_MAGIC.len() + 1 + FRAMES_PREFIX.len()) as u64,
+            frame_count,
+            end,
+            offsets,
+        })
+    }
+}
+
+/// Reads the frames of a sequence of rpeg images, one at a time. See [`RpegSequenceWriter`] for
+/// the format, and an example.
+pub struct RpegSequenceReader<R> {
+    reader: BufReader<R>,
+    frame_count: u32,
+    frames_read: u32,
+    position: u64,
+    header_len: u64,
+    // Where each frame starts, once it's been needed
+    offsets: Option<Vec<u64>>,
+}
+
+impl RpegSequenceReader<Box<dyn Read>> {
+    /// Opens a sequence from either stdin or a file, reading its header.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the provided file can't be opened, or there is an error reading from it or stdin
+    /// * If the input doesn't start with the header of a sequence
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - An optional file path to read from. If None, stdin will be read from
+    ///   instead
+    pub fn open(file_path: Option<&str>) -> Result<Self, RpegError> {
+        let reader: Box<dyn Read> = match stdio::input_path(file_path).as_deref() {
+            Some(file_path) => Box::new(std::fs::File::open(file_path)?),
+            None => Box::new(stdio::binary_stdin()),
+        };
+
+        RpegSequenceReader::new(reader)
+    }
+}
+
+impl<R: Read> RpegSequenceReader<R> {
+    /// Creates a reader over any source of a sequence, reading its header.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If there is an error reading from `reader`
+    /// * If `reader` doesn't start with the header of a sequence
+    pub fn new(reader: R) -> Result<Self, RpegError> {
+        let mut reader = BufReader::new(reader);
+        let (frame_count, header_len) = read_sequence_header(&mut reader)?;
+
+        Ok(RpegSequenceReader {
+            reader,
+            frame_count,
+            frames_read: 0,
+            position: header_len,
+            header_len,
+            offsets: None,
+        })
+    }
+
+    /// The number of frames in the sequence, according to its header.
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    /// Reads the next frame, returning `Ok(None)` once every frame has been read.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If there is an error reading from the underlying source
+    /// * If the sequence ends before all of its frames, or a frame isn't valid rpeg data
+    pub fn next_frame(&mut self) -> Result<Option<RpegImage>, RpegError> {
+        if self.frames_read == self.frame_count {
+            return Ok(None);
+        }
+
+        let line = read_line(&mut self.reader, self.position)?;
+        let frame_len = parse_frame_len(&line, self.frames_read, self.position)?;
+        self.position += line.len() as u64 + 1;
+
+        let mut bytes = Vec::new();
+        (&mut self.reader).take(frame_len).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < frame_len {
+            return Err(RpegError::Format {
+                message: format!(
+                    "The sequence ended partway through frame {}",
+                    self.frames_read
+                ),
+                offset: self.position + bytes.len() as u64,
+            });
+        }
+
+        // Offsets within the frame are relative to the whole sequence
+        let frame = RpegImage::from_bytes(&bytes).map_err(|err| match err {
+            RpegError::Format { message, offset } => RpegError::Format {
+                message,
+                offset: self.position + offset,
+            },
+            err => err,
+        })?;
+        self.position += frame_len;
+        self.frames_read += 1;
+
+        Ok(Some(frame))
+    }
+}
+
+impl<R: Read> Iterator for RpegSequenceReader<R> {
+    type Item = Result<RpegImage, RpegError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+impl<R: Read + Seek> RpegSequenceReader<R> {
+    /// Reads the frame at `index` (counting from 0), going straight to it if the sequence has
+    /// an index, or otherwise skipping over the frames before it without reading them. Frames
+    /// read afterwards with [`RpegSequenceReader::next_frame`] follow on from this one.
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::FrameOutOfRange`] if the sequence doesn't have that many frames
+    /// * If there is an error reading from or seeking in the underlying source
+    /// * If the sequence is cut off, or the frame isn't valid rpeg data
+    pub fn read_frame(&mut self, index: u32) -> Result<RpegImage, RpegError> {
+        if index >= self.frame_count {
+            return Err(RpegError::FrameOutOfRange {
+                index,
+                frame_count: self.frame_count,
+            });
+        }
+
+        if self.offsets.is_none() {
+            let frame_count = self.frame_count;
+            let offsets = match read_index(&mut self.reader, frame_count)? {
+                Some((offsets, _)) => offsets,
+                None => scan_offsets(&mut self.reader, self.header_len, frame_count)?.0,
+            };
+            self.offsets = Some(offsets);
+        }
+
+        self.position = self.offsets.as_ref().unwrap()[index as usize];
+        self.reader.seek(SeekFrom::Start(self.position))?;
+        self.frames_read = index;
+
+        Ok(self.next_frame()?.unwrap())
+    }
+}
+
+/// Reads the index at the end of a sequence, returning the offset of each frame and of the
+/// index itself, or `None` if there isn't a (valid) index.
+fn read_index(
+    reader: &mut (impl Read + Seek),
+    frame_count: u32,
+) -> Result<Option<(Vec<u64>, u64)>, RpegError> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if len < FOOTER_LEN as u64 {
+        return Ok(None);
+    }
+
+    let mut footer = [0; FOOTER_LEN];
+    reader.seek(SeekFrom::End(-(FOOTER_LEN as i64)))?;
+    reader.read_exact(&mut footer)?;
+    let Some(index_offset) = std::str::from_utf8(&footer)
+        .ok()
+        .and_then(|footer| footer.strip_prefix(FOOTER_PREFIX))
+        .and_then(|offset| offset.trim_end().parse::<u64>().ok())
+    else {
+        return Ok(None);
+    };
+
+    // An index left behind by an interrupted append may not match the frames any more
+    reader.seek(SeekFrom::Start(index_offset))?;
+    let mut lines = BufReader::new(reader).lines();
+    if !matches!(lines.next(), Some(Ok(line)) if line == INDEX_MAGIC) {
+        return Ok(None);
+    }
+
+    let offsets = lines
+        .take(frame_count as usize)
+        .map(|line| line.ok().and_then(|line| line.parse().ok()))
+        .collect::<Option<Vec<u64>>>();
+
+    Ok(offsets
+        .filter(|offsets| offsets.len() == frame_count as usize)
+        .map(|offsets| (offsets, index_offset)))
+}
+
+/// Finds the offset of each frame by skipping over them from the start, also returning the
+/// offset of the end of the last frame.
+fn scan_offsets(
+    reader: &mut (impl Read + Seek),
+    header_len: u64,
+    frame_count: u32,
+) -> Result<(Vec<u64>, u64), RpegError> {
+    // The frame count comes from the file, so it's only trusted as far as the frames are found
+    let mut offsets = Vec::new();
+    let mut offset = header_len;
+
+    for index in 0..frame_count {
+        reader.seek(SeekFrom::Start(offset))?;
+        let line = read_line(&mut BufReader::new(&mut *reader), offset)?;
+        let frame_len = parse_frame_len(&line, index, offset)?;
+
+        offsets.push(offset);
+        offset = (line.len() as u64 + 1)
+            .checked_add(frame_len)
+            .and_then(|len| offset.checked_add(len))
+            .ok_or_else(|| RpegError::Format {
+                message: format!("The length of frame {index} is too large"),
+                offset,
+            })?;
+    }
+
+    Ok((offsets, offset))
+}
+
+fn parse_frame_len(line: &str, index: u32, offset: u64) -> Result<u64, RpegError> {
+    line.strip_prefix(FRAME_PREFIX)
+        .and_then(|len| len.parse().ok())
+        .ok_or_else(|| RpegError::Format {
+            message: format!("Expected the length of frame {index}"),
+            offset,
+        })
+}
+
+/// Reads the first two lines of a sequence, returning its frame count and their length.
+fn read_sequence_header(reader: &mut impl BufRead) -> Result<(u32, u64), RpegError> {
+    let magic = read_line(reader, 0)?;
+    if magic != SEQUENCE_MAGIC {
+        return Err(RpegError::Format {
+            message: format!("Expected {SEQUENCE_MAGIC:?}, found {magic:?}"),
+            offset: 0,
+        });
+    }
+
+    let offset = magic.len() as u64 + 1;
+    let line = read_line(reader, offset)?;
+    let frame_count = line
+        .strip_prefix(FRAMES_PREFIX)
+        .and_then(|count| count.parse().ok())
+        .ok_or_else(|| RpegError::Format {
+            message: "Expected the number of frames in the sequence".to_string(),
+            offset,
+        })?;
+
+    Ok((frame_count, offset + line.len() as u64 + 1))
+}
+
+/// Reads a line (which must end in `\n`) without its line ending.
+fn read_line(reader: &mut impl BufRead, offset: u64) -> Result<String, RpegError> {
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line)?;
+
+    if line.pop() != Some(b'\n') {
+        return Err(RpegError::Format {
+            message: "The sequence ended unexpectedly".to_string(),
+            offset: offset + line.len() as u64,
+        });
+    }
+
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn frame(index: u8) -> RpegImage {
+        RpegImage::new(vec![[index, 0x0A, 0x0D, 0x1A]; index as usize + 1], 2, 2)
+    }
+
+    #[test]
+    fn test_append() {
+        let mut writer = RpegSequenceWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_frame(&frame(0)).unwrap();
+        let mut file = writer.finish().unwrap();
+
+        let mut writer = RpegSequenceWriter::append(&mut file).unwrap();
+        assert_eq!(writer.frame_count(), 1);
+        writer.write_frame(&frame(1)).unwrap();
+        writer.write_frame(&frame(2)).unwrap();
+        writer.finish().unwrap();
+
+        let bytes = file.into_inner();
+        assert!(bytes.starts_with(b"Compressed image sequence\nframes 0000000003\nframe 34\n"));
+
+        let reader = RpegSequenceReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.frame_count(), 3);
+        let frames = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(frames, vec![frame(0), frame(1), frame(2)]);
+    }
+
+    #[test]
+    fn test_truncated_sequence() {
+        let mut writer = RpegSequenceWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_frame(&frame(0)).unwrap();
+        writer.write_frame(&frame(1)).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut reader = RpegSequenceReader::new(&bytes[..bytes.len() - 1]).unwrap();
+        assert!(reader.next_frame().unwrap().is_some());
+        assert!(matches!(
+            reader.next_frame(),
+            Err(RpegError::Format { offset, .. }) if offset == bytes.len() as u64 - 1
+        ));
+
+        assert!(RpegSequenceReader::new(b"Compressed image format 2\n".as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_damaged_frame_count() {
+        let mut writer = RpegSequenceWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_frame(&frame(0)).unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+
+        // A frame count far beyond the frames there are, which must not be allocated for up front
+        let count = b"frames 0000000001";
+        let at = bytes.windows(count.len()).position(|w| w == count).unwrap();
+        bytes[at..at + count.len()].copy_from_slice(b"frames 4294967295");
+
+        let mut reader = RpegSequenceReader::new(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(reader.frame_count(), u32::MAX);
+        assert!(matches!(
+            reader.read_frame(1),
+            Err(RpegError::Format { offset, .. }) if offset == bytes.len() as u64
+        ));
+
+        // And a frame length which overflows the offset of the next frame
+        let mut bytes = b"Compressed image sequence\nframes 0000000002\n".to_vec();
+        bytes.extend(format!("frame {}\n", u64::MAX).as_bytes());
+        let mut reader = RpegSequenceReader::new(Cursor::new(bytes)).unwrap();
+        assert!(matches!(
+            reader.read_frame(1),
+            Err(RpegError::Format { .. })
+        ));
+    }
+
+    #[test]
+    fn test_seeking() {
+        for indexed in [false, true] {
+            let file = Cursor::new(Vec::new());
+            let mut writer = match indexed {
+                false => RpegSequenceWriter::new(file).unwrap(),
+                true => RpegSequenceWriter::new_indexed(file).unwrap(),
+            };
+            for index in 0..3 {
+                writer.write_frame(&frame(index)).unwrap();
+            }
+            let mut file = writer.finish().unwrap();
+
+            // Appending replaces the index with a longer one
+            let mut writer = RpegSequenceWriter::append(&mut file).unwrap();
+            writer.write_frame(&frame(3)).unwrap();
+            writer.finish().unwrap();
+
+            let bytes = file.into_inner();
+            if indexed {
+                let footer = &bytes[bytes.len() - FOOTER_LEN..];
+                assert!(footer.starts_with(b"index at 000000000000000"));
+            }
+
+            let mut reader = RpegSequenceReader::new(Cursor::new(bytes)).unwrap();
+            assert_eq!(reader.frame_count(), 4);
+            assert_eq!(reader.read_frame(2).unwrap(), frame(2));
+            assert_eq!(reader.next_frame().unwrap(), Some(frame(3)));
+            assert_eq!(reader.next_frame().unwrap(), None);
+            assert_eq!(reader.read_frame(0).unwrap(), frame(0));
+            assert!(matches!(
+                reader.read_frame(4),
+                Err(RpegError::FrameOutOfRange {
+                    index: 4,
+                    frame_count: 4
+                })
+            ));
+        }
+    }
+}
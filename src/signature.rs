@@ -0,0 +1,200 @@
+use crate::checksum::Crc32;
+use crate::header::{self, RpegHeader};
+use crate::sha256::HmacSha256;
+use crate::trailer::{self, SIGNATURE_LEN};
+use crate::{RpegError, RpegImage};
+
+impl RpegImage {
+    /// Encodes the image as signed rpeg data, for reference outputs which shouldn't be modified
+    /// and passed off as something else. Enabled by the `crypto` feature.
+    ///
+    /// Signed data is marked by an `s` after the version in the header ("Compressed image format
+    /// 2s"), and ends with an HMAC-SHA256 of the header and everything after it under `key`
+    /// (after any other trailers, such as a checksum). Only someone with the key can make a valid
+    /// signature, so reading the data with the same key set by [`ReadOptions::verify_key`]
+    /// catches any change to it.
+    ///
+    /// Readers without a key (or without the `crypto` feature) still read signed data, ignoring
+    /// its signature.
+    ///
+    /// [`ReadOptions::verify_key`]: crate::ReadOptions::verify_key
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{ReadOptions, RpegError, RpegImage};
+    ///
+    /// let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]], 2, 2);
+    /// let mut signed = image.to_signed(b"grader's key");
+    /// assert!(signed.starts_with(b"Compressed image format 2s\n"));
+    ///
+    /// let options = ReadOptions::new().verify_key(b"grader's key");
+    /// let verified = RpegImage::from_bytes_with_options(&signed, &options).unwrap();
+    /// assert!(verified.header.signed);
+    /// assert_eq!(verified.words, image.words);
+    ///
+    /// signed[31] ^= 0xFF;
+    /// assert!(matches!(
+    ///     RpegImage::from_bytes_with_options(&signed, &options),
+    ///     Err(RpegError::SignatureMismatch)
+    /// ));
+    /// ```
+    pub fn to_signed(&self, key: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(64 + self.words.len() * 4);
+        header::write_signed_header(&mut bytes, &self.header).expect("Writing to a Vec can't fail");
+        let header_len = bytes.len();
+
+        let mut crc = Crc32::new();
+        for word in &self.words {
+            bytes.extend_from_slice(word);
+            crc.update(word);
+        }
+        trailer::write(&mut bytes, &self.header, self.words.len() as u64, &crc)
+            .expect("Writing to a Vec can't fail");
+
+        let mut mac = start(key, &self.header);
+        mac.update(&bytes[header_len..]);
+        bytes.extend(mac.finish());
+
+        bytes
+    }
+}
+
+/// Starts the signature of data with the given header. Rather than the header's bytes as they
+/// were read, the signature covers the header as it's written, which is the same for a header
+/// read back from written data, and so can be worked out again by streaming readers.
+pub(crate) fn start(key: &[u8], header: &RpegHeader) -> HmacSha256 {
+    let mut bytes = Vec::new();
+    header::write_signed_header(&mut bytes, header).expect("Writing to a Vec can't fail");
+
+    let mut mac = HmacSha256::new(key);
+    mac.update(&bytes);
+    mac
+}
+
+/// Checks a finished signature against the one at the end of the data (if it has one).
+pub(crate) fn check(
+    mac: HmacSha256,
+    signature: Option<[u8; SIGNATURE_LEN]>,
+) -> Result<(), RpegError> {
+    let signature = signature.ok_or(RpegError::SignatureMissing)?;
+
+    // Every byte is compared, so that the time taken doesn't reveal how much of it matched
+    let difference = mac
+        .finish()
+        .iter()
+        .zip(signature)
+        .fold(0, |difference, (a, b)| difference | (a ^ b));
+
+    match difference {
+        0 => Ok(()),
+        _ => Err(RpegError::SignatureMismatch),
+    }
+}
+
+/// Checks the signature of data with the given header, where `rest` is everything after the
+/// header, and `end` is the offset of the end of the data.
+pub(crate) fn verify(
+    key: &[u8],
+    header: &RpegHeader,
+    rest: &[u8],
+    end: u64,
+) -> Result<(), RpegError> {
+    let (signed, signature) = trailer::split_signature(header, rest, end)?;
+
+    let mut mac = start(key, header);
+    mac.update(signed);
+    check(mac, signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReadOptions, RpegConcatReader, RpegReader, RpegWriter, WriteOptions};
+
+    #[test]
+    fn test_signed_image() {
+        let mut image = RpegImage::new(vec![[0x01, 0x23, 0x45, 0x67]; 4], 4, 4);
+        image.header.checksum = true;
+        image.header.word_count = true;
+        image.header.signed = true;
+        image.header.comments = vec!["line one".to_string(), "line two".to_string()];
+        let signed = image.to_signed(b"key");
+        assert!(signed.starts_with(b"Compressed image format 2cts\n"));
+
+        // Without a key, the signature is simply ignored
+        assert_eq!(RpegImage::from_bytes(&signed).unwrap(), image);
+        let options = ReadOptions::new().verify_key(b"key");
+        assert_eq!(
+            RpegImage::from_bytes_with_options(&signed, &options).unwrap(),
+            image
+        );
+
+        let wrong_key = ReadOptions::new().verify_key(b"not the key");
+        assert!(matches!(
+            RpegImage::from_bytes_with_options(&signed, &wrong_key),
+            Err(RpegError::SignatureMismatch)
+        ));
+        assert!(matches!(
+            RpegImage::from_bytes_with_options(&image.to_bytes(), &options),
+            Err(RpegError::SignatureMissing)
+        ));
+
+        // Changing a comment changes the signed header, and so the signature
+        let mut tampered = signed.clone();
+        let comment = tampered
+            .windows(3)
+            .position(|bytes| bytes == b"one")
+            .unwrap();
+        tampered[comment..comment + 3].copy_from_slice(b"two");
+        assert!(matches!(
+            RpegImage::from_bytes_with_options(&tampered, &options),
+            Err(RpegError::SignatureMismatch)
+        ));
+        tampered.truncate(20);
+        assert!(RpegImage::from_bytes_with_options(&tampered, &options).is_err());
+    }
+
+    #[test]
+    fn test_signed_stream() {
+        let options = WriteOptions::new().checksum(true).signing_key(b"key");
+        let mut writer = RpegWriter::with_options(Vec::new(), 4, 2, &options).unwrap();
+        writer.write_word([0x00, 0x11, 0x22, 0x33]).unwrap();
+        writer.write_word([0x44, 0x55, 0x66, 0x77]).unwrap();
+        let signed = writer.finish().unwrap();
+
+        let mut image = RpegImage::new(
+            vec![[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]],
+            4,
+            2,
+        );
+        image.header.checksum = true;
+        image.header.signed = true;
+        assert_eq!(signed, image.to_signed(b"key"));
+
+        let options = ReadOptions::new().verify_key(b"key");
+        let reader = RpegReader::with_options(&signed[..], &options).unwrap();
+        assert_eq!(reader.count(), 2);
+
+        // The signature is only checked once the whole payload has been read
+        let mut tampered = signed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        let mut reader = RpegReader::with_options(&tampered[..], &options).unwrap();
+        assert!(reader.read_word().unwrap().is_some());
+        assert!(reader.read_word().unwrap().is_some());
+        assert!(matches!(
+            reader.read_word(),
+            Err(RpegError::SignatureMismatch)
+        ));
+        assert!(matches!(
+            RpegReader::with_options(&image.to_bytes()[..], &options),
+            Err(RpegError::SignatureMissing)
+        ));
+
+        let bytes = [signed.clone(), signed].concat();
+        let reader = RpegConcatReader::with_options(&bytes[..], &options);
+        assert_eq!(
+            reader.collect::<Result<Vec<_>, _>>().unwrap(),
+            [image.clone(), image]
+        );
+    }
+}
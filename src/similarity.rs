@@ -0,0 +1,208 @@
+use crate::{BitLayout, RpegImage};
+use std::fmt;
+
+/// How similar two images' words are, from [`similarity`]. Unlike an
+/// [`RpegDiff`](crate::RpegDiff), which lists what differs, this measures how much, so that output
+/// which is nearly right (such as one with a rounding error in a single field) can be told apart
+/// from output which is completely wrong, such as when giving partial credit.
+///
+/// Its [`Display`](fmt::Display) implementation summarizes it in a line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Similarity {
+    /// The number of words in the longer image
+    pub word_count: usize,
+    /// The number of words which are exactly the same in both images
+    pub identical_words: usize,
+    /// The mean absolute difference between the bytes of the two images, from 0 (the same) to
+    /// 255. Every byte of a word past the end of the shorter image counts as differing by 255
+    pub mean_byte_difference: f64,
+    /// How similar each field of the layout is, in the layout's order (empty if no layout was
+    /// given)
+    pub fields: Vec<FieldSimilarity>,
+}
+
+/// How similar one field of two images' words is, as part of a [`Similarity`]. Only the words
+/// in both images are compared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSimilarity {
+    /// The name of the field in the layout
+    pub name: String,
+    /// The number of words compared: those in both images
+    pub compared_words: usize,
+    /// The number of words in which the field has the same value in both images
+    pub identical_words: usize,
+    /// The mean absolute difference between the values of the field
+    pub mean_difference: f64,
+    /// The biggest absolute difference between the values of the field
+    pub max_difference: u64,
+}
+
+impl Similarity {
+    /// The fraction of words which are exactly the same in both images, from 0 to 1. Two empty
+    /// images are identical.
+    pub fn identical_fraction(&self) -> f64 {
+        fraction(self.identical_words, self.word_count)
+    }
+}
+
+impl FieldSimilarity {
+    /// The fraction of the words compared in which the field has the same value in both images,
+    /// from 0 to 1.
+    pub fn identical_fraction(&self) -> f64 {
+        fraction(self.identical_words, self.compared_words)
+    }
+}
+
+impl fmt::Display for Similarity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1}% of {} words identical, mean byte difference {:.2}",
+            self.identical_fraction() * 100.0,
+            self.word_count,
+            self.mean_byte_difference
+        )?;
+
+        for field in &self.fields {
+            write!(
+                f,
+                "; {}: {:.1}% identical, mean difference {:.2}",
+                field.name,
+                field.identical_fraction() * 100.0,
+                field.mean_difference
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Measures how similar two images' words are, such as a student's output (`right`) against a
+/// reference (`left`). See [`Similarity`].
+///
+/// Only the words of the images are compared, not their sizes or other header fields.
+///
+/// # Arguments
+///
+/// * `left` - The first image, such as a reference output
+/// * `right` - The second image
+/// * `layout` - An optional layout of the fields of each word, to also compare field by field
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::{similarity, BitLayout, RpegImage};
+///
+/// let expected = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]; 4], 4, 4);
+/// let mut actual = expected.clone();
+/// actual.words[0][3] = 0x34;
+/// let layout: BitLayout = "a:9, b:5s, c:5s, d:5s, pb:4, pr:4".parse().unwrap();
+///
+/// let similarity = similarity(&expected, &actual, Some(&layout));
+/// assert_eq!(similarity.identical_fraction(), 0.75);
+/// assert_eq!(similarity.mean_byte_difference, 1.0 / 16.0);
+///
+/// // Only the `pr` field of one word is off, and only by one
+/// let pr = &similarity.fields[5];
+/// assert_eq!((pr.name.as_str(), pr.identical_words, pr.max_difference), ("pr", 3, 1));
+/// assert!(similarity.fields[..5].iter().all(|field| field.identical_fraction() == 1.0));
+/// ```
+pub fn similarity(left: &RpegImage, right: &RpegImage, layout: Option<&BitLayout>) -> Similarity {
+    let word_count = left.words.len().max(right.words.len());
+    let common = left.words.iter().zip(&right.words);
+
+    let identical_words = common.clone().filter(|(left, right)| left == right).count();
+    let missing_words = word_count - left.words.len().min(right.words.len());
+    let total_byte_difference: u64 = common
+        .clone()
+        .flat_map(|(left, right)| left.iter().zip(right))
+        .map(|(left, right)| left.abs_diff(*right) as u64)
+        .chain(std::iter::once(missing_words as u64 * 4 * 255))
+        .sum();
+
+    let fields = layout
+        .into_iter()
+        .flat_map(|layout| layout.fields())
+        .map(|(name, field)| {
+            let differences: Vec<u64> = common
+                .clone()
+                .map(|(left, right)| field.value(*left).abs_diff(field.value(*right)))
+                .collect();
+
+            FieldSimilarity {
+                name: name.to_string(),
+                compared_words: differences.len(),
+                identical_words: differences.iter().filter(|&&diff| diff == 0).count(),
+                mean_difference: mean(differences.iter().sum(), differences.len()),
+                max_difference: differences.iter().copied().max().unwrap_or(0),
+            }
+        })
+        .collect();
+
+    Similarity {
+        word_count,
+        identical_words,
+        mean_byte_difference: mean(total_byte_difference, word_count * 4),
+        fields,
+    }
+}
+
+/// `part` as a fraction of `whole`, taking nothing out of nothing to be everything.
+fn fraction(part: usize, whole: usize) -> f64 {
+    match whole {
+        0 => 1.0,
+        whole => part as f64 / whole as f64,
+    }
+}
+
+/// The mean of values adding up to `total`, or 0 if there are none.
+fn mean(total: u64, count: usize) -> f64 {
+    match count {
+        0 => 0.0,
+        count => total as f64 / count as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similarity() {
+        let left = RpegImage::new(vec![[0x00; 4]; 3], 4, 3);
+        let right = RpegImage::new(vec![[0x00; 4], [0x00, 0x00, 0x00, 0x0A]], 4, 2);
+        let similarity = similarity(&left, &right, None);
+
+        assert_eq!(similarity.word_count, 3);
+        assert_eq!(similarity.identical_words, 1);
+        // The missing word counts as completely different
+        assert_eq!(similarity.mean_byte_difference, (10.0 + 4.0 * 255.0) / 12.0);
+        assert!(similarity.fields.is_empty());
+        assert_eq!(
+            similarity.to_string(),
+            "33.3% of 3 words identical, mean byte difference 85.83"
+        );
+
+        let empty = RpegImage::new(vec![], 0, 0);
+        let similarity = super::similarity(&empty, &empty, None);
+        assert_eq!(similarity.identical_fraction(), 1.0);
+        assert_eq!(similarity.mean_byte_difference, 0.0);
+    }
+
+    #[test]
+    fn test_field_similarity() {
+        let layout: BitLayout = "a:16, b:16s".parse().unwrap();
+        let left = RpegImage::new(vec![[0x00, 0x01, 0x00, 0x05], [0x00; 4]], 4, 2);
+        let right = RpegImage::new(vec![[0x00, 0x01, 0xFF, 0xFF], [0x00; 4], [0x01; 4]], 4, 3);
+        let similarity = similarity(&left, &right, Some(&layout));
+
+        let a = &similarity.fields[0];
+        assert_eq!((a.compared_words, a.identical_words), (2, 2));
+        // b is 5 in the first word of left and -1 in right
+        let b = &similarity.fields[1];
+        assert_eq!((b.identical_words, b.max_difference), (1, 6));
+        assert_eq!(b.mean_difference, 3.0);
+        assert!(similarity.to_string().ends_with(
+            "; a: 100.0% identical, mean difference 0.00; b: 50.0% identical, mean difference 3.00"
+        ));
+    }
+}
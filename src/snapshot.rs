@@ -0,0 +1,140 @@
+use crate::RpegImage;
+use std::fmt::Write;
+
+/// The version of the snapshot format, on its first line. See [`RpegImage::to_snapshot`].
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// The most words on each line of a snapshot, which keeps lines of 4-byte words under 80
+/// characters.
+const WORDS_PER_LINE: usize = 8;
+
+impl<const N: usize> RpegImage<N> {
+    /// Renders the image as text meant for snapshot (golden file) tests, such as with `insta`:
+    /// every field of the header on its own line, then the words as a grid of hex.
+    ///
+    /// The header is written in full and always in the same order (extra fields and metadata in
+    /// order of name), so two images have the same snapshot exactly when they're equal. The
+    /// words follow one row of 2x2 blocks at a time, at most 8 words to a line, with each line
+    /// labelled by the row and column of its first word.
+    ///
+    /// # Stability
+    ///
+    /// The first line of a snapshot is `rpeg snapshot 1`. The rendering for that version will
+    /// never change, so snapshots won't churn when this crate is updated. If the rendering ever
+    /// does need to change, the version on the first line will change with it, and only in a
+    /// new major version of the crate.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let mut image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]; 3], 6, 2);
+    /// image.header.comments.push("from \"tiny.ppm\"".to_string());
+    ///
+    /// assert_eq!(
+    ///     image.to_snapshot(),
+    ///     "rpeg snapshot 1\n\
+    ///      size 6x2\n\
+    ///      version 2\n\
+    ///      checksum no\n\
+    ///      word_count no\n\
+    ///      signed no\n\
+    ///      binary no\n\
+    ///      comment \"from \\\"tiny.ppm\\\"\"\n\
+    ///      words 3 (4 bytes each)\n\
+    ///      r0 c0: 00112233 00112233 00112233\n"
+    /// );
+    /// ```
+    pub fn to_snapshot(&self) -> String {
+        let header = &self.header;
+        let yes_no = |flag: bool| if flag { "yes" } else { "no" };
+
+        let mut snapshot = String::with_capacity(160 + self.words.len() * (N * 2 + 1));
+        writeln!(snapshot, "rpeg snapshot {SNAPSHOT_VERSION}").unwrap();
+        writeln!(snapshot, "size {}x{}", header.width, header.height).unwrap();
+        writeln!(snapshot, "version {}", header.version).unwrap();
+        writeln!(snapshot, "checksum {}", yes_no(header.checksum)).unwrap();
+        writeln!(snapshot, "word_count {}", yes_no(header.word_count)).unwrap();
+        writeln!(snapshot, "signed {}", yes_no(header.signed)).unwrap();
+        writeln!(snapshot, "binary {}", yes_no(header.binary)).unwrap();
+        for (name, value) in &header.extra_fields {
+            writeln!(snapshot, "field {name:?} {value}").unwrap();
+        }
+        for comment in &header.comments {
+            writeln!(snapshot, "comment {comment:?}").unwrap();
+        }
+        for (key, value) in &header.metadata {
+            writeln!(snapshot, "metadata {key:?} {value:?}").unwrap();
+        }
+        writeln!(snapshot, "words {} ({N} bytes each)", self.words.len()).unwrap();
+
+        // Labels are padded to the same width, so the words line up
+        let row_len = (header.width as usize / 2).max(1);
+        let rows = self.words.len().div_ceil(row_len);
+        let row_digits = rows.saturating_sub(1).to_string().len();
+        let last_column = row_len.min(self.words.len()).saturating_sub(1);
+        let column_digits = (last_column / WORDS_PER_LINE * WORDS_PER_LINE)
+            .to_string()
+            .len();
+
+        for (row, words) in self.words.chunks(row_len).enumerate() {
+            for (line, words) in words.chunks(WORDS_PER_LINE).enumerate() {
+                let column = line * WORDS_PER_LINE;
+                write!(snapshot, "r{row:<row_digits$} c{column:<column_digits$}:").unwrap();
+                for word in words {
+                    snapshot.push(' ');
+                    for byte in word {
+                        write!(snapshot, "{byte:02x}").unwrap();
+                    }
+                }
+                snapshot.push('\n');
+            }
+        }
+
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_grid() {
+        // 10 words to a row, so each row wraps after 8
+        let words = (0..25u8).map(|index| [index; 4]).collect();
+        let snapshot = RpegImage::new(words, 20, 6).to_snapshot();
+        let lines: Vec<&str> = snapshot.lines().skip(8).collect();
+
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("r0 c0: 00000000 01010101 "));
+        assert_eq!(lines[1], "r0 c8: 08080808 09090909");
+        assert_eq!(
+            lines[4],
+            "r2 c0: 14141414 15151515 16161616 17171717 18181818"
+        );
+
+        // Labels are padded to line up
+        let snapshot = RpegImage::new(vec![[0; 4]; 20 * 12], 40, 24).to_snapshot();
+        assert!(snapshot.contains("\nr0  c0 : 00000000"));
+        assert!(snapshot.contains("\nr3  c16: 00000000"));
+        assert!(snapshot.ends_with("\nr11 c16: 00000000 00000000 00000000 00000000\n"));
+    }
+
+    #[test]
+    fn test_snapshot_header() {
+        let mut image = RpegImage::<2>::new(vec![], 0, 0);
+        image.header.checksum = true;
+        image.header.extra_fields.insert("quality".to_string(), 90);
+        image
+            .header
+            .metadata
+            .insert("tool".to_string(), "rpeg=1".to_string());
+        let snapshot = image.to_snapshot();
+
+        assert!(snapshot.starts_with("rpeg snapshot 1\nsize 0x0\nversion 2\nchecksum yes\n"));
+        assert!(snapshot.ends_with(
+            "binary no\nfield \"quality\" 90\nmetadata \"tool\" \"rpeg=1\"\nwords 0 (2 bytes each)\n"
+        ));
+    }
+}
@@ -0,0 +1,222 @@
+//! Where rpeg data is read from and written to, so that the same functions can work with files,
+//! stdin and stdout, or bytes in memory (as in tests, where stdin and stdout can't be swapped
+//! out).
+
+use crate::stdio;
+use std::io::{Cursor, Read, Write};
+
+/// Somewhere rpeg data (or PPM data) can be read from, such as by
+/// [`read_in_rpeg_data_from`](crate::read_in_rpeg_data_from).
+///
+/// This is implemented for:
+///
+/// * `Option<&str>` - a file path, or stdin if None, as taken by
+///   [`read_in_rpeg_data`](crate::read_in_rpeg_data)
+/// * `&str` and `&Path` - the file at that path
+/// * [`File`](std::fs::File) and [`Stdin`](std::io::Stdin) (which is read in binary mode, as
+///   with [`read_in_rpeg_data`](crate::read_in_rpeg_data))
+/// * `&[u8]`, `&[u8; N]`, `Vec<u8>`, and `Cursor`s of them - bytes already in memory (from the
+///   cursor's position onwards)
+///
+/// # Examples
+/// ```
+/// let bytes = b"Compressed image format 2\n2 2\n\x00\x11\x22\x33";
+/// let (raw_bytes, width, height) = csc411_rpegio::read_in_rpeg_data_from(bytes).unwrap();
+///
+/// assert_eq!((raw_bytes, width, height), (vec![[0x00, 0x11, 0x22, 0x33]], 2, 2));
+/// ```
+pub trait RpegSource {
+    /// Reads every byte from the source.
+    fn read_bytes(self) -> std::io::Result<Vec<u8>>;
+}
+
+/// Somewhere rpeg data (or PPM data) can be written to, such as by
+/// [`try_output_rpeg_data_to`](crate::try_output_rpeg_data_to).
+///
+/// This is implemented for:
+///
+/// * `&str` and `&Path` - the file at that path, which is created or replaced
+/// * [`File`](std::fs::File) and [`Stdout`](std::io::Stdout) (which is written in binary mode,
+///   as with [`output_rpeg_data`](crate::output_rpeg_data))
+/// * `Vec<u8>` and `Cursor`s of one or of a `&mut [u8]` - memory, which the data is added to
+///   (or written over, from the cursor's position onwards)
+///
+/// # Examples
+/// ```
+/// let mut output = Vec::new();
+/// csc411_rpegio::try_output_rpeg_data_to(&mut output, &[[0x00, 0x11, 0x22, 0x33]], 2, 2)
+///     .unwrap();
+///
+/// assert_eq!(output, b"Compressed image format 2\n2 2\n\x00\x11\x22\x33");
+/// ```
+pub trait RpegSink {
+    /// Returns a writer for the sink. It's buffered if writing straight to the sink would be
+    /// slow, so it must be flushed once everything has been written.
+    fn writer(&mut self) -> std::io::Result<Box<dyn Write + '_>>;
+}
+
+impl RpegSource for Option<&str> {
+    fn read_bytes(self) -> std::io::Result<Vec<u8>> {
+        match stdio::input_path(self).as_deref() {
+            Some(file_path) => file_path.read_bytes(),
+            None => std::io::stdin().read_bytes(),
+        }
+    }
+}
+
+impl RpegSource for &str {
+    fn read_bytes(self) -> std::io::Result<Vec<u8>> {
+        std::path::Path::new(self).read_bytes()
+    }
+}
+
+impl RpegSource for &std::path::Path {
+    fn read_bytes(self) -> std::io::Result<Vec<u8>> {
+        std::fs::read(self)
+    }
+}
+
+impl RpegSource for std::fs::File {
+    fn read_bytes(mut self) -> std::io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.read_to_end(&mut bytes)?;
+
+        Ok(bytes)
+    }
+}
+
+impl RpegSource for std::io::Stdin {
+    fn read_bytes(self) -> std::io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        stdio::binary_stdin().read_to_end(&mut bytes)?;
+
+        Ok(bytes)
+    }
+}
+
+impl RpegSource for &[u8] {
+    fn read_bytes(self) -> std::io::Result<Vec<u8>> {
+        Ok(self.to_vec())
+    }
+}
+
+impl<const N: usize> RpegSource for &[u8; N] {
+    fn read_bytes(self) -> std::io::Result<Vec<u8>> {
+        Ok(self.to_vec())
+    }
+}
+
+impl RpegSource for Vec<u8> {
+    fn read_bytes(self) -> std::io::Result<Vec<u8>> {
+        Ok(self)
+    }
+}
+
+impl<T: AsRef<[u8]>> RpegSource for Cursor<T> {
+    fn read_bytes(mut self) -> std::io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.read_to_end(&mut bytes)?;
+
+        Ok(bytes)
+    }
+}
+
+impl RpegSink for &str {
+    fn writer(&mut self) -> std::io::Result<Box<dyn Write + '_>> {
+        let file = std::fs::File::create(*self)?;
+
+        Ok(Box::new(std::io::BufWriter::new(file)))
+    }
+}
+
+impl RpegSink for &std::path::Path {
+    fn writer(&mut self) -> std::io::Result<Box<dyn Write + '_>> {
+        let file = std::fs::File::create(*self)?;
+
+        Ok(Box::new(std::io::BufWriter::new(file)))
+    }
+}
+
+impl RpegSink for std::fs::File {
+    fn writer(&mut self) -> std::io::Result<Box<dyn Write + '_>> {
+        Ok(Box::new(std::io::BufWriter::new(self)))
+    }
+}
+
+impl RpegSink for std::io::Stdout {
+    fn writer(&mut self) -> std::io::Result<Box<dyn Write + '_>> {
+        Ok(Box::new(std::io::BufWriter::new(stdio::binary_stdout())))
+    }
+}
+
+impl RpegSink for Vec<u8> {
+    fn writer(&mut self) -> std::io::Result<Box<dyn Write + '_>> {
+        Ok(Box::new(self))
+    }
+}
+
+impl RpegSink for Cursor<Vec<u8>> {
+    fn writer(&mut self) -> std::io::Result<Box<dyn Write + '_>> {
+        Ok(Box::new(self))
+    }
+}
+
+impl RpegSink for Cursor<&mut Vec<u8>> {
+    fn writer(&mut self) -> std::io::Result<Box<dyn Write + '_>> {
+        Ok(Box::new(self))
+    }
+}
+
+impl RpegSink for Cursor<&mut [u8]> {
+    fn writer(&mut self) -> std::io::Result<Box<dyn Write + '_>> {
+        Ok(Box::new(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sources() {
+        let bytes = b"Compressed image format 2\n2 2\n\x00\x11\x22\x33";
+        let mut cursor = Cursor::new(bytes.to_vec());
+        cursor.set_position(11);
+
+        assert_eq!((&bytes[..]).read_bytes().unwrap(), bytes);
+        assert_eq!(bytes.to_vec().read_bytes().unwrap(), bytes);
+        assert_eq!(cursor.read_bytes().unwrap(), &bytes[11..]);
+
+        let path = std::env::temp_dir().join("csc411_rpegio_test_sources.rpeg");
+        std::fs::write(&path, bytes).unwrap();
+        let from_path = Some(path.to_str().unwrap()).read_bytes();
+        let from_file = std::fs::File::open(&path).unwrap().read_bytes();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(from_path.unwrap(), bytes);
+        assert_eq!(from_file.unwrap(), bytes);
+        assert!(Some("/does/not/exist.rpeg").read_bytes().is_err());
+    }
+
+    #[test]
+    fn test_sinks() {
+        let mut output = vec![0xFF];
+        output.writer().unwrap().write_all(b"abc").unwrap();
+        assert_eq!(output, b"\xFFabc");
+
+        let mut buffer = [0; 5];
+        let mut cursor = Cursor::new(&mut buffer[..]);
+        cursor.set_position(1);
+        cursor.writer().unwrap().write_all(b"abc").unwrap();
+        assert_eq!(buffer, *b"\0abc\0");
+
+        let path = std::env::temp_dir().join("csc411_rpegio_test_sinks.rpeg");
+        let mut writer = path.as_path();
+        let mut file = writer.writer().unwrap();
+        file.write_all(b"abc").unwrap();
+        file.flush().unwrap();
+        drop(file);
+        let written = std::fs::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written.unwrap(), b"abc");
+    }
+}
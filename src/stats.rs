@@ -0,0 +1,215 @@
+use crate::{RpegError, RpegImage};
+use std::fmt;
+
+/// Statistics of the bytes of an image's payload, from [`RpegImage::stats`].
+///
+/// Real compressed images have fairly high entropy. An entropy near zero, or a run of identical
+/// bytes covering most of the payload, usually means the same word is being written over and
+/// over, which makes for a quick automated sanity check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PayloadStats {
+    /// The number of times each byte value appears in the payload
+    pub histogram: [u64; 256],
+    /// The Shannon entropy of the bytes of the payload, in bits per byte (between 0 and 8)
+    pub entropy: f64,
+    /// The length of the longest run of identical bytes in the payload
+    pub longest_run: u64,
+    /// The byte repeated in the longest run (the first such run, if there's a tie), or None if
+    /// the payload is empty
+    pub longest_run_byte: Option<u8>,
+}
+
+impl PayloadStats {
+    /// The total number of bytes in the payload.
+    pub fn len(&self) -> u64 {
+        self.histogram.iter().sum()
+    }
+
+    /// Whether the payload is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// How much smaller an image's rpeg data is than the PPM it was compressed from, from
+/// [`RpegImage::compression_report`]. Its [`Display`](fmt::Display) implementation gives both
+/// numbers in a line.
+///
+/// Both numbers count every byte of each file, headers included: the compression ratio is the
+/// size of the PPM divided by the size of the rpeg data, and the bits per pixel are the bits of
+/// rpeg data for each pixel of the (trimmed) image it holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CompressionReport {
+    /// The size of the original PPM file, in bytes
+    pub ppm_bytes: u64,
+    /// The size of the rpeg data (as written by [`RpegImage::to_bytes`]), in bytes
+    pub rpeg_bytes: u64,
+    /// The number of pixels in the image, its width times its height
+    pub pixels: u64,
+}
+
+impl CompressionReport {
+    /// The size of the PPM divided by the size of the rpeg data.
+    pub fn ratio(&self) -> f64 {
+        self.ppm_bytes as f64 / self.rpeg_bytes as f64
+    }
+
+    /// The number of bits of rpeg data for each pixel of the image.
+    pub fn bits_per_pixel(&self) -> f64 {
+        (self.rpeg_bytes * 8) as f64 / self.pixels as f64
+    }
+}
+
+impl fmt::Display for CompressionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes -> {} bytes, a compression ratio of {:.2}:1 at {:.2} bits per pixel",
+            self.ppm_bytes,
+            self.rpeg_bytes,
+            self.ratio(),
+            self.bits_per_pixel()
+        )
+    }
+}
+
+impl RpegImage {
+    /// Compares the size of the image's rpeg data to the size of the PPM it was compressed from.
+    /// See [`CompressionReport`].
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let image = RpegImage::new(vec![[0x00; 4]; 100 * 75], 200, 150);
+    /// let report = image.compression_report(90_015);
+    ///
+    /// assert_eq!(report.rpeg_bytes, 30_034);
+    /// assert_eq!(format!("{:.3}", report.ratio()), "2.997");
+    /// assert_eq!(
+    ///     report.to_string(),
+    ///     "90015 bytes -> 30034 bytes, a compression ratio of 3.00:1 at 8.01 bits per pixel"
+    /// );
+    /// ```
+    pub fn compression_report(&self, ppm_bytes: u64) -> CompressionReport {
+        CompressionReport {
+            ppm_bytes,
+            rpeg_bytes: self.to_bytes().len() as u64,
+            pixels: self.width() as u64 * self.height() as u64,
+        }
+    }
+
+    /// Compares the size of the image's rpeg data to the size of the PPM file at `ppm_path`.
+    /// See [`RpegImage::compression_report`].
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the size of the file can't be found (such as if it doesn't exist)
+    pub fn compression_report_for(&self, ppm_path: &str) -> Result<CompressionReport, RpegError> {
+        let ppm_bytes = std::fs::metadata(ppm_path)?.len();
+
+        Ok(self.compression_report(ppm_bytes))
+    }
+}
+
+impl<const N: usize> RpegImage<N> {
+    /// Works out statistics of the bytes of the image's payload: how often each byte value
+    /// appears, their Shannon entropy, and the longest run of identical bytes. See
+    /// [`PayloadStats`].
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let stats = RpegImage::new(vec![[0x00, 0x00, 0xFF, 0xFF]; 4], 4, 4).stats();
+    /// assert_eq!(stats.histogram[0xFF], 8);
+    /// assert_eq!(stats.entropy, 1.0);
+    /// assert_eq!((stats.longest_run, stats.longest_run_byte), (2, Some(0x00)));
+    ///
+    /// let stats = RpegImage::new(vec![[0x2A; 4]; 1000], 100, 40).stats();
+    /// assert_eq!(stats.entropy, 0.0);
+    /// assert_eq!(stats.longest_run, 4000);
+    /// ```
+    pub fn stats(&self) -> PayloadStats {
+        let mut histogram = [0; 256];
+        let mut longest_run = 0;
+        let mut longest_run_byte = None;
+        let mut run: Option<(u8, u64)> = None;
+
+        for &byte in self.words.iter().flatten() {
+            histogram[byte as usize] += 1;
+
+            let len = match run {
+                Some((run_byte, len)) if run_byte == byte => len + 1,
+                _ => 1,
+            };
+            run = Some((byte, len));
+            if len > longest_run {
+                longest_run = len;
+                longest_run_byte = Some(byte);
+            }
+        }
+
+        let total: u64 = histogram.iter().sum();
+        let entropy = histogram
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let probability = count as f64 / total as f64;
+                -probability * probability.log2()
+            })
+            .sum::<f64>()
+            // Avoids a negative zero when every byte is the same
+            .abs();
+
+        PayloadStats {
+            histogram,
+            entropy,
+            longest_run,
+            longest_run_byte,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        // Every byte value once, so the entropy is as high as it gets
+        let words: Vec<[u8; 4]> = (0..64u8)
+            .map(|index| std::array::from_fn(|position| index * 4 + position as u8))
+            .collect();
+        let stats = RpegImage::new(words, 16, 16).stats();
+        assert!(stats.histogram.iter().all(|&count| count == 1));
+        assert!((stats.entropy - 8.0).abs() < 1e-9);
+        assert_eq!((stats.longest_run, stats.longest_run_byte), (1, Some(0)));
+        assert_eq!(stats.len(), 256);
+
+        let words = vec![[1, 2, 2, 3], [3, 3, 3, 2]];
+        let stats = RpegImage::new(words, 4, 2).stats();
+        assert_eq!((stats.longest_run, stats.longest_run_byte), (4, Some(3)));
+
+        let stats = RpegImage::<4>::new(vec![], 0, 0).stats();
+        assert!(stats.is_empty());
+        assert_eq!(stats.entropy, 0.0);
+        assert_eq!(stats.longest_run_byte, None);
+    }
+
+    #[test]
+    fn test_compression_report() {
+        let image = RpegImage::new(vec![[0x00; 4]; 4], 4, 4);
+        let report = image.compression_report(61);
+        assert_eq!(report.rpeg_bytes, 30 + 16);
+        assert_eq!(report.pixels, 16);
+        assert_eq!(report.bits_per_pixel(), 23.0);
+
+        let path = std::env::temp_dir().join("csc411_rpegio_test_compression_report.ppm");
+        std::fs::write(&path, [0; 61]).unwrap();
+        let found = image.compression_report_for(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(found.unwrap(), report);
+        assert!(image.compression_report_for("/does/not/exist.ppm").is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! Handles to stdin and stdout which read and write bytes exactly as given.
+//!
+//! On most platforms (WASI included) these are just the locked standard library handles. On
+//! Windows, the standard library's stdin and stdout treat a console as text (decoding input and
+//! rejecting non-UTF-8 output), so these instead read and write straight to the underlying OS
+//! handles. Either way, no newline translation is ever performed, so binary payload bytes like
+//! `0x0A`, `0x0D`, and `0x1A` (Ctrl-Z) pass through unchanged.
+//!
+//! The high-level functions which would read from stdin or write to stdout use the files named
+//! by [`INPUT_VAR`] and [`OUTPUT_VAR`] instead, when those environment variables are set.
+
+use std::io::Write;
+
+/// The environment variable naming a file for the high-level functions to read from in place
+/// of stdin.
+pub(crate) const INPUT_VAR: &str = "RPEG_INPUT";
+
+/// The environment variable naming a file for the high-level functions to write to in place of
+/// stdout.
+pub(crate) const OUTPUT_VAR: &str = "RPEG_OUTPUT";
+
+/// The file to read from: `file_path` if one was given, or else the file named by
+/// [`INPUT_VAR`], or None (for stdin) if that isn't set either.
+pub(crate) fn input_path(file_path: Option<&str>) -> Option<String> {
+    file_path.map(String::from).or_else(|| env_path(INPUT_VAR))
+}
+
+/// The file to write to in place of stdout, named by [`OUTPUT_VAR`], if it's set.
+pub(crate) fn output_path() -> Option<String> {
+    env_path(OUTPUT_VAR)
+}
+
+fn env_path(var: &str) -> Option<String> {
+    std::env::var(var).ok().filter(|path| !path.is_empty())
+}
+
+/// Where the high-level functions write: stdout in binary mode, or the file named by
+/// [`OUTPUT_VAR`].
+pub(crate) enum Output {
+    Stdout(BinaryStdout),
+    File(std::fs::File),
+}
+
+/// Returns a handle to the high-level functions' output, creating (or truncating) the file
+/// named by [`OUTPUT_VAR`] if it's set. See [`Output`].
+pub(crate) fn output() -> std::io::Result<Output> {
+    match output_path() {
+        Some(path) => Ok(Output::File(std::fs::File::create(path)?)),
+        None => Ok(Output::Stdout(binary_stdout())),
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::File(file) => file.write(buf),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.write_all(buf),
+            Output::File(file) => file.write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::File(file) => file.flush(),
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub(crate) type BinaryStdout = std::io::StdoutLock<'static>;
+
+/// Returns a handle to stdout which writes bytes exactly as given.
+#[cfg(not(windows))]
+pub(crate) fn binary_stdout() -> std::io::StdoutLock<'static> {
+    std::io::stdout().lock()
+}
+
+/// Returns a handle to stdin which reads bytes exactly as they were sent.
+#[cfg(not(windows))]
+pub(crate) fn binary_stdin() -> std::io::StdinLock<'static> {
+    std::io::stdin().lock()
+}
+
+#[cfg(windows)]
+pub(crate) use windows::{binary_stdin, binary_stdout, BinaryStdout};
+
+#[cfg(windows)]
+mod windows {
+    use std::io::{Read, Write};
+    use std::mem::ManuallyDrop;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle};
+
+    pub(crate) struct BinaryStdout {
+        // Held for the lifetime of the handle so that nothing else can interleave output with ours
+        _lock: std::io::StdoutLock<'static>,
+        raw: ManuallyDrop<std::fs::File>,
+    }
+
+    /// Returns a handle to stdout which writes bytes exactly as given.
+    pub(crate) fn binary_stdout() -> BinaryStdout {
+        let mut lock = std::io::stdout().lock();
+
+        // Anything already buffered in the standard library's stdout must come out first
+        let _ = lock.flush();
+
+        // SAFETY: the handle is owned by the standard library's stdout, which outlives us. The
+        // File is wrapped in ManuallyDrop so that we never close the handle ourselves
+        let raw = unsafe { std::fs::File::from_raw_handle(lock.as_raw_handle()) };
+
+        BinaryStdout {
+            _lock: lock,
+            raw: ManuallyDrop::new(raw),
+        }
+    }
+
+    impl Write for BinaryStdout {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.raw.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.raw.flush()
+        }
+    }
+
+    pub(crate) struct BinaryStdin {
+        // Held for the lifetime of the handle so that nothing else can read input out from under us
+        _lock: std::io::StdinLock<'static>,
+        raw: ManuallyDrop<std::fs::File>,
+    }
+
+    /// Returns a handle to stdin which reads bytes exactly as they were sent.
+    pub(crate) fn binary_stdin() -> BinaryStdin {
+        let lock = std::io::stdin().lock();
+
+        // SAFETY: the handle is owned by the standard library's stdin, which outlives us. The
+        // File is wrapped in ManuallyDrop so that we never close the handle ourselves
+        let raw = unsafe { std::fs::File::from_raw_handle(lock.as_raw_handle()) };
+
+        BinaryStdin {
+            _lock: lock,
+            raw: ManuallyDrop::new(raw),
+        }
+    }
+
+    impl Read for BinaryStdin {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.raw.read(buf)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RpegImage;
+
+    #[test]
+    fn test_env_overrides() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("rpegio-env-override-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let image = RpegImage::new(vec![[0x00, 0x11, 0x22, 0x33]; 4], 4, 4);
+
+        // No other test reads stdin or writes stdout, so the variables can't get in their way
+        std::env::set_var(OUTPUT_VAR, path);
+        let written = image.write();
+        std::env::remove_var(OUTPUT_VAR);
+        written.unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), image.to_bytes());
+
+        std::env::set_var(INPUT_VAR, path);
+        let read = RpegImage::read(None);
+        // A path which is given is used in place of the variable's
+        let explicit = input_path(Some("other.rpeg"));
+        std::env::remove_var(INPUT_VAR);
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(read.unwrap(), image);
+        assert_eq!(explicit.as_deref(), Some("other.rpeg"));
+    }
+}
@@ -0,0 +1,870 @@
+use crate::checksum::Crc32;
+use crate::compression;
+use crate::header::{self, ByteReader, RpegHeader};
+#[cfg(feature = "crypto")]
+use crate::sha256::HmacSha256;
+use crate::timing::Stopwatch;
+use crate::{format, stdio, trailer, ReadOptions, RpegError, RpegFormat, WriteOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::time::Duration;
+
+/// Counters describing how much rpeg data an [`RpegReader`] or [`RpegWriter`] has processed so
+/// far, and how long it has taken.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::RpegWriter;
+///
+/// let mut writer = RpegWriter::new(Vec::new(), 2, 2).unwrap();
+/// writer.write_word([0x00, 0x11, 0x22, 0x33]).unwrap();
+///
+/// let stats = writer.stats();
+/// assert_eq!(stats.header_bytes, 30);
+/// assert_eq!(stats.payload_words, 1);
+/// assert_eq!(stats.total_bytes(), 34);
+/// println!("{:.0} bytes/second", stats.bytes_per_second());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IoStats {
+    /// The number of bytes in the header
+    pub header_bytes: u64,
+    /// The number of words of the payload processed
+    pub payload_words: u64,
+    /// The number of bytes in each word of the payload
+    pub word_size: u64,
+    /// The time elapsed between creating the reader or writer and asking for these statistics
+    pub elapsed: Duration,
+}
+
+impl IoStats {
+    /// The total number of bytes (header and payload) processed.
+    pub fn total_bytes(&self) -> u64 {
+        self.header_bytes + self.payload_words * self.word_size
+    }
+
+    /// The average number of bytes processed per second, or 0 if no time has elapsed (which can
+    /// happen on platforms with a coarse clock).
+    pub fn bytes_per_second(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+
+        self.total_bytes() as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Reads rpeg data one word at a time, never holding more than a fixed-size buffer of the input
+/// in memory.
+///
+/// The header is read and validated as soon as the reader is created. The words of the payload
+/// can then be read with [`RpegReader::read_word`], or by using the reader as an [`Iterator`].
+///
+/// Like [`RpegImage`](crate::RpegImage), readers are generic over the number of bytes `N` in each
+/// word, which defaults to 4.
+///
+/// # Examples
+/// ```no_run
+/// use csc411_rpegio::RpegReader;
+///
+/// let mut reader = RpegReader::open(Some("path/to/file.rpeg")).unwrap();
+/// println!("Image size: {}x{}", reader.header().width, reader.header().height);
+///
+/// for word in reader {
+///     let word: [u8; 4] = word.unwrap();
+///
+///     // Do something with each word as it arrives
+/// }
+/// ```
+pub struct RpegReader<R, const N: usize = 4> {
+    bytes: ByteReader<BufReader<R>>,
+    header: RpegHeader,
+    header_len: u64,
+    payload_words: u64,
+    // For data with trailers, how far to read ahead of the words returned, the bytes read ahead,
+    // whether they're the end of the data, and the checksum so far
+    lookahead: usize,
+    pending: Vec<u8>,
+    at_end: bool,
+    crc: Crc32,
+    // With a key to verify signatures with, the signature so far
+    #[cfg(feature = "crypto")]
+    mac: Option<HmacSha256>,
+    started: Stopwatch,
+}
+
+impl RpegReader<Box<dyn Read>> {
+    /// Opens rpeg data from either stdin or a file, reading and validating its header.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the provided file can't be opened, or there is an error reading from it or stdin
+    /// * If the rpeg data header is badly formatted
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - An optional file path to read from. If None, stdin will be read from
+    ///   instead
+    pub fn open(file_path: Option<&str>) -> Result<Self, RpegError> {
+        RpegReader::open_with_options(file_path, &ReadOptions::default())
+    }
+
+    /// Like [`RpegReader::open`], but with the given options.
+    ///
+    /// Like [`RpegImage::read`](crate::RpegImage::read), this detects compressed input (see
+    /// [`RpegImage::from_bytes`](crate::RpegImage::from_bytes)), though compressed input is
+    /// decompressed into memory all at once.
+    pub fn open_with_options(
+        file_path: Option<&str>,
+        options: &ReadOptions,
+    ) -> Result<Self, RpegError> {
+        let mut reader: Box<dyn Read> = match stdio::input_path(file_path).as_deref() {
+            Some(file_path) => Box::new(std::fs::File::open(file_path)?),
+            None => Box::new(stdio::binary_stdin()),
+        };
+
+        // Compressed input is detected from its magic bytes, and decompressed up front
+        let mut start = Vec::with_capacity(compression::MAGIC_LEN);
+        (&mut reader)
+            .take(compression::MAGIC_LEN as u64)
+            .read_to_end(&mut start)?;
+
+        let reader: Box<dyn Read> = if compression::is_compressed(&start) {
+            let mut bytes = start;
+            reader.read_to_end(&mut bytes)?;
+            let bytes = compression::decompress(&bytes)?.unwrap_or(bytes);
+
+            Box::new(std::io::Cursor::new(bytes))
+        } else {
+            Box::new(std::io::Cursor::new(start).chain(reader))
+        };
+
+        RpegReader::with_options(reader, options)
+    }
+}
+
+impl<R: Read> RpegReader<R> {
+    /// Creates a reader over any source of rpeg data, reading and validating its header.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If there is an error reading from `reader`
+    /// * If the rpeg data header is badly formatted
+    pub fn new(reader: R) -> Result<Self, RpegError> {
+        RpegReader::with_options(reader, &ReadOptions::default())
+    }
+
+    /// Like [`RpegReader::new`], but with the given options.
+    pub fn with_options(reader: R, options: &ReadOptions) -> Result<Self, RpegError> {
+        let started = Stopwatch::start();
+        let reader = BufReader::with_capacity(options.buffer_capacity, reader);
+        let mut bytes = ByteReader::new(reader);
+        let header = header::read_header(&mut bytes, options)?;
+
+        RpegReader::from_parts(bytes, header, options, started)
+    }
+}
+
+impl<R: Read, const N: usize> RpegReader<R, N> {
+    /// Creates a reader over data in a custom variant of the rpeg format, reading and validating
+    /// its header. See [`RpegFormat`] for details.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the format's words aren't `N` bytes long
+    /// * If there is an error reading from `reader`
+    /// * If the header is badly formatted
+    pub fn with_format(
+        reader: R,
+        format: &dyn RpegFormat,
+        options: &ReadOptions,
+    ) -> Result<Self, RpegError> {
+        format::check_word_size(format, N)?;
+
+        let started = Stopwatch::start();
+        let reader = BufReader::with_capacity(options.buffer_capacity, reader);
+        let mut bytes = ByteReader::new(reader);
+        let header = header::read_header_in_format(&mut bytes, format)?;
+
+        RpegReader::from_parts(bytes, header, options, started)
+    }
+
+    fn from_parts(
+        bytes: ByteReader<BufReader<R>>,
+        header: RpegHeader,
+        options: &ReadOptions,
+        started: Stopwatch,
+    ) -> Result<Self, RpegError> {
+        #[cfg(feature = "crypto")]
+        let mac = match &options.verify_key {
+            Some(_) if !header.signed => return Err(RpegError::SignatureMissing),
+            Some(key) => Some(crate::signature::start(key, &header)),
+            None => None,
+        };
+        #[cfg(not(feature = "crypto"))]
+        let _ = options;
+
+        let header_len = bytes.position();
+        let lookahead = trailer::max_len(&header);
+
+        Ok(RpegReader {
+            bytes,
+            header,
+            header_len,
+            payload_words: 0,
+            lookahead,
+            pending: Vec::new(),
+            at_end: false,
+            crc: Crc32::new(),
+            #[cfg(feature = "crypto")]
+            mac,
+            started,
+        })
+    }
+
+    /// The header of the rpeg data being read.
+    pub fn header(&self) -> &RpegHeader {
+        &self.header
+    }
+
+    /// Statistics about how much has been read so far. Header bytes include any line endings, and
+    /// only complete words of the payload are counted.
+    pub fn stats(&self) -> IoStats {
+        IoStats {
+            header_bytes: self.header_len,
+            payload_words: self.payload_words,
+            word_size: N as u64,
+            elapsed: self.started.elapsed(),
+        }
+    }
+
+    /// Reads the next word of the payload, returning `Ok(None)` once the payload has been read
+    /// in full.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If there is an error reading from the underlying source
+    /// * If the payload ends partway through a word (its length isn't a multiple of `N`)
+    /// * [`RpegError::ChecksumMismatch`] or [`RpegError::WordCountMismatch`] if the data has a
+    ///   checksum or word count at its end which doesn't match the payload. This is only known
+    ///   once the whole payload has been read
+    /// * [`RpegError::SignatureMismatch`] if the options have a key to verify signatures with,
+    ///   and the data's signature doesn't match. This is also only known at the end
+    pub fn read_word(&mut self) -> Result<Option<[u8; N]>, RpegError> {
+        if self.lookahead > 0 {
+            return self.read_word_before_trailers();
+        }
+
+        let mut word = [0; N];
+        let filled = read_fully(&mut self.bytes, &mut word)?;
+
+        if filled == N {
+            self.payload_words += 1;
+            Ok(Some(word))
+        } else if filled == 0 {
+            Ok(None)
+        } else {
+            Err(self.length_error(filled))
+        }
+    }
+
+    fn read_word_before_trailers(&mut self) -> Result<Option<[u8; N]>, RpegError> {
+        // Trailers are only told apart from the payload by being at the very end, so enough is
+        // read ahead to be sure the next word isn't part of them
+        if !self.at_end {
+            let wanted = N + self.lookahead;
+            let filled = self.pending.len();
+            self.pending.resize(wanted, 0);
+            let read = read_fully(&mut self.bytes, &mut self.pending[filled..])?;
+            self.pending.truncate(filled + read);
+
+            if self.pending.len() == wanted {
+                return Ok(Some(self.take_pending_word()));
+            }
+
+            // This is the end of the data, which leaves just the trailers and the last few words
+            self.at_end = true;
+            let pending = std::mem::take(&mut self.pending);
+            let end = self.bytes.position();
+            let words = self.payload_words;
+            let (pending, _signature) = trailer::split_signature(&self.header, &pending, end)?;
+            #[cfg(feature = "crypto")]
+            if let Some(mut mac) = self.mac.take() {
+                mac.update(pending);
+                crate::signature::check(mac, _signature)?;
+            }
+            let payload = trailer::split(&self.header, pending, words, self.crc, N, end)?;
+
+            if !payload.len().is_multiple_of(N) {
+                return Err(self.length_error(payload.len()));
+            }
+            self.pending = payload.to_vec();
+        }
+
+        if self.pending.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(self.take_pending_word()))
+        }
+    }
+
+    fn take_pending_word(&mut self) -> [u8; N] {
+        let word: [u8; N] = self.pending[..N].try_into().unwrap();
+        self.pending.drain(..N);
+        self.crc.update(&word);
+        #[cfg(feature = "crypto")]
+        if let Some(mac) = &mut self.mac {
+            mac.update(&word);
+        }
+        self.payload_words += 1;
+
+        word
+    }
+
+    /// The error for a payload which has `extra` bytes after its last complete word.
+    fn length_error(&self, extra: usize) -> RpegError {
+        let payload_len = self.payload_words * N as u64 + extra as u64;
+
+        crate::codec::length_error(payload_len, N, self.bytes.position()).into()
+    }
+}
+
+/// Reads into `buffer` until it's full or the input runs out, returning the number of bytes read.
+fn read_fully<R: Read>(
+    bytes: &mut ByteReader<BufReader<R>>,
+    buffer: &mut [u8],
+) -> Result<usize, RpegError> {
+    let mut filled = 0;
+
+    while filled < buffer.len() {
+        let available = bytes.fill_buf()?;
+
+        if available.is_empty() {
+            break;
+        }
+
+        let count = available.len().min(buffer.len() - filled);
+        buffer[filled..filled + count].copy_from_slice(&available[..count]);
+        bytes.consume(count);
+        filled += count;
+    }
+
+    Ok(filled)
+}
+
+impl<R: Read, const N: usize> Iterator for RpegReader<R, N> {
+    type Item = Result<[u8; N], RpegError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_word().transpose()
+    }
+}
+
+/// Writes rpeg data one word at a time, never holding more than a fixed-size buffer of the output
+/// in memory.
+///
+/// The header is written as soon as the writer is created. Once every word has been written with
+/// [`RpegWriter::write_word`], call [`RpegWriter::finish`] to make sure everything has actually
+/// been written out.
+///
+/// Like [`RpegImage`](crate::RpegImage), writers are generic over the number of bytes `N` in each
+/// word, which defaults to 4.
+///
+/// # Examples
+/// ```
+/// use csc411_rpegio::RpegWriter;
+///
+/// let mut writer = RpegWriter::stdout(2, 2).unwrap();
+/// writer.write_word([0x00, 0x11, 0x22, 0x33]).unwrap();
+/// writer.finish().unwrap();
+/// ```
+pub struct RpegWriter<W: Write, const N: usize = 4> {
+    writer: BufWriter<W>,
+    header_len: u64,
+    payload_words: u64,
+    // Only the trailers the header calls for are written after the payload
+    header: RpegHeader,
+    crc: Crc32,
+    #[cfg(feature = "crypto")]
+    mac: Option<HmacSha256>,
+    started: Stopwatch,
+}
+
+impl RpegWriter<Box<dyn Write>> {
+    /// Creates a writer which outputs rpeg data to stdout, in binary mode.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If something goes wrong writing the header to stdout
+    pub fn stdout(width: u32, height: u32) -> Result<Self, RpegError> {
+        RpegWriter::stdout_with_options(width, height, &WriteOptions::default())
+    }
+
+    /// Like [`RpegWriter::stdout`], but with the given options.
+    pub fn stdout_with_options(
+        width: u32,
+        height: u32,
+        options: &WriteOptions,
+    ) -> Result<Self, RpegError> {
+        RpegWriter::with_options(Box::new(stdio::output()?), width, height, options)
+    }
+}
+
+impl<W: Write> RpegWriter<W> {
+    /// Creates a writer over any destination for rpeg data, writing the header.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If something goes wrong writing the header to `writer`
+    pub fn new(writer: W, width: u32, height: u32) -> Result<Self, RpegError> {
+        RpegWriter::with_options(writer, width, height, &WriteOptions::default())
+    }
+
+    /// Like [`RpegWriter::new`], but with the given options.
+    pub fn with_options(
+        writer: W,
+        width: u32,
+        height: u32,
+        options: &WriteOptions,
+    ) -> Result<Self, RpegError> {
+        let started = Stopwatch::start();
+        let mut header_bytes = Vec::new();
+        let header = RpegHeader {
+            version: options.version,
+            checksum: options.checksum,
+            word_count: options.word_count,
+            binary: options.binary_header,
+            comments: options.comments.clone(),
+            metadata: options.metadata.clone(),
+            ..RpegHeader::new(width, height)
+        };
+        header::write_header(&mut header_bytes, &header)?;
+
+        #[cfg(feature = "crypto")]
+        if let Some(key) = &options.signing_key {
+            let header = RpegHeader {
+                signed: true,
+                ..header
+            };
+            header_bytes.clear();
+            header::write_signed_header(&mut header_bytes, &header)?;
+            let mac = crate::signature::start(key, &header);
+
+            let mut writer =
+                RpegWriter::from_header_bytes(writer, &header_bytes, header, options, started)?;
+            writer.mac = Some(mac);
+            return Ok(writer);
+        }
+
+        RpegWriter::from_header_bytes(writer, &header_bytes, header, options, started)
+    }
+}
+
+impl<W: Write, const N: usize> RpegWriter<W, N> {
+    /// Creates a writer of data in a custom variant of the rpeg format, writing the header. See
+    /// [`RpegFormat`] for details. Any comments in `options` are written after those already in
+    /// `header`, and any metadata in `options` takes precedence over that in `header`. Custom
+    /// formats never have trailers, such as checksums.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the format's words aren't `N` bytes long
+    /// * If something goes wrong writing the header to `writer`
+    pub fn with_format(
+        writer: W,
+        header: &RpegHeader,
+        format: &dyn RpegFormat,
+        options: &WriteOptions,
+    ) -> Result<Self, RpegError> {
+        format::check_word_size(format, N)?;
+
+        let started = Stopwatch::start();
+        let mut header = header.clone();
+        header.comments.extend(options.comments.iter().cloned());
+        header.metadata.extend(options.metadata.clone());
+        let mut header_bytes = Vec::new();
+        header::write_header_in_format(&mut header_bytes, &header, format)?;
+
+        header.checksum = false;
+        header.word_count = false;
+        RpegWriter::from_header_bytes(writer, &header_bytes, header, options, started)
+    }
+
+    fn from_header_bytes(
+        writer: W,
+        header_bytes: &[u8],
+        header: RpegHeader,
+        options: &WriteOptions,
+        started: Stopwatch,
+    ) -> Result<Self, RpegError> {
+        let mut writer = BufWriter::with_capacity(options.buffer_capacity, writer);
+        writer.write_all(header_bytes)?;
+
+        Ok(RpegWriter {
+            writer,
+            header_len: header_bytes.len() as u64,
+            payload_words: 0,
+            header,
+            crc: Crc32::new(),
+            #[cfg(feature = "crypto")]
+            mac: None,
+            started,
+        })
+    }
+
+    /// Writes the next word of the payload.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If something goes wrong writing to the underlying destination
+    pub fn write_word(&mut self, word: [u8; N]) -> Result<(), RpegError> {
+        self.writer.write_all(&word)?;
+        self.payload_words += 1;
+        if self.header.checksum {
+            self.crc.update(&word);
+        }
+        #[cfg(feature = "crypto")]
+        if let Some(mac) = &mut self.mac {
+            mac.update(&word);
+        }
+
+        Ok(())
+    }
+
+    /// Statistics about how much has been written so far. Note that some of it may still be
+    /// buffered until [`RpegWriter::finish`] is called.
+    pub fn stats(&self) -> IoStats {
+        IoStats {
+            header_bytes: self.header_len,
+            payload_words: self.payload_words,
+            word_size: N as u64,
+            elapsed: self.started.elapsed(),
+        }
+    }
+
+    /// Writes any trailers (such as a checksum or signature) after the payload, and flushes any
+    /// buffered output, returning the underlying destination.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If something goes wrong writing to the underlying destination
+    pub fn finish(mut self) -> Result<W, RpegError> {
+        let mut trailers = Vec::new();
+        trailer::write(&mut trailers, &self.header, self.payload_words, &self.crc)?;
+        self.writer.write_all(&trailers)?;
+
+        // The signature covers the trailers too, and comes after them
+        #[cfg(feature = "crypto")]
+        if let Some(mut mac) = self.mac.take() {
+            mac.update(&trailers);
+            self.writer.write_all(&mac.finish())?;
+        }
+
+        self.writer
+            .into_inner()
+            .map_err(|err| err.into_error().into())
+    }
+}
+
+/// Reads rpeg data from either stdin or a file, passing each word to `consumer` as soon as it is
+/// read. Unlike [`read_in_rpeg_data`](crate::read_in_rpeg_data), the payload is never held in
+/// memory all at once, so this uses the same (small) amount of memory regardless of image size.
+///
+/// Returns the header of the rpeg data once every word has been consumed.
+///
+/// # Errors Returned
+///
+/// * If there is an unexpected error reading from the provided file or stdin
+/// * If the rpeg data header is badly formatted
+/// * If the number of raw bytes following the header is not a multiple of 4. Note that all of the
+///   complete words before the end of the input will already have been consumed
+///
+/// # Arguments
+///
+/// * `file_path` - An optional file path to read from. If None, stdin will be read from instead
+/// * `consumer` - A function called with the header and each word of the payload, in order
+///
+/// # Examples
+/// ```no_run
+/// let mut word_count = 0;
+///
+/// let header = csc411_rpegio::stream_in_rpeg_data(None, |header, word| {
+///     // Do something with each word as it arrives. The header is provided so that you can tell
+///     // where in the image each word belongs
+///     let column = word_count % (header.width / 2);
+///     word_count += 1;
+/// })
+/// .unwrap();
+///
+/// println!("{}x{} image with {word_count} words", header.width, header.height);
+/// ```
+pub fn stream_in_rpeg_data(
+    file_path: Option<&str>,
+    mut consumer: impl FnMut(&RpegHeader, [u8; 4]),
+) -> Result<RpegHeader, RpegError> {
+    let mut reader = RpegReader::open(file_path)?;
+
+    while let Some(word) = reader.read_word()? {
+        consumer(&reader.header, word);
+    }
+
+    Ok(reader.header)
+}
+
+/// Outputs rpeg data to stdout, taking each word from `words` only as it is written. Unlike
+/// [`output_rpeg_data`](crate::output_rpeg_data), the payload never needs to be held in memory
+/// all at once, so this uses the same (small) amount of memory regardless of image size.
+///
+/// # Errors Returned
+///
+/// * [`RpegError::BrokenPipe`] if stdout is closed before all of the data is written
+/// * [`RpegError::Io`] if anything else goes wrong writing to stdout
+///
+/// # Arguments
+///
+/// * `words` - The words of compressed image data, in order
+/// * `width` - The width of the image
+/// * `height` - The height of the image
+///
+/// # Examples
+/// ```
+/// // Produce each word lazily. In your program, these would be computed from the image
+/// let words = (0..4u32).map(|i| i.to_be_bytes());
+///
+/// csc411_rpegio::stream_out_rpeg_data(words, 4, 4).unwrap();
+/// ```
+pub fn stream_out_rpeg_data(
+    words: impl IntoIterator<Item = [u8; 4]>,
+    width: u32,
+    height: u32,
+) -> Result<(), RpegError> {
+    let mut writer = RpegWriter::stdout(width, height)?;
+
+    for word in words {
+        writer.write_word(word)?;
+    }
+
+    writer.finish()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes_per_second() {
+        let mut stats = IoStats {
+            header_bytes: 30,
+            payload_words: 5,
+            word_size: 4,
+            elapsed: Duration::ZERO,
+        };
+        assert_eq!(stats.bytes_per_second(), 0.0);
+
+        stats.elapsed = Duration::from_millis(500);
+        assert_eq!(stats.bytes_per_second(), 100.0);
+    }
+
+    #[test]
+    fn test_reader_streams_words() {
+        let input: &[u8] = b"Compressed image format 2\n4 2\n\x00\x11\x22\x33\x44\x55\x66\x77";
+        let reader = RpegReader::new(input).unwrap();
+
+        assert_eq!(reader.header(), &RpegHeader::new(4, 2));
+        assert_eq!(
+            reader.collect::<Result<Vec<_>, _>>().unwrap(),
+            vec![[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]]
+        );
+    }
+
+    #[test]
+    fn test_reader_rejects_partial_word() {
+        let input: &[u8] = b"Compressed image format 2\n4 2\n\x00\x11\x22\x33\x44\x55";
+        let mut reader = RpegReader::new(input).unwrap();
+
+        assert_eq!(reader.read_word().unwrap(), Some([0x00, 0x11, 0x22, 0x33]));
+        assert_eq!(reader.stats().header_bytes, 30);
+        assert_eq!(reader.stats().payload_words, 1);
+        assert!(matches!(
+            reader.read_word(),
+            Err(RpegError::Format { offset: 36, .. })
+        ));
+    }
+
+    #[test]
+    fn test_tiny_buffers() {
+        let mut writer =
+            RpegWriter::with_options(Vec::new(), 4, 2, &WriteOptions::new().buffer_capacity(1))
+                .unwrap();
+        writer.write_word([0x00, 0x11, 0x22, 0x33]).unwrap();
+        let output = writer.finish().unwrap();
+
+        let options = ReadOptions::new().buffer_capacity(1);
+        let reader = RpegReader::with_options(output.as_slice(), &options).unwrap();
+        assert_eq!(reader.header(), &RpegHeader::new(4, 2));
+        assert_eq!(
+            reader.collect::<Result<Vec<_>, _>>().unwrap(),
+            vec![[0x00, 0x11, 0x22, 0x33]]
+        );
+    }
+
+    #[test]
+    fn test_custom_format_round_trip() {
+        struct Custom;
+
+        impl RpegFormat for Custom {
+            fn magic(&self) -> &str {
+                "Custom"
+            }
+
+            fn version(&self) -> u32 {
+                7
+            }
+
+            fn word_size(&self) -> usize {
+                4
+            }
+
+            fn header_fields(&self) -> &[&str] {
+                &["height", "depth", "width"]
+            }
+        }
+
+        let mut header = RpegHeader::new(4, 2);
+        header.extra_fields.insert("depth".to_string(), 3);
+
+        let options = WriteOptions::new();
+        let mut writer: RpegWriter<_> =
+            RpegWriter::with_format(Vec::new(), &header, &Custom, &options).unwrap();
+        writer.write_word([0x00, 0x11, 0x22, 0x33]).unwrap();
+        let output = writer.finish().unwrap();
+        assert_eq!(output, b"Custom\n2 3 4\n\x00\x11\x22\x33");
+
+        let reader =
+            RpegReader::<_, 4>::with_format(output.as_slice(), &Custom, &ReadOptions::new());
+        let reader = reader.unwrap();
+        assert_eq!(
+            reader.header(),
+            &RpegHeader {
+                version: 7,
+                ..header
+            }
+        );
+        assert_eq!(reader.count(), 1);
+    }
+
+    #[test]
+    fn test_format_word_size_is_checked() {
+        struct Wide;
+
+        impl RpegFormat for Wide {
+            fn magic(&self) -> &str {
+                "Wide"
+            }
+
+            fn version(&self) -> u32 {
+                1
+            }
+
+            fn word_size(&self) -> usize {
+                8
+            }
+        }
+
+        let input: &[u8] = b"Wide\n4 2\n";
+        assert!(matches!(
+            RpegReader::<_, 4>::with_format(input, &Wide, &ReadOptions::new()),
+            Err(RpegError::WordSizeMismatch {
+                expected: 4,
+                found: 8
+            })
+        ));
+    }
+
+    #[test]
+    fn test_writer_round_trip() {
+        let mut writer = RpegWriter::new(Vec::new(), 4, 2).unwrap();
+        writer.write_word([0x0A, 0x0D, 0x0A, 0x1A]).unwrap();
+        writer.write_word([0x44, 0x55, 0x66, 0x77]).unwrap();
+        let output = writer.finish().unwrap();
+
+        let reader = RpegReader::new(output.as_slice()).unwrap();
+        assert_eq!(reader.header(), &RpegHeader::new(4, 2));
+        assert_eq!(
+            reader.collect::<Result<Vec<_>, _>>().unwrap(),
+            vec![[0x0A, 0x0D, 0x0A, 0x1A], [0x44, 0x55, 0x66, 0x77]]
+        );
+    }
+
+    #[test]
+    fn test_checksum_round_trip() {
+        let words = [[0x0A, 0x0D, 0x0A, 0x1A], [0x44, 0x55, 0x66, 0x77]];
+        let options = WriteOptions::new().checksum(true);
+        let mut writer = RpegWriter::with_options(Vec::new(), 4, 2, &options).unwrap();
+        for word in words {
+            writer.write_word(word).unwrap();
+        }
+        let mut output = writer.finish().unwrap();
+
+        let expected_crc = crate::checksum::tests::crc32(&output[31..39]);
+        assert_eq!(&output[..31], b"Compressed image format 2c\n4 2\n");
+        assert_eq!(output[39..], expected_crc.to_be_bytes());
+
+        // Tiny buffers make sure the trailer is found even when it's split between reads
+        let options = ReadOptions::new().buffer_capacity(3);
+        let reader = RpegReader::with_options(output.as_slice(), &options).unwrap();
+        assert!(reader.header().checksum);
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), words);
+
+        // A flipped bit in the payload is only noticed at the end
+        output[32] ^= 0x01;
+        let mut reader = RpegReader::new(output.as_slice()).unwrap();
+        assert!(reader.read_word().unwrap().is_some());
+        assert!(reader.read_word().unwrap().is_some());
+        assert!(matches!(
+            reader.read_word(),
+            Err(RpegError::ChecksumMismatch { expected, .. }) if expected == expected_crc
+        ));
+
+        // Losing a whole word is caught too, though the length is a multiple of four
+        output[32] ^= 0x01;
+        output.drain(35..39);
+        let reader = RpegReader::new(output.as_slice()).unwrap();
+        assert!(matches!(
+            reader.collect::<Result<Vec<_>, _>>(),
+            Err(RpegError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_word_count_round_trip() {
+        let words = vec![[0x31, 0x0A, 0x32, 0x0A]; 3];
+        let options = WriteOptions::new().word_count(true).checksum(true);
+        let mut writer = RpegWriter::with_options(Vec::new(), 4, 2, &options).unwrap();
+        for &word in &words {
+            writer.write_word(word).unwrap();
+        }
+        let output = writer.finish().unwrap();
+        assert_eq!(&output[44..46], b"3\n");
+
+        let options = ReadOptions::new().buffer_capacity(5);
+        let reader = RpegReader::with_options(output.as_slice(), &options).unwrap();
+        assert!(reader.header().word_count);
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), words);
+
+        let input: &[u8] = b"Compressed image format 2t\n4 2\n\x00\x11\x22\x33\x44\x55\x66\x772\n";
+        let reader = RpegReader::new(input).unwrap();
+        assert_eq!(reader.count(), 2);
+
+        let input: &[u8] = b"Compressed image format 2t\n4 2\n\x00\x11\x22\x33\x44\x55\x66\x773\n";
+        let reader = RpegReader::new(input).unwrap();
+        assert!(matches!(
+            reader.collect::<Result<Vec<_>, _>>(),
+            Err(RpegError::WordCountMismatch {
+                expected: 3,
+                found: 2
+            })
+        ));
+    }
+}
@@ -0,0 +1,247 @@
+use crate::header::{self, ByteReader};
+use crate::{stdio, ReadOptions, RpegError, RpegImage};
+
+/// The suffix on the first line of the header which marks the plain-text format.
+const TEXT_SUFFIX: &str = " [TEXT]";
+
+/// How each word is written in the plain-text format. See [`RpegImage::to_text`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextStyle {
+    /// Each word as an unsigned decimal number, such as `1122867`
+    #[default]
+    Decimal,
+    /// Each word as eight hexadecimal digits with a `0x` prefix, such as `0x00112233`
+    Hex,
+}
+
+impl RpegImage {
+    /// Encodes the image in a plain-text companion to the rpeg format, which (unlike
+    /// [`debug_output_rpeg_data`](crate::debug_output_rpeg_data)) can be parsed back into exactly
+    /// the same image with [`RpegImage::from_text`].
+    ///
+    /// The header is the same as usual, except that the first line ends in ` [TEXT]`. Each word
+    /// is then written as a single number (reading its bytes as a big-endian integer), with one
+    /// row of blocks (half the width of the image) on each line. Any trailers (such as a
+    /// checksum) aren't written, but are recreated when the image is written as binary data.
+    ///
+    /// # Arguments
+    ///
+    /// * `style` - Whether to write the words in decimal or hexadecimal
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::{RpegImage, TextStyle};
+    ///
+    /// let words = vec![[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]];
+    /// let image = RpegImage::new(words, 4, 2);
+    /// let text = image.to_text(TextStyle::Hex);
+    ///
+    /// assert_eq!(text, "Compressed image format 2 [TEXT]\n4 2\n0x00112233 0x44556677\n");
+    /// assert_eq!(RpegImage::from_text(&text).unwrap(), image);
+    /// ```
+    pub fn to_text(&self, style: TextStyle) -> String {
+        use std::fmt::Write;
+
+        let mut text = Vec::new();
+        header::write_header_with_suffix(&mut text, &self.header, TEXT_SUFFIX)
+            .expect("Writing to a Vec can't fail");
+        let mut text = String::from_utf8(text).expect("Headers are always valid UTF-8");
+
+        let row_len = (self.width() as usize / 2).max(1);
+        for row in self.words.chunks(row_len) {
+            for (index, word) in row.iter().enumerate() {
+                if index != 0 {
+                    text.push(' ');
+                }
+
+                let word = u32::from_be_bytes(*word);
+                match style {
+                    TextStyle::Decimal => write!(text, "{word}").unwrap(),
+                    TextStyle::Hex => write!(text, "0x{word:08X}").unwrap(),
+                }
+            }
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// Parses an image in the plain-text format written by [`RpegImage::to_text`]. Words may be
+    /// decimal or hexadecimal (with a `0x` prefix), in any mix, separated by any whitespace.
+    ///
+    /// # Errors Returned
+    ///
+    /// * If the header is badly formatted, or its first line doesn't end in ` [TEXT]`
+    /// * If a word isn't a number, or doesn't fit in four bytes
+    pub fn from_text(text: &str) -> Result<Self, RpegError> {
+        let mut bytes = ByteReader::new(text.as_bytes());
+        let header =
+            header::read_header_with_suffix(&mut bytes, &ReadOptions::default(), TEXT_SUFFIX)?;
+        let header_len = bytes.position() as usize;
+
+        let mut words = Vec::new();
+        let mut offset = header_len;
+        for line in text[header_len..].split_inclusive('\n') {
+            for token in line.split_ascii_whitespace() {
+                // Tokens are slices of `line`, so their offsets can be recovered for errors
+                let token_offset = offset + (token.as_ptr() as usize - line.as_ptr() as usize);
+                let word = parse_word(token).ok_or_else(|| RpegError::Format {
+                    message: format!("Expected a word, found {token:?}"),
+                    offset: token_offset as u64,
+                })?;
+
+                words.push(word.to_be_bytes());
+            }
+            offset += line.len();
+        }
+
+        Ok(RpegImage { header, words })
+    }
+
+    /// Reads and parses an image in the plain-text format from either stdin or a file. See
+    /// [`RpegImage::from_text`].
+    ///
+    /// # Errors Returned
+    ///
+    /// * If there is an unexpected error reading from the provided file or stdin
+    /// * If the input isn't valid UTF-8
+    /// * If the input isn't in the plain-text format, as for [`RpegImage::from_text`]
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - An optional file path to read from. If None, stdin will be read from
+    ///   instead
+    pub fn read_text(file_path: Option<&str>) -> Result<Self, RpegError> {
+        let bytes = crate::read_raw_bytes(file_path)?;
+        let text = std::str::from_utf8(&bytes).map_err(|err| RpegError::Format {
+            message: "The text format must be valid UTF-8".to_string(),
+            offset: err.valid_up_to() as u64,
+        })?;
+
+        RpegImage::from_text(text)
+    }
+
+    /// Outputs the image to stdout in the plain-text format. See [`RpegImage::to_text`].
+    ///
+    /// # Errors Returned
+    ///
+    /// * [`RpegError::BrokenPipe`] if stdout is closed before all of the data is written
+    /// * [`RpegError::Io`] if anything else goes wrong writing to stdout
+    pub fn write_text(&self, style: TextStyle) -> Result<(), RpegError> {
+        use std::io::Write;
+
+        let mut stdout = stdio::output()?;
+        stdout.write_all(self.to_text(style).as_bytes())?;
+        stdout.flush()?;
+
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for RpegImage {
+    type Err = RpegError;
+
+    /// Parses an image in the plain-text format, as [`RpegImage::from_text`] does. Together with
+    /// the alternate form of [`Display`](std::fmt::Display) (`{:#}`), which writes it, tiny
+    /// images can be written inline in tests.
+    ///
+    /// # Examples
+    /// ```
+    /// use csc411_rpegio::RpegImage;
+    ///
+    /// let image: RpegImage = "Compressed image format 2 [TEXT]\n4 2\n0x00112233 0x44556677\n"
+    ///     .parse()
+    ///     .unwrap();
+    /// assert_eq!(image.words, [[0x00, 0x11, 0x22, 0x33], [0x44, 0x55, 0x66, 0x77]]);
+    ///
+    /// assert_eq!(format!("{image:#}").parse::<RpegImage>().unwrap(), image);
+    /// ```
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        RpegImage::from_text(text)
+    }
+}
+
+/// Writes an image in the plain-text format, with each word in hexadecimal (as
+/// [`RpegImage::to_text`] writes them with [`TextStyle::Hex`], whatever the size of the words).
+pub(crate) fn write_hex_text<const N: usize>(
+    f: &mut std::fmt::Formatter<'_>,
+    image: &RpegImage<N>,
+) -> std::fmt::Result {
+    let mut header = Vec::new();
+    header::write_header_with_suffix(&mut header, &image.header, TEXT_SUFFIX)
+        .expect("Writing to a Vec can't fail");
+    f.write_str(std::str::from_utf8(&header).expect("Headers are always valid UTF-8"))?;
+
+    let row_len = (image.width() as usize / 2).max(1);
+    for row in image.words.chunks(row_len) {
+        for (index, word) in row.iter().enumerate() {
+            f.write_str(if index == 0 { "0x" } else { " 0x" })?;
+            for byte in word {
+                write!(f, "{byte:02X}")?;
+            }
+        }
+        f.write_str("\n")?;
+    }
+
+    Ok(())
+}
+
+fn parse_word(token: &str) -> Option<u32> {
+    match token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
+
+/// Parses a word written as (at most) eight hexadecimal digits, with or without a `0x` prefix.
+pub(crate) fn parse_hex_word(hex: &str) -> Option<[u8; 4]> {
+    let hex = hex
+        .strip_prefix("0x")
+        .or_else(|| hex.strip_prefix("0X"))
+        .unwrap_or(hex);
+    if hex.is_empty() || hex.len() > 8 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    u32::from_str_radix(hex, 16).ok().map(u32::to_be_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_round_trip() {
+        let words = (0..6u32).map(|n| (n * 0x1234_5679).to_be_bytes()).collect();
+        let mut image = RpegImage::new(words, 4, 6);
+        image.header.comments.push("a comment".to_string());
+        image.header.checksum = true;
+
+        for style in [TextStyle::Decimal, TextStyle::Hex] {
+            let text = image.to_text(style);
+            assert_eq!(text.lines().count(), 6);
+            assert_eq!(RpegImage::from_text(&text).unwrap(), image);
+        }
+
+        assert_eq!(format!("{image:#}"), image.to_text(TextStyle::Hex));
+        assert_eq!(format!("{image:#}").parse::<RpegImage>().unwrap(), image);
+
+        let text = image.to_text(TextStyle::Decimal);
+        assert!(text.starts_with("Compressed image format 2c [TEXT]\n# a comment\n4 6\n0 "));
+    }
+
+    #[test]
+    fn test_text_errors() {
+        let text = "Compressed image format 2 [TEXT]\n2 2\n1 0x2\n  4294967296\n";
+        assert!(matches!(
+            RpegImage::from_text(text),
+            Err(RpegError::Format { offset: 45, .. })
+        ));
+
+        assert!(RpegImage::from_text("Compressed image format 2\n2 2\n1\n").is_err());
+        assert!(RpegImage::from_text("Compressed image format 2 [TEXT]\n2 2\n-1\n").is_err());
+    }
+}
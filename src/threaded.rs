@@ -0,0 +1,125 @@
+use crate::{RpegError, RpegHeader, RpegReader};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::JoinHandle;
+
+/// The maximum number of words that can be waiting in the channel before the background thread
+/// stops reading and waits for the receiver to catch up.
+const CHANNEL_CAPACITY: usize = 16 * 1024;
+
+/// A handle to the background thread started by [`spawn_rpeg_reader`]. Joining it reports whether
+/// the whole payload was read successfully.
+pub type RpegReaderThread = JoinHandle<Result<(), RpegError>>;
+
+/// Reads rpeg data from either stdin or a file on a background thread, delivering each word over
+/// a channel as soon as it is read. This lets the caller work on earlier words while later words
+/// are still being read.
+///
+/// The header is read before this function returns. Returns a tuple containing, in order:
+/// 1. The header of the rpeg data
+/// 2. A [`Receiver`] which yields each word of the payload, in order, and disconnects once the
+///    payload has been read in full (or an error occurs)
+/// 3. A [`RpegReaderThread`] handle for the background thread. Joining it reports whether the
+///    whole payload was read successfully, which can't otherwise be told apart from the payload
+///    simply ending
+///
+/// At most a fixed number of words are buffered in the channel, so if the caller falls behind,
+/// the background thread waits rather than reading the whole input into memory.
+///
+/// # Errors Returned
+///
+/// * If the provided file can't be opened, or there is an error reading the header
+/// * If the rpeg data header is badly formatted
+///
+/// Errors reading the payload (including a payload whose length isn't a multiple of 4) are
+/// instead returned by joining the background thread.
+///
+/// # Arguments
+///
+/// * `file_path` - An optional file path to read from. If None, stdin will be read from instead
+///
+/// # Examples
+/// ```no_run
+/// let (header, words, reader_thread) =
+///     csc411_rpegio::spawn_rpeg_reader(Some("path/to/file.rpeg")).unwrap();
+///
+/// for word in words {
+///     // Do something with each word as it arrives
+/// }
+///
+/// // Make sure the payload wasn't cut short by an error
+/// reader_thread.join().unwrap().unwrap();
+/// ```
+pub fn spawn_rpeg_reader(
+    file_path: Option<&str>,
+) -> Result<(RpegHeader, Receiver<[u8; 4]>, RpegReaderThread), RpegError> {
+    let file_path = file_path.map(str::to_string);
+    let (header_sender, header_receiver) = mpsc::sync_channel(1);
+    let (word_sender, word_receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+
+    // stdin can't be handed between threads, so the input is opened on the background thread and
+    // the header sent back separately
+    let handle = std::thread::spawn(move || {
+        let mut reader = match RpegReader::open(file_path.as_deref()) {
+            Ok(reader) => reader,
+            Err(err) => {
+                let _ = header_sender.send(Err(err));
+                return Ok(());
+            }
+        };
+
+        if header_sender.send(Ok(reader.header().clone())).is_err() {
+            return Ok(());
+        }
+
+        while let Some(word) = reader.read_word()? {
+            // If the receiver was dropped, nobody wants the rest of the words
+            if word_sender.send(word).is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    });
+
+    match header_receiver.recv() {
+        Ok(Ok(header)) => Ok((header, word_receiver, handle)),
+        Ok(Err(err)) => Err(err),
+        // The thread can only hang up without sending a header if it panicked
+        Err(_) => match handle.join() {
+            Ok(_) => unreachable!("Reader thread exited without reporting a header"),
+            Err(panic) => std::panic::resume_unwind(panic),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawn_rpeg_reader() {
+        let path = std::env::temp_dir().join(format!("rpegio-threaded-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            b"Compressed image format 2\n4 2\n\x00\x11\x22\x33\x44\x55",
+        )
+        .unwrap();
+
+        let (header, words, reader_thread) = spawn_rpeg_reader(path.to_str()).unwrap();
+        let words: Vec<[u8; 4]> = words.iter().collect();
+        let result = reader_thread.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(header, RpegHeader::new(4, 2));
+        assert_eq!(words, vec![[0x00, 0x11, 0x22, 0x33]]);
+        assert!(matches!(result, Err(RpegError::Format { .. })));
+    }
+
+    #[test]
+    fn test_spawn_rpeg_reader_missing_file() {
+        assert!(matches!(
+            spawn_rpeg_reader(Some("this/file/does/not/exist.rpeg")),
+            Err(RpegError::Io(_))
+        ));
+    }
+}
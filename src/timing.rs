@@ -0,0 +1,130 @@
+#[cfg(feature = "timing")]
+use std::cell::Cell;
+use std::time::Duration;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+
+/// Wall-clock durations of each stage of reading and writing rpeg data.
+///
+/// Each field holds how long that stage took the most recent time it ran on the current thread,
+/// or zero if it has never run. Retrieve them with [`last_timings`].
+#[cfg(feature = "timing")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Timings {
+    /// Reading the raw bytes of the input (header and payload) into memory
+    pub payload_read: Duration,
+    /// Parsing the header from the raw bytes
+    pub header_parse: Duration,
+    /// Grouping the raw bytes of the payload into four-byte words
+    pub grouping: Duration,
+    /// Writing the header and payload to the output
+    pub output: Duration,
+}
+
+#[cfg(feature = "timing")]
+thread_local! {
+    static LAST_TIMINGS: Cell<Timings> = Cell::new(Timings::default());
+}
+
+/// Returns how long each stage of reading and writing rpeg data took the most recent time it ran
+/// on the current thread. Reads are timed by [`read_in_rpeg_data`](crate::read_in_rpeg_data)
+/// and [`RpegImage::read`](crate::RpegImage::read) (or just the parsing stages, for
+/// [`RpegImage::from_bytes`](crate::RpegImage::from_bytes)), and writes are timed by
+/// [`output_rpeg_data`](crate::output_rpeg_data) and
+/// [`try_output_rpeg_data`](crate::try_output_rpeg_data).
+///
+/// # Examples
+/// ```no_run
+/// let (raw_bytes, width, height) = csc411_rpegio::read_in_rpeg_data(None).unwrap();
+///
+/// let timings = csc411_rpegio::last_timings();
+/// eprintln!("Reading input: {:?}", timings.payload_read);
+/// eprintln!("Parsing header: {:?}", timings.header_parse);
+/// eprintln!("Grouping words: {:?}", timings.grouping);
+/// ```
+#[cfg(feature = "timing")]
+pub fn last_timings() -> Timings {
+    LAST_TIMINGS.with(Cell::get)
+}
+
+/// A stage of reading or writing rpeg data which can be timed.
+#[derive(Clone, Copy)]
+pub(crate) enum Stage {
+    PayloadRead,
+    HeaderParse,
+    Grouping,
+    Output,
+}
+
+/// Runs `f`, recording how long it took as the duration of `stage` when the `timing` feature is
+/// enabled.
+#[inline]
+pub(crate) fn timed<T>(stage: Stage, f: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "timing")]
+    {
+        let start = Stopwatch::start();
+        let result = f();
+        let elapsed = start.elapsed();
+
+        LAST_TIMINGS.with(|last_timings| {
+            let mut timings = last_timings.get();
+            match stage {
+                Stage::PayloadRead => timings.payload_read = elapsed,
+                Stage::HeaderParse => timings.header_parse = elapsed,
+                Stage::Grouping => timings.grouping = elapsed,
+                Stage::Output => timings.output = elapsed,
+            }
+            last_timings.set(timings);
+        });
+
+        result
+    }
+
+    #[cfg(not(feature = "timing"))]
+    {
+        let _ = stage;
+        f()
+    }
+}
+
+/// Measures elapsed wall-clock time, like [`Instant`], except on `wasm32-unknown-unknown` (as in
+/// a browser), which has no clock for `Instant` to read, and where every measurement is zero.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Stopwatch {
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    started: Instant,
+}
+
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Stopwatch {
+            #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+            started: Instant::now(),
+        }
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        return self.started.elapsed();
+
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        Duration::ZERO
+    }
+}
+
+#[cfg(all(test, feature = "timing"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timed_records_stage() {
+        let before = last_timings();
+        timed(Stage::Output, || {
+            std::thread::sleep(Duration::from_millis(5))
+        });
+        let after = last_timings();
+
+        assert!(after.output >= Duration::from_millis(5));
+        assert_eq!(after.payload_read, before.payload_read);
+    }
+}
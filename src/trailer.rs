@@ -0,0 +1,137 @@
+use crate::checksum::{Crc32, TRAILER_LEN};
+pub(crate) use crate::codec::{MAX_WORD_COUNT_LEN, SIGNATURE_LEN};
+use crate::{codec, RpegError, RpegHeader};
+
+/// The most bytes of trailers which can follow the payload of data with the given header.
+pub(crate) fn max_len(header: &RpegHeader) -> usize {
+    let checksum = if header.checksum { TRAILER_LEN } else { 0 };
+    let word_count = if header.word_count {
+        MAX_WORD_COUNT_LEN
+    } else {
+        0
+    };
+
+    let signature = if header.signed { SIGNATURE_LEN } else { 0 };
+
+    checksum + word_count + signature
+}
+
+/// Splits the signature (if any) off the very end of the data, after any other trailers. See
+/// [`codec::split_signature`].
+pub(crate) fn split_signature<'a>(
+    header: &RpegHeader,
+    rest: &'a [u8],
+    end: u64,
+) -> Result<(&'a [u8], Option<[u8; SIGNATURE_LEN]>), RpegError> {
+    Ok(codec::split_signature(header.trailers(), rest, end)?)
+}
+
+/// Writes the trailers (if any) which follow a payload of `words` words, with checksum `crc`.
+pub(crate) fn write(
+    writer: &mut impl std::io::Write,
+    header: &RpegHeader,
+    words: u64,
+    crc: &Crc32,
+) -> std::io::Result<()> {
+    if header.word_count {
+        writer.write_all(format!("{words}\n").as_bytes())?;
+    }
+
+    if header.checksum {
+        writer.write_all(&crc.finish().to_be_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Splits the checksum and word count (if any) off the end of the data, checking that they
+/// match the payload. See [`codec::split_trailers`].
+pub(crate) fn split<'a>(
+    header: &RpegHeader,
+    rest: &'a [u8],
+    words_read: u64,
+    crc: Crc32,
+    word_size: usize,
+    end: u64,
+) -> Result<&'a [u8], RpegError> {
+    Ok(codec::split_trailers(
+        header.trailers(),
+        rest,
+        words_read,
+        crc,
+        word_size,
+        end,
+    )?)
+}
+
+/// Splits the payload of data whose header is `header_len` bytes long off its trailers. See
+/// [`codec::split_payload`].
+pub(crate) fn split_payload<'a>(
+    header: &RpegHeader,
+    bytes: &'a [u8],
+    header_len: usize,
+    word_size: usize,
+) -> Result<&'a [u8], RpegError> {
+    Ok(codec::split_payload(
+        header.trailers(),
+        bytes,
+        header_len,
+        word_size,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_count(rest: &[u8], words_read: u64) -> Result<&[u8], RpegError> {
+        let header = RpegHeader {
+            word_count: true,
+            ..RpegHeader::new(2, 2)
+        };
+
+        split(&header, rest, words_read, Crc32::new(), 4, 0)
+    }
+
+    #[test]
+    fn test_word_count() {
+        assert_eq!(split_count(b"0\n", 0).unwrap(), b"");
+        assert_eq!(split_count(b"\x001\n22\n", 1).unwrap(), b"\x001\n2");
+        assert_eq!(split_count(b"10\n", 10).unwrap(), b"");
+        assert_eq!(split_count(b"1234567810\n", 8).unwrap(), b"12345678");
+
+        assert!(matches!(
+            split_count(b"1234\n", 0),
+            Err(RpegError::WordCountMismatch {
+                expected: 1234,
+                found: 0
+            })
+        ));
+        assert!(matches!(
+            split_count(b"abcd3\n", 0),
+            Err(RpegError::WordCountMismatch {
+                expected: 3,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            split_count(b"abc33\n", 0),
+            Err(RpegError::WordCountMismatch {
+                expected: 3,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            split_count(b"abc333\n", 0),
+            Err(RpegError::WordCountMismatch {
+                expected: 33,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            split_count(b"abcd", 0),
+            Err(RpegError::Format { .. })
+        ));
+        assert!(matches!(split_count(b"", 0), Err(RpegError::Format { .. })));
+    }
+}